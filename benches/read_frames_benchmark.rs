@@ -134,6 +134,49 @@ fn criterion_benchmark_histograms(c: &mut Criterion) {
     );
 }
 
+/// Compare reading through the memory map (`--features mmap`)
+/// against the default path of reopening the file for each chunk.
+#[cfg(feature = "mmap")]
+fn criterion_benchmark_mmap(c: &mut Criterion) {
+    let mut read_bench = c.benchmark_group("Memory-mapped read benchmarks");
+
+    for (name, path, n_frames) in [("short", SHORT_SIFF_PATH, 40), ("long", LONG_SIFF_PATH, 49999)] {
+        let file_reader = corrosiff::open_siff(path).unwrap();
+        let mmap_reader = unsafe { corrosiff::SiffReader::open_mmap(path).unwrap() };
+        let frame_vec = Vec::<u64>::from_iter(0..n_frames);
+        if n_frames > 1000 { read_bench.sample_size(10); }
+
+        for (method, siffreader) in [("file", &file_reader), ("mmap", &mmap_reader)] {
+            read_bench.bench_with_input(
+                BenchmarkId::new(format!("Read {} siff intensity, {}", name, method), n_frames),
+                &frame_vec.as_slice(),
+                |bench, frames| {
+                    bench.iter(|| black_box(siffreader.get_frames_intensity(frames, None).unwrap()))
+                },
+            );
+
+            read_bench.bench_with_input(
+                BenchmarkId::new(format!("Read {} siff histogram, {}", name, method), n_frames),
+                &frame_vec.as_slice(),
+                |bench, frames| {
+                    bench.iter(|| black_box(siffreader.get_histogram(frames).unwrap()))
+                },
+            );
+
+            read_bench.bench_with_input(
+                BenchmarkId::new(format!("Read {} siff flim, {}", name, method), n_frames),
+                &frame_vec.as_slice(),
+                |bench, frames| {
+                    bench.iter(|| black_box(siffreader.get_frames_flim(frames, None).unwrap()))
+                },
+            );
+        }
+    }
+}
+
+#[cfg(not(feature = "mmap"))]
+fn criterion_benchmark_mmap(_c: &mut Criterion) {}
+
 // /// Open multiple files, read either a few frames quickly with and without registration
 // /// (to compare overhead latency) and then many frames with and without registration
 // /// (to compare the actual effect of adding registration)
@@ -203,6 +246,7 @@ criterion_group!(
     config = Criterion::default();
     targets = criterion_benchmark_read_frames,
     criterion_benchmark_histograms,
+    criterion_benchmark_mmap,
     //criterion_benchmark_tau_d,
 );
 criterion_main!(benches);
\ No newline at end of file
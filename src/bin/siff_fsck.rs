@@ -0,0 +1,68 @@
+use std::env;
+use corrosiff;
+
+const USE_MESSAGE : &str = "\x1b[31mUsage: siff_fsck <filename> [<filename> ...] \
+    | siff_fsck <filename> -r <repaired_path>\x1b[0m";
+
+macro_rules! send_use_msg {
+    () => {
+        panic!("{}", USE_MESSAGE)
+    };
+}
+
+/// Checks every frame of one or more `.siff` files for
+/// damage (e.g. from an incomplete transfer) and prints
+/// a report of every problem found in each.
+/// 
+/// Exits with status 1 if any file has a problem.
+/// 
+/// With `-r`, instead copies every frame up to the last
+/// intact one into a new file that can be opened normally.
+/// 
+/// # Example
+/// 
+/// ```
+/// siff_fsck my_siff.siff my_other_siff.siff
+/// siff_fsck crashed.siff -r crashed_repaired.siff
+/// ```
+fn main(){
+    let args : Vec<String> = env::args().collect();
+    if args.len() < 2 {send_use_msg!();}
+
+    let mut filenames = Vec::new();
+    let mut repaired_path = None;
+    let mut args_iter = args.iter().skip(1);
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-r" => {
+                repaired_path = Some(args_iter.next().unwrap_or_else(|| send_use_msg!()));
+            },
+            _ => filenames.push(arg),
+        }
+    }
+
+    if let Some(repaired_path) = repaired_path {
+        if filenames.len() != 1 {send_use_msg!();}
+        let num_frames = corrosiff::fsck::repair_file(filenames[0], repaired_path)
+            .expect("Unable to repair file");
+        println!("Recovered {} frames into {}", num_frames, repaired_path);
+        return;
+    }
+
+    let mut all_ok = true;
+    for filename in filenames {
+        match corrosiff::fsck::check_file(filename) {
+            Ok(report) => {
+                all_ok &= report.is_ok();
+                print!("{}: {}", filename, report);
+            },
+            Err(err) => {
+                all_ok = false;
+                println!("{}: {}", filename, err);
+            },
+        }
+    }
+    if !all_ok {
+        std::process::exit(1);
+    }
+}
@@ -1,8 +1,9 @@
 use std::env;
-use corrosiff;
+use corrosiff::{self, RegistrationDict};
 
 const USE_MESSAGE : &str = "\x1b[31mUsage: siff_to_tiff <filename>\
-    [-m <mode>] [-o <output_path>]\x1b[0m";
+    [-m <mode>] [-p <payload>] [-f <first>:<last>] [-r <registration_file>]\
+    [-o <output_path>]\x1b[0m";
 
 macro_rules! send_use_msg {
     () => {
@@ -10,40 +11,107 @@ macro_rules! send_use_msg {
     };
 }
 
+/// Reads a registration file: one frame per line,
+/// as `<frame> <y shift> <x shift>` (separated by
+/// whitespace or commas).
+fn read_registration(path : &str) -> RegistrationDict {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("Could not read {} : {}", path, err))
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let values = line.split(|c : char| c == ',' || c.is_whitespace())
+                .filter(|value| !value.is_empty())
+                .collect::<Vec<_>>();
+            match values.as_slice() {
+                [frame, y, x] => (
+                    frame.parse().unwrap_or_else(|_| send_use_msg!()),
+                    (
+                        y.parse().unwrap_or_else(|_| send_use_msg!()),
+                        x.parse().unwrap_or_else(|_| send_use_msg!()),
+                    )
+                ),
+                _ => panic!("Invalid registration line : {}", line),
+            }
+        })
+        .collect()
+}
 
 /// Converts a `.siff` file to a `.tiff` file
-/// 
-/// If `-m` not specified, uses ScanImage format.
+///
+/// If `-m` not specified, uses ScanImage format (otherwise
+/// `OME` or `ImageJ`).
+/// If `-p` not specified, writes intensity (`Intensity`,
+/// `Lifetime`, or `Phasor`).
+/// If `-f` not specified, writes all frames (otherwise
+/// frames `first` through `last`, exclusive).
+/// If `-r` is specified, registers each frame with the
+/// shifts in the registration file (lines of `frame y x`).
 /// If `-o` not specified, uses the same path as
 /// the input file.
-/// 
+///
 /// # Example
-/// 
+///
 /// ```
 /// siff_to_tiff my_siff.siff -m OME -o my_tiff.tiff
+/// siff_to_tiff my_siff.siff -p Lifetime -f 0:1000 -r shifts.txt
 /// ```
 fn main(){
     let args : Vec<String> = env::args().collect();
     if args.len() < 2 {send_use_msg!();}
     let filename = &args[1];
     let mut mode = None;
+    let mut payload = None;
+    let mut frames = None;
+    let mut registration = None;
     let mut save_path = None;
 
-    while let Some(arg) = args.iter().next() {
+    let mut args_iter = args.iter().skip(2);
+    while let Some(arg) = args_iter.next() {
         match arg.as_str() {
             "-m" => {
-                mode = Some(args.iter().next().unwrap_or_else(|| send_use_msg!()));
+                mode = Some(args_iter.next().unwrap_or_else(|| send_use_msg!()));
+            },
+            "-p" => {
+                payload = Some(args_iter.next().unwrap_or_else(|| send_use_msg!()));
+            },
+            "-f" => {
+                let range = args_iter.next().unwrap_or_else(|| send_use_msg!());
+                let (first, last) = range.split_once(':').unwrap_or_else(|| send_use_msg!());
+                let first : u64 = first.parse().unwrap_or_else(|_| send_use_msg!());
+                let last : u64 = last.parse().unwrap_or_else(|_| send_use_msg!());
+                frames = Some((first..last).collect::<Vec<_>>());
+            },
+            "-r" => {
+                registration = Some(read_registration(
+                    args_iter.next().unwrap_or_else(|| send_use_msg!())
+                ));
             },
             "-o" => {
-                save_path = Some(args.iter().next().unwrap_or_else(|| send_use_msg!()));
+                save_path = Some(args_iter.next().unwrap_or_else(|| send_use_msg!()));
             },
             _ => (),
         }
     }
 
-    corrosiff::siff_to_tiff(
+    let mode = corrosiff::TiffMode::from_string_slice(
+        mode.unwrap_or(&"ScanImage".to_string())
+    ).unwrap();
+
+    if payload.is_none() && frames.is_none() && registration.is_none() {
+        corrosiff::siff_to_tiff(filename, mode, save_path)
+            .expect("Failure in siff_to_tiff's implementation");
+        return;
+    }
+
+    corrosiff::siff_to_tiff_payload(
         filename,
-        corrosiff::TiffMode::from_string_slice(mode.unwrap_or(&"ScanImage".to_string())).unwrap(),
-        save_path
-    ).expect("Failure in siff_to_tiff's implementation");    
-}
\ No newline at end of file
+        mode,
+        corrosiff::TiffPayload::from_string_slice(
+            payload.unwrap_or(&"Intensity".to_string())
+        ).unwrap(),
+        frames.as_deref(),
+        registration.as_ref(),
+        save_path,
+    ).expect("Failure in siff_to_tiff's implementation");
+}
@@ -0,0 +1,50 @@
+use std::env;
+use corrosiff;
+
+const USE_MESSAGE : &str = "\x1b[31mUsage: siff_transcode <filename> \
+    -e <raw|compressed> [-o <output_path>]\x1b[0m";
+
+macro_rules! send_use_msg {
+    () => {
+        panic!("{}", USE_MESSAGE)
+    };
+}
+
+/// Rewrites a `.siff` file with every frame stored
+/// in the requested encoding (raw or compressed).
+/// 
+/// If `-o` not specified, saves next to the input
+/// file with `_raw` or `_compressed` appended to its name.
+/// 
+/// # Example
+/// 
+/// ```
+/// siff_transcode my_siff.siff -e compressed -o my_compressed_siff.siff
+/// ```
+fn main(){
+    let args : Vec<String> = env::args().collect();
+    if args.len() < 2 {send_use_msg!();}
+    let filename = &args[1];
+    let mut encoding = None;
+    let mut save_path = None;
+
+    let mut args_iter = args.iter().skip(2);
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-e" => {
+                encoding = Some(args_iter.next().unwrap_or_else(|| send_use_msg!()));
+            },
+            "-o" => {
+                save_path = Some(args_iter.next().unwrap_or_else(|| send_use_msg!()));
+            },
+            _ => send_use_msg!(),
+        }
+    }
+
+    let encoding = corrosiff::SiffEncoding::from_string_slice(
+        encoding.unwrap_or_else(|| send_use_msg!())
+    ).unwrap_or_else(|_| send_use_msg!());
+
+    corrosiff::transcode_siff(filename, encoding, save_path)
+        .expect("Failure in siff_transcode's implementation");
+}
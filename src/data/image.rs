@@ -8,6 +8,7 @@ mod dimensions;
 mod intensity;
 mod flim;
 mod utils;
+pub (crate) mod photons;
 
 /// Functionality for loading arrays with
 /// image data (either FLIM format or intensity format)
@@ -19,7 +20,7 @@ pub mod load {
     pub (crate) use super::flim::{load_array_tau_d, load_array_tau_d_registered};
 }
 
-pub (crate) use dimensions::{Dimensions, DimensionsError, roll};
+pub (crate) use dimensions::{Dimensions, DimensionsError, roll, macros::wrap_coordinate};
 
 use ndarray;
 
@@ -15,6 +15,14 @@ pub const SIFF_YMASK : u64 = ((1<<63) | ((1<<63) - 1)) & !((1<<48)-1);
 /// Bits 32-48 bits are the x coordinate
 pub const SIFF_XMASK : u64 = ((1<<48)- 1) & !((1<<32)-1);
 
+/// Shifts a pixel coordinate by `shift`, wrapping around
+/// `max` in both directions (so a shift of `-1` moves
+/// `0` to `max - 1`), as `roll` does.
+#[inline]
+pub fn wrap_coordinate(coordinate : usize, shift : i32, max : usize) -> usize {
+    (coordinate as i64 + shift as i64).rem_euclid(max as i64) as usize
+}
+
 /// Parses a `u64` from a photon in a raw `.siff` read
 /// to the y coordinate of the photon. If a shift is
 /// provided, it will add the shift to the y coordinate.
@@ -54,7 +62,9 @@ macro_rules! photon_to_y {
         (((($photon & SIFF_YMASK) >> 48) as i32) + $shift) as usize
     };
     ($photon : expr, $shift : expr, $max : expr) => {
-        ((((($photon & SIFF_YMASK) >> 48) as i32 + $shift) as usize) % ($max as usize))
+        $crate::data::image::dimensions::macros::wrap_coordinate(
+            (($photon & SIFF_YMASK) >> 48) as usize, $shift, $max as usize
+        )
     };
 }
 
@@ -98,7 +108,9 @@ macro_rules! photon_to_x {
     };
 
     ($photon : expr, $shift : expr, $max : expr) => {
-        ((((($photon & SIFF_XMASK) >> 32) as i32 + $shift) as usize) % ($max as usize))
+        $crate::data::image::dimensions::macros::wrap_coordinate(
+            (($photon & SIFF_XMASK) >> 32) as usize, $shift, $max as usize
+        )
     };
 }
 
@@ -141,6 +153,7 @@ pub (crate) use photon_to_tau_USIZE;
 /// 
 /// * `roll` - A tuple of the number of pixels to roll in the y_shift,
 /// and x_shift directions. Positive values shift the data right and down,
+/// and shifts larger than the array wrap around (as in `wrap_coordinate`)
 /// 
 /// ## Example
 /// 
@@ -150,14 +163,15 @@ pub (crate) use photon_to_tau_USIZE;
 /// 
 /// ```
 /// 
-/// ## Panics
-/// 
-/// * If the shifts are out of bounds
-/// 
 /// ## See also
 /// 
 /// `roll` - for rolling an array and returning a new array
 pub fn roll_inplace<T : Clone>(array : &mut ArrayViewMut2<T>, roll : (i32, i32)) {
+    if array.is_empty() {return ()}
+    let roll = (
+        macros::wrap_coordinate(0, roll.0, array.nrows()) as i32,
+        macros::wrap_coordinate(0, roll.1, array.ncols()) as i32,
+    );
     if roll == (0,0) {return ()}
     
     let copied = array.to_owned();
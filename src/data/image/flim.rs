@@ -5,6 +5,7 @@ pub mod phasor;
 use bytemuck::try_cast_slice;
 use binrw::io::{Read, Seek};
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use std::io::{
     Error as IOError,
     ErrorKind as IOErrorKind
@@ -29,12 +30,12 @@ use super::load::load_array_intensity;
 /// into a pre-allocated array by reading the given
 /// `strip_bytes` from the reader. Used for
 /// "raw" format frames.
-#[binrw::parser(reader)]
-fn _load_tau_d_raw<T : Into<u64>>(
+fn _load_tau_d_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut3<u16>,
     strip_bytes : T,
     ydim : u32,
-    xdim : u32
+    xdim : u32,
 ) -> binrw::BinResult<()>{
     let hdim = array.shape()[2];
     photonwise_op!(
@@ -59,13 +60,13 @@ fn _load_tau_d_raw<T : Into<u64>>(
 /// `strip_bytes` from the reader. Used for
 /// "raw" format frames. Applies registration to the data
 /// by shifting the pixels during reads.
-#[binrw::parser(reader)]
-fn _load_tau_d_raw_registered<T : Into<u64>>(
+fn _load_tau_d_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut3<u16>,
     strip_bytes : T,
     ydim : u32,
     xdim : u32,
-    reg : (i32, i32)
+    reg : (i32, i32),
 ) -> binrw::BinResult<()>{
     let hdim = array.shape()[2];
     photonwise_op!(
@@ -89,31 +90,29 @@ fn _load_tau_d_raw_registered<T : Into<u64>>(
 /// into a pre-allocated array by reading the given
 /// `strip_bytes` from the reader. Used for
 /// "compressed" format frames.
-#[binrw::parser(reader)]
-fn _load_tau_d_compressed<T: Into<u64>>(
+fn _load_tau_d_compressed<R : FrameRead, T: Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut3<u16>,
     strip_byte_counts : T,
     ydim : u32,
-    xdim : u32
+    xdim : u32,
 )-> binrw::BinResult<()>{
     reader.seek(std::io::SeekFrom::Current(
         -(ydim as i64 * xdim as i64 * std::mem::size_of::<u16>() as i64)
     ))?;
 
-    let mut intensity_data : Vec<u8> = vec![0;
+    let intensity_data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-    reader.read_exact(&mut intensity_data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&intensity_data)
+    let intensity_data = intensity_data.cast::<u16>()
     .map_err(|err| binrw::Error::Io(
         IOError::new(IOErrorKind::InvalidData, err)
     ))?;
 
-    let mut arrival_data = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut arrival_data)?;
+    let arrival_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let arrival_data = try_cast_slice::<u8, u16>(&arrival_data)
+    let arrival_data = arrival_data.cast::<u16>()
     .map_err(|err| binrw::Error::Io(
         IOError::new(IOErrorKind::InvalidData, err)
     ))?;
@@ -145,19 +144,17 @@ fn _load_tau_d_compressed<T: Into<u64>>(
 /// `strip_bytes` from the reader. Used for
 /// "compressed" format frames. Applies registration to the data
 /// by shifting the pixels during reads.
-#[binrw::parser(reader, endian)]
-fn _load_tau_d_compressed_registered<T : Into<u64>>(
+fn _load_tau_d_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut3<u16>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
-    reg : (i32, i32)
+    reg : (i32, i32),
 )->binrw::BinResult<()>{
 
     _load_tau_d_compressed(
-        reader,
-        endian,
-        (array, strip_byte_counts, ydim, xdim)
+        reader, array, strip_byte_counts, ydim, xdim
     )?;
 
     array.axis_iter_mut(Axis(2)).for_each(|mut image| roll_inplace(&mut image, (reg.0, reg.1)));
@@ -168,7 +165,7 @@ fn _load_tau_d_compressed_registered<T : Into<u64>>(
 
 /// Loads a single frame of a tau_d array with dimensions
 /// `ydim` x `xdim` x `hdim` from a `.siff` file.
-pub fn load_array_tau_d<I : IFD, ReaderT: Read + Seek>(
+pub fn load_array_tau_d<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     array_data : &mut ArrayViewMut3<u16>
@@ -201,7 +198,7 @@ pub fn load_array_tau_d<I : IFD, ReaderT: Read + Seek>(
 /// `ydim` x `xdim` x `hdim` from a `.siff` file and applies
 /// registration to the data. Calls the internal `_load_tau_d_raw_registered`
 /// and `_load_tau_d_compressed_registered` functions.
-pub fn load_array_tau_d_registered<I : IFD, ReaderT : Read + Seek>(
+pub fn load_array_tau_d_registered<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     array_data : &mut ArrayViewMut3<u16>,
@@ -6,6 +6,7 @@ use unregistered::*;
 
 use std::io::{Error as IOError, ErrorKind as IOErrorKind};
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use crate::{
     tiff::{
         IFD,
@@ -70,7 +71,7 @@ fn _load_flim_array_empirical<ReaderT, I>(
     reader : &mut ReaderT,
     ifd : &I,
     array : &mut ArrayViewMut2<f64>
-    ) -> Result<(), CorrosiffError> where I : IFD, ReaderT : Read + Seek {
+    ) -> Result<(), CorrosiffError> where I : IFD, ReaderT : FrameRead {
 
     load_array_from_siff!(
         reader,
@@ -119,7 +120,7 @@ fn _load_flim_array_empirical<ReaderT, I>(
 /// TODO: Write me!
 /// ```
 /// 
-pub fn load_flim_empirical_and_intensity_arrays<I: IFD, ReaderT : Read + Seek>(
+pub fn load_flim_empirical_and_intensity_arrays<I: IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut ArrayViewMut2<f64>,
@@ -153,7 +154,7 @@ pub fn load_flim_empirical_and_intensity_arrays<I: IFD, ReaderT : Read + Seek>(
 }
 
 pub fn load_flim_empirical_and_intensity_arrays_registered
-<I : IFD, ReaderT : Read + Seek>(
+<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut ArrayViewMut2<f64>,
@@ -189,7 +190,7 @@ pub fn load_flim_empirical_and_intensity_arrays_registered
     )
 }
 
-pub fn extract_lifetime_intensity_mask<I: IFD, ReaderT : Read + Seek>(
+pub fn extract_lifetime_intensity_mask<I: IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut ArrayViewMut1<f64>,
@@ -227,7 +228,7 @@ pub fn extract_lifetime_intensity_mask<I: IFD, ReaderT : Read + Seek>(
     )
 }
 
-pub fn extract_lifetime_intensity_mask_registered<I : IFD, ReaderT : Read + Seek>(
+pub fn extract_lifetime_intensity_mask_registered<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut ArrayViewMut1<f64>,
@@ -295,7 +296,7 @@ pub fn extract_lifetime_intensity_mask_registered<I : IFD, ReaderT : Read + Seek
 /// ```
 /// 
 /// 
-pub fn sum_lifetime_intensity_mask< I : IFD, ReaderT : Read + Seek>(
+pub fn sum_lifetime_intensity_mask< I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut f64,
@@ -330,7 +331,7 @@ pub fn sum_lifetime_intensity_mask< I : IFD, ReaderT : Read + Seek>(
     )
 }
 
-pub fn sum_lifetime_intensity_mask_registered< I : IFD, ReaderT : Read + Seek>(
+pub fn sum_lifetime_intensity_mask_registered< I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut f64,
@@ -368,7 +369,7 @@ pub fn sum_lifetime_intensity_mask_registered< I : IFD, ReaderT : Read + Seek>(
     )
 }
 
-pub fn sum_lifetime_intensity_masks< I : IFD, ReaderT : Read + Seek>(
+pub fn sum_lifetime_intensity_masks< I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut ArrayViewMut1<f64>,
@@ -404,7 +405,7 @@ pub fn sum_lifetime_intensity_masks< I : IFD, ReaderT : Read + Seek>(
 }
 
 
-pub fn sum_lifetime_intensity_masks_registered< I : IFD, ReaderT : Read + Seek>(
+pub fn sum_lifetime_intensity_masks_registered< I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     lifetime : &mut ArrayViewMut1<f64>,
@@ -1,4 +1,5 @@
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use itertools::izip;
 
 use crate::{
@@ -24,18 +25,17 @@ use crate::{
 /// note how this function needs to compute the
 /// intensity anyways! Hard to think of a reason to
 /// use it.
-#[binrw::parser(reader, endian)]
-pub fn _load_flim_array_empirical_compressed_registered<T: Into<u64>>(
+pub fn _load_flim_array_empirical_compressed_registered<R : FrameRead, T: Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut2<f64>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
     registration : (i32, i32),
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     
     _load_flim_array_empirical_compressed(
-        reader, endian,
-        (array, strip_byte_counts, ydim, xdim)
+        reader, array, strip_byte_counts, ydim, xdim
     )?;
 
     roll_inplace(array, registration);
@@ -46,14 +46,14 @@ pub fn _load_flim_array_empirical_compressed_registered<T: Into<u64>>(
 /// note how this function needs to compute the
 /// intensity anyways! Hard to think of a reason to
 /// use it.
-#[binrw::parser(reader)]
-pub fn _load_flim_array_empirical_uncompressed_registered<T: Into<u64>>(
+pub fn _load_flim_array_empirical_uncompressed_registered<R : FrameRead, T: Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut2<f64>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
     registration : (i32, i32),
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
         
     let mut intensity = Array2::<u16>::zeros(
         (ydim as usize, xdim as usize)
@@ -78,18 +78,17 @@ pub fn _load_flim_array_empirical_uncompressed_registered<T: Into<u64>>(
 }
 
 
-#[binrw::parser(reader, endian)]
-pub fn _load_flim_intensity_empirical_compressed_registered<T : Into<u64>>(
+pub fn _load_flim_intensity_empirical_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     lifetime_array : &mut ArrayViewMut2<f64>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
     registration : (i32, i32),
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     _load_flim_intensity_empirical_compressed(
-        reader, endian,
-        (lifetime_array, intensity_array, strip_byte_counts.into(), ydim, xdim)
+        reader, lifetime_array, intensity_array, strip_byte_counts.into(), ydim, xdim
     )?;
 
     roll_inplace(lifetime_array, registration);
@@ -99,15 +98,15 @@ pub fn _load_flim_intensity_empirical_compressed_registered<T : Into<u64>>(
 }
 
 
-#[binrw::parser(reader)]
-pub fn _load_flim_intensity_empirical_uncompressed_registered<T : Into<u64>>(
+pub fn _load_flim_intensity_empirical_uncompressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     lifetime_array : &mut ArrayViewMut2<f64>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
     registration : (i32, i32),
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
 
     photonwise_op!(
         reader,
@@ -128,8 +127,8 @@ pub fn _load_flim_intensity_empirical_uncompressed_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _extract_mask_empirical_intensity_raw_registered<T : Into<u64>>(
+pub fn _extract_mask_empirical_intensity_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
     lifetime : &mut ArrayViewMut1<f64>,
@@ -155,8 +154,8 @@ pub fn _extract_mask_empirical_intensity_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_mask_empirical_intensity_raw_registered<T : Into<u64>>(
+pub fn _sum_mask_empirical_intensity_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lifetime_sum : &mut f64,
     intensity_sum : &mut u64,
@@ -183,8 +182,8 @@ pub fn _sum_mask_empirical_intensity_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_masks_empirical_intensity_raw_registered<T : Into<u64>>(
+pub fn _sum_masks_empirical_intensity_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     lifetime_sum : &mut ArrayViewMut1<f64>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -221,8 +220,8 @@ pub fn _sum_masks_empirical_intensity_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn _sum_mask_empirical_intensity_compressed_registered<T : Into<u64>>(
+pub fn _sum_mask_empirical_intensity_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lifetime_sum : &mut f64,
     intensity_sum : &mut u64,
@@ -237,22 +236,19 @@ pub fn _sum_mask_empirical_intensity_compressed_registered<T : Into<u64>>(
     let mask_rolled = roll(mask, (-registration.0, -registration.1));
     _sum_mask_empirical_intensity_compressed(
         reader,
-        endian,
-        (
-            &mask_rolled.view(),
-            lifetime_sum,
-            intensity_sum,
-            strip_byte_counts.into(),
-            ydim,
-            xdim,
-        )
+        &mask_rolled.view(),
+        lifetime_sum,
+        intensity_sum,
+        strip_byte_counts.into(),
+        ydim,
+        xdim,
     )?;
 
     Ok(())
 }
 
-#[binrw::parser(reader,endian)]
-pub fn _sum_masks_empirical_intensity_compressed_registered<T : Into<u64>>(
+pub fn _sum_masks_empirical_intensity_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     lifetime_sum : &mut ArrayViewMut1<f64>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -271,22 +267,19 @@ pub fn _sum_masks_empirical_intensity_compressed_registered<T : Into<u64>>(
 
     _sum_masks_empirical_intensity_compressed(
         reader,
-        endian,
-        (
-            &masks_rolled.view(),
-            lifetime_sum,
-            intensity_sum,
-            strip_byte_counts.into(),
-            ydim,
-            xdim,
-        )
+        &masks_rolled.view(),
+        lifetime_sum,
+        intensity_sum,
+        strip_byte_counts.into(),
+        ydim,
+        xdim,
     )?;
 
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn _extract_mask_empirical_intensity_compressed_registered<T : Into<u64>>(
+pub fn _extract_mask_empirical_intensity_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
     lifetime : &mut ArrayViewMut1<f64>,
@@ -302,16 +295,13 @@ pub fn _extract_mask_empirical_intensity_compressed_registered<T : Into<u64>>(
     let table_rolled = roll(lookup_table, (-registration.0, -registration.1));
     _extract_mask_empirical_intensity_compressed(
         reader,
-        endian,
-        (
-            &mask_rolled.view(),
-            &table_rolled.view(),
-            lifetime,
-            intensity,
-            strip_byte_counts.into(),
-            ydim,
-            xdim,
-        )
+        &mask_rolled.view(),
+        &table_rolled.view(),
+        lifetime,
+        intensity,
+        strip_byte_counts.into(),
+        ydim,
+        xdim,
     )?;
 
      Ok(())
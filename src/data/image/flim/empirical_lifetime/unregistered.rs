@@ -1,5 +1,6 @@
 use std::io::{Error as IOError, ErrorKind as IOErrorKind};
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use bytemuck::try_cast_slice;
 use itertools::izip;
 
@@ -13,31 +14,26 @@ use crate::{
 /// note how this function needs to compute the
 /// intensity anyways! Hard to think of a reason to
 /// use it.
-#[binrw::parser(reader)]
-pub fn _load_flim_array_empirical_compressed<T: Into<u64>>(
+pub fn _load_flim_array_empirical_compressed<R : FrameRead, T: Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut2<f64>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     
-    let mut intensity_read : Vec<u8> = vec![0; ((ydim * xdim)*std::mem::size_of::<u16>() as u32) as usize];
-    reader.seek(std::io::SeekFrom::Current(-(intensity_read.len() as i64)))?;
+    let intensity_bytes = ((ydim * xdim)*std::mem::size_of::<u16>() as u32) as usize;
+    reader.seek(std::io::SeekFrom::Current(-(intensity_bytes as i64)))?;
 
-    reader.read_exact(&mut intensity_read)?;
-    let intensity = try_cast_slice::<u8, u16>(&intensity_read).map_err(
+    let intensity_read = reader.read_frame_bytes(intensity_bytes)?;
+    let intensity = intensity_read.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
     // All the photons in order as `u16`s
-    let mut lifetime_read : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-        
-    // let bytes = strip_byte_counts.into();
-    // let mut lifetime_read : Vec<u8> = vec![0; (8*((bytes/8) as usize)) as usize];
-
-    reader.read_exact(&mut lifetime_read)?;
+    let lifetime_read = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let arrival_times = try_cast_slice::<u8, u16>(&lifetime_read).map_err(
+    let arrival_times = lifetime_read.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -60,13 +56,13 @@ pub fn _load_flim_array_empirical_compressed<T: Into<u64>>(
 /// note how this function needs to compute the
 /// intensity anyways! Hard to think of a reason to
 /// use it.
-#[binrw::parser(reader)]
-pub fn _load_flim_array_empirical_uncompressed<T : Into<u64>>(
+pub fn _load_flim_array_empirical_uncompressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut2<f64>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     
     let mut intensity = Array2::<u16>::zeros(
         (ydim as usize, xdim as usize)
@@ -92,22 +88,22 @@ pub fn _load_flim_array_empirical_uncompressed<T : Into<u64>>(
 }
 
 
-#[binrw::parser(reader)]
-pub fn _load_flim_intensity_empirical_compressed<T : Into<u64>>(
+pub fn _load_flim_intensity_empirical_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     lifetime_array : &mut ArrayViewMut2<f64>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     
-    let mut intensity_read : Vec<u8> = vec![0; ((ydim * xdim)*std::mem::size_of::<u16>() as u32) as usize];
-    reader.seek(std::io::SeekFrom::Current(-(intensity_read.len() as i64)))?;
+    let intensity_bytes = ((ydim * xdim)*std::mem::size_of::<u16>() as u32) as usize;
+    reader.seek(std::io::SeekFrom::Current(-(intensity_bytes as i64)))?;
 
-    reader.read_exact(&mut intensity_read)?;
+    let intensity_read = reader.read_frame_bytes(intensity_bytes)?;
     
     // Set intensity_array's data to the `u16` version of intensity_read
-    let intensity = try_cast_slice::<u8, u16>(&intensity_read).map_err(
+    let intensity = intensity_read.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -119,10 +115,9 @@ pub fn _load_flim_intensity_empirical_compressed<T : Into<u64>>(
     );
 
     // All the photons in order as `u16`s
-    let mut lifetime_read : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_read)?;
+    let lifetime_read = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let arrival_times = try_cast_slice::<u8, u16>(&lifetime_read).map_err(
+    let arrival_times = lifetime_read.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -143,14 +138,14 @@ pub fn _load_flim_intensity_empirical_compressed<T : Into<u64>>(
 }
 
 
-#[binrw::parser(reader)]
-pub fn _load_flim_intensity_empirical_uncompressed<T : Into<u64>>(
+pub fn _load_flim_intensity_empirical_uncompressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     lifetime_array : &mut ArrayViewMut2<f64>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
     ydim : u32,
     xdim : u32,
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
  
     photonwise_op!(
         reader,
@@ -170,8 +165,8 @@ pub fn _load_flim_intensity_empirical_uncompressed<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _extract_mask_empirical_intensity_raw<T : Into<u64>>(
+pub fn _extract_mask_empirical_intensity_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
     lifetime_array : &mut ArrayViewMut1<f64>,
@@ -196,8 +191,8 @@ pub fn _extract_mask_empirical_intensity_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_mask_empirical_intensity_raw<T : Into<u64>>(
+pub fn _sum_mask_empirical_intensity_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lifetime_sum : &mut f64,
     intensity_sum : &mut u64,
@@ -223,8 +218,8 @@ pub fn _sum_mask_empirical_intensity_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_masks_empirical_intensity_raw<T : Into<u64>>(
+pub fn _sum_masks_empirical_intensity_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     lifetime_sum : &mut ArrayViewMut1<f64>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -262,8 +257,8 @@ pub fn _sum_masks_empirical_intensity_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_mask_empirical_intensity_compressed<T : Into<u64>>(
+pub fn _sum_mask_empirical_intensity_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lifetime_sum : &mut f64,
     intensity_sum : &mut u64,
@@ -276,20 +271,17 @@ pub fn _sum_mask_empirical_intensity_compressed<T : Into<u64>>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
-    let mut lifetime_data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_data)?;
+    let lifetime_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let lifetime_data = try_cast_slice::<u8, u16>(&lifetime_data).map_err(
+    let lifetime_data = lifetime_data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -310,8 +302,8 @@ pub fn _sum_mask_empirical_intensity_compressed<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_masks_empirical_intensity_compressed<T : Into<u64>>(
+pub fn _sum_masks_empirical_intensity_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     lifetime_sum : &mut ArrayViewMut1<f64>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -324,20 +316,17 @@ pub fn _sum_masks_empirical_intensity_compressed<T : Into<u64>>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
-    let mut lifetime_data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_data)?;
+    let lifetime_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let lifetime_data = try_cast_slice::<u8, u16>(&lifetime_data).map_err(
+    let lifetime_data = lifetime_data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -368,8 +357,8 @@ pub fn _sum_masks_empirical_intensity_compressed<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _extract_mask_empirical_intensity_compressed<T : Into<u64>>(
+pub fn _extract_mask_empirical_intensity_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
     lifetime_array : &mut ArrayViewMut1<f64>,
@@ -383,20 +372,17 @@ pub fn _extract_mask_empirical_intensity_compressed<T : Into<u64>>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
-    let mut lifetime_data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_data)?;
+    let lifetime_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let lifetime_data = try_cast_slice::<u8, u16>(&lifetime_data).map_err(
+    let lifetime_data = lifetime_data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
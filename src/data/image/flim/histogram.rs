@@ -3,6 +3,7 @@
 use binrw::io::{Read, Seek};
 use bytemuck::try_cast_slice;
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use itertools::izip;
 
 use std::io::{
@@ -11,7 +12,7 @@ use std::io::{
 };
 
 use crate::{
-    data::image::utils::photonwise_op, tiff::{
+    data::image::utils::{photonwise_op, for_each_photon}, tiff::{
     Tag, TiffTagID::{Siff, StripByteCounts, StripOffsets}, IFD
     }, CorrosiffError
 };
@@ -35,15 +36,14 @@ fn _load_histogram_compressed<I, ReaderT>(
     reader : &mut ReaderT,
     histogram : &mut ArrayViewMut1<u64>
     ) -> Result<(), IOError> 
-    where I : IFD, ReaderT : Read + Seek {
+    where I : IFD, ReaderT : FrameRead {
 
     let strip_byte_counts = ifd.get_tag(StripByteCounts).ok_or(IOError::other("Failed to get StripByteCounts"))?.value();
 
-    let mut data: Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut data)?;
+    let data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
     let hlen = histogram.len();
-    try_cast_slice::<u8, u16>(&data).map_err(
+    data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?.iter().for_each(|&x| {histogram[x as usize % hlen] += 1});
 
@@ -56,21 +56,17 @@ fn _load_histogram_uncompressed<I, ReaderT>(
     reader : &mut ReaderT,
     histogram : &mut ArrayViewMut1<u64>
     ) -> Result<(), IOError> 
-    where ReaderT : Read + Seek, I : IFD{
+    where ReaderT : FrameRead, I : IFD{
 
     let strip_byte_counts = ifd.get_tag(StripByteCounts).ok_or(IOError::other("Failed to get StripByteCounts"))?.value();
-    let mut data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut data)?;
+    let data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
     let hlen = histogram.len();
 
-    unsafe {
-        let (_, data, _) = data.align_to::<u64>();
-        data.iter().for_each(|&x| {
-            let tau = photon_to_tau_USIZE!(x);
-            histogram[tau % hlen] += 1;
-        });
-    }
+    for_each_photon(&data, |&x| {
+        let tau = photon_to_tau_USIZE!(x);
+        histogram[tau % hlen] += 1;
+    });
 
     Ok(())
 }
@@ -105,7 +101,7 @@ fn _load_histogram_uncompressed<I, ReaderT>(
 /// ```
 pub fn load_histogram<I, ReaderT>(
     ifd: &I, reader: &mut ReaderT, histogram : &mut ArrayViewMut1<u64>
-    )-> Result<(), IOError> where I : IFD, ReaderT : Read + Seek {
+    )-> Result<(), IOError> where I : IFD, ReaderT : FrameRead {
 
     let curr_pos = reader.stream_position()?;
     reader.seek(
@@ -132,12 +128,12 @@ pub fn load_histogram<I, ReaderT>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-fn _load_histogram_mask_uncompressed<I : IFD>(
-    ifd: &I,
+fn _load_histogram_mask_uncompressed<R : FrameRead, I : IFD>(
+    reader : &mut R,
+    ifd : &I,
     mask : &ArrayView2<bool>,
     histogram : &mut ArrayViewMut1<u64>,
-    ) -> Result<(), IOError> {
+) -> Result<(), IOError> {
 
     let xdim = ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32;
     let ydim = ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32;
@@ -156,12 +152,12 @@ fn _load_histogram_mask_uncompressed<I : IFD>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-fn _load_histogram_mask_compressed<I : IFD>(
-    ifd: &I,
+fn _load_histogram_mask_compressed<R : FrameRead, I : IFD>(
+    reader : &mut R,
+    ifd : &I,
     mask : &ArrayView2<bool>,
-    histogram : &mut ArrayViewMut1<u64>
-    ) -> Result<(), IOError> {
+    histogram : &mut ArrayViewMut1<u64>,
+) -> Result<(), IOError> {
 
     let xdim = ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32;
     let ydim = ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32;
@@ -170,24 +166,21 @@ fn _load_histogram_mask_compressed<I : IFD>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
     let strip_byte_counts = ifd.get_tag(StripByteCounts).ok_or(IOError::other("Failed to get StripByteCounts"))?.value();
 
-    let mut data: Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut data)?;
+    let data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
     let hlen = histogram.len();
 
-    let arrival_times = try_cast_slice::<u8, u16>(&data).map_err(
+    let arrival_times = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -210,7 +203,7 @@ fn _load_histogram_mask_compressed<I : IFD>(
     // // come back to this! Maybe there's a mistake in how
     // // the data is being saved??? Or maybe sometimes the laser
     // // sync is missed, like one in every several thousand pulses?
-    // try_cast_slice::<u8, u16>(&data).map_err(
+    // data.cast::<u16>().map_err(
     //     |err| IOError::new(IOErrorKind::InvalidData, err)
     // )?.iter().zip(mask.iter()).for_each(|(&x, &m)| if m != 0 && x < histogram.len() as u16 {histogram[x as usize] += 1});
 
@@ -220,7 +213,7 @@ fn _load_histogram_mask_compressed<I : IFD>(
 /// Reads the data pointed to by the IFD and uses it to
 /// increment the counts of the histogram provided by all
 /// pixels within the mask.
-pub fn load_histogram_mask<I : IFD, ReaderT : Read + Seek>(
+pub fn load_histogram_mask<I : IFD, ReaderT : FrameRead>(
     reader: &mut ReaderT,
     ifd: &I,
     histogram : &mut ArrayViewMut1<u64>,
@@ -238,10 +231,10 @@ pub fn load_histogram_mask<I : IFD, ReaderT : Read + Seek>(
     )?;
     match ifd.get_tag(Siff).ok_or(IOError::other("Failed to get Siff tag"))?.value().into() {
         0 => {
-            _load_histogram_mask_uncompressed(reader, binrw::Endian::Little, (ifd, mask, histogram))
+            _load_histogram_mask_uncompressed(reader, ifd, mask, histogram)
         },
         1 => {
-            _load_histogram_mask_compressed(reader, binrw::Endian::Little, (ifd, mask, histogram))
+            _load_histogram_mask_compressed(reader, ifd, mask, histogram)
         },
         _ => {
             Err(IOError::new(IOErrorKind::InvalidData,
@@ -259,7 +252,7 @@ pub fn load_histogram_mask<I : IFD, ReaderT : Read + Seek>(
 /// Reads the data pointed to by the IFD and uses it to
 /// increment the counts of the histogram provided by all
 /// pixels within the mask, adjusting for the registration of the frame.
-pub fn load_histogram_mask_registered<I : IFD, ReaderT : Read + Seek>(
+pub fn load_histogram_mask_registered<I : IFD, ReaderT : FrameRead>(
     reader: &mut ReaderT,
     ifd : &I,
     histogram : &mut ArrayViewMut1<u64>,
@@ -294,7 +287,7 @@ impl FlimHistogram {
     /// 
     /// A new FlimHistogram 
     fn from_ifd<'a, 'b, I, ReaderT>(ifd : &'a I, reader : &'b mut ReaderT, n_bins : u32)
-    -> Result<Self, IOError> where I : IFD, ReaderT : Read + Seek {
+    -> Result<Self, IOError> where I : IFD, ReaderT : FrameRead {
         let curr_pos = reader.stream_position()?;
 
         reader.seek(
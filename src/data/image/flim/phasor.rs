@@ -28,6 +28,7 @@ use registered::{
 
 use std::io::{Error as IOError, ErrorKind as IOErrorKind};
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use num_complex::Complex;
 use binrw::io::{Read, Seek};
 use crate::{
@@ -79,7 +80,7 @@ pub (crate) mod exports {
 /// TODO: Write me!
 /// ```
 /// 
-pub fn load_flim_phasor_and_intensity_arrays<I : IFD, ReaderT: Read + Seek> (
+pub fn load_flim_phasor_and_intensity_arrays<I : IFD, ReaderT : FrameRead> (
     reader : &mut ReaderT,
     ifd : &I,
     phasor_data : &mut ArrayViewMut2<Complex<f64>>,
@@ -149,7 +150,7 @@ pub fn load_flim_phasor_and_intensity_arrays<I : IFD, ReaderT: Read + Seek> (
 /// TODO: Write me!
 /// ```
 /// 
-pub fn load_flim_phasor_and_intensity_arrays_registered<I : IFD, ReaderT: Read + Seek> (
+pub fn load_flim_phasor_and_intensity_arrays_registered<I : IFD, ReaderT : FrameRead> (
     reader : &mut ReaderT,
     ifd : &I,
     phasor_data : &mut ArrayViewMut2<Complex<f64>>,
@@ -193,7 +194,7 @@ pub fn load_flim_phasor_and_intensity_arrays_registered<I : IFD, ReaderT: Read +
 
 /// Populates a 1d masked phasor and intensity array based on the frame of interest. The
 /// frame is not registered during reading.
-pub fn extract_phasor_and_intensity_mask<I : IFD, ReaderT : Read + Seek>(
+pub fn extract_phasor_and_intensity_mask<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     phasor_data : &mut ArrayViewMut1<Complex<f64>>,
@@ -237,7 +238,7 @@ pub fn extract_phasor_and_intensity_mask<I : IFD, ReaderT : Read + Seek>(
     )
 }
 
-pub fn extract_phasor_and_intensity_mask_registered<I : IFD, ReaderT : Read + Seek>(
+pub fn extract_phasor_and_intensity_mask_registered<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     phasor_data : &mut ArrayViewMut1<Complex<f64>>,
@@ -315,7 +316,7 @@ pub fn extract_phasor_and_intensity_mask_registered<I : IFD, ReaderT : Read + Se
 /// use std::fs::File;
 /// TODO:
 /// ```
-pub fn sum_phasor_intensity_mask< I : IFD, ReaderT : Read + Seek>(
+pub fn sum_phasor_intensity_mask< I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     phasor : &mut Complex<f64>,
@@ -390,7 +391,7 @@ pub fn sum_phasor_intensity_mask< I : IFD, ReaderT : Read + Seek>(
 /// use std::fs::File;
 /// TODO:
 /// ```
-pub fn sum_phasor_intensity_mask_registered< I :IFD, ReaderT: Read + Seek>(
+pub fn sum_phasor_intensity_mask_registered< I :IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     phasor : &mut Complex<f64>,
@@ -461,7 +462,7 @@ pub fn sum_phasor_intensity_mask_registered< I :IFD, ReaderT: Read + Seek>(
 /// ## Example
 /// 
 /// 
-pub fn sum_phasor_intensity_masks< I : IFD, ReaderT : Read + Seek>(
+pub fn sum_phasor_intensity_masks< I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     phasor : &mut ArrayViewMut1<Complex<f64>>,
@@ -530,7 +531,7 @@ pub fn sum_phasor_intensity_masks< I : IFD, ReaderT : Read + Seek>(
 /// ## Example
 /// 
 /// 
-pub fn sum_phasor_intensity_masks_registered<I:IFD, ReaderT: Read + Seek>(
+pub fn sum_phasor_intensity_masks_registered<I:IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     phasor : &mut ArrayViewMut1<Complex<f64>>,
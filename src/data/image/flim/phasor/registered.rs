@@ -7,6 +7,7 @@ use num_complex::{Complex, c64};
 use bytemuck::try_cast_slice;
 use itertools::izip;
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 
 use crate::{
     data::image::flim::phasor::unregistered::*,
@@ -15,8 +16,8 @@ use crate::{
     CorrosiffError,
 };
 
-#[binrw::parser(reader, endian)]
-pub fn _load_flim_intensity_phasor_compressed_registered<T : Into<u64>>(
+pub fn _load_flim_intensity_phasor_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut2<Complex<f64>>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
@@ -25,11 +26,10 @@ pub fn _load_flim_intensity_phasor_compressed_registered<T : Into<u64>>(
     cos_lookup : &ArrayView1<f64>,
     sin_lookup : &ArrayView1<f64>,
     registration : (i32, i32),
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     
     _load_flim_intensity_phasor_compressed(
-        reader, endian,
-        (phasor_array, intensity_array, strip_byte_counts.into(), ydim, xdim, cos_lookup, sin_lookup)
+        reader, phasor_array, intensity_array, strip_byte_counts.into(), ydim, xdim, cos_lookup, sin_lookup
     )?;
 
     roll_inplace(phasor_array, registration);
@@ -39,8 +39,8 @@ pub fn _load_flim_intensity_phasor_compressed_registered<T : Into<u64>>(
 }
 
 
-#[binrw::parser(reader)]
-pub fn _load_flim_intensity_phasor_raw_registered<T : Into<u64>>(
+pub fn _load_flim_intensity_phasor_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut2<Complex<f64>>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
@@ -49,7 +49,7 @@ pub fn _load_flim_intensity_phasor_raw_registered<T : Into<u64>>(
     cos_lookup : &ArrayView1<f64>,
     sin_lookup : &ArrayView1<f64>,
     registration : (i32, i32),
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
  
     photonwise_op!(
         reader,
@@ -73,8 +73,8 @@ pub fn _load_flim_intensity_phasor_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _extract_mask_phasor_intensity_raw_registered<T : Into<u64>>(
+pub fn _extract_mask_phasor_intensity_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut1<Complex<f64>>,
     intensity_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
@@ -109,8 +109,8 @@ pub fn _extract_mask_phasor_intensity_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn _extract_mask_phasor_intensity_compressed_registered<T : Into<u64>>(
+pub fn _extract_mask_phasor_intensity_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut1<Complex<f64>>,
     intensity_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
@@ -126,16 +126,16 @@ pub fn _extract_mask_phasor_intensity_compressed_registered<T : Into<u64>>(
     let mask_rolled = roll(mask, (-registration.0, -registration.1));
     let lookup_table_rolled = roll(&lookup_table, (-registration.0, -registration.1));
     _extract_mask_phasor_intensity_compressed(
-        reader, endian,
-        (phasor_array, intensity_array, &mask_rolled.view(), strip_byte_counts.into(), ydim, xdim, &cos_lookup, &sin_lookup, &lookup_table_rolled.view())
+        reader, phasor_array, intensity_array, &mask_rolled.view(), strip_byte_counts.into(),
+        ydim, xdim, &cos_lookup, &sin_lookup, &lookup_table_rolled.view()
     )?;
     Ok(())
 }
 
 
 
-#[binrw::parser(reader)]
-pub fn _sum_mask_phasor_intensity_raw_registered<T : Into<u64>>(
+pub fn _sum_mask_phasor_intensity_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     phasor_sum : &mut Complex<f64>,
     intensity_sum : &mut u64,
@@ -167,8 +167,8 @@ pub fn _sum_mask_phasor_intensity_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_masks_phasor_intensity_raw_registered<T : Into<u64>>(
+pub fn _sum_masks_phasor_intensity_raw_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     phasor_sum : &mut ArrayViewMut1<Complex<f64>>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -213,8 +213,8 @@ pub fn _sum_masks_phasor_intensity_raw_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn _sum_mask_phasor_intensity_compressed_registered<T : Into<u64>>(
+pub fn _sum_mask_phasor_intensity_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     phasor_sum : &mut Complex<f64>,
     intensity_sum : &mut u64,
@@ -227,16 +227,17 @@ pub fn _sum_mask_phasor_intensity_compressed_registered<T : Into<u64>>(
 ) -> Result<(), CorrosiffError> {
 
     let mask_rolled = roll(mask, (-registration.0, -registration.1));
-    _sum_mask_phasor_intensity_compressed(reader, endian, 
-        (&mask_rolled.view(), phasor_sum, intensity_sum, strip_byte_counts.into(), ydim, xdim, cos_lookup, sin_lookup)
+    _sum_mask_phasor_intensity_compressed(
+        reader, &mask_rolled.view(), phasor_sum, intensity_sum, strip_byte_counts.into(),
+        ydim, xdim, cos_lookup, sin_lookup
     )?;
 
     Ok(())
 }
 
 
-#[binrw::parser(reader, endian)]
-pub fn _sum_masks_phasor_intensity_compressed_registered<T : Into<u64>>(
+pub fn _sum_masks_phasor_intensity_compressed_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     phasor_sum : &mut ArrayViewMut1<Complex<f64>>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -255,8 +256,9 @@ pub fn _sum_masks_phasor_intensity_compressed_registered<T : Into<u64>>(
         roll_inplace(&mut mask.view_mut(), (-registration.0, -registration.1));
     });
 
-    _sum_masks_phasor_intensity_compressed(reader, endian,
-        (&masks_rolled.view(), phasor_sum, intensity_sum, strip_byte_counts.into(), ydim, xdim, cos_lookup, sin_lookup)
+    _sum_masks_phasor_intensity_compressed(
+        reader, &masks_rolled.view(), phasor_sum, intensity_sum, strip_byte_counts.into(),
+        ydim, xdim, cos_lookup, sin_lookup
     )?;
 
     Ok(())
@@ -3,13 +3,14 @@ use num_complex::{Complex, c64};
 use bytemuck::try_cast_slice;
 use itertools::izip;
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 
 use crate::{
     CorrosiffError, data::image::{dimensions::macros::*, flim::phasor, utils::photonwise_op}
 };
 
-#[binrw::parser(reader)]
-pub fn _load_flim_intensity_phasor_compressed<T : Into<u64>>(
+pub fn _load_flim_intensity_phasor_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut2<Complex<f64>>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
@@ -17,18 +18,15 @@ pub fn _load_flim_intensity_phasor_compressed<T : Into<u64>>(
     xdim : u32,
     cos_lookup : &ArrayView1<f64>,
     sin_lookup : &ArrayView1<f64>,
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
     
-    let mut intensity_read : Vec<u8> = vec![0;
-        ((ydim * xdim)*std::mem::size_of::<u16>() as u32) as usize
-    ];
-
-    reader.seek(std::io::SeekFrom::Current(-(intensity_read.len() as i64)))?;
+    let intensity_bytes = ((ydim * xdim)*std::mem::size_of::<u16>() as u32) as usize;
+    reader.seek(std::io::SeekFrom::Current(-(intensity_bytes as i64)))?;
 
-    reader.read_exact(&mut intensity_read)?;
+    let intensity_read = reader.read_frame_bytes(intensity_bytes)?;
 
     // Set intensity_array's data to the `u16` version of intensity_read
-    let intensity = try_cast_slice::<u8, u16>(&intensity_read).map_err(
+    let intensity = intensity_read.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -39,10 +37,9 @@ pub fn _load_flim_intensity_phasor_compressed<T : Into<u64>>(
         ).map_err(|err| IOError::new(IOErrorKind::InvalidData, err))?
     );
 
-    let mut arrival_time_read : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut arrival_time_read)?;
+    let arrival_time_read = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let arrival_times = try_cast_slice::<u8, u16>(&arrival_time_read).map_err(
+    let arrival_times = arrival_time_read.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -66,8 +63,8 @@ pub fn _load_flim_intensity_phasor_compressed<T : Into<u64>>(
 }
 
 
-#[binrw::parser(reader)]
-pub fn _load_flim_intensity_phasor_raw<T : Into<u64>>(
+pub fn _load_flim_intensity_phasor_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut2<Complex<f64>>,
     intensity_array : &mut ArrayViewMut2<u16>,
     strip_byte_counts : T,
@@ -75,7 +72,7 @@ pub fn _load_flim_intensity_phasor_raw<T : Into<u64>>(
     xdim : u32,
     cos_lookup : &ArrayView1<f64>,
     sin_lookup : &ArrayView1<f64>,
-    ) -> Result<(), CorrosiffError> {
+) -> Result<(), CorrosiffError> {
  
     photonwise_op!(
         reader,
@@ -99,8 +96,8 @@ pub fn _load_flim_intensity_phasor_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _extract_mask_phasor_intensity_raw<T : Into<u64>>(
+pub fn _extract_mask_phasor_intensity_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut1<Complex<f64>>,
     intensity_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
@@ -135,8 +132,8 @@ pub fn _extract_mask_phasor_intensity_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn _extract_mask_phasor_intensity_compressed<T : Into<u64>>(
+pub fn _extract_mask_phasor_intensity_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     phasor_array : &mut ArrayViewMut1<Complex<f64>>,
     intensity_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
@@ -152,20 +149,17 @@ pub fn _extract_mask_phasor_intensity_compressed<T : Into<u64>>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
-    let mut lifetime_data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_data)?;
+    let lifetime_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let lifetime_data = try_cast_slice::<u8, u16>(&lifetime_data).map_err(
+    let lifetime_data = lifetime_data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -194,8 +188,8 @@ pub fn _extract_mask_phasor_intensity_compressed<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_mask_phasor_intensity_raw<T : Into<u64>>(
+pub fn _sum_mask_phasor_intensity_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     phasor_sum : &mut Complex<f64>,
     intensity_sum : &mut u64,
@@ -226,8 +220,8 @@ pub fn _sum_mask_phasor_intensity_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_masks_phasor_intensity_raw<T : Into<u64>>(
+pub fn _sum_masks_phasor_intensity_raw<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     phasor_sum : &mut ArrayViewMut1<Complex<f64>>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -271,8 +265,8 @@ pub fn _sum_masks_phasor_intensity_raw<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn _sum_mask_phasor_intensity_compressed<T : Into<u64>>(
+pub fn _sum_mask_phasor_intensity_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     mask : &ArrayView2<bool>,
     phasor_sum : &mut Complex<f64>,
     intensity_sum : &mut u64,
@@ -287,20 +281,17 @@ pub fn _sum_mask_phasor_intensity_compressed<T : Into<u64>>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
-    let mut lifetime_data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_data)?;
+    let lifetime_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let lifetime_data = try_cast_slice::<u8, u16>(&lifetime_data).map_err(
+    let lifetime_data = lifetime_data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -324,8 +315,8 @@ pub fn _sum_mask_phasor_intensity_compressed<T : Into<u64>>(
 }
 
 
-#[binrw::parser(reader)]
-pub fn _sum_masks_phasor_intensity_compressed<T : Into<u64>>(
+pub fn _sum_masks_phasor_intensity_compressed<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     masks : &ArrayView3<bool>,
     phasor_sum : &mut ArrayViewMut1<Complex<f64>>,
     intensity_sum : &mut ArrayViewMut1<u64>,
@@ -340,20 +331,17 @@ pub fn _sum_masks_phasor_intensity_compressed<T : Into<u64>>(
         -((ydim * xdim * std::mem::size_of::<u16>() as u32) as i64)
     ))?;
 
-    let mut data : Vec<u8> = vec![0;
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let intensity_data = try_cast_slice::<u8, u16>(&data).map_err(
+    let intensity_data = data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
-    let mut lifetime_data : Vec<u8> = vec![0; strip_byte_counts.into() as usize];
-    reader.read_exact(&mut lifetime_data)?;
+    let lifetime_data = reader.read_frame_bytes(strip_byte_counts.into() as usize)?;
 
-    let lifetime_data = try_cast_slice::<u8, u16>(&lifetime_data).map_err(
+    let lifetime_data = lifetime_data.cast::<u16>().map_err(
         |err| IOError::new(IOErrorKind::InvalidData, err)
     )?;
 
@@ -8,6 +8,7 @@
 use binrw::io::{Read, Seek};
 use bytemuck::try_cast_slice;
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 
 use std::io::{
     Error as IOError,
@@ -25,8 +26,12 @@ use crate::data::image::{
     utils::load_array_from_siff,
     intensity::siff::{
         tiff::{
+            TiffLayout,
             load_array_tiff,
             load_array_tiff_registered,
+            extract_mask_tiff,
+            sum_mask_tiff,
+            sum_masks_tiff,
         },
         registered::{
             load_array_raw_siff_registered,
@@ -72,16 +77,16 @@ pub (crate) mod exports {
 
 /// Parses a raw `.siff` format frame and returns
 /// an `Intensity` struct containing the intensity data.
-#[binrw::parser(reader, endian)]
-fn raw_siff_parser<T : Into<u64>>(
+fn raw_siff_parser<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     strip_bytes : T,
     ydim : u32,
-    xdim : u32
-    ) -> binrw::BinResult<Array2<u16>> {
+    xdim : u32,
+) -> binrw::BinResult<Array2<u16>> {
     let mut frame = Array2::<u16>::zeros(
         (ydim as usize, xdim as usize)
     );
-    load_array_raw_siff(reader, endian, (&mut frame.view_mut(), strip_bytes, ydim, xdim))?;
+    load_array_raw_siff(reader, &mut frame.view_mut(), strip_bytes, ydim, xdim)?;
     Ok(frame)
 }
 
@@ -90,17 +95,17 @@ fn raw_siff_parser<T : Into<u64>>(
 /// 
 /// Expected to be at the data strip, so it will go backwards by the size of the
 /// intensity data and read that.
-#[binrw::parser(reader, endian)]
-fn compressed_siff_parser(
-        ydim : u32,
-        xdim : u32
-    ) -> binrw::BinResult<Array2<u16>> {
+fn compressed_siff_parser<R : FrameRead>(
+    reader : &mut R,
+    ydim : u32,
+    xdim : u32,
+) -> binrw::BinResult<Array2<u16>> {
     
     let mut frame = Array2::<u16>::zeros(
         (ydim as usize, xdim as usize)
     );
 
-    load_array_compressed_siff(reader, endian, (&mut frame.view_mut(), ydim, xdim))?;
+    load_array_compressed_siff(reader, &mut frame.view_mut(), ydim, xdim)?;
     Ok(frame)
 }
 
@@ -136,8 +141,10 @@ pub fn load_array<'a, ReaderT, I>(
         reader : &'a mut ReaderT,
         ifd : &'a I,
         array : &'a mut ArrayViewMut2<u16>
-    ) -> Result<(), CorrosiffError> where I : IFD, ReaderT : Read + Seek{
+    ) -> Result<(), CorrosiffError> where I : IFD, ReaderT : FrameRead{
     
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -162,6 +169,7 @@ pub fn load_array<'a, ReaderT, I>(
             load_array_tiff,
             (
                 &mut array.view_mut(),
+                &layout,
                 ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32
             )
@@ -213,8 +221,10 @@ pub fn load_array_registered<'a, T, S>(
     ifd : &'a S,
     array : &'a mut ArrayViewMut2<u16>,
     registration : (i32, i32),    
-) -> Result<(), CorrosiffError> where S : IFD, T : Read + Seek {
+) -> Result<(), CorrosiffError> where S : IFD, T : FrameRead {
     
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -241,6 +251,7 @@ pub fn load_array_registered<'a, T, S>(
             load_array_tiff_registered,
             (
                 &mut array.view_mut(),
+                &layout,
                 ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
                 registration
@@ -261,13 +272,15 @@ pub fn load_array_registered<'a, T, S>(
 /// the flattened pixel map.
 /// * `mask` - The mask to apply to the frame, where `true` means
 /// the pixel should be loaded into the `target_array`.
-pub fn extract_mask<I : IFD, ReaderT : Read + Seek>(
+pub fn extract_mask<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     target_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
 ) -> Result<(), IOError> {
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -291,6 +304,18 @@ pub fn extract_mask<I : IFD, ReaderT : Read + Seek>(
                 ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32
             )
+        ),
+        (
+            extract_mask_tiff,
+            (
+                target_array,
+                mask,
+                lookup_table,
+                &layout,
+                ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
+                ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
+                (0, 0)
+            )
         )
     )
 }
@@ -308,7 +333,7 @@ pub fn extract_mask<I : IFD, ReaderT : Read + Seek>(
 /// * `mask` - The mask to apply to the frame, where `true` means
 /// the pixel should be loaded into the `target_array`
 /// * `registration` - A tuple of the pixelwise shifts
-pub fn extract_mask_registered<I : IFD, ReaderT : Read + Seek>(
+pub fn extract_mask_registered<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     target_array : &mut ArrayViewMut1<u16>,
@@ -317,6 +342,8 @@ pub fn extract_mask_registered<I : IFD, ReaderT : Read + Seek>(
     registration : (i32, i32),
 ) -> Result<(), IOError> {
     
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -342,6 +369,18 @@ pub fn extract_mask_registered<I : IFD, ReaderT : Read + Seek>(
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
                 registration
             )
+        ),
+        (
+            extract_mask_tiff,
+            (
+                target_array,
+                mask,
+                lookup_table,
+                &layout,
+                ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
+                ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
+                registration
+            )
         )
     )
 }
@@ -380,13 +419,15 @@ pub fn extract_mask_registered<I : IFD, ReaderT : Read + Seek>(
 /// 
 /// - `sum_mask_registered` - for summing the intensity
 /// data of a frame with registration
-pub fn sum_mask<I : IFD, ReaderT : Read + Seek>(
+pub fn sum_mask<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     frame_sum : &mut u64,
     mask : &ArrayView2<bool>,
 ) -> Result<(), IOError> {
 
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -408,11 +449,22 @@ pub fn sum_mask<I : IFD, ReaderT : Read + Seek>(
                 ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32
             )
+        ),
+        (
+            sum_mask_tiff,
+            (
+                frame_sum,
+                &mask.view(),
+                &layout,
+                ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
+                ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
+                (0, 0)
+            )
         )
     )
 }
 
-pub fn sum_mask_registered<I : IFD, ReaderT : Read + Seek>(
+pub fn sum_mask_registered<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     frame_sum : &mut u64,
@@ -420,6 +472,8 @@ pub fn sum_mask_registered<I : IFD, ReaderT : Read + Seek>(
     registration : (i32, i32),
 ) -> Result<(), IOError> {
     
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -443,6 +497,17 @@ pub fn sum_mask_registered<I : IFD, ReaderT : Read + Seek>(
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
                 registration
             )
+        ),
+        (
+            sum_mask_tiff,
+            (
+                frame_sum,
+                &mask.view(),
+                &layout,
+                ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
+                ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
+                registration
+            )
         )
     )
 }
@@ -480,13 +545,15 @@ pub fn sum_mask_registered<I : IFD, ReaderT : Read + Seek>(
 /// 
 /// - `sum_masks_registered` - for summing the intensity
 /// data of a frame with registration
-pub fn sum_masks<I : IFD, ReaderT: Read + Seek>(
+pub fn sum_masks<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     frame_sums : &mut ArrayViewMut1<u64>,
     masks : &ArrayView3<bool>,
 ) -> Result<(), IOError> {
 
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -508,6 +575,17 @@ pub fn sum_masks<I : IFD, ReaderT: Read + Seek>(
                 ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32
             )
+        ),
+        (
+            sum_masks_tiff,
+            (
+                &mut frame_sums.view_mut(),
+                &masks.view(),
+                &layout,
+                ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
+                ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
+                (0, 0)
+            )
         )
     )
 }
@@ -559,7 +637,7 @@ pub fn sum_masks<I : IFD, ReaderT: Read + Seek>(
 /// 
 /// - `sum_masks`` - for summing the intensity
 /// data of a frame without registration (faster)
-pub fn sum_masks_registered<I : IFD, ReaderT: Read + Seek>(
+pub fn sum_masks_registered<I : IFD, ReaderT : FrameRead>(
     reader : &mut ReaderT,
     ifd : &I,
     frame_sums : &mut ArrayViewMut1<u64>,
@@ -567,6 +645,8 @@ pub fn sum_masks_registered<I : IFD, ReaderT: Read + Seek>(
     registration : (i32, i32),
 ) -> Result<(), IOError> {
 
+    let layout = TiffLayout::for_ifd(ifd, reader)?;
+
     load_array_from_siff!(
         reader,
         ifd,
@@ -590,6 +670,17 @@ pub fn sum_masks_registered<I : IFD, ReaderT: Read + Seek>(
                 ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
                 registration
             )
+        ),
+        (
+            sum_masks_tiff,
+            (
+                &mut frame_sums.view_mut(),
+                &masks.view(),
+                &layout,
+                ifd.height().ok_or(IOError::other("Failed to get height"))?.into() as u32,
+                ifd.width().ok_or(IOError::other("Failed to get width"))?.into() as u32,
+                registration
+            )
         )
     )
 }
@@ -631,6 +722,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn multi_strip_tiff() {
+        use crate::tiff::{BigTag, TiffTagID, TiffTagType};
+        // A 4 x 2 image in two strips, stored out of order,
+        // with the strip offsets and byte counts out-of-line.
+        let image = (0..8u16).map(|x| x * 100).collect::<Vec<_>>();
+        let mut file = vec![0u8; 64];
+        [48u64, 32, 8, 8].iter().enumerate()
+            .for_each(|(i, x)| file[8*i..8*(i+1)].copy_from_slice(&x.to_le_bytes()));
+        file[48..56].copy_from_slice(bytemuck::cast_slice(&image[..4]));
+        file[32..40].copy_from_slice(bytemuck::cast_slice(&image[4..]));
+
+        let ifd = BigTiffIFD::from_tags(vec![
+            BigTag::new(TiffTagID::ImageWidth, TiffTagType::Long, 1, 2),
+            BigTag::new(TiffTagID::ImageLength, TiffTagType::Long, 1, 4),
+            BigTag::new(TiffTagID::BitsPerSample, TiffTagType::Short, 1, 16),
+            BigTag::new(TiffTagID::StripOffsets, TiffTagType::Long8, 2, 0),
+            BigTag::new(TiffTagID::StripByteCounts, TiffTagType::Long8, 2, 16),
+        ], None);
+        let mut reader = std::io::Cursor::new(file);
+        assert_eq!(ifd.strips(&mut reader).unwrap(), vec![(48, 8), (32, 8)]);
+
+        let mut array = Array2::<u16>::zeros((4, 2));
+        load_array(&mut reader, &ifd, &mut array.view_mut()).unwrap();
+        assert_eq!(array.iter().cloned().collect::<Vec<_>>(), image);
+        assert_eq!(reader.position(), 0);
+
+        load_array_registered(&mut reader, &ifd, &mut array.view_mut(), (1, 0)).unwrap();
+        assert_eq!(array[[1, 0]], 0);
+    }
+
     #[test]
     fn frame_vs_siffreader(){
         let test_paths = get_test_paths().expect("Failed to read test paths");
@@ -1,5 +1,6 @@
 use bytemuck::try_cast_slice;
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use itertools::izip;
 use std::io::{
     Error as IOError,
@@ -42,14 +43,14 @@ use crate::data::image::{
 /// 
 /// `load_array_raw_siff` - for loading an array
 /// without registration (plausibly faster?)
-#[binrw::parser(reader)]
-pub fn load_array_raw_siff_registered<T : Into<u64>> (
+pub fn load_array_raw_siff_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     array : &mut ArrayViewMut2<u16>,
     strip_bytes : T,
     ydim : u32,
     xdim : u32,
     registration : (i32, i32),
-    ) -> binrw::BinResult<()> {
+) -> binrw::BinResult<()> {
         
     photonwise_op!(
         reader,
@@ -67,8 +68,8 @@ pub fn load_array_raw_siff_registered<T : Into<u64>> (
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn extract_mask_raw_siff_registered<T : Into<u64>>(
+pub fn extract_mask_raw_siff_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     target_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
@@ -93,8 +94,8 @@ pub fn extract_mask_raw_siff_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn sum_mask_raw_siff_registered<T : Into<u64>>(
+pub fn sum_mask_raw_siff_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     frame_sum : &mut u64,
     mask : &ArrayView2<bool>,
     strip_bytes : T,
@@ -119,8 +120,8 @@ pub fn sum_mask_raw_siff_registered<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn sum_masks_raw_siff_registered<T : Into<u64>>(
+pub fn sum_masks_raw_siff_registered<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     frame_sums : &mut ArrayViewMut1<u64>,
     masks : &ArrayView3<bool>,
     strip_bytes : T,
@@ -150,21 +151,21 @@ pub fn sum_masks_raw_siff_registered<T : Into<u64>>(
 }
 
 /// Why doesn't ndarray have a roll method??
-#[binrw::parser(reader, endian)]
-pub fn load_array_compressed_siff_registered(
-        array : &mut ArrayViewMut2<u16>,
-        ydim : u32,
-        xdim : u32,
-        registration : (i32, i32),
-    ) -> binrw::BinResult<()> {
+pub fn load_array_compressed_siff_registered<R : FrameRead>(
+    reader : &mut R,
+    array : &mut ArrayViewMut2<u16>,
+    ydim : u32,
+    xdim : u32,
+    registration : (i32, i32),
+) -> binrw::BinResult<()> {
 
-    load_array_compressed_siff(reader, endian, (array, ydim, xdim))?;
+    load_array_compressed_siff(reader, array, ydim, xdim)?;
     roll_inplace(array, registration);
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn extract_mask_compressed_siff_registered(
+pub fn extract_mask_compressed_siff_registered<R : FrameRead>(
+    reader : &mut R,
     target_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
@@ -177,8 +178,7 @@ pub fn extract_mask_compressed_siff_registered(
         (ydim as usize, xdim as usize),
         vec![0; ydim as usize * xdim as usize]
     ).unwrap();
-    load_array_compressed_siff_registered(reader, endian,
-        (&mut frame_array.view_mut(), ydim, xdim, registration))?;
+    load_array_compressed_siff_registered(reader, &mut frame_array.view_mut(), ydim, xdim, registration)?;
     
     for (&mask_px, &frame_px, &lookup_px) in izip!(
         mask.iter(), frame_array.iter(), lookup_table.iter()
@@ -189,8 +189,8 @@ pub fn extract_mask_compressed_siff_registered(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn sum_mask_compressed_siff_registered(
+pub fn sum_mask_compressed_siff_registered<R : FrameRead>(
+    reader : &mut R,
     frame_sum : &mut u64,
     mask : &ArrayView2<bool>,
     ydim : u32,
@@ -201,12 +201,11 @@ pub fn sum_mask_compressed_siff_registered(
         -(ydim as i64 * xdim as i64 * std::mem::size_of::<u16>() as i64)
     ))?;
     
-    let mut data : Vec<u8> = vec![0; 
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let data = try_cast_slice::<u8, u16>(&data).map_err(|err| binrw::Error::Io(
+    let data = data.cast::<u16>().map_err(|err| binrw::Error::Io(
         IOError::new(IOErrorKind::InvalidData, err))
     )?;
 
@@ -225,8 +224,8 @@ pub fn sum_mask_compressed_siff_registered(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn sum_masks_compressed_siff_registered(
+pub fn sum_masks_compressed_siff_registered<R : FrameRead>(
+    reader : &mut R,
     frame_sums : &mut ArrayViewMut1<u64>,
     masks : &ArrayView3<bool>,
     ydim : u32,
@@ -238,12 +237,11 @@ pub fn sum_masks_compressed_siff_registered(
         -(ydim as i64 * xdim as i64 * std::mem::size_of::<u16>() as i64)
     ))?;
     
-    let mut data : Vec<u8> = vec![0; 
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let data = try_cast_slice::<u8, u16>(&data).map_err(|err| binrw::Error::Io(
+    let data = data.cast::<u16>().map_err(|err| binrw::Error::Io(
         IOError::new(IOErrorKind::InvalidData, err))
     )?;
 
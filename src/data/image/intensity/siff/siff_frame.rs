@@ -1,4 +1,5 @@
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use binrw::io::{Read, Seek, Write};
 use binrw::BinWrite;
 
@@ -53,7 +54,7 @@ impl SiffFrame {
     /// this will throw an `IOError`
     #[allow(dead_code)]
     pub fn from_ifd<'a, 'b, I, ReaderT>(ifd : &'a I, reader : &'b mut ReaderT) 
-    -> Result<Self, IOError> where I : IFD, ReaderT : Read + Seek {
+    -> Result<Self, IOError> where I : IFD, ReaderT : FrameRead {
         let cur_pos = reader.stream_position()?;
 
         reader.seek(
@@ -67,20 +68,20 @@ impl SiffFrame {
 
         let parsed = match ifd.get_tag(Siff).unwrap().value().into() {
             0 => {
-                raw_siff_parser(reader, binrw::Endian::Little,
-                (
+                raw_siff_parser(
+                    reader,
                     ifd.get_tag(StripByteCounts).unwrap().value(),
                     ifd.height().unwrap().into() as u32,
                     ifd.width().unwrap().into() as u32,
                 )
-            )},
+            },
             1 => {
-                compressed_siff_parser(reader, binrw::Endian::Little, 
-                (
+                compressed_siff_parser(
+                    reader,
                     ifd.height().unwrap().into() as u32,
                     ifd.width().unwrap().into() as u32,
                 )
-            )},
+            },
             _ => {Err(
                 binrw::error::Error::Io(IOError::new(
                     IOErrorKind::InvalidData, "Invalid Siff tag")
@@ -103,7 +104,7 @@ impl SiffFrame {
     /// First writes the head of the IFD, then drops the data in place
     /// as a flat array of u16s.
     pub fn write_frame_as_tiff
-    <ReaderT : Read + Seek, WriterT : Write + Seek, I : IFD>
+    <ReaderT : FrameRead, WriterT : Write + Seek, I : IFD>
     (reader : &mut ReaderT, writer : &mut WriterT, ifd : &I)
     -> binrw::io::Result<()> {
 
@@ -220,7 +221,8 @@ impl SiffFrame {
         };
         let pos = writer.stream_position()?;
         // write the next IFD location
-        let mut next_ifd : u64 = pos + image_size + description_length + std::mem::size_of::<I::PointerSize>() as u64;
+        let end_of_frame : u64 = pos + image_size + description_length + std::mem::size_of::<I::PointerSize>() as u64;
+        let mut next_ifd = end_of_frame;
         if ifd.next_ifd().is_none() || (ifd.next_ifd().unwrap().into() == 0) {
             next_ifd = 0;
         }
@@ -247,7 +249,7 @@ impl SiffFrame {
         };
 
         // debug_assert_eq!(amount_written, image_size as usize);
-        debug_assert_eq!(writer.stream_position()?, next_ifd);
+        debug_assert_eq!(writer.stream_position()?, end_of_frame);
 
         Ok(())
     }
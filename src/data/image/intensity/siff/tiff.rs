@@ -1,60 +1,215 @@
 use binrw;
+use binrw::io::{Read, Seek};
 use ndarray::prelude::*;
-use bytemuck::try_cast_slice;
+use crate::utils::FrameRead;
+use itertools::{izip, Itertools};
 use std::io::{
     Error as IOError,
     ErrorKind as IOErrorKind,
+    SeekFrom,
 };
 
 use crate::data::image::dimensions::roll_inplace;
+use crate::tiff::{
+    IFD,
+    Tag,
+    TiffTagID::{BitsPerSample, Compression, SampleFormat, SamplesPerPixel, Siff},
+};
+
+/// Where and how the pixels of a plain (non-`.siff`)
+/// Tiff frame are stored.
+///
+/// ## Fields
+///
+/// * `strips` - The (offset, byte count) of each strip
+/// of the image, in order
+///
+/// * `bytes_per_pixel` - 1 for 8 bit images, 2 for 16 bit
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TiffLayout {
+    pub strips : Vec<(u64, u64)>,
+    pub bytes_per_pixel : usize,
+}
+
+impl TiffLayout {
+    /// Reads the layout of the frame described by `ifd`.
+    /// Only uncompressed, single-sample, unsigned 8 or 16 bit
+    /// images can be read as intensity data. Returns the reader
+    /// to its original position.
+    ///
+    /// ## Errors
+    ///
+    /// * `IOError` - If the strips can't be read, or the
+    /// image is stored in a way that can't be read as intensity
+    pub fn from_ifd<I : IFD, R : Read + Seek>(ifd : &I, reader : &mut R)
+    -> Result<Self, IOError> {
+        // Missing tags take the Tiff spec's defaults
+        let value = |tag_id, default : u64| ifd.get_tag(tag_id)
+            .map_or(default, |tag| tag.value().into());
+        let bits_per_sample = value(BitsPerSample, 1);
+        if value(Compression, 1) != 1
+            || value(SamplesPerPixel, 1) != 1
+            || value(SampleFormat, 1) != 1
+            || !(bits_per_sample == 8 || bits_per_sample == 16) {
+            return Err(IOError::new(
+                IOErrorKind::InvalidData,
+                "Only uncompressed single-channel 8 or 16 bit unsigned Tiffs can be read"
+            ));
+        }
+        Ok(TiffLayout {
+            strips : ifd.strips(reader)?,
+            bytes_per_pixel : bits_per_sample as usize / 8,
+        })
+    }
+
+    /// The layout of the frame described by `ifd` if it's a plain
+    /// Tiff frame (see `from_ifd`), or an empty layout if it's a
+    /// `.siff` frame -- plain Tiffs may be split into strips, `.siff`s
+    /// never are.
+    ///
+    /// ## Errors
+    ///
+    /// * `IOError` - As `from_ifd`, for plain Tiff frames
+    pub fn for_ifd<I : IFD, R : Read + Seek>(ifd : &I, reader : &mut R)
+    -> Result<Self, IOError> {
+        match ifd.get_tag(Siff) {
+            None => TiffLayout::from_ifd(ifd, reader),
+            Some(_) => Ok(TiffLayout::default()),
+        }
+    }
+}
 
 /// Parses a `tiff` format frame and fills an array
 /// with the data
-/// 
-/// Expected to be at the data strip already.
-#[binrw::parser(reader)]
-pub fn load_array_tiff(
-        array : &mut ArrayViewMut2<u16>,
-        ydim : u32,
-        xdim : u32
-    ) -> binrw::BinResult<()> {
-    
-    let mut data : Vec<u8> = vec![0; 
-        ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-    reader.read_exact(&mut data)?;
-
-    let data = try_cast_slice::<u8, u16>(&data).map_err(|err| binrw::Error::Io(
-        IOError::new(IOErrorKind::InvalidData, err))
-    )?;
+///
+/// The image may be split into several strips (see `TiffLayout`),
+/// which are read in order. Leaves the reader at the end
+/// of the last strip read.
+pub fn load_array_tiff<R : FrameRead>(
+    reader : &mut R,
+    array : &mut ArrayViewMut2<u16>,
+    layout : &TiffLayout,
+    ydim : u32,
+    xdim : u32,
+) -> binrw::BinResult<()> {
+
+    let image_bytes = ydim as usize * xdim as usize * layout.bytes_per_pixel;
+    let mut strips = Vec::with_capacity(layout.strips.len());
+    let mut filled = 0;
+    for &(offset, byte_count) in &layout.strips {
+        if filled == image_bytes { break; }
+        let len = (image_bytes - filled).min(byte_count as usize);
+        reader.seek(SeekFrom::Start(offset))?;
+        strips.push(reader.read_frame_bytes(len)?);
+        filled += len;
+    }
+    if filled < image_bytes {
+        return Err(binrw::Error::Io(IOError::new(
+            IOErrorKind::UnexpectedEof, "Strips are smaller than the image")
+        ));
+    }
 
-    array.iter_mut().zip(data.iter()).for_each(|(a, &v)| *a = v);
+    // A pixel may straddle two strips
+    let data = strips.iter().flat_map(|strip| strip.iter().copied());
+    match layout.bytes_per_pixel {
+        1 => array.iter_mut().zip(data).for_each(|(a, v)| *a = v as u16),
+        _ => array.iter_mut().zip(data.tuples())
+            .for_each(|(a, (lo, hi))| *a = u16::from_le_bytes([lo, hi])),
+    }
 
     Ok(())
 }
 
 /// Parses a `tiff` format frame and fills an array
 /// with the data, then applies registration
-/// 
-/// Expected to be at the data strip already.
-#[binrw::parser(reader, endian)]
-pub fn load_array_tiff_registered(
-        array : &mut ArrayViewMut2<u16>,
-        ydim : u32,
-        xdim : u32,
-        registration : (i32, i32)
-    ) -> binrw::BinResult<()> {
-        
+///
+/// See `load_array_tiff` for the `layout`.
+pub fn load_array_tiff_registered<R : FrameRead>(
+    reader : &mut R,
+    array : &mut ArrayViewMut2<u16>,
+    layout : &TiffLayout,
+    ydim : u32,
+    xdim : u32,
+    registration : (i32, i32),
+) -> binrw::BinResult<()> {
+
     load_array_tiff(
         reader,
-        endian,
-        (
-            array,
-            ydim,
-            xdim
-        )
+        array,
+        layout,
+        ydim,
+        xdim,
     )?;
 
     roll_inplace(array, registration);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Reads a `tiff` format frame, then adds the pixels
+/// where `mask` is `true` to the `target_array` at the
+/// locations given by the `lookup_table`.
+pub fn extract_mask_tiff<R : FrameRead>(
+    reader : &mut R,
+    target_array : &mut ArrayViewMut1<u16>,
+    mask : &ArrayView2<bool>,
+    lookup_table : &ArrayView2<usize>,
+    layout : &TiffLayout,
+    ydim : u32,
+    xdim : u32,
+    registration : (i32, i32),
+) -> binrw::BinResult<()> {
+    let mut frame_array = Array2::<u16>::zeros((ydim as usize, xdim as usize));
+    load_array_tiff_registered(reader, &mut frame_array.view_mut(), layout, ydim, xdim, registration)?;
+
+    for (&mask_px, &frame_px, &lookup_px) in izip!(
+        mask.iter(), frame_array.iter(), lookup_table.iter()
+    ) {
+       target_array[lookup_px] += mask_px as u16 * frame_px;
+    }
+    Ok(())
+}
+
+/// Reads a `tiff` format frame and adds the sum of
+/// its pixels where `mask` is `true` to `frame_sum`.
+pub fn sum_mask_tiff<R : FrameRead>(
+    reader : &mut R,
+    frame_sum : &mut u64,
+    mask : &ArrayView2<bool>,
+    layout : &TiffLayout,
+    ydim : u32,
+    xdim : u32,
+    registration : (i32, i32),
+) -> binrw::BinResult<()> {
+    let mut frame_array = Array2::<u16>::zeros((ydim as usize, xdim as usize));
+    load_array_tiff_registered(reader, &mut frame_array.view_mut(), layout, ydim, xdim, registration)?;
+
+    frame_array.iter().zip(mask.iter()).for_each(|(&d, m)| {
+        *frame_sum += (d as u64) * (*m as u64);
+    });
+    Ok(())
+}
+
+/// Reads a `tiff` format frame and adds the sum of its
+/// pixels in each mask of `masks` to the corresponding
+/// element of `frame_sums`.
+pub fn sum_masks_tiff<R : FrameRead>(
+    reader : &mut R,
+    frame_sums : &mut ArrayViewMut1<u64>,
+    masks : &ArrayView3<bool>,
+    layout : &TiffLayout,
+    ydim : u32,
+    xdim : u32,
+    registration : (i32, i32),
+) -> binrw::BinResult<()> {
+    let mut frame_array = Array2::<u16>::zeros((ydim as usize, xdim as usize));
+    load_array_tiff_registered(reader, &mut frame_array.view_mut(), layout, ydim, xdim, registration)?;
+
+    masks.axis_iter(Axis(0)).zip(frame_sums.iter_mut()).for_each(
+        |(mask, mask_sum)| {
+            frame_array.iter().zip(mask.iter()).for_each(|(&d, mask_px)| {
+                *mask_sum += (d as u64) * (*mask_px as u64);
+            })
+        }
+    );
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use bytemuck::try_cast_slice;
 use ndarray::prelude::*;
+use crate::utils::FrameRead;
 use itertools::izip;
 
 use std::io::{
@@ -39,13 +40,13 @@ use crate::data::image::{
 /// 
 /// `load_array_raw_siff_registered` - for loading an array
 /// and shifting the data based on registration.
-#[binrw::parser(reader)]
-pub fn load_array_raw_siff<T : Into<u64>>(
-        array : &mut ArrayViewMut2<u16>,
-        strip_bytes : T,
-        ydim : u32,
-        xdim : u32,
-    ) -> binrw::BinResult<()> {
+pub fn load_array_raw_siff<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
+    array : &mut ArrayViewMut2<u16>,
+    strip_bytes : T,
+    ydim : u32,
+    xdim : u32,
+) -> binrw::BinResult<()> {
 
     photonwise_op!(
         reader,
@@ -63,8 +64,8 @@ pub fn load_array_raw_siff<T : Into<u64>>(
     Ok(())
 }
 
-#[binrw::parser(reader)]
-pub fn extract_mask_raw_siff<T : Into<u64>>(
+pub fn extract_mask_raw_siff<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     target_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
@@ -89,8 +90,8 @@ pub fn extract_mask_raw_siff<T : Into<u64>>(
 /// Computes the sum of the intensity data in a raw frame
 /// masked by a bool array and stores it by changing the
 /// `frame_sum` argument.
-#[binrw::parser(reader)]
-pub fn sum_mask_raw_siff<T : Into<u64>>(
+pub fn sum_mask_raw_siff<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     frame_sum : &mut u64,
     mask : &ArrayView2<bool>,
     strip_bytes : T,
@@ -113,8 +114,8 @@ pub fn sum_mask_raw_siff<T : Into<u64>>(
 }
 
 /// Iterates over all the masks for each pixel
-#[binrw::parser(reader)]
-pub fn sum_masks_raw_siff<T : Into<u64>>(
+pub fn sum_masks_raw_siff<R : FrameRead, T : Into<u64>>(
+    reader : &mut R,
     frame_sums : &mut ArrayViewMut1<u64>,
     masks : &ArrayView3<bool>,
     strip_bytes : T,
@@ -144,23 +145,22 @@ pub fn sum_masks_raw_siff<T : Into<u64>>(
 /// 
 /// Expected to be at the data strip, so it will go backwards by the size of the
 /// intensity data and read that.
-#[binrw::parser(reader)]
-pub fn load_array_compressed_siff(
-        array : &mut ArrayViewMut2<u16>,
-        ydim : u32,
-        xdim : u32
-    ) -> binrw::BinResult<()> {
+pub fn load_array_compressed_siff<R : FrameRead>(
+    reader : &mut R,
+    array : &mut ArrayViewMut2<u16>,
+    ydim : u32,
+    xdim : u32,
+) -> binrw::BinResult<()> {
     
     reader.seek(std::io::SeekFrom::Current(
         -(ydim as i64 * xdim as i64 * std::mem::size_of::<u16>() as i64)
     ))?;
     
-    let mut data : Vec<u8> = vec![0; 
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let data = try_cast_slice::<u8, u16>(&data).map_err(|err| binrw::Error::Io(
+    let data = data.cast::<u16>().map_err(|err| binrw::Error::Io(
         IOError::new(IOErrorKind::InvalidData, err))
     )?;
 
@@ -169,8 +169,8 @@ pub fn load_array_compressed_siff(
     Ok(())
 }
 
-#[binrw::parser(reader, endian)]
-pub fn extract_mask_compressed_siff(
+pub fn extract_mask_compressed_siff<R : FrameRead>(
+    reader : &mut R,
     target_array : &mut ArrayViewMut1<u16>,
     mask : &ArrayView2<bool>,
     lookup_table : &ArrayView2<usize>,
@@ -182,8 +182,7 @@ pub fn extract_mask_compressed_siff(
         (ydim as usize, xdim as usize),
         vec![0; ydim as usize * xdim as usize]
     ).unwrap();
-    load_array_compressed_siff(reader, endian,
-        (&mut frame_array.view_mut(), ydim, xdim))?;
+    load_array_compressed_siff(reader, &mut frame_array.view_mut(), ydim, xdim)?;
     
     for (&mask_px, &frame_px, &lookup_px) in izip!(
         mask.iter(), frame_array.iter(), lookup_table.iter()
@@ -197,24 +196,23 @@ pub fn extract_mask_compressed_siff(
 /// Computes the sum of the intensity data in a compressed frame
 /// masked by a bool array and stores it by changing the
 /// `frame_sum` argument.
-#[binrw::parser(reader)]
-pub fn sum_mask_compressed_siff(
+pub fn sum_mask_compressed_siff<R : FrameRead>(
+    reader : &mut R,
     frame_sum : &mut u64,
     mask : &ArrayView2<bool>,
     ydim : u32,
-    xdim : u32
+    xdim : u32,
 ) -> binrw::BinResult<()> {
         
         reader.seek(std::io::SeekFrom::Current(
             -(ydim as i64 * xdim as i64 * std::mem::size_of::<u16>() as i64)
         ))?;
         
-        let mut data : Vec<u8> = vec![0; 
+        let data = reader.read_frame_bytes(
             ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-        ];
-        reader.read_exact(&mut data)?;
+        )?;
     
-        let data = try_cast_slice::<u8, u16>(&data).map_err(|err| binrw::Error::Io(
+        let data = data.cast::<u16>().map_err(|err| binrw::Error::Io(
             IOError::new(IOErrorKind::InvalidData, err))
         )?;
 
@@ -230,8 +228,8 @@ pub fn sum_mask_compressed_siff(
 }
 
 /// Iterates over all the masks for each pixel
-#[binrw::parser(reader)]
-pub fn sum_masks_compressed_siff(
+pub fn sum_masks_compressed_siff<R : FrameRead>(
+    reader : &mut R,
     frame_sums : &mut ArrayViewMut1<u64>,
     masks : &ArrayView3<bool>,
     ydim : u32,
@@ -242,12 +240,11 @@ pub fn sum_masks_compressed_siff(
         -(ydim as i64 * xdim as i64 * std::mem::size_of::<u16>() as i64)
     ))?;
     
-    let mut data : Vec<u8> = vec![0; 
+    let data = reader.read_frame_bytes(
         ydim as usize * xdim as usize * std::mem::size_of::<u16>()
-    ];
-    reader.read_exact(&mut data)?;
+    )?;
 
-    let data = try_cast_slice::<u8, u16>(&data).map_err(|err| binrw::Error::Io(
+    let data = data.cast::<u16>().map_err(|err| binrw::Error::Io(
         IOError::new(IOErrorKind::InvalidData, err))
     )?;
 
@@ -0,0 +1,339 @@
+//! Photon-level encoding of `.siff` frames. Most of
+//! the rest of `image` only ever _reads_ photons on the
+//! way to building an array -- this module is for code
+//! that needs to produce or re-encode the photons themselves.
+
+use binrw::io::{Read, Seek, SeekFrom};
+use ndarray::prelude::*;
+use crate::utils::FrameRead;
+
+use crate::{
+    CorrosiffError,
+    tiff::{IFD, Tag, TiffTagID::{Siff, StripByteCounts, StripOffsets}},
+    utils::FramesError,
+};
+use super::dimensions::macros::*;
+
+/// Packs a photon's pixel coordinates and arrival time
+/// into the `u64` format used by raw `.siff` frames:
+/// the highest 16 bits are the y coordinate, the next 16
+/// the x coordinate, and the lowest 32 the arrival time bin.
+///
+/// ## Arguments
+///
+/// * `y` - The row of the photon
+///
+/// * `x` - The column of the photon
+///
+/// * `tau` - The arrival time of the photon in bins
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let photon = pack_photon(3, 4, 120);
+/// assert_eq!(photon_to_y!(photon), 3);
+/// assert_eq!(photon_to_x!(photon), 4);
+/// assert_eq!(photon_to_tau_USIZE!(photon), 120);
+/// ```
+pub fn pack_photon(y : u16, x : u16, tau : u32) -> u64 {
+    (((y as u64) << 48) & SIFF_YMASK)
+    | (((x as u64) << 32) & SIFF_XMASK)
+    | ((tau as u64) & SIFF_TAU_MASK)
+}
+
+/// A single photon of a `.siff` file: the frame it
+/// arrived in, the pixel it landed on, and its arrival
+/// time (in bins of the FLIM histogram).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Photon {
+    pub frame : u64,
+    pub y : u16,
+    pub x : u16,
+    pub tau : u32,
+}
+
+impl Photon {
+    /// Unpacks a photon stored as in `pack_photon`, shifting
+    /// its pixel by `shift` (wrapping around a frame of
+    /// `shape`, as registered frames do).
+    ///
+    /// ## Arguments
+    ///
+    /// * `frame` - The frame the photon belongs to
+    ///
+    /// * `photon` - The packed photon
+    ///
+    /// * `shift` - The `(y, x)` shift to apply
+    ///
+    /// * `shape` - The shape of the frame `(y, x)`
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let photon = Photon::from_packed(2, pack_photon(0, 3, 120), (-1, 1), (8, 16));
+    /// assert_eq!(photon, Photon { frame : 2, y : 7, x : 4, tau : 120 });
+    /// ```
+    pub fn from_packed(frame : u64, photon : u64, shift : (i32, i32), shape : (usize, usize))
+    -> Self {
+        Photon {
+            frame,
+            y : wrap_coordinate(photon_to_y!(photon), shift.0, shape.0) as u16,
+            x : wrap_coordinate(photon_to_x!(photon), shift.1, shape.1) as u16,
+            tau : photon_to_tau_USIZE!(photon) as u32,
+        }
+    }
+}
+
+/// Reads every photon of a `.siff` frame, in either
+/// encoding, as packed `u64`s (see `pack_photon`).
+/// Raw frames are returned in the order they are stored
+/// in the file. Compressed frames are returned sorted by
+/// pixel (row-major), in the order of their arrival times.
+/// Returns the reader to its original position if successful.
+///
+/// ## Arguments
+///
+/// * `reader` - The file containing the frame
+///
+/// * `ifd` - The IFD of the frame to read
+///
+/// ## Errors
+///
+/// * `CorrosiffError::FramesError(FramesError::FormatError)` - If the
+/// frame has no `Siff` tag (e.g. it's a `.tiff`), an unknown
+/// `Siff` encoding, is missing its dimensions, a raw strip that isn't
+/// a whole number of photons, or its strip offset leaves no room for
+/// a compressed frame's intensity image.
+///
+/// * `CorrosiffError::IOError` - If the frame data cannot be read.
+pub fn read_frame_photons<I : IFD, ReaderT : FrameRead>(
+    reader : &mut ReaderT,
+    ifd : &I,
+) -> Result<Vec<u64>, CorrosiffError> {
+    let siff_compress = ifd.get_tag(Siff).map(|tag| tag.value().into())
+        .ok_or(FramesError::FormatError("Frame has no Siff tag".to_string()))?;
+    let strip_offset : u64 = ifd.get_tag(StripOffsets)
+        .ok_or(FramesError::FormatError("Strip offset not found".to_string()))?
+        .value().into();
+    let strip_bytes : u64 = ifd.get_tag(StripByteCounts)
+        .ok_or(FramesError::FormatError("Strip byte count not found".to_string()))?
+        .value().into();
+    let ydim = ifd.height()
+        .ok_or(FramesError::FormatError("Image height not found".to_string()))?
+        .into() as usize;
+    let xdim = ifd.width()
+        .ok_or(FramesError::FormatError("Image width not found".to_string()))?
+        .into() as usize;
+
+    let pos = reader.stream_position()?;
+    let photons = match siff_compress {
+        0 => {
+            if strip_bytes % std::mem::size_of::<u64>() as u64 != 0 {
+                return Err(FramesError::FormatError(
+                    "Raw strip is not a whole number of photons".to_string()
+                ).into());
+            }
+            reader.seek(SeekFrom::Start(strip_offset))?;
+            let data = reader.read_frame_bytes(strip_bytes as usize)?;
+            data.chunks_exact(std::mem::size_of::<u64>())
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        },
+        1 => {
+            let intensity_bytes = (ydim * xdim * std::mem::size_of::<u16>()) as u64;
+            let intensity_offset = strip_offset.checked_sub(intensity_bytes).ok_or(
+                FramesError::FormatError(
+                    "Strip offset lies before the intensity image".to_string()
+                )
+            )?;
+            reader.seek(SeekFrom::Start(intensity_offset))?;
+            let data = reader.read_frame_bytes((intensity_bytes + strip_bytes) as usize)?;
+            let data = data.cast::<u16>().map_err(
+                |err| FramesError::FormatError(err.to_string())
+            )?;
+            let (intensity, arrivals) = data.split_at(ydim * xdim);
+
+            let mut arrivals = arrivals.iter();
+            let mut photons = Vec::with_capacity(arrivals.len());
+            for (px_idx, &count) in intensity.iter().enumerate() {
+                let (y, x) = ((px_idx / xdim) as u16, (px_idx % xdim) as u16);
+                for _ in 0..count {
+                    let &tau = arrivals.next().ok_or(FramesError::FormatError(
+                        "Fewer arrival times than photons in the intensity image".to_string()
+                    ))?;
+                    photons.push(pack_photon(y, x, tau as u32));
+                }
+            }
+            photons
+        },
+        other => {
+            return Err(FramesError::FormatError(
+                format!("Unknown Siff encoding {}", other)
+            ).into());
+        },
+    };
+    reader.seek(SeekFrom::Start(pos))?;
+    Ok(photons)
+}
+
+/// Converts a list of packed photons into the two
+/// components of a compressed `.siff` frame: an intensity
+/// image and the arrival times of every photon, sorted by pixel
+/// (row-major). Photons within a pixel keep their relative order.
+///
+/// ## Arguments
+///
+/// * `photons` - The photons of the frame, packed as in `pack_photon`
+///
+/// * `shape` - The shape of the frame `(y, x)`
+///
+/// ## Returns
+///
+/// * `(intensity, arrival_times)` - The photon count of each pixel
+/// and the arrival time of each photon.
+///
+/// ## Errors
+///
+/// * `FramesError::FormatError` - If a photon lies outside of the
+/// frame, has an arrival time that does not fit in a `u16`, or if a pixel
+/// contains more than `u16::MAX` photons.
+pub fn compress_photons(photons : &[u64], shape : (usize, usize))
+-> Result<(Array2<u16>, Vec<u16>), FramesError> {
+    let (ydim, xdim) = shape;
+    let mut intensity = Array2::<u16>::zeros(shape);
+    let mut counts = vec![0usize; ydim * xdim];
+
+    for &photon in photons {
+        let (y, x) = (photon_to_y!(photon), photon_to_x!(photon));
+        if y >= ydim || x >= xdim {
+            return Err(FramesError::FormatError(format!(
+                "Photon at ({}, {}) lies outside of a frame with shape ({}, {})",
+                y, x, ydim, xdim
+            )));
+        }
+        if photon_to_tau_USIZE!(photon) > u16::MAX as usize {
+            return Err(FramesError::FormatError(format!(
+                "Arrival time {} cannot be stored in a compressed frame",
+                photon_to_tau_USIZE!(photon)
+            )));
+        }
+        counts[y * xdim + x] += 1;
+    }
+
+    // Where each pixel's arrival times begin
+    let mut starts = Vec::with_capacity(counts.len());
+    let mut total = 0;
+    for (&count, px) in counts.iter().zip(intensity.iter_mut()) {
+        *px = u16::try_from(count).map_err(|_| FramesError::FormatError(
+            format!("{} photons in one pixel cannot be stored in a compressed frame", count)
+        ))?;
+        starts.push(total);
+        total += count;
+    }
+
+    let mut arrival_times = vec![0u16; photons.len()];
+    for &photon in photons {
+        let px_idx = photon_to_y!(photon) * xdim + photon_to_x!(photon);
+        arrival_times[starts[px_idx]] = photon_to_tau_USIZE!(photon) as u16;
+        starts[px_idx] += 1;
+    }
+
+    Ok((intensity, arrival_times))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_and_unpack() {
+        let photon = pack_photon(511, 3, 4_000_000);
+        assert_eq!(photon_to_y!(photon), 511);
+        assert_eq!(photon_to_x!(photon), 3);
+        assert_eq!(photon_to_tau_USIZE!(photon), 4_000_000);
+        assert_eq!(photon_to_y!(photon, -1, 512), 510);
+    }
+
+    #[test]
+    fn unpack_with_shift() {
+        let photon = pack_photon(0, 3, 120);
+        assert_eq!(
+            Photon::from_packed(2, photon, (0, 0), (6, 5)),
+            Photon { frame : 2, y : 0, x : 3, tau : 120 }
+        );
+        assert_eq!(
+            Photon::from_packed(2, photon, (-1, 4), (6, 5)),
+            Photon { frame : 2, y : 5, x : 2, tau : 120 }
+        );
+        // Shifts larger than the frame wrap more than once
+        assert_eq!(
+            Photon::from_packed(2, photon, (-13, -7), (6, 10)),
+            Photon { frame : 2, y : 5, x : 6, tau : 120 }
+        );
+    }
+
+    #[test]
+    fn compress_sorts_by_pixel() {
+        let photons = vec![
+            pack_photon(1, 0, 7),
+            pack_photon(0, 1, 3),
+            pack_photon(1, 0, 2),
+            pack_photon(0, 0, 9),
+        ];
+        let (intensity, arrivals) = compress_photons(&photons, (2, 2)).unwrap();
+        assert_eq!(intensity, array![[1, 1], [2, 0]]);
+        assert_eq!(arrivals, vec![9, 3, 7, 2]);
+
+        assert!(compress_photons(&[pack_photon(2, 0, 0)], (2, 2)).is_err());
+        assert!(compress_photons(&[pack_photon(0, 0, 70_000)], (2, 2)).is_err());
+    }
+
+    #[test]
+    fn corrupt_compressed_ifd() {
+        use crate::tiff::{BigTag, BigTiffIFD, TiffTagID::*, TiffTagType::*};
+        let tags = |strip_offset : u64, with_height : bool| {
+            let mut tags = vec![
+                BigTag::new(ImageWidth, Long, 1, 4),
+                BigTag::new(StripOffsets, Long8, 1, strip_offset),
+                BigTag::new(StripByteCounts, Long8, 1, 0),
+                BigTag::new(Siff, Short, 1, 1),
+            ];
+            if with_height { tags.push(BigTag::new(ImageLength, Long, 1, 4)); }
+            BigTiffIFD::from_tags(tags, None)
+        };
+        let mut reader = std::io::Cursor::new(vec![0u8; 64]);
+
+        // The intensity image would have to start before the file does
+        assert!(matches!(
+            read_frame_photons(&mut reader, &tags(8, true)),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+        assert!(matches!(
+            read_frame_photons(&mut reader, &tags(32, false)),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+        assert_eq!(read_frame_photons(&mut reader, &tags(32, true)).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn partial_raw_photon() {
+        use crate::tiff::{BigTag, BigTiffIFD, TiffTagID::*, TiffTagType::*};
+        let tags = |strip_bytes : u64| BigTiffIFD::from_tags(vec![
+            BigTag::new(ImageWidth, Long, 1, 4),
+            BigTag::new(ImageLength, Long, 1, 4),
+            BigTag::new(StripOffsets, Long8, 1, 8),
+            BigTag::new(StripByteCounts, Long8, 1, strip_bytes),
+            BigTag::new(Siff, Short, 1, 0),
+        ], None);
+        let mut bytes = vec![0u8; 8];
+        bytes.extend_from_slice(&pack_photon(1, 2, 30).to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 12]);
+        let mut reader = std::io::Cursor::new(bytes);
+
+        assert_eq!(read_frame_photons(&mut reader, &tags(8)).unwrap(), vec![pack_photon(1, 2, 30)]);
+        assert!(matches!(
+            read_frame_photons(&mut reader, &tags(12)),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+    }
+}
@@ -32,6 +32,10 @@ macro_rules! load_array_from_siff {
         ( $raw_func : ident, ($($raw_args : expr),*) ),
         ( $compressed_func : ident, ($($compressed_args : expr),*) )
     ) => {{
+        // Plain Tiffs have no photon data to parse
+        let siff_compress : u64 = $ifd.get_tag(Siff)
+            .ok_or(IOError::new(IOErrorKind::InvalidData, "Not a .siff frame (no Siff tag)"))?
+            .value().into();
         let pos = $reader.stream_position()?;
         $reader.seek(
             std::io::SeekFrom::Start(
@@ -44,12 +48,12 @@ macro_rules! load_array_from_siff {
             )
         )?;
 
-        match $ifd.get_tag(Siff).unwrap().value().into() {
+        match siff_compress {
             0 => {
-                $raw_func($reader, binrw::Endian::Little, ( $($raw_args),* ) )
+                $raw_func($reader, $($raw_args),*)
             },
             1 => {
-                $compressed_func($reader, binrw::Endian::Little, ( $($compressed_args),* ))
+                $compressed_func($reader, $($compressed_args),*)
             },
             _ => {
                 Ok(())
@@ -95,10 +99,10 @@ macro_rules! load_array_from_siff {
         match $ifd.get_tag(Siff) {
             Some(tag) => { match tag.value().into() {
                 0 => {
-                    $raw_func($reader, binrw::Endian::Little, ( $($raw_args),* ) )
+                    $raw_func($reader, $($raw_args),*)
                 },
                 1 => {
-                    $compressed_func($reader, binrw::Endian::Little, ( $($compressed_args),* ))
+                    $compressed_func($reader, $($compressed_args),*)
                 },
                 _ => {
                 Ok(())
@@ -115,7 +119,7 @@ macro_rules! load_array_from_siff {
                 }
             }},
             None => {
-                $tiff_func($reader, binrw::Endian::Little, ( $($tiff_args),* ))
+                $tiff_func($reader, $($tiff_args),*)
             }
         }.map_err(|err| {
             let _ = $reader.seek(std::io::SeekFrom::Start(pos));
@@ -135,18 +139,23 @@ macro_rules! load_array_from_siff {
 /// )
 /// ```
 /// Read the raw-format .siff frame and apply an operation
-/// to every photon. Consumes `strip_bytes`
+/// to every photon. Consumes `strip_bytes`. The photons are
+/// decoded in place, so they needn't be aligned (e.g. when
+/// they're read straight from a memory map).
 macro_rules! photonwise_op (
     ($reader : ident, $strip_bytes : ident, $op : expr) => {
-        let mut data : Vec<u8> = vec![0; $strip_bytes.into() as usize];
-        $reader.read_exact(&mut data)?;
-
-        unsafe {
-            let (_, data, _) = data.align_to::<u64>();
-            data.iter().for_each( $op );
-        }
+        let data = $reader.read_frame_bytes($strip_bytes.into() as usize)?;
+        crate::data::image::utils::for_each_photon(&data, $op);
     }
 );
 
+/// Decodes every packed `u64` photon in `data` and applies
+/// `op` to it. Works with any alignment of `data`.
+pub (crate) fn for_each_photon<F : FnMut(&u64)>(data : &[u8], mut op : F) {
+    data.chunks_exact(std::mem::size_of::<u64>())
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .for_each(|photon| op(&photon));
+}
+
 pub (crate) use load_array_from_siff;
 pub (crate) use photonwise_op;
\ No newline at end of file
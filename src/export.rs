@@ -0,0 +1,17 @@
+//! # Export
+//!
+//! Writers for getting the arrays returned by a
+//! `SiffReader` (lifetimes, phasors, etc.) out of
+//! Rust in formats that other tools can read, without
+//! passing through Python first.
+
+mod float_tiff;
+mod npy;
+mod sdt;
+mod zarr;
+
+pub use float_tiff::{TiffFloat, PhasorLayout, write_float_tiff, write_phasor_tiff};
+pub use npy::{NpyElement, NpyPayload, NpyWriter, NpzWriter, NpzEntry};
+pub use sdt::{SdtElement, SdtWriter, check_sdt};
+pub use zarr::{ZarrArray, ZarrCompression, create_zarr_group};
+pub (crate) use zarr::{json_number, json_string};
@@ -0,0 +1,288 @@
+//! Writes stacks of floating point images (e.g. the
+//! empirical lifetime or phasor of each frame) as plain
+//! `BigTiff` files, one page per frame, with the
+//! `BitsPerSample` and `SampleFormat` tags set so that
+//! Fiji, napari, `tifffile`, etc. read them as floats.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use ndarray::prelude::*;
+use num_complex::Complex;
+
+use crate::{
+    CorrosiffError,
+    data::image::DimensionsError,
+    tiff::{
+        BigTag, Tag, TiffKind, TiffTagID, TiffTagType,
+        image_tags, write_bigtiff_header, write_linked_pages,
+    },
+};
+
+/// The floating point types that can be stored
+/// in a `.tiff` page (`SampleFormat = 3`).
+pub trait TiffFloat : bytemuck::Pod {
+    /// The `BitsPerSample` of a page of this type
+    const BITS_PER_SAMPLE : u16;
+}
+
+impl TiffFloat for f32 {
+    const BITS_PER_SAMPLE : u16 = 32;
+}
+
+impl TiffFloat for f64 {
+    const BITS_PER_SAMPLE : u16 = 64;
+}
+
+/// How the real and imaginary parts of a phasor
+/// stack are laid out in a `.tiff` file.
+///
+/// ## Variants
+///
+/// * `Pages` - Two pages per frame: G (the real part)
+/// followed by S (the imaginary part). Opens in Fiji as a
+/// stack with twice as many slices as frames.
+/// * `Channels` - One page per frame, with two samples per
+/// pixel (G then S).
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum PhasorLayout {
+    #[default]
+    Pages,
+    Channels,
+}
+
+/// The tags for a page of `T` with `samples` samples per pixel,
+/// stored contiguously for each pixel.
+fn float_tags<T : TiffFloat>(shape : (usize, usize), samples : u16) -> Vec<BigTag> {
+    let mut tags = image_tags(shape, T::BITS_PER_SAMPLE, 3);
+    if samples > 1 {
+        // Up to four `Short` values fit inline in a `BigTag`
+        let per_sample = |value : u16| (0..samples as u64)
+            .fold(0u64, |acc, sample| acc | (value as u64) << (16 * sample));
+        tags.retain(|tag| !matches!(
+            tag.tag(),
+            TiffTagID::BitsPerSample | TiffTagID::SamplesPerPixel | TiffTagID::SampleFormat
+        ));
+        tags.extend([
+            BigTag::new(TiffTagID::BitsPerSample, TiffTagType::Short, samples as u64, per_sample(T::BITS_PER_SAMPLE)),
+            BigTag::new(TiffTagID::SamplesPerPixel, TiffTagType::Short, 1, samples as u64),
+            BigTag::new(TiffTagID::ExtraSamples, TiffTagType::Short, samples as u64 - 1, 0),
+            BigTag::new(TiffTagID::SampleFormat, TiffTagType::Short, samples as u64, per_sample(3)),
+        ]);
+    }
+    tags
+}
+
+/// Writes each strip in `strips` as a page with `tags`, linking
+/// each page to the next, into a new `BigTiff` file at `path`.
+fn write_pages<P : AsRef<Path>, I : Iterator<Item = Vec<u8>>>(
+    path : P,
+    tags : &[BigTag],
+    strips : I,
+) -> Result<(), CorrosiffError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_bigtiff_header(&mut writer)?;
+    write_linked_pages(
+        &mut writer,
+        TiffKind::BigTiff,
+        tags,
+        strips.map(|strip| Ok::<_, CorrosiffError>((b"", strip))),
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Checks that a stack has at least one frame and returns
+/// the shape of its frames.
+fn frame_shape<T>(stack : &ArrayView3<T>) -> Result<(usize, usize), CorrosiffError> {
+    match stack.dim() {
+        (0, _, _) => Err(DimensionsError::IncorrectFrames.into()),
+        (_, ydim, xdim) => Ok((ydim, xdim)),
+    }
+}
+
+/// `write_float_tiff(path, stack)` writes a stack of `f32` or `f64`
+/// images (frames along the first axis, as returned by
+/// `SiffReader::get_frames_flim`) to a new `.tiff` file,
+/// one page per frame.
+///
+/// ## Arguments
+///
+/// * `path` - Where to write the `.tiff` file
+///
+/// * `stack` - The images to write, with shape `(frames, y, x)`
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// use corrosiff::{SiffReader, export::write_float_tiff};
+/// let reader = SiffReader::open("file.siff")?;
+/// let (lifetime, _) = reader.get_frames_flim(&reader.frames_vec(), None)?;
+/// write_float_tiff("lifetime.tiff", &lifetime.view())?;
+/// ```
+///
+/// ## Errors
+///
+/// * `CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames)` - If
+/// the stack has no frames
+///
+/// * `CorrosiffError::IOError` - If the file cannot be written
+pub fn write_float_tiff<P : AsRef<Path>, T : TiffFloat>(
+    path : P,
+    stack : &ArrayView3<T>,
+) -> Result<(), CorrosiffError> {
+    let shape = frame_shape(stack)?;
+    write_pages(
+        path,
+        &float_tags::<T>(shape, 1),
+        stack.axis_iter(Axis(0)).map(|frame| {
+            bytemuck::cast_slice(&frame.iter().cloned().collect::<Vec<T>>()).to_vec()
+        }),
+    )
+}
+
+/// `write_phasor_tiff(path, phasor, layout)` writes a stack of complex
+/// phasors (frames along the first axis, as returned by
+/// `SiffReader::get_frames_phasor`) to a new `.tiff` file of
+/// `f32` or `f64` pages, with the real and imaginary parts arranged
+/// as described by `layout`.
+///
+/// ## Arguments
+///
+/// * `path` - Where to write the `.tiff` file
+///
+/// * `phasor` - The phasors to write, with shape `(frames, y, x)`
+///
+/// * `layout` - The `PhasorLayout` of the real and imaginary parts
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// use corrosiff::{SiffReader, export::{write_phasor_tiff, PhasorLayout}};
+/// let reader = SiffReader::open("file.siff")?;
+/// let (phasor, _) = reader.get_frames_phasor(&reader.frames_vec(), None)?;
+/// write_phasor_tiff("phasor.tiff", &phasor.view(), PhasorLayout::Pages)?;
+/// ```
+///
+/// ## Errors
+///
+/// * `CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames)` - If
+/// the stack has no frames
+///
+/// * `CorrosiffError::IOError` - If the file cannot be written
+pub fn write_phasor_tiff<P : AsRef<Path>, T : TiffFloat>(
+    path : P,
+    phasor : &ArrayView3<Complex<T>>,
+    layout : PhasorLayout,
+) -> Result<(), CorrosiffError> {
+    let shape = frame_shape(phasor)?;
+    match layout {
+        PhasorLayout::Pages => write_pages(
+            path,
+            &float_tags::<T>(shape, 1),
+            phasor.axis_iter(Axis(0)).flat_map(|frame| {
+                [
+                    frame.iter().map(|x| x.re).collect::<Vec<T>>(),
+                    frame.iter().map(|x| x.im).collect::<Vec<T>>(),
+                ]
+            }).map(|page| bytemuck::cast_slice(&page).to_vec()),
+        ),
+        PhasorLayout::Channels => write_pages(
+            path,
+            &float_tags::<T>(shape, 2),
+            phasor.axis_iter(Axis(0)).map(|frame| {
+                bytemuck::cast_slice(
+                    &frame.iter().flat_map(|x| [x.re, x.im]).collect::<Vec<T>>()
+                ).to_vec()
+            }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Seek, SeekFrom};
+    use crate::{tests::{TempPath, read_ifds}, tiff::{BigTiffIFD, IFD}};
+
+    /// Reads every IFD of a plain `BigTiff` and the bytes of its strip
+    fn read_pages<P : AsRef<Path>>(path : P) -> Vec<(BigTiffIFD, Vec<u8>)> {
+        let mut file = File::open(&path).unwrap();
+        read_ifds(&path).into_iter().map(|ifd| {
+            let mut strip = vec![0u8; ifd.get_tag(TiffTagID::StripByteCounts).unwrap().value() as usize];
+            file.seek(SeekFrom::Start(ifd.get_tag(TiffTagID::StripOffsets).unwrap().value())).unwrap();
+            file.read_exact(&mut strip).unwrap();
+            (ifd, strip)
+        }).collect()
+    }
+
+    #[test]
+    fn float_stacks() {
+        let path = TempPath::new("float_stack.tiff");
+        let stack = Array3::from_shape_fn((3, 4, 5), |(t, y, x)| (t * 100 + y * 10 + x) as f64 / 7.0);
+        write_float_tiff(&path, &stack.view()).unwrap();
+
+        let pages = read_pages(&path);
+        assert_eq!(pages.len(), 3);
+        pages.iter().enumerate().for_each(|(frame, (ifd, strip))| {
+            assert_eq!(ifd.get_tag(TiffTagID::BitsPerSample).unwrap().value(), 64);
+            assert_eq!(ifd.get_tag(TiffTagID::SampleFormat).unwrap().value(), 3);
+            assert_eq!(
+                bytemuck::pod_collect_to_vec::<u8, f64>(strip),
+                stack.index_axis(Axis(0), frame).iter().cloned().collect::<Vec<_>>()
+            );
+        });
+
+        // Non-standard layouts are written in logical order
+        let transposed = stack.mapv(|x| x as f32).permuted_axes([0, 2, 1]);
+        write_float_tiff(&path, &transposed.view()).unwrap();
+        let pages = read_pages(&path);
+        assert_eq!(pages[0].0.get_tag(TiffTagID::ImageWidth).unwrap().value(), 4);
+        assert_eq!(pages[0].0.get_tag(TiffTagID::BitsPerSample).unwrap().value(), 32);
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, f32>(&pages[2].1),
+            transposed.index_axis(Axis(0), 2).iter().cloned().collect::<Vec<_>>()
+        );
+
+        assert!(matches!(
+            write_float_tiff(&path, &Array3::<f32>::zeros((0, 4, 5)).view()),
+            Err(CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames))
+        ));
+    }
+
+    #[test]
+    fn phasor_layouts() {
+        let path = TempPath::new("phasor_stack.tiff");
+        let phasor = Array3::from_shape_fn((2, 3, 4), |(t, y, x)| {
+            Complex::new((t + y) as f32 / 5.0, -((y * x) as f32) / 3.0)
+        });
+
+        write_phasor_tiff(&path, &phasor.view(), PhasorLayout::Pages).unwrap();
+        let pages = read_pages(&path);
+        assert_eq!(pages.len(), 4);
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, f32>(&pages[2].1),
+            phasor.index_axis(Axis(0), 1).iter().map(|x| x.re).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, f32>(&pages[3].1),
+            phasor.index_axis(Axis(0), 1).iter().map(|x| x.im).collect::<Vec<_>>()
+        );
+
+        write_phasor_tiff(&path, &phasor.view(), PhasorLayout::Channels).unwrap();
+        let pages = read_pages(&path);
+        assert_eq!(pages.len(), 2);
+        let ifd = &pages[1].0;
+        assert_eq!(ifd.get_tag(TiffTagID::SamplesPerPixel).unwrap().value(), 2);
+        assert_eq!(ifd.get_tag(TiffTagID::BitsPerSample).unwrap().num_values(), 2);
+        assert_eq!(ifd.get_tag(TiffTagID::BitsPerSample).unwrap().value(), 32 | 32 << 16);
+        assert_eq!(ifd.get_tag(TiffTagID::SampleFormat).unwrap().value(), 3 | 3 << 16);
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, f32>(&pages[1].1),
+            phasor.index_axis(Axis(0), 1).iter().flat_map(|x| [x.re, x.im]).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,479 @@
+//! Writes NumPy `.npy` files, and `.npz` archives of them,
+//! one chunk of values at a time, so that arrays larger
+//! than memory (e.g. the `tau_d` of a whole acquisition)
+//! can be streamed to disk as they're computed.
+//!
+//! `.npz` files are uncompressed (`stored`) zip archives,
+//! always written with Zip64 extensions so that entries
+//! can be larger than 4 GB.
+
+use std::{
+    io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult, Seek, SeekFrom, Write},
+    marker::PhantomData,
+};
+
+use ndarray::prelude::*;
+use num_complex::Complex;
+
+/// The types that can be stored in a `.npy` file.
+pub trait NpyElement : Copy {
+    /// The NumPy `dtype.descr` of the type (always little-endian)
+    const DESCR : &'static str;
+
+    /// Appends the little-endian bytes of `self` to `bytes`
+    fn extend_le_bytes(&self, bytes : &mut Vec<u8>);
+}
+
+macro_rules! impl_npy_element {
+    ($($t:ty => $descr:expr),*) => {$(
+        impl NpyElement for $t {
+            const DESCR : &'static str = $descr;
+            fn extend_le_bytes(&self, bytes : &mut Vec<u8>) {
+                bytes.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+    )*};
+}
+
+impl_npy_element!(
+    u8 => "|u1", u16 => "<u2", u32 => "<u4", u64 => "<u8",
+    i32 => "<i4", i64 => "<i8", f32 => "<f4", f64 => "<f8"
+);
+
+impl NpyElement for Complex<f32> {
+    const DESCR : &'static str = "<c8";
+    fn extend_le_bytes(&self, bytes : &mut Vec<u8>) {
+        self.re.extend_le_bytes(bytes);
+        self.im.extend_le_bytes(bytes);
+    }
+}
+
+impl NpyElement for Complex<f64> {
+    const DESCR : &'static str = "<c16";
+    fn extend_le_bytes(&self, bytes : &mut Vec<u8>) {
+        self.re.extend_le_bytes(bytes);
+        self.im.extend_le_bytes(bytes);
+    }
+}
+
+/// Enum for specifying which array is computed from
+/// the frames of a `.siff` file when they are saved
+/// to a `.npy` or `.npz` file.
+///
+/// ## Variants
+///
+/// * `Intensity` - Photon counts, `uint16` with shape `(frames, y, x)`
+/// * `Lifetime` - The empirical lifetime (in units of arrival time bins),
+/// `float64` with shape `(frames, y, x)`
+/// * `Phasor` - The phasor of each pixel, `complex128` with shape `(frames, y, x)`
+/// * `TauD` - The arrival time histogram of each pixel, `uint16` with shape
+/// `(frames, y, x, arrival time bins)`
+///
+/// ## Example
+///
+/// ```
+/// use corrosiff::export::NpyPayload;
+///
+/// let payload = NpyPayload::from_string_slice("TauD").unwrap();
+/// assert_eq!(payload.name(), "tau_d");
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum NpyPayload {
+    #[default]
+    Intensity,
+    Lifetime,
+    Phasor,
+    TauD,
+}
+
+impl NpyPayload {
+    /// `from_string_slice(str)` parses a string slice
+    /// to produce an `NpyPayload` enum. Useful for argument
+    /// parsing from the command line.
+    ///
+    /// ## Arguments
+    ///
+    /// * `str` - A string slice that holds the name of the payload
+    pub fn from_string_slice(str : &str) -> IOResult<NpyPayload> {
+        match str {
+            "Intensity" => Ok(NpyPayload::Intensity),
+            "Lifetime" => Ok(NpyPayload::Lifetime),
+            "Phasor" => Ok(NpyPayload::Phasor),
+            "TauD" => Ok(NpyPayload::TauD),
+            _ => Err(IOError::new(IOErrorKind::InvalidInput, "Invalid NpyPayload")),
+        }
+    }
+
+    /// The name of the array in a `.npz` archive
+    pub fn name(&self) -> &'static str {
+        match self {
+            NpyPayload::Intensity => "intensity",
+            NpyPayload::Lifetime => "lifetime",
+            NpyPayload::Phasor => "phasor",
+            NpyPayload::TauD => "tau_d",
+        }
+    }
+}
+
+/// The `.npy` (version 1.0) header of a C-ordered array
+/// of `T` with shape `shape`, padded so that the data
+/// begins on a 64-byte boundary.
+fn npy_header<T : NpyElement>(shape : &[usize]) -> Vec<u8> {
+    let shape_string = match shape {
+        [length] => format!("({},)", length),
+        _ => format!(
+            "({})",
+            shape.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR, shape_string
+    );
+    // magic (6) + version (2) + header length (2) + dict + newline
+    let unpadded = 10 + dict.len() + 1;
+    dict.extend(std::iter::repeat(' ').take((64 - unpadded % 64) % 64));
+    dict.push('\n');
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+/// Writes a single `.npy` array of `T` to any `Write`r,
+/// in as many pieces as the caller likes. The values
+/// are written in C order, so each call to `write`
+/// continues where the last one stopped.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let file = BufWriter::new(File::create("intensity.npy")?);
+/// let mut npy = NpyWriter::<_, u16>::new(file, &[100, 256, 128])?;
+/// for chunk in frames.chunks(10) {
+///     npy.write(&reader.get_frames_intensity(chunk, None)?.view())?;
+/// }
+/// npy.finish()?;
+/// ```
+pub struct NpyWriter<W : Write, T : NpyElement> {
+    writer : W,
+    remaining : usize,
+    _element : PhantomData<T>,
+}
+
+impl<W : Write, T : NpyElement> NpyWriter<W, T> {
+    /// Writes the header of an array with shape `shape`
+    /// to `writer`. The values must all be written with
+    /// `write` before calling `finish`.
+    pub fn new(mut writer : W, shape : &[usize]) -> IOResult<Self> {
+        writer.write_all(&npy_header::<T>(shape))?;
+        Ok(NpyWriter {
+            writer,
+            remaining : shape.iter().product(),
+            _element : PhantomData,
+        })
+    }
+
+    /// Appends the values of `values` (in logical C order,
+    /// regardless of its memory layout) to the array.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` - If there are more values than the shape
+    /// passed to `new` has room for
+    pub fn write<D : Dimension>(&mut self, values : &ArrayView<T, D>) -> IOResult<()> {
+        if values.len() > self.remaining {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                "More values written than the shape of the npy array"
+            ));
+        }
+        let mut bytes = Vec::with_capacity(values.len() * std::mem::size_of::<T>());
+        values.iter().for_each(|value| value.extend_le_bytes(&mut bytes));
+        self.writer.write_all(&bytes)?;
+        self.remaining -= values.len();
+        Ok(())
+    }
+
+    /// Completes the array and returns the underlying writer.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` - If fewer values were written than
+    /// the shape passed to `new`
+    pub fn finish(mut self) -> IOResult<W> {
+        if self.remaining != 0 {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                format!("{} values of the npy array were never written", self.remaining)
+            ));
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The CRC-32 (IEEE) lookup table used by zip archives
+const CRC32_TABLE : [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+};
+
+/// Continues the CRC-32 `crc` (pass `0` to start) over `bytes`
+pub (crate) fn crc32_update(crc : u32, bytes : &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |c, &b| CRC32_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8))
+}
+
+/// MS-DOS date of 1980-01-01, the earliest a zip can record
+const ZIP_DATE : u16 = (1 << 5) | 1;
+/// Version 4.5 of the zip specification, which introduced Zip64
+const ZIP_VERSION : u16 = 45;
+
+/// A finished entry of an `NpzWriter`
+struct NpzRecord {
+    name : String,
+    crc : u32,
+    size : u64,
+    offset : u64,
+}
+
+/// Writes a `.npz` archive (as read by `numpy.load`) one
+/// array at a time. Each array is streamed into the archive
+/// through the `NpzEntry` returned by `start_array`, usually
+/// wrapped in an `NpyWriter`.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let mut npz = NpzWriter::new(BufWriter::new(File::create("flim.npz")?));
+/// let mut npy = NpyWriter::<_, f64>::new(npz.start_array("lifetime")?, &shape)?;
+/// npy.write(&lifetime.view())?;
+/// npy.finish()?.finish()?;
+/// npz.finish()?;
+/// ```
+pub struct NpzWriter<W : Write + Seek> {
+    writer : W,
+    records : Vec<NpzRecord>,
+}
+
+impl<W : Write + Seek> NpzWriter<W> {
+    /// Starts an archive at the current position of `writer`
+    pub fn new(writer : W) -> Self {
+        NpzWriter { writer, records : Vec::new() }
+    }
+
+    /// Begins a new array named `name` (`.npy` is appended to
+    /// the name of the file in the archive, so `numpy.load(...)[name]`
+    /// finds it). The bytes written to the returned `NpzEntry` are the
+    /// contents of the `.npy` file, and the entry must be `finish`ed
+    /// before starting another.
+    pub fn start_array(&mut self, name : &str) -> IOResult<NpzEntry<'_, W>> {
+        let name = format!("{}.npy", name);
+        let offset = self.writer.stream_position()?;
+
+        let mut header = Vec::with_capacity(50 + name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // flags
+        header.extend_from_slice(&0u16.to_le_bytes()); // stored
+        header.extend_from_slice(&0u16.to_le_bytes()); // time
+        header.extend_from_slice(&ZIP_DATE.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // crc, filled in by `finish`
+        header.extend_from_slice(&u32::MAX.to_le_bytes()); // sizes are in the Zip64 field
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // Zip64 extra field
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes()); // sizes, filled in by `finish`
+        header.extend_from_slice(&0u64.to_le_bytes());
+        self.writer.write_all(&header)?;
+
+        Ok(NpzEntry {
+            npz : self,
+            record : NpzRecord { name, crc : 0, size : 0, offset },
+        })
+    }
+
+    /// Writes the central directory, completing the
+    /// archive, and returns the underlying writer.
+    pub fn finish(mut self) -> IOResult<W> {
+        let directory_offset = self.writer.stream_position()?;
+        let mut directory = Vec::new();
+        for record in self.records.iter() {
+            directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            directory.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // made by
+            directory.extend_from_slice(&ZIP_VERSION.to_le_bytes()); // needed
+            directory.extend_from_slice(&0u16.to_le_bytes());
+            directory.extend_from_slice(&0u16.to_le_bytes());
+            directory.extend_from_slice(&0u16.to_le_bytes());
+            directory.extend_from_slice(&ZIP_DATE.to_le_bytes());
+            directory.extend_from_slice(&record.crc.to_le_bytes());
+            directory.extend_from_slice(&u32::MAX.to_le_bytes());
+            directory.extend_from_slice(&u32::MAX.to_le_bytes());
+            directory.extend_from_slice(&(record.name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&28u16.to_le_bytes()); // extra field length
+            directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+            directory.extend_from_slice(&0u16.to_le_bytes()); // disk
+            directory.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            directory.extend_from_slice(&0u32.to_le_bytes()); // external attributes
+            directory.extend_from_slice(&u32::MAX.to_le_bytes()); // offset is in the Zip64 field
+            directory.extend_from_slice(record.name.as_bytes());
+            directory.extend_from_slice(&1u16.to_le_bytes());
+            directory.extend_from_slice(&24u16.to_le_bytes());
+            directory.extend_from_slice(&record.size.to_le_bytes());
+            directory.extend_from_slice(&record.size.to_le_bytes());
+            directory.extend_from_slice(&record.offset.to_le_bytes());
+        }
+
+        let num_records = self.records.len() as u64;
+        let zip64_end_offset = directory_offset + directory.len() as u64;
+        // Zip64 end of central directory record
+        directory.extend_from_slice(&0x06064b50u32.to_le_bytes());
+        directory.extend_from_slice(&44u64.to_le_bytes());
+        directory.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        directory.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&num_records.to_le_bytes());
+        directory.extend_from_slice(&num_records.to_le_bytes());
+        directory.extend_from_slice(&(zip64_end_offset - directory_offset).to_le_bytes());
+        directory.extend_from_slice(&directory_offset.to_le_bytes());
+        // Zip64 end of central directory locator
+        directory.extend_from_slice(&0x07064b50u32.to_le_bytes());
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&zip64_end_offset.to_le_bytes());
+        directory.extend_from_slice(&1u32.to_le_bytes());
+        // End of central directory record
+        let num_records = num_records.min(u16::MAX as u64) as u16;
+        directory.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&num_records.to_le_bytes());
+        directory.extend_from_slice(&num_records.to_le_bytes());
+        directory.extend_from_slice(&u32::MAX.to_le_bytes());
+        directory.extend_from_slice(&u32::MAX.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+
+        self.writer.write_all(&directory)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// One file of an `NpzWriter` being written. Everything
+/// written to it is stored in the archive as-is.
+pub struct NpzEntry<'a, W : Write + Seek> {
+    npz : &'a mut NpzWriter<W>,
+    record : NpzRecord,
+}
+
+impl<W : Write + Seek> Write for NpzEntry<'_, W> {
+    fn write(&mut self, buf : &[u8]) -> IOResult<usize> {
+        let written = self.npz.writer.write(buf)?;
+        self.record.crc = crc32_update(self.record.crc, &buf[..written]);
+        self.record.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        self.npz.writer.flush()
+    }
+}
+
+impl<W : Write + Seek> NpzEntry<'_, W> {
+    /// Fills in the checksum and size of the entry, so
+    /// that the next one can be started.
+    pub fn finish(self) -> IOResult<()> {
+        let writer = &mut self.npz.writer;
+        let end = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(self.record.offset + 14))?;
+        writer.write_all(&self.record.crc.to_le_bytes())?;
+        writer.seek(SeekFrom::Start(
+            self.record.offset + 30 + self.record.name.len() as u64 + 4
+        ))?;
+        writer.write_all(&self.record.size.to_le_bytes())?;
+        writer.write_all(&self.record.size.to_le_bytes())?;
+        writer.seek(SeekFrom::Start(end))?;
+        self.npz.records.push(self.record);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn npy_in_chunks() {
+        let array = Array3::from_shape_fn((4, 3, 2), |(t, y, x)| (t * 6 + y * 2 + x) as u16);
+        let mut npy = NpyWriter::<_, u16>::new(Vec::new(), &[4, 3, 2]).unwrap();
+        npy.write(&array.slice(s![..3, .., ..])).unwrap();
+        // Partial arrays can't be finished
+        assert!(NpyWriter::<_, u16>::new(Vec::new(), &[4]).unwrap().finish().is_err());
+        npy.write(&array.slice(s![3.., .., ..])).unwrap();
+        assert!(npy.write(&array.slice(s![..1, .., ..])).is_err());
+        let bytes = npy.finish().unwrap();
+
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(
+            std::str::from_utf8(&bytes[10..10 + header_len]).unwrap().trim_end(),
+            "{'descr': '<u2', 'fortran_order': False, 'shape': (4, 3, 2), }"
+        );
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, u16>(&bytes[10 + header_len..]),
+            (0..24).collect::<Vec<u16>>()
+        );
+
+        let header = npy_header::<Complex<f64>>(&[7]);
+        assert!(std::str::from_utf8(&header[10..]).unwrap().contains("'descr': '<c16'"));
+        assert!(std::str::from_utf8(&header[10..]).unwrap().contains("'shape': (7,)"));
+    }
+
+    #[test]
+    fn npz_archive() {
+        assert_eq!(crc32_update(0, b"123456789"), 0xCBF43926);
+        assert_eq!(crc32_update(crc32_update(0, b"12345"), b"6789"), 0xCBF43926);
+
+        let mut npz = NpzWriter::new(Cursor::new(Vec::new()));
+        let mut npy = NpyWriter::<_, f64>::new(npz.start_array("a").unwrap(), &[2]).unwrap();
+        npy.write(&array![1.0, 2.0].view()).unwrap();
+        npy.finish().unwrap().finish().unwrap();
+        let mut entry = npz.start_array("bc").unwrap();
+        entry.write_all(b"123456789").unwrap();
+        entry.finish().unwrap();
+        let bytes = npz.finish().unwrap().into_inner();
+
+        // First local header
+        assert_eq!(&bytes[..4], &0x04034b50u32.to_le_bytes());
+        assert_eq!(&bytes[30..35], b"a.npy");
+        let size = u64::from_le_bytes(bytes[39..47].try_into().unwrap());
+        assert_eq!(size, 128 + 16);
+        let second = 30 + 5 + 20 + size as usize;
+        assert_eq!(&bytes[second..second + 4], &0x04034b50u32.to_le_bytes());
+        assert_eq!(
+            u32::from_le_bytes(bytes[second + 14..second + 18].try_into().unwrap()),
+            0xCBF43926
+        );
+        // End of central directory records both entries
+        let end = bytes.len() - 22;
+        assert_eq!(&bytes[end..end + 4], &0x06054b50u32.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([bytes[end + 10], bytes[end + 11]]), 2);
+    }
+}
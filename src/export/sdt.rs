@@ -0,0 +1,412 @@
+//! Writes Becker & Hickl `.sdt` files (read by SPCImage,
+//! FLIMfit, and `sdtfile`) holding the arrival time histogram
+//! of every pixel of one or more images.
+//!
+//! An `.sdt` file is a fixed binary file header, an ASCII info
+//! section, a setup section, one measurement description block
+//! (the shape of the images and the timing of the histogram
+//! bins), and then one data block per image, each of which is
+//! a short block header followed by the histograms (`y`, then
+//! `x`, then arrival time, which varies fastest).
+//!
+//! The number of bins stored (`adc_re`) is always rounded up to
+//! a power of two, as B&H hardware would record it, and the
+//! extra bins are left empty.
+
+use std::{
+    io::{Error as IOError, ErrorKind as IOErrorKind, Result as IOResult, Write},
+    marker::PhantomData,
+};
+
+use ndarray::prelude::*;
+
+use crate::export::NpyElement;
+
+const FILE_HEADER_LENGTH : usize = 42;
+const MEAS_DESC_BLOCK_LENGTH : usize = 512;
+const BLOCK_HEADER_LENGTH : usize = 22;
+
+/// `header_valid` of a complete file
+const HEADER_VALID : u16 = 0x5555;
+/// The 16-bit words of the file header sum to this
+const FILE_CHECKSUM : u16 = 0x55AA;
+/// SPC-150, software revision 15
+const REVISION : i16 = 0x28F;
+
+/// A block holding a page of decay curves (one per pixel)
+const PAGE_BLOCK : u16 = 0x10;
+/// A block of measured data
+const MEAS_DATA : u16 = 0x1;
+
+/// The types of histogram counts an `.sdt` file can hold
+pub trait SdtElement : NpyElement {
+    /// The data type bits of the block type
+    const DATA_TYPE : u16;
+}
+
+impl SdtElement for u16 {
+    const DATA_TYPE : u16 = 0x000;
+}
+
+impl SdtElement for u32 {
+    const DATA_TYPE : u16 = 0x100;
+}
+
+/// Formats nanoseconds since the Unix epoch (UTC) as the
+/// `MM:DD:YYYY` date and `HH:MM:SS` time B&H files use.
+fn bh_date_time(epoch_ns : u64) -> (String, String) {
+    let seconds = epoch_ns / 1_000_000_000;
+    let (days, time) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Converts days since 1970-01-01 to a civil date
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    (
+        format!("{:02}:{:02}:{:04}", month, day, year),
+        format!("{:02}:{:02}:{:02}", time / 3600, (time / 60) % 60, time % 60),
+    )
+}
+
+/// Checks that `num_blocks` images of histograms of `shape`
+/// can be stored in an `.sdt` file of `T` counts, without writing
+/// anything, so that the file need not be created if they can't.
+/// Takes the same arguments as `SdtWriter::new`.
+///
+/// ## Errors
+///
+/// * `InvalidInput` - If the histograms have more than 16384 bins,
+/// an image is too large to store, or the file would exceed the
+/// 2 GB the format can address.
+pub fn check_sdt<T : SdtElement>(
+    shape : (usize, usize, usize),
+    num_blocks : usize,
+    bin_width_ps : f64,
+    epoch_ns : Option<u64>,
+) -> IOResult<()> {
+    SdtLayout::new::<T>(shape, num_blocks, bin_width_ps, epoch_ns).map(|_| ())
+}
+
+/// Everything in an `.sdt` file before the first data block,
+/// and where the data blocks go
+struct SdtLayout {
+    header : Vec<u8>,
+    adc_resolution : usize,
+    first_block_offset : usize,
+}
+
+impl SdtLayout {
+    /// See `check_sdt`
+    fn new<T : SdtElement>(
+        shape : (usize, usize, usize),
+        num_blocks : usize,
+        bin_width_ps : f64,
+        epoch_ns : Option<u64>,
+    ) -> IOResult<Self> {
+        let (ydim, xdim, num_bins) = shape;
+        let adc_resolution = num_bins.max(1).next_power_of_two();
+        let block_length = ydim * xdim * adc_resolution * std::mem::size_of::<T>();
+        if adc_resolution > i16::MAX as usize || block_length > i32::MAX as usize
+            || ydim > i32::MAX as usize || xdim > i32::MAX as usize {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                format!("Histograms of shape {:?} cannot be stored in an .sdt file", shape)
+            ));
+        }
+        let tac_range = adc_resolution as f64 * bin_width_ps * 1e-12;
+        let (date, time) = epoch_ns.map(bh_date_time).unwrap_or_default();
+
+        let info = format!(
+            "*IDENTIFICATION\r\n\
+            \x20 ID        : SPC Setup & Data File\r\n\
+            \x20 Title     : corrosiff export\r\n\
+            \x20 Version   : 1  781 M\r\n\
+            \x20 Revision  : {} bits ADC\r\n\
+            \x20 Date      : {}\r\n\
+            \x20 Time      : {}\r\n\
+            \x20 Author    : corrosiff\r\n\
+            \x20 Company   : \r\n\
+            \x20 Contents  : Arrival time histograms of {} images\r\n\
+            *END\r\n\r\n",
+            adc_resolution.trailing_zeros(), date, time, num_blocks,
+        );
+        let setup = format!(
+            "*SETUP\r\n\
+            \x20#SP [SP_TAC_R,F,{:e}]\r\n\
+            \x20#SP [SP_TAC_G,I,1]\r\n\
+            \x20#SP [SP_ADC_RE,I,{}]\r\n\
+            \x20#SP [SP_SCAN_X,I,{}]\r\n\
+            \x20#SP [SP_SCAN_Y,I,{}]\r\n\
+            \x20#SP [SP_IMG_X,I,{}]\r\n\
+            \x20#SP [SP_IMG_Y,I,{}]\r\n\
+            *END\r\n\r\n",
+            tac_range, adc_resolution, xdim, ydim, xdim, ydim,
+        );
+
+        let info_offset = FILE_HEADER_LENGTH;
+        let setup_offset = info_offset + info.len();
+        let meas_desc_offset = setup_offset + setup.len();
+        let first_block_offset = meas_desc_offset + MEAS_DESC_BLOCK_LENGTH;
+        // Offsets are stored as 32-bit integers
+        let file_size = num_blocks.checked_mul(BLOCK_HEADER_LENGTH + block_length)
+            .and_then(|blocks| blocks.checked_add(first_block_offset));
+        if file_size.map_or(true, |size| size > i32::MAX as usize) {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                "An .sdt file cannot be larger than 2 GB".to_string()
+            ));
+        }
+
+        // File header
+        let mut header = Vec::with_capacity(FILE_HEADER_LENGTH);
+        header.extend_from_slice(&REVISION.to_le_bytes());
+        header.extend_from_slice(&(info_offset as i32).to_le_bytes());
+        header.extend_from_slice(&(info.len() as i16).to_le_bytes());
+        header.extend_from_slice(&(setup_offset as i32).to_le_bytes());
+        header.extend_from_slice(&(setup.len() as i16).to_le_bytes());
+        header.extend_from_slice(&(first_block_offset as i32).to_le_bytes());
+        // More than 0x7FFE blocks are counted in `reserved1`
+        header.extend_from_slice(&(num_blocks.min(0x7FFF) as i16).to_le_bytes());
+        header.extend_from_slice(&(block_length as i32).to_le_bytes());
+        header.extend_from_slice(&(meas_desc_offset as i32).to_le_bytes());
+        header.extend_from_slice(&1i16.to_le_bytes());
+        header.extend_from_slice(&(MEAS_DESC_BLOCK_LENGTH as i16).to_le_bytes());
+        header.extend_from_slice(&HEADER_VALID.to_le_bytes());
+        header.extend_from_slice(&(num_blocks as u32).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        let sum = header.chunks_exact(2)
+            .fold(0u16, |sum, word| sum.wrapping_add(u16::from_le_bytes([word[0], word[1]])));
+        header.extend_from_slice(&FILE_CHECKSUM.wrapping_sub(sum).to_le_bytes());
+
+        // Measurement description, every field not set is 0
+        let mut meas_desc = vec![0u8; MEAS_DESC_BLOCK_LENGTH];
+        let mut put = |offset : usize, bytes : &[u8]| {
+            meas_desc[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        put(0, time.as_bytes());
+        put(9, date.as_bytes());
+        put(20, b"corrosiff");
+        put(68, &(tac_range as f32).to_le_bytes()); // tac_r
+        put(72, &1i16.to_le_bytes()); // tac_g
+        put(82, &100f32.to_le_bytes()); // tac_lh
+        put(86, &(adc_resolution as i16).to_le_bytes()); // adc_re
+        put(90, &1i16.to_le_bytes()); // ncx
+        put(92, &1i16.to_le_bytes()); // ncy
+        put(94, &1i16.to_le_bytes()); // page
+        put(121, b"SPC-150"); // mod_type
+        put(177, &(xdim as i32).to_le_bytes()); // scan_x
+        put(181, &(ydim as i32).to_le_bytes()); // scan_y
+        put(185, &1i32.to_le_bytes()); // scan_rx
+        put(189, &1i32.to_le_bytes()); // scan_ry
+        put(199, &150i16.to_le_bytes()); // mod_type_code
+        put(313, &(xdim as i32).to_le_bytes()); // image_x
+        put(317, &(ydim as i32).to_le_bytes()); // image_y
+        put(321, &1i32.to_le_bytes()); // image_rx
+        put(325, &1i32.to_le_bytes()); // image_ry
+
+        let mut bytes = header;
+        bytes.extend_from_slice(info.as_bytes());
+        bytes.extend_from_slice(setup.as_bytes());
+        bytes.extend_from_slice(&meas_desc);
+
+        Ok(SdtLayout { header : bytes, adc_resolution, first_block_offset })
+    }
+}
+
+/// Writes the images of an `.sdt` file one at a time. The
+/// number of images must be known in advance (it's stored
+/// in the file header), but they never need to be in memory
+/// together.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let file = BufWriter::new(File::create("decays.sdt")?);
+/// let mut sdt = SdtWriter::<_, u16>::new(file, (256, 128, 629), 2, 20.0, None)?;
+/// for frame in [0, 1] {
+///     let tau_d = reader.get_frames_tau_d(&[frame], None)?;
+///     sdt.write_block(&tau_d.index_axis(Axis(0), 0))?;
+/// }
+/// sdt.finish()?;
+/// ```
+pub struct SdtWriter<W : Write, T : SdtElement> {
+    writer : W,
+    shape : (usize, usize, usize),
+    adc_resolution : usize,
+    num_blocks : usize,
+    blocks_written : usize,
+    next_block_offset : usize,
+    _element : PhantomData<T>,
+}
+
+impl<W : Write, T : SdtElement> SdtWriter<W, T> {
+    /// Writes the file header, info, setup, and measurement
+    /// description of an `.sdt` file.
+    ///
+    /// ## Arguments
+    ///
+    /// * `writer` - Where to write the file, pointing to its start
+    ///
+    /// * `shape` - The shape of each image's histograms `(y, x, arrival time bins)`
+    ///
+    /// * `num_blocks` - The number of images that will be written
+    ///
+    /// * `bin_width_ps` - The width of each arrival time bin in picoseconds
+    ///
+    /// * `epoch_ns` - When the data was acquired, in nanoseconds
+    /// since the Unix epoch (the date and time are left blank if `None`)
+    ///
+    /// ## Errors
+    ///
+    /// As `check_sdt`
+    pub fn new(
+        mut writer : W,
+        shape : (usize, usize, usize),
+        num_blocks : usize,
+        bin_width_ps : f64,
+        epoch_ns : Option<u64>,
+    ) -> IOResult<Self> {
+        let layout = SdtLayout::new::<T>(shape, num_blocks, bin_width_ps, epoch_ns)?;
+        writer.write_all(&layout.header)?;
+
+        Ok(SdtWriter {
+            writer,
+            shape,
+            adc_resolution : layout.adc_resolution,
+            num_blocks,
+            blocks_written : 0,
+            next_block_offset : layout.first_block_offset,
+            _element : PhantomData,
+        })
+    }
+
+    /// Writes the histograms of the next image, with shape
+    /// `(y, x, arrival time bins)`.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` - If the histograms have the wrong shape, or
+    /// all `num_blocks` images have already been written.
+    pub fn write_block(&mut self, decays : &ArrayView3<T>) -> IOResult<()> {
+        let (ydim, xdim, num_bins) = self.shape;
+        if decays.dim() != self.shape || self.blocks_written == self.num_blocks {
+            return Err(IOError::new(
+                IOErrorKind::InvalidInput,
+                format!("Histograms of shape {:?} are not the next image of the .sdt file", decays.dim())
+            ));
+        }
+
+        let block_length = ydim * xdim * self.adc_resolution * std::mem::size_of::<T>();
+        // Fits in 32 bits, as checked by `new`
+        let data_offset = self.next_block_offset + BLOCK_HEADER_LENGTH;
+        let block_type = PAGE_BLOCK | MEAS_DATA | T::DATA_TYPE;
+
+        let mut bytes = Vec::with_capacity(BLOCK_HEADER_LENGTH + block_length);
+        bytes.extend_from_slice(&(self.blocks_written as i16).to_le_bytes());
+        bytes.extend_from_slice(&(data_offset as i32).to_le_bytes());
+        bytes.extend_from_slice(&((data_offset + block_length) as i32).to_le_bytes());
+        bytes.extend_from_slice(&block_type.to_le_bytes());
+        bytes.extend_from_slice(&0i16.to_le_bytes());
+        bytes.extend_from_slice(&((self.blocks_written & 0xFF_FFFF) as u32).to_le_bytes());
+        bytes.extend_from_slice(&(block_length as u32).to_le_bytes());
+
+        let padding = (self.adc_resolution - num_bins) * std::mem::size_of::<T>();
+        decays.lanes(Axis(2)).into_iter().for_each(|decay| {
+            decay.iter().for_each(|count| count.extend_le_bytes(&mut bytes));
+            bytes.resize(bytes.len() + padding, 0);
+        });
+        self.writer.write_all(&bytes)?;
+
+        self.blocks_written += 1;
+        self.next_block_offset = data_offset + block_length;
+        Ok(())
+    }
+
+    /// Flushes the file and returns the writer.
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidData` - If fewer than `num_blocks` images were written
+    pub fn finish(mut self) -> IOResult<W> {
+        if self.blocks_written != self.num_blocks {
+            return Err(IOError::new(
+                IOErrorKind::InvalidData,
+                format!("Only {} of {} images were written", self.blocks_written, self.num_blocks)
+            ));
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_time() {
+        assert_eq!(bh_date_time(0), ("01:01:1970".to_string(), "00:00:00".to_string()));
+        assert_eq!(
+            bh_date_time(1_709_210_096_000_000_000),
+            ("02:29:2024".to_string(), "12:34:56".to_string())
+        );
+    }
+
+    #[test]
+    fn sdt_layout() {
+        let decays = Array3::from_shape_fn((2, 3, 5), |(y, x, t)| (100 * y + 10 * x + t) as u16);
+        let mut sdt = SdtWriter::<_, u16>::new(Vec::new(), (2, 3, 5), 2, 50.0, Some(0)).unwrap();
+        sdt.write_block(&decays.view()).unwrap();
+        assert!(sdt.write_block(&decays.slice(s![.., .., ..4])).is_err());
+        sdt.write_block(&decays.view()).unwrap();
+        assert!(sdt.write_block(&decays.view()).is_err());
+        let bytes = sdt.finish().unwrap();
+
+        let i16_at = |offset : usize| i16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let i32_at = |offset : usize| i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let f32_at = |offset : usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+        let checksum = bytes[..FILE_HEADER_LENGTH].chunks_exact(2)
+            .fold(0u16, |sum, word| sum.wrapping_add(u16::from_le_bytes([word[0], word[1]])));
+        assert_eq!(checksum, FILE_CHECKSUM);
+        assert_eq!(i16_at(18), 2);
+
+        let info = &bytes[i32_at(2) as usize..][..i16_at(6) as usize];
+        assert!(std::str::from_utf8(info).unwrap().contains("Date      : 01:01:1970"));
+
+        // 8 bins of 50 ps
+        let meas_desc = i32_at(24) as usize;
+        assert_eq!(i16_at(meas_desc + 86), 8);
+        assert!((f32_at(meas_desc + 68) - 400e-12).abs() < 1e-15);
+        assert_eq!((i32_at(meas_desc + 177), i32_at(meas_desc + 181)), (3, 2));
+
+        let first_block = i32_at(14) as usize;
+        assert_eq!(first_block, meas_desc + MEAS_DESC_BLOCK_LENGTH);
+        let data_offset = i32_at(first_block + 2) as usize;
+        let block_length = i32_at(first_block + 18) as usize;
+        assert_eq!(block_length, 2 * 3 * 8 * 2);
+        let data = bytemuck::pod_collect_to_vec::<u8, u16>(&bytes[data_offset..data_offset + block_length]);
+        assert_eq!(&data[8 * 4..8 * 5], &[110, 111, 112, 113, 114, 0, 0, 0]);
+
+        let second_block = i32_at(first_block + 6) as usize;
+        assert_eq!(i16_at(second_block), 1);
+        assert_eq!(second_block + BLOCK_HEADER_LENGTH + block_length, bytes.len());
+    }
+
+    #[test]
+    fn sdt_size_limit() {
+        // 128 KB per image, so 2 GB is reached just short of 16384 images
+        assert!(check_sdt::<u16>((32, 32, 64), 16000, 20.0, None).is_ok());
+        assert!(check_sdt::<u16>((32, 32, 64), 16384, 20.0, None).is_err());
+        assert!(check_sdt::<u16>((32, 32, 64), usize::MAX, 20.0, None).is_err());
+        assert!(SdtWriter::<_, u16>::new(Vec::new(), (32, 32, 64), 16384, 20.0, None).is_err());
+        assert!(check_sdt::<u16>((32, 32, 1 << 15), 1, 20.0, None).is_err());
+    }
+}
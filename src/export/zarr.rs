@@ -0,0 +1,276 @@
+//! Writes Zarr (version 2) directory stores: a directory
+//! per array, holding its `.zarray` description, its `.zattrs`
+//! attributes, and one file per chunk. Arrays are chunked only
+//! along their first (frame) axis, so each chunk is a block of
+//! whole frames and chunks can be written independently (and in
+//! parallel) as they're computed.
+//!
+//! Chunks are either stored raw or compressed with `zlib`,
+//! which every Zarr implementation can read.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Result as IOResult, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, write::ZlibEncoder};
+use ndarray::prelude::*;
+
+use crate::export::NpyElement;
+
+/// How the chunks of a Zarr array are stored.
+///
+/// ## Variants
+///
+/// * `Raw` - The bytes of the chunk, uncompressed
+/// * `Zlib(level)` - Compressed with `zlib` at `level` (0-9)
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ZarrCompression {
+    #[default]
+    Raw,
+    Zlib(u32),
+}
+
+impl ZarrCompression {
+    /// The `compressor` entry of the `.zarray` file
+    fn to_json(self) -> String {
+        match self {
+            ZarrCompression::Raw => "null".to_string(),
+            ZarrCompression::Zlib(level) => format!("{{\"id\": \"zlib\", \"level\": {}}}", level),
+        }
+    }
+}
+
+/// Escapes a string as a JSON string literal (with quotes)
+pub (crate) fn json_string(value : &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    value.chars().for_each(|c| match c {
+        '"' => escaped.push_str("\\\""),
+        '\\' => escaped.push_str("\\\\"),
+        '\n' => escaped.push_str("\\n"),
+        '\r' => escaped.push_str("\\r"),
+        '\t' => escaped.push_str("\\t"),
+        c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+        c => escaped.push(c),
+    });
+    escaped.push('"');
+    escaped
+}
+
+/// Renders a float as a JSON number, or `null` if it's NaN or
+/// infinite (which JSON has no way to write)
+pub (crate) fn json_number(value : f64) -> String {
+    match value.is_finite() {
+        true => value.to_string(),
+        false => "null".to_string(),
+    }
+}
+
+/// Renders `attributes` (keys and JSON-encoded values) as a JSON object
+fn json_object(attributes : &[(String, String)]) -> String {
+    format!(
+        "{{\n{}\n}}",
+        attributes.iter()
+            .map(|(key, value)| format!("    {}: {}", json_string(key), value))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    )
+}
+
+/// Creates the directory `path` as a Zarr group (a container
+/// for arrays), with `attributes` in its `.zattrs`.
+///
+/// ## Arguments
+///
+/// * `path` - The directory of the group. Created if it doesn't exist.
+///
+/// * `attributes` - Pairs of keys and JSON-encoded values
+pub fn create_zarr_group<P : AsRef<Path>>(path : P, attributes : &[(String, String)])
+-> IOResult<()> {
+    fs::create_dir_all(&path)?;
+    fs::write(path.as_ref().join(".zgroup"), "{\n    \"zarr_format\": 2\n}")?;
+    fs::write(path.as_ref().join(".zattrs"), json_object(attributes))
+}
+
+/// A Zarr array of `T` being written, chunked along its
+/// first axis. Chunks can be written in any order, and
+/// from several threads at once.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let array = ZarrArray::<u16>::create(
+///     "file.zarr/intensity", &[1000, 256, 128], 100, ZarrCompression::Zlib(1), &[]
+/// )?;
+/// for (idx, chunk) in frames.chunks(100).enumerate() {
+///     array.write_chunk(idx, &reader.get_frames_intensity(chunk, None)?.view())?;
+/// }
+/// ```
+pub struct ZarrArray<T : NpyElement> {
+    path : PathBuf,
+    shape : Vec<usize>,
+    chunk_length : usize,
+    compression : ZarrCompression,
+    _element : PhantomData<T>,
+}
+
+impl<T : NpyElement> ZarrArray<T> {
+    /// Creates the directory of the array and writes its
+    /// `.zarray` and `.zattrs` files.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - The directory of the array (usually inside a group).
+    /// Created if it doesn't exist.
+    ///
+    /// * `shape` - The shape of the whole array
+    ///
+    /// * `chunk_length` - The length of each chunk along the first axis
+    /// (the chunks span the whole array along every other axis)
+    ///
+    /// * `compression` - How each chunk is stored
+    ///
+    /// * `attributes` - Pairs of keys and JSON-encoded values for `.zattrs`
+    pub fn create<P : AsRef<Path>>(
+        path : P,
+        shape : &[usize],
+        chunk_length : usize,
+        compression : ZarrCompression,
+        attributes : &[(String, String)],
+    ) -> IOResult<Self> {
+        let chunk_length = chunk_length.max(1);
+        let chunks = std::iter::once(chunk_length)
+            .chain(shape.iter().skip(1).cloned())
+            .collect::<Vec<_>>();
+        let as_list = |values : &[usize]| format!(
+            "[{}]", values.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")
+        );
+
+        fs::create_dir_all(&path)?;
+        fs::write(
+            path.as_ref().join(".zarray"),
+            json_object(&[
+                ("zarr_format".to_string(), "2".to_string()),
+                ("shape".to_string(), as_list(shape)),
+                ("chunks".to_string(), as_list(&chunks)),
+                ("dtype".to_string(), json_string(T::DESCR)),
+                ("compressor".to_string(), compression.to_json()),
+                ("fill_value".to_string(), "null".to_string()),
+                ("order".to_string(), json_string("C")),
+                ("filters".to_string(), "null".to_string()),
+                ("dimension_separator".to_string(), json_string(".")),
+            ])
+        )?;
+        fs::write(path.as_ref().join(".zattrs"), json_object(attributes))?;
+
+        Ok(ZarrArray {
+            path : path.as_ref().to_path_buf(),
+            shape : shape.to_vec(),
+            chunk_length,
+            compression,
+            _element : PhantomData,
+        })
+    }
+
+    /// The number of chunks along the first axis
+    pub fn num_chunks(&self) -> usize {
+        self.shape.first().map_or(0, |&length| (length + self.chunk_length - 1) / self.chunk_length)
+    }
+
+    /// Writes the chunk `index` (along the first axis) of the
+    /// array. `values` should contain exactly the elements of that
+    /// chunk, i.e. `chunk_length` entries along the first axis, or
+    /// fewer for the last chunk (which is padded with zeros, as Zarr
+    /// stores every chunk at full size).
+    ///
+    /// ## Errors
+    ///
+    /// * `InvalidInput` - If `values` has the wrong shape for the chunk
+    pub fn write_chunk<D : Dimension>(&self, index : usize, values : &ArrayView<T, D>)
+    -> IOResult<()> {
+        let expected_length = self.chunk_length
+            .min(self.shape[0].saturating_sub(index * self.chunk_length));
+        if index >= self.num_chunks()
+            || values.shape().first() != Some(&expected_length)
+            || values.shape()[1..] != self.shape[1..] {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Values of shape {:?} are not chunk {} of the Zarr array", values.shape(), index)
+            ));
+        }
+
+        let chunk_bytes = self.chunk_length
+            * self.shape[1..].iter().product::<usize>()
+            * std::mem::size_of::<T>();
+        let mut bytes = Vec::with_capacity(chunk_bytes);
+        values.iter().for_each(|value| value.extend_le_bytes(&mut bytes));
+        bytes.resize(chunk_bytes, 0);
+
+        let key = std::iter::once(index.to_string())
+            .chain(self.shape.iter().skip(1).map(|_| "0".to_string()))
+            .collect::<Vec<_>>()
+            .join(".");
+        let mut file = BufWriter::new(File::create(self.path.join(key))?);
+        match self.compression {
+            ZarrCompression::Raw => file.write_all(&bytes)?,
+            ZarrCompression::Zlib(level) => {
+                let mut encoder = ZlibEncoder::new(file.by_ref(), Compression::new(level.min(9)));
+                encoder.write_all(&bytes)?;
+                encoder.finish()?;
+            },
+        }
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use flate2::read::ZlibDecoder;
+    use crate::tests::TempPath;
+
+    #[test]
+    fn zarr_chunks() {
+        let path = TempPath::new("chunks.zarr");
+        let array = Array3::from_shape_fn((5, 3, 4), |(t, y, x)| (t * 12 + y * 4 + x) as f64);
+        create_zarr_group(&path, &[("name".to_string(), json_string("a \"b\"\n"))]).unwrap();
+        assert_eq!(
+            fs::read_to_string(path.as_ref().join(".zattrs")).unwrap(),
+            "{\n    \"name\": \"a \\\"b\\\"\\n\"\n}"
+        );
+        assert_eq!(json_number(2.5), "2.5");
+        assert_eq!(json_number(f64::NAN), "null");
+        assert_eq!(json_number(f64::NEG_INFINITY), "null");
+
+        let zarr = ZarrArray::<f64>::create(
+            path.as_ref().join("values"), &[5, 3, 4], 2, ZarrCompression::Zlib(3), &[]
+        ).unwrap();
+        assert_eq!(zarr.num_chunks(), 3);
+        let zarray = fs::read_to_string(path.as_ref().join("values/.zarray")).unwrap();
+        assert!(zarray.contains("\"chunks\": [2, 3, 4]"));
+        assert!(zarray.contains("\"dtype\": \"<f8\""));
+        assert!(zarray.contains("\"compressor\": {\"id\": \"zlib\", \"level\": 3}"));
+
+        // Out of order, and the last is padded
+        zarr.write_chunk(2, &array.slice(s![4.., .., ..])).unwrap();
+        zarr.write_chunk(0, &array.slice(s![..2, .., ..])).unwrap();
+        assert!(zarr.write_chunk(1, &array.slice(s![..1, .., ..])).is_err());
+        assert!(zarr.write_chunk(3, &array.slice(s![..2, .., ..])).is_err());
+
+        let read_chunk = |key : &str| {
+            let mut bytes = Vec::new();
+            ZlibDecoder::new(File::open(path.as_ref().join("values").join(key)).unwrap())
+                .read_to_end(&mut bytes).unwrap();
+            bytemuck::pod_collect_to_vec::<u8, f64>(&bytes)
+        };
+        assert_eq!(read_chunk("0.0.0"), (0..24).map(|x| x as f64).collect::<Vec<_>>());
+        let last = read_chunk("2.0.0");
+        assert_eq!(last.len(), 24);
+        assert_eq!(&last[..12], &(48..60).map(|x| x as f64).collect::<Vec<_>>()[..]);
+        assert!(last[12..].iter().all(|&x| x == 0.0));
+    }
+}
@@ -0,0 +1,734 @@
+//! Integrity checks for `.siff` files.
+//!
+//! Incompletely-transferred or otherwise damaged files
+//! usually only reveal themselves as a `FileFormatError`
+//! when opened or a panic partway through reading frames.
+//! `check_file` instead walks the whole IFD chain itself and
+//! inspects every frame, collecting everything it finds wrong
+//! into an `FsckReport` rather than stopping at the first problem.
+//!
+//! ## Example
+//!
+//! ```rust, ignore
+//! let report = corrosiff::fsck::check_file("damaged.siff")?;
+//! if !report.is_ok() {
+//!     println!("{}", report);
+//! }
+//! ```
+
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    CorrosiffError,
+    tiff::{BigTiffIFD, FileFormat, IFD, Tag, TiffTagID},
+};
+
+/// Frames per thread when checking frame data
+const FRAMES_PER_CHUNK : usize = 200;
+
+/// Something wrong with a file or one of its frames.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The file header, NVFD, or ROI string can't be read.
+    UnreadableHeader(String),
+    /// The pointer to the next IFD lies past the end of the file.
+    NextIfdPastEof { offset : u64 },
+    /// The pointer to the next IFD points back to an IFD
+    /// already in the chain.
+    NextIfdLoop { offset : u64 },
+    /// The IFD at `offset` can't be parsed (e.g. it's been cut off).
+    UnreadableIfd { offset : u64, error : String },
+    /// The IFD is missing a tag needed to read the frame.
+    MissingTag(TiffTagID),
+    /// The `Siff` tag is neither raw (0) nor compressed (1).
+    UnknownEncoding(u64),
+    /// `ImageWidth` x `ImageLength` is too large for the
+    /// size of the intensity image to be computed.
+    ImageTooLarge { width : u64, height : u64 },
+    /// Part of the frame's data, spanning `start` to `end`,
+    /// lies past the end of the file.
+    DataPastEof { start : u64, end : u64 },
+    /// `StripByteCounts` does not match the encoding of the frame. For
+    /// compressed frames `expected` is two bytes per photon in the intensity
+    /// image; for raw frames it's the largest whole number of photons.
+    StripByteCountMismatch { byte_count : u64, expected : u64 },
+    /// `count` photons lie outside of `ImageWidth` x `ImageLength`,
+    /// the first at `first` (`(y, x)`).
+    PhotonsOutOfFrame { count : usize, first : (u64, u64) },
+    /// `count` photons have arrival times of at least the number
+    /// of arrival time bins in the NVFD, as late as `max_tau`.
+    TauOutOfRange { count : usize, max_tau : u64, num_bins : u32 },
+    /// The frame data can't be read.
+    UnreadableData(String),
+    /// The frame's metadata (`ImageDescription`) string can't be read
+    /// or is missing fields `SiffReader` needs.
+    UnreadableMetadata(String),
+}
+
+impl Problem {
+    /// Whether the problem is with the link to the _next_
+    /// IFD rather than the frame itself -- the frame can still
+    /// be read, but nothing after it can.
+    pub fn is_chain_problem(&self) -> bool {
+        matches!(self, Problem::NextIfdPastEof {..} | Problem::NextIfdLoop {..} | Problem::UnreadableIfd {..})
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Problem::UnreadableHeader(err) => write!(f, "Unreadable header: {}", err),
+            Problem::NextIfdPastEof { offset } => {
+                write!(f, "Next IFD at {} is past the end of the file", offset)
+            },
+            Problem::NextIfdLoop { offset } => {
+                write!(f, "Next IFD at {} loops back to an earlier IFD", offset)
+            },
+            Problem::UnreadableIfd { offset, error } => {
+                write!(f, "IFD at {} can't be read: {}", offset, error)
+            },
+            Problem::MissingTag(tag) => write!(f, "Missing tag {}", tag),
+            Problem::UnknownEncoding(siff) => write!(f, "Unknown Siff encoding {}", siff),
+            Problem::ImageTooLarge { width, height } => {
+                write!(f, "Image of {} x {} pixels is too large", width, height)
+            },
+            Problem::DataPastEof { start, end } => {
+                write!(f, "Frame data from {} to {} runs past the end of the file", start, end)
+            },
+            Problem::StripByteCountMismatch { byte_count, expected } => {
+                write!(f, "StripByteCounts is {} but should be {}", byte_count, expected)
+            },
+            Problem::PhotonsOutOfFrame { count, first } => {
+                write!(f, "{} photons outside of the frame (first at {:?})", count, first)
+            },
+            Problem::TauOutOfRange { count, max_tau, num_bins } => {
+                write!(
+                    f, "{} photons with arrival times outside of the {} bins (up to {})",
+                    count, num_bins, max_tau
+                )
+            },
+            Problem::UnreadableData(err) => write!(f, "Frame data can't be read: {}", err),
+            Problem::UnreadableMetadata(err) => write!(f, "Frame metadata can't be read: {}", err),
+        }
+    }
+}
+
+/// The problems with one frame (IFD) of a file.
+#[derive(Debug, Clone)]
+pub struct FrameReport {
+    pub frame : u64,
+    /// Where the frame's IFD is in the file
+    pub ifd_offset : u64,
+    pub problems : Vec<Problem>,
+}
+
+impl FrameReport {
+    /// Whether the frame itself can be read, ignoring
+    /// problems with the link to the next IFD.
+    pub fn is_intact(&self) -> bool {
+        self.problems.iter().all(Problem::is_chain_problem)
+    }
+}
+
+/// Everything `check_file` found wrong with a file.
+#[derive(Debug, Clone)]
+pub struct FsckReport {
+    pub file_size : u64,
+    /// Problems that don't belong to any frame, e.g. an
+    /// unreadable header or first IFD
+    pub file_problems : Vec<Problem>,
+    /// Every frame that could be found, in order
+    pub frames : Vec<FrameReport>,
+}
+
+impl FsckReport {
+    /// `true` if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.file_problems.is_empty() && self.frames.iter().all(|frame| frame.problems.is_empty())
+    }
+
+    /// Every problem found, with the frame it belongs to
+    /// (`None` for problems with the file as a whole).
+    pub fn problems(&self) -> impl Iterator<Item = (Option<u64>, &Problem)> {
+        self.file_problems.iter().map(|problem| (None, problem))
+            .chain(self.frames.iter().flat_map(
+                |frame| frame.problems.iter().map(move |problem| (Some(frame.frame), problem))
+            ))
+    }
+
+    /// The frames with at least one problem
+    pub fn bad_frames(&self) -> Vec<u64> {
+        self.frames.iter().filter(|frame| !frame.problems.is_empty())
+            .map(|frame| frame.frame).collect()
+    }
+}
+
+impl Display for FsckReport {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+        let num_problems = self.problems().count();
+        writeln!(
+            f, "{} frames, {} bytes: {}",
+            self.frames.len(),
+            self.file_size,
+            if num_problems == 0 { "no problems found".to_string() }
+            else { format!("{} problems found", num_problems) },
+        )?;
+        for (frame, problem) in self.problems() {
+            match frame {
+                Some(frame) => writeln!(f, "  frame {}: {}", frame, problem)?,
+                None => writeln!(f, "  file: {}", problem)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks the structure of the file at `path` and every
+/// frame in it. See the module documentation.
+///
+/// ## Arguments
+///
+/// * `path` - The `.siff` file to check
+///
+/// ## Returns
+///
+/// * `FsckReport` - Every problem found, organized by frame
+///
+/// ## Errors
+///
+/// * `CorrosiffError::IOError` - Only if the file can't be opened
+/// at all. Problems reading its contents are part of the report.
+pub fn check_file<P : AsRef<Path>>(path : P) -> Result<FsckReport, CorrosiffError> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut reader = BufReader::with_capacity(400, file);
+
+    let mut report = FsckReport { file_size, file_problems : Vec::new(), frames : Vec::new() };
+
+    let file_format = match FileFormat::parse_filetype(&mut reader) {
+        Ok(file_format) => file_format,
+        Err(err) => {
+            report.file_problems.push(Problem::UnreadableHeader(err));
+            // Still try to find the IFDs
+            reader.seek(SeekFrom::Start(0))?;
+            match FileFormat::minimal_filetype(&mut reader) {
+                Ok(file_format) => file_format,
+                Err(_) => return Ok(report),
+            }
+        },
+    };
+
+    let ifds = _walk_chain(&file_format, &mut reader, &mut report);
+
+    // Then check the frames themselves
+    let num_bins = file_format.num_flim_tau_bins();
+    let frame_problems = ifds.par_chunks(FRAMES_PER_CHUNK).map(|chunk| {
+        let mut reader = BufReader::with_capacity(400, File::open(path)?);
+        Ok(chunk.iter().map(|ifd| _check_frame(ifd, &mut reader, file_size, num_bins))
+            .collect::<Vec<_>>())
+    }).collect::<Result<Vec<_>, std::io::Error>>()?;
+
+    report.frames.iter_mut().zip(frame_problems.into_iter().flatten())
+        .for_each(|(frame, mut problems)| {
+            // Keep the chain problem (if any) last
+            problems.append(&mut frame.problems);
+            frame.problems = problems;
+        });
+    Ok(report)
+}
+
+/// Copies every frame of the file at `path` up to the last
+/// fully intact one (as judged by `check_file`) into a new file
+/// at `output`, with the IFD chain terminated after that frame so
+/// that the new file can be opened normally. Useful for recovering
+/// the data from an acquisition that crashed, in which case the last
+/// IFD usually points into garbage or the last strip is cut short.
+///
+/// Everything from the first damaged frame on is discarded, even if
+/// some later frames are intact, because ScanImage writes frames in
+/// order and the IFD chain past a damaged frame can't be trusted.
+/// The frames kept are copied byte-for-byte.
+///
+/// ## Arguments
+///
+/// * `path` - The damaged file
+///
+/// * `output` - Where to write the repaired file. Must not be `path`.
+///
+/// ## Returns
+///
+/// * `usize` - The number of frames in the repaired file
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let recovered = corrosiff::fsck::repair_file("crashed.siff", "crashed_repaired.siff")?;
+/// let reader = corrosiff::open_siff("crashed_repaired.siff")?;
+/// assert_eq!(reader.num_frames(), recovered);
+/// ```
+///
+/// ## Errors
+///
+/// * `CorrosiffError::FileFormatError` - If the header or the first
+/// frame is damaged, so there's nothing to recover.
+///
+/// * `CorrosiffError::IOError` - If the file can't be read, the
+/// new file can't be written, or `output` is the same file as `path`.
+pub fn repair_file<P : AsRef<Path>, Q : AsRef<Path>>(path : P, output : Q)
+-> Result<usize, CorrosiffError> {
+    if output.as_ref().exists()
+        && output.as_ref().canonicalize()? == path.as_ref().canonicalize()? {
+        return Err(CorrosiffError::IOError(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Cannot repair a file in place"
+        )));
+    }
+    let report = check_file(&path)?;
+    if !report.file_problems.is_empty() {
+        return Err(CorrosiffError::FileFormatError);
+    }
+    let num_intact = report.frames.iter().take_while(|frame| frame.is_intact()).count();
+    if num_intact == 0 {
+        return Err(CorrosiffError::FileFormatError);
+    }
+
+    let mut reader = BufReader::with_capacity(400, File::open(&path)?);
+    let file_format = FileFormat::minimal_filetype(&mut reader)
+        .map_err(|_| CorrosiffError::FileFormatError)?;
+
+    // Keep everything up to the end of the last page kept
+    // (ScanImage pages are `[IFD][description][pre-strip][strip]`)
+    let mut end = 0;
+    let mut last_ifd_size = 0;
+    for frame in &report.frames[..num_intact] {
+        let ifd = file_format.read_ifd(&mut reader, frame.ifd_offset)?;
+        let value = |id : TiffTagID| ifd.get_tag(id).map_or(0, |tag| tag.value());
+        last_ifd_size = _ifd_size(&file_format, ifd.num_tags() as u64);
+        end = end
+            .max(frame.ifd_offset + last_ifd_size)
+            .max(value(TiffTagID::StripOffsets) + value(TiffTagID::StripByteCounts));
+    }
+    let pointer_size = if file_format.is_bigtiff() { 8 } else { 4 };
+    let next_ifd_pointer = report.frames[num_intact - 1].ifd_offset + last_ifd_size - pointer_size;
+
+    reader.seek(SeekFrom::Start(0))?;
+    let mut writer = std::io::BufWriter::new(File::create(&output)?);
+    std::io::copy(&mut reader.take(end), &mut writer)?;
+    writer.seek(SeekFrom::Start(next_ifd_pointer))?;
+    writer.write_all(&vec![0u8; pointer_size as usize])?;
+    writer.flush()?;
+    Ok(num_intact)
+}
+
+/// Size in bytes of an IFD with `num_tags` tags, including
+/// its tag count and pointer to the next IFD
+fn _ifd_size(file_format : &FileFormat, num_tags : u64) -> u64 {
+    if file_format.is_bigtiff() { 8 + 20 * num_tags + 8 } else { 2 + 12 * num_tags + 4 }
+}
+
+/// Follows the IFD chain from the first IFD, recording a
+/// `FrameReport` for every IFD found and the reason the chain
+/// ended early, if it did. Returns the IFDs.
+fn _walk_chain<R : Read + Seek>(
+    file_format : &FileFormat,
+    reader : &mut R,
+    report : &mut FsckReport,
+) -> Vec<BigTiffIFD> {
+    let mut ifds = Vec::new();
+    let mut visited = HashSet::new();
+    let mut offset = file_format.first_ifd_val();
+
+    while offset != 0 {
+        let problem = if offset >= report.file_size {
+            Some(Problem::NextIfdPastEof { offset })
+        } else if !visited.insert(offset) {
+            Some(Problem::NextIfdLoop { offset })
+        } else {
+            match file_format.read_ifd(reader, offset) {
+                Ok(ifd) => {
+                    report.frames.push(FrameReport {
+                        frame : ifds.len() as u64,
+                        ifd_offset : offset,
+                        problems : Vec::new(),
+                    });
+                    offset = ifd.next_ifd.unwrap_or(0);
+                    ifds.push(ifd);
+                    None
+                },
+                Err(err) => Some(Problem::UnreadableIfd { offset, error : err.to_string() }),
+            }
+        };
+
+        if let Some(problem) = problem {
+            match report.frames.last_mut() {
+                Some(frame) => frame.problems.push(problem),
+                None => report.file_problems.push(problem),
+            }
+            break;
+        }
+    }
+    ifds
+}
+
+/// Everything that can be wrong with a single frame.
+fn _check_frame<R : Read + Seek>(
+    ifd : &BigTiffIFD,
+    reader : &mut R,
+    file_size : u64,
+    num_bins : Option<u32>,
+) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let mut tag_value = |id : TiffTagID| -> Option<u64> {
+        let value = ifd.get_tag(id).map(|tag| tag.value());
+        if value.is_none() {
+            problems.push(Problem::MissingTag(id));
+        }
+        value
+    };
+    let (width, height, description, strip_offset, strip_bytes) = (
+        tag_value(TiffTagID::ImageWidth),
+        tag_value(TiffTagID::ImageLength),
+        tag_value(TiffTagID::ImageDescription),
+        tag_value(TiffTagID::StripOffsets),
+        tag_value(TiffTagID::StripByteCounts),
+    );
+    let (width, height, description, strip_offset, strip_bytes) = match (
+        width, height, description, strip_offset, strip_bytes
+    ) {
+        (Some(w), Some(h), Some(d), Some(o), Some(b)) => (w, h, d, o, b),
+        _ => return problems,
+    };
+
+    // ScanImage `.tiff`s have no photons to check, so
+    // act like they're uncompressed with no pre-strip data
+    let siff = ifd.get_tag(TiffTagID::Siff).map(|tag| tag.value());
+    let intensity_bytes = match siff {
+        None | Some(0) => 0,
+        Some(1) => match width.checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(std::mem::size_of::<u16>() as u64))
+        {
+            Some(intensity_bytes) => intensity_bytes,
+            None => {
+                problems.push(Problem::ImageTooLarge { width, height });
+                return problems;
+            },
+        },
+        Some(other) => {
+            problems.push(Problem::UnknownEncoding(other));
+            return problems;
+        },
+    };
+
+    let data_start = strip_offset.saturating_sub(intensity_bytes);
+    let data_end = strip_offset.saturating_add(strip_bytes);
+    if strip_offset < intensity_bytes || data_end > file_size {
+        problems.push(Problem::DataPastEof { start : data_start, end : data_end });
+        return problems;
+    }
+
+    match (strip_offset - intensity_bytes).checked_sub(description) {
+        Some(description_length) => {
+            if let Err(err) = _check_metadata(reader, description, description_length) {
+                problems.push(Problem::UnreadableMetadata(err));
+            }
+        },
+        None => problems.push(Problem::UnreadableMetadata(
+            "Description starts after the frame data".to_string()
+        )),
+    }
+
+    if siff.is_none() {
+        return problems;
+    }
+    if let Err(err) = _check_photons(
+        reader, siff == Some(1), (height, width), data_start, intensity_bytes, strip_bytes,
+        num_bins, &mut problems,
+    ) {
+        problems.push(Problem::UnreadableData(err.to_string()));
+    }
+    problems
+}
+
+/// Reads the frame's metadata string the way `FrameMetadata` does
+/// and makes sure the fields every `SiffReader` timestamp method
+/// needs are present.
+fn _check_metadata<R : Read + Seek>(reader : &mut R, offset : u64, length : u64)
+-> Result<(), String> {
+    let mut bytes = vec![0u8; length as usize];
+    reader.seek(SeekFrom::Start(offset)).map_err(|err| err.to_string())?;
+    reader.read_exact(&mut bytes).map_err(|err| err.to_string())?;
+    let metadata = String::from_utf8(bytes).map_err(|err| err.to_string())?;
+
+    for field in ["frameNumbers = ", "frameTimestamps_sec = ", "\nepoch = "] {
+        let value = metadata.find(field)
+            .map(|start| &metadata[start + field.len()..])
+            .and_then(|rest| rest.find('\n').map(|end| rest[..end].trim()));
+        if value.and_then(|value| value.parse::<f64>().ok()).is_none() {
+            return Err(format!("No valid `{}` field", field.trim()));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the strip holds a whole number of photons, agrees with
+/// the intensity image (for compressed frames), and that every photon
+/// is inside the frame and has a valid arrival time. Problems with the
+/// photons are added to `problems`; reading errors are returned.
+#[allow(clippy::too_many_arguments)]
+fn _check_photons<R : Read + Seek>(
+    reader : &mut R,
+    compressed : bool,
+    shape : (u64, u64),
+    data_start : u64,
+    intensity_bytes : u64,
+    strip_bytes : u64,
+    num_bins : Option<u32>,
+    problems : &mut Vec<Problem>,
+) -> std::io::Result<()> {
+    let mut data = vec![0u8; (intensity_bytes + strip_bytes) as usize];
+    reader.seek(SeekFrom::Start(data_start))?;
+    reader.read_exact(&mut data)?;
+    let (intensity, strip) = data.split_at(intensity_bytes as usize);
+
+    // Arrival times, and coordinates for raw photons (see `pack_photon`)
+    let mut out_of_frame = (0usize, (0u64, 0u64));
+    let taus : Vec<u64> = if compressed {
+        let num_photons = intensity.chunks_exact(2)
+            .map(|px| u16::from_le_bytes([px[0], px[1]]) as u64)
+            .sum::<u64>();
+        if strip_bytes != 2 * num_photons {
+            problems.push(Problem::StripByteCountMismatch {
+                byte_count : strip_bytes, expected : 2 * num_photons
+            });
+        }
+        strip.chunks_exact(2).map(|tau| u16::from_le_bytes([tau[0], tau[1]]) as u64).collect()
+    } else {
+        if strip_bytes % 8 != 0 {
+            problems.push(Problem::StripByteCountMismatch {
+                byte_count : strip_bytes, expected : strip_bytes - strip_bytes % 8
+            });
+        }
+        strip.chunks_exact(8).map(|bytes| {
+            let photon = u64::from_le_bytes(bytes.try_into().unwrap());
+            let (y, x) = (photon >> 48, (photon >> 32) & 0xFFFF);
+            if y >= shape.0 || x >= shape.1 {
+                if out_of_frame.0 == 0 {
+                    out_of_frame.1 = (y, x);
+                }
+                out_of_frame.0 += 1;
+            }
+            photon & 0xFFFF_FFFF
+        }).collect()
+    };
+
+    if out_of_frame.0 > 0 {
+        problems.push(Problem::PhotonsOutOfFrame { count : out_of_frame.0, first : out_of_frame.1 });
+    }
+
+    if let Some(num_bins) = num_bins {
+        let late = taus.iter().filter(|&&tau| tau >= num_bins as u64);
+        let (count, max_tau) = late.fold((0, 0), |(count, max), &tau| (count + 1, max.max(tau)));
+        if count > 0 {
+            problems.push(Problem::TauOutOfRange { count, max_tau, num_bins });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::s;
+    use crate::{
+        SiffEncoding, SiffReader,
+        tests::{TempPath, write_classic_siff, write_synthetic_siff},
+    };
+
+    /// Where each IFD of a file is, and where its
+    /// description and strip begin
+    fn layout(path : &TempPath) -> Vec<(u64, u64, u64)> {
+        let reader = SiffReader::open(path).unwrap();
+        let mut file = File::open(path).unwrap();
+        let file_format = FileFormat::minimal_filetype(&mut file).unwrap();
+        let mut offset = file_format.first_ifd_val();
+        (0..reader.num_frames()).map(|_| {
+            let ifd = file_format.read_ifd(&mut file, offset).unwrap();
+            let page = (
+                offset,
+                ifd.get_tag(TiffTagID::ImageDescription).unwrap().value(),
+                ifd.get_tag(TiffTagID::StripOffsets).unwrap().value(),
+            );
+            offset = ifd.next_ifd.unwrap_or(0);
+            page
+        }).collect()
+    }
+
+    #[test]
+    fn fsck_reports_each_frame() {
+        let shape = (12, 16);
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let path = TempPath::new(&format!("fsck_{:?}.siff", encoding));
+            write_synthetic_siff(&path, 5, shape, encoding);
+            let report = check_file(&path).unwrap();
+            assert!(report.is_ok(), "{}", report);
+            assert_eq!(report.frames.len(), 5);
+
+            let pages = layout(&path);
+            let mut bytes = std::fs::read(&path).unwrap();
+            let original = bytes.clone();
+
+            // A late photon in frame 1 and a mangled description in frame 3
+            let strip = pages[1].2 as usize;
+            let late = match encoding {
+                SiffEncoding::Raw => {
+                    bytes[strip..strip + 4].copy_from_slice(&5000u32.to_le_bytes());
+                    5000
+                },
+                SiffEncoding::Compressed => {
+                    bytes[strip..strip + 2].copy_from_slice(&70u16.to_le_bytes());
+                    70
+                },
+            };
+            let description = pages[3].1 as usize;
+            bytes[description..description + 5].copy_from_slice(b"XXXXX");
+            // and the file ends partway through the last frame
+            bytes.truncate(pages[4].2 as usize + 10);
+            std::fs::write(&path, &bytes).unwrap();
+
+            let report = check_file(&path).unwrap();
+            assert_eq!(report.bad_frames(), vec![1, 3, 4], "{}", report);
+            assert_eq!(report.frames[1].problems, vec![Problem::TauOutOfRange {
+                count : 1, max_tau : late, num_bins : 64
+            }]);
+            assert!(matches!(report.frames[3].problems[..], [Problem::UnreadableMetadata(_)]));
+            assert!(matches!(report.frames[4].problems[..], [Problem::DataPastEof {..}]));
+
+            // The second IFD points past the end of the file, and the third back to the first
+            let mut bytes = original.clone();
+            let next_ifd_pointer = |page : usize| -> usize {
+                let ifd = pages[page].0 as usize;
+                let num_tags = u64::from_le_bytes(original[ifd..ifd + 8].try_into().unwrap());
+                ifd + 8 + 20 * num_tags as usize
+            };
+            let pointer = next_ifd_pointer(2);
+            bytes[pointer..pointer + 8].copy_from_slice(&pages[0].0.to_le_bytes());
+            std::fs::write(&path, &bytes).unwrap();
+            let report = check_file(&path).unwrap();
+            assert_eq!(report.frames.len(), 3);
+            assert_eq!(report.frames[2].problems, vec![Problem::NextIfdLoop { offset : pages[0].0 }]);
+            assert!(report.frames[2].is_intact());
+
+            let pointer = next_ifd_pointer(1);
+            bytes[pointer..pointer + 8].copy_from_slice(&(original.len() as u64 + 100).to_le_bytes());
+            std::fs::write(&path, &bytes).unwrap();
+            let report = check_file(&path).unwrap();
+            assert_eq!(report.frames.len(), 2);
+            assert!(matches!(report.frames[1].problems[..], [Problem::NextIfdPastEof {..}]));
+        }
+    }
+
+    #[test]
+    fn huge_dimensions() {
+        use crate::tiff::{BigTag, TiffTagType::*};
+        let ifd = BigTiffIFD::from_tags(vec![
+            BigTag::new(TiffTagID::ImageWidth, Long8, 1, u32::MAX as u64),
+            BigTag::new(TiffTagID::ImageLength, Long8, 1, u32::MAX as u64),
+            BigTag::new(TiffTagID::ImageDescription, Long8, 1, u64::MAX),
+            BigTag::new(TiffTagID::StripOffsets, Long8, 1, 64),
+            BigTag::new(TiffTagID::StripByteCounts, Long8, 1, 0),
+            BigTag::new(TiffTagID::Siff, Short, 1, 1),
+        ], None);
+        let mut reader = std::io::Cursor::new(vec![0u8; 64]);
+        assert_eq!(
+            _check_frame(&ifd, &mut reader, 64, None),
+            vec![Problem::ImageTooLarge { width : u32::MAX as u64, height : u32::MAX as u64 }]
+        );
+
+        // A description that can't fit before the strip
+        let mut tags = ifd.tags().clone();
+        tags[0] = BigTag::new(TiffTagID::ImageWidth, Long8, 1, 4);
+        tags[1] = BigTag::new(TiffTagID::ImageLength, Long8, 1, 4);
+        let ifd = BigTiffIFD::from_tags(tags, None);
+        assert!(matches!(
+            _check_frame(&ifd, &mut reader, 64, None)[..],
+            [Problem::UnreadableMetadata(_)]
+        ));
+    }
+
+    #[test]
+    fn repair_keeps_intact_frames() {
+        let shape = (12, 16);
+        for (classic, encoding) in [
+            (false, SiffEncoding::Raw), (false, SiffEncoding::Compressed), (true, SiffEncoding::Compressed)
+        ] {
+            let path = TempPath::new(&format!("repair_{}_{:?}.siff", classic, encoding));
+            let repaired = TempPath::new(&format!("repair_{}_{:?}_repaired.siff", classic, encoding));
+            if classic {
+                write_classic_siff(&path, 5, shape, encoding);
+            } else {
+                write_synthetic_siff(&path, 5, shape, encoding);
+            }
+            let original = SiffReader::open(&path).unwrap();
+            let frames = original.frames_vec();
+            let intensity = original.get_frames_intensity(&frames, None).unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+
+            // Nothing to fix
+            assert_eq!(repair_file(&path, &repaired).unwrap(), 5);
+            assert_eq!(std::fs::read(&repaired).unwrap(), bytes);
+
+            // The acquisition crashed while writing the last strip,
+            // and the fourth frame's strip was overwritten with garbage
+            let last_strip = (original.get_frame_metadata(&[4]).unwrap()[0].data_offset) as usize;
+            let mut damaged = bytes[..last_strip + 6].to_vec();
+            let fourth = original.get_frame_metadata(&[3]).unwrap()[0].data_offset as usize;
+            damaged[fourth..fourth + 4].copy_from_slice(&[0xFF; 4]);
+            std::fs::write(&path, &damaged).unwrap();
+            assert_eq!(check_file(&path).unwrap().bad_frames(), vec![3, 4]);
+
+            assert_eq!(repair_file(&path, &repaired).unwrap(), 3);
+            assert!(check_file(&repaired).unwrap().is_ok());
+            let reader = SiffReader::open(&repaired).unwrap();
+            assert_eq!(reader.num_frames(), 3);
+            assert_eq!(
+                reader.get_frames_intensity(&[0, 1, 2], None).unwrap(),
+                intensity.slice(s![..3, .., ..])
+            );
+
+            // Only the link from the third frame is broken
+            let mut damaged = bytes.clone();
+            let third = check_file(&repaired).unwrap().frames[2].ifd_offset as usize;
+            let (count_size, tag_size, pointer_size) = if classic { (2, 12, 4) } else { (8, 20, 8) };
+            let mut num_tags = [0u8; 8];
+            num_tags[..count_size].copy_from_slice(&bytes[third..third + count_size]);
+            let pointer = third + count_size + tag_size * u64::from_le_bytes(num_tags) as usize;
+            damaged[pointer..pointer + pointer_size].copy_from_slice(&vec![0xEE; pointer_size]);
+            std::fs::write(&path, &damaged).unwrap();
+            assert_eq!(repair_file(&path, &repaired).unwrap(), 3);
+            assert_eq!(SiffReader::open(&repaired).unwrap().num_frames(), 3);
+            assert!(check_file(&repaired).unwrap().is_ok());
+
+            // The output would overwrite the file being repaired
+            let before = std::fs::read(&path).unwrap();
+            assert!(matches!(repair_file(&path, &path), Err(CorrosiffError::IOError(_))));
+            assert_eq!(std::fs::read(&path).unwrap(), before);
+
+            // Nothing intact
+            let mut damaged = bytes.clone();
+            let first_strip = original.get_frame_metadata(&[0]).unwrap()[0].data_offset as usize;
+            damaged.truncate(first_strip + 2);
+            std::fs::write(&path, &damaged).unwrap();
+            assert!(matches!(repair_file(&path, &repaired), Err(CorrosiffError::FileFormatError)));
+        }
+    }
+}
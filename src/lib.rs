@@ -23,12 +23,11 @@
 use std::{
     io::Result as IOResult,
     io::Error as IOError,
-    io::Seek,
+    io::Write,
     path::{Path,PathBuf},
     fs::File,
     collections::HashMap,
 };
-use binrw::BinRead;
 
 use ndarray::prelude::*;
 use num_traits::identities::Zero;
@@ -39,13 +38,23 @@ mod utils;
 
 use crate::data::image::DimensionsError;
 
+pub mod export;
+pub mod fsck;
 pub mod metadata;
+pub mod ptu;
 pub mod siffreader;
+pub mod siffseries;
+pub mod siffwriter;
 
 pub use siffreader::{SiffReader, RegistrationDict};
+pub use siffseries::SiffSeries;
+pub use fsck::FsckReport;
+pub use siffwriter::{SiffWriter, SiffEncoding};
 pub use utils::FramesError;
 pub use metadata::FrameMetadata;
 pub use data::time::ClockBase;
+pub use data::image::photons::Photon;
+pub use tiff::{Tag, TagValue, TiffTagID, TiffTagType};
 
 /// The `CorrosiffError` class
 /// reflects the major types of errors
@@ -108,6 +117,10 @@ impl std::fmt::Display for CorrosiffError {
 /// 
 /// * `ScanImage` - The standard ScanImage format
 /// * `OME` - The OME-TIFF format
+/// * `ImageJ` - A plain classic `.tiff` (so at most 4 GB) with an
+/// ImageJ hyperstack description, so that Fiji splits the frames
+/// by channel, z, and time. Flyback frames are left out, so each
+/// volume holds only its imaging planes
 /// 
 /// ## Examples
 /// 
@@ -128,6 +141,7 @@ impl std::fmt::Display for CorrosiffError {
 pub enum TiffMode {
     ScanImage,
     OME,
+    ImageJ,
 }
 
 impl TiffMode {
@@ -142,6 +156,7 @@ impl TiffMode {
         match str {
             "ScanImage" => Ok(TiffMode::ScanImage),
             "OME" => Ok(TiffMode::OME),
+            "ImageJ" => Ok(TiffMode::ImageJ),
             _ => Err(
                 std::io::Error::new(
                     std::io::ErrorKind::InvalidInput,
@@ -152,6 +167,65 @@ impl TiffMode {
     }
 }
 
+/// Enum for specifying which image is computed from
+/// each frame of a `.siff` file when it is exported
+/// to a `.tiff` file.
+///
+/// ## Variants
+///
+/// * `Intensity` - Photon counts, as `u16` pages
+/// * `Lifetime` - The empirical lifetime (mean arrival time, in
+/// units of arrival time bins) of each pixel, as `f32` pages
+/// * `Phasor` - The phasor of each pixel's arrival time histogram,
+/// as two `f32` pages per frame: G (the real part) followed by S
+/// (the imaginary part)
+///
+/// ## Example
+///
+/// ```
+/// use corrosiff::TiffPayload;
+///
+/// let payload = TiffPayload::from_string_slice("Lifetime");
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TiffPayload {
+    #[default]
+    Intensity,
+    Lifetime,
+    Phasor,
+}
+
+impl TiffPayload {
+    /// `from_string_slice(str)` parses a string slice
+    /// to produce a `TiffPayload` enum. Useful for argument
+    /// parsing from the command line.
+    ///
+    /// ## Arguments
+    ///
+    /// * `str` - A string slice that holds the name of the payload
+    pub fn from_string_slice(str : & str) -> IOResult<TiffPayload> {
+        match str {
+            "Intensity" => Ok(TiffPayload::Intensity),
+            "Lifetime" => Ok(TiffPayload::Lifetime),
+            "Phasor" => Ok(TiffPayload::Phasor),
+            _ => Err(
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid TiffPayload"
+                )
+            ),
+        }
+    }
+
+    /// Number of pages written for each frame
+    pub fn pages_per_frame(&self) -> usize {
+        match self {
+            TiffPayload::Phasor => 2,
+            _ => 1,
+        }
+    }
+}
+
 /// `open_siff(filename)` opens a `.siff` file
 /// or a ScanImage-Flim `.tiff` file, reads the data,
 /// and returns a `SiffReader` object.
@@ -272,10 +346,12 @@ pub fn scan_first_timestamp<P : AsRef<Path>>(file_path : &P)
 
     let file_format = tiff::FileFormat::minimal_filetype(&mut file)
     .map_err(|_| CorrosiffError::FileFormatError)?;
+    // Plain Tiffs have no timestamps
+    if !file_format.is_scanimage() {
+        return Err(CorrosiffError::FileFormatError);
+    }
 
-    file.seek(std::io::SeekFrom::Start(file_format.first_ifd_val())).unwrap();
-
-    let first_ifd = tiff::BigTiffIFD::read(&mut file)
+    let first_ifd = file_format.read_ifd(&mut file, file_format.first_ifd_val())
     .map_err(|_| CorrosiffError::FileFormatError)?;
 
     Ok(
@@ -314,6 +390,10 @@ pub fn scan_timestamps<P: AsRef<Path>>(
 
     let file_format = tiff::FileFormat::minimal_filetype(&mut file)
     .map_err(|_| CorrosiffError::FileFormatError)?;
+    // Plain Tiffs have no timestamps
+    if !file_format.is_scanimage() {
+        return Err(CorrosiffError::FileFormatError);
+    }
 
     let mut ifd_buff = binrw::io::BufReader::with_capacity(400, &file);
     let mut ifds = file_format.get_ifd_iter(&mut ifd_buff);
@@ -322,7 +402,7 @@ pub fn scan_timestamps<P: AsRef<Path>>(
         (
             metadata::get_epoch_timestamps_laser(
                 &[&ifds.next().ok_or_else(|| CorrosiffError::FileFormatError)?],
-                &mut ifds.reader
+                ifds.reader()
             )[0],
 
             metadata::get_epoch_timestamps_laser(
@@ -404,7 +484,8 @@ pub fn get_frames(
 /// is `TiffMode::ScanImage`, the main metadata remains
 /// unconverted, and it uses the standard ScanImage format.
 /// If the mode is `TiffMode::OME`, the metadata is converted
-/// to the OME-TIFF format.
+/// to the OME-TIFF format. If the mode is `TiffMode::ImageJ`,
+/// the first frame describes the ImageJ hyperstack dimensions.
 /// 
 /// ## Arguments
 /// 
@@ -419,7 +500,7 @@ pub fn get_frames(
 /// 
 /// ```rust, ignore
 /// use corrosiff::{siff_to_tiff, TiffMode};
-/// // Produces "file.tiff" in OME-TIFF format (not-yet implemented)
+/// // Produces "file.tiff" in OME-TIFF format
 /// siff_to_tiff("file.siff", TiffMode::from_string_slice("OME"), None);
 /// // Produces "file2.tiff" in ScanImage format
 /// siff_to_tiff("file.siff", TiffMode::from_string_slice("ScanImage"), Some("file2.tiff"));
@@ -438,14 +519,224 @@ pub fn siff_to_tiff(
         .map(PathBuf::from)
         .unwrap_or_else(|| {file_path.with_extension("tiff")});
 
-    let mut tiff_file = File::create(save_path)?;
+    let mut tiff_file = std::io::BufWriter::new(File::create(save_path)?);
 
     siffreader.write_header_to_file(&mut tiff_file, &mode)
     .map_err(|err| IOError::new(std::io::ErrorKind::InvalidData, err))?;
-    siffreader.write_tiff_frames_to_file(&mut tiff_file, None)?;
+    match mode {
+        TiffMode::ScanImage => siffreader.write_tiff_frames_to_file(&mut tiff_file, None)?,
+        TiffMode::OME => siffreader.write_ome_tiff_frames_to_file(&mut tiff_file, None)?,
+        TiffMode::ImageJ => siffreader.write_tiff_payload_to_file(
+            &mut tiff_file, &mode, None, None, TiffPayload::Intensity
+        )?,
+    }
+    tiff_file.flush()?;
+    Ok(())
+}
+
+/// `siff_to_tiff_payload(filename, mode, payload, frames, registration, save_path)`
+/// converts a `.siff` file to a `.tiff` file like `siff_to_tiff`, but
+/// can register the frames, write only some of them, and write the
+/// empirical lifetime or phasor of each frame instead of its intensity.
+/// Each page keeps the ScanImage metadata string of its frame.
+///
+/// ## Arguments
+///
+/// * `filename` - A string slice that holds the name of the file to open
+/// * `mode` - The `TiffMode` to write the file in
+/// * `payload` - The `TiffPayload` to compute from each frame
+/// * `frames` - An optional slice of the frames to write. If `None`,
+/// all frames are written.
+/// * `registration` - An optional `RegistrationDict` to shift each frame by
+/// * `save_path` - An optional string slice that holds the path
+/// to save the converted file. If not specified, the file is saved
+/// in the same directory as the original file, with the same name
+/// but the extension `.tiff`.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// use corrosiff::{siff_to_tiff_payload, TiffMode, TiffPayload};
+/// // Produces "file.tiff" with the registered lifetime of every frame
+/// siff_to_tiff_payload(
+///     "file.siff",
+///     TiffMode::ScanImage,
+///     TiffPayload::Lifetime,
+///     None,
+///     Some(&registration),
+///     None
+/// )?;
+/// ```
+///
+/// ## Errors
+///
+/// * `CorrosiffError::DimensionsError` - If the frames are out of bounds,
+/// don't share a shape, or if no frames are requested.
+///
+/// * `CorrosiffError::FramesError(FramesError::RegistrationFramesMissing)` -
+/// If registration is used, and the registration values are missing for some frames
+///
+/// * `CorrosiffError::IOError` - If the output would overwrite the input,
+/// or if reading or writing fails. Nothing is written if the arguments
+/// are invalid.
+pub fn siff_to_tiff_payload(
+    filename : &str,
+    mode : TiffMode,
+    payload : TiffPayload,
+    frames : Option<&[u64]>,
+    registration : Option<&RegistrationDict>,
+    save_path : Option<&String>,
+) -> Result<(), CorrosiffError> {
+    let file_path = PathBuf::from(filename);
+    let siffreader = SiffReader::open(&file_path)?;
+
+    let save_path = save_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| file_path.with_extension("tiff"));
+
+    if save_path.exists() && save_path.canonicalize()? == file_path.canonicalize()? {
+        return Err(CorrosiffError::IOError(IOError::new(
+            std::io::ErrorKind::InvalidInput,
+            "Cannot convert a file onto itself"
+        )));
+    }
+    let all_frames = siffreader.frames_vec();
+    let mut checked_registration = registration;
+    siffreader._check_tiff_payload(
+        frames.unwrap_or(&all_frames), &mut checked_registration, &mode, payload
+    )?;
+
+    let mut tiff_file = std::io::BufWriter::new(File::create(save_path)?);
+    siffreader.write_header_to_file(&mut tiff_file, &mode)?;
+    siffreader.write_tiff_payload_to_file(&mut tiff_file, &mode, frames, registration, payload)?;
+    tiff_file.flush()?;
+    Ok(())
+}
+
+/// `transcode_siff(filename, encoding, save_path)` rewrites a `.siff`
+/// file with every frame stored in the requested `SiffEncoding`.
+/// The transcoding is lossless: the header, every frame's metadata
+/// string, and the arrival time data of every pixel are preserved,
+/// so e.g. `get_frames_tau_d` returns the same array for both files.
+///
+/// ## Arguments
+///
+/// * `filename` - A string slice that holds the name of the file to open
+/// * `encoding` - The `SiffEncoding` to store the frames in
+/// * `save_path` - An optional string slice that holds the path
+/// to save the transcoded file. If not specified, the file is saved
+/// in the same directory as the original file, with `_raw` or `_compressed`
+/// appended to its name.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// use corrosiff::{transcode_siff, SiffEncoding};
+/// // Produces "file_compressed.siff"
+/// transcode_siff("file.siff", SiffEncoding::Compressed, None)?;
+/// ```
+///
+/// ## Errors
+///
+/// * `CorrosiffError::FramesError(FramesError::FormatError)` - If a frame
+/// is not a `.siff` frame or cannot be stored in the requested encoding.
+///
+/// * `CorrosiffError::IOError` - If the output would overwrite the input,
+/// or if reading or writing fails.
+pub fn transcode_siff(
+    filename : &str,
+    encoding : SiffEncoding,
+    save_path : Option<&String>,
+) -> Result<(), CorrosiffError> {
+    let file_path = PathBuf::from(filename);
+    let siffreader = SiffReader::open(&file_path)?;
+
+    let save_path = save_path.map(PathBuf::from).unwrap_or_else(|| {
+        let suffix = match encoding {
+            SiffEncoding::Raw => "raw",
+            SiffEncoding::Compressed => "compressed",
+        };
+        file_path.with_file_name(format!(
+            "{}_{}.siff",
+            file_path.file_stem().unwrap_or_default().to_string_lossy(),
+            suffix
+        ))
+    });
+
+    if save_path.exists() && save_path.canonicalize()? == file_path.canonicalize()? {
+        return Err(CorrosiffError::IOError(IOError::new(
+            std::io::ErrorKind::InvalidInput,
+            "Cannot transcode a file in place"
+        )));
+    }
+
+    let writer = SiffWriter::create(&save_path, &siffreader.nvfd(), &siffreader.roi_string())?;
+    siffreader.write_siff_frames(writer, None, encoding)?;
     Ok(())
 }
 
+/// `concatenate_siffs(filenames, save_path, boundary_marker)` merges
+/// several `.siff` files recorded with identical settings into one
+/// file, with the frames of each file following those of the file before
+/// it. The pages of every file are copied byte for byte, with their IFDs
+/// chained together and their offsets rebased, so every frame keeps its
+/// encoding and metadata string. The new file has the header of the first.
+///
+/// ## Arguments
+///
+/// * `filenames` - The files to merge, in order
+/// * `save_path` - Where to save the merged file
+/// * `boundary_marker` - If provided, this text is appended to the
+/// metadata of the first frame of every file after the first, so that
+/// `SiffReader::get_appended_text` shows where each file began.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// use corrosiff::concatenate_siffs;
+/// concatenate_siffs(&["trial1.siff", "trial2.siff"], "trials.siff", Some("New trial"))?;
+///
+/// let reader = corrosiff::open_siff("trials.siff")?;
+/// // (first frame of trial2, "New trial", timestamp)
+/// let boundaries = reader.get_appended_text(&reader.frames_vec());
+/// ```
+///
+/// ## Errors
+///
+/// * `CorrosiffError::FramesError(FramesError::FormatError)` - If there are
+/// no files or they were acquired with different settings (see
+/// `FileFormat::acquisition_mismatch` -- fields like `SI.hScan2D.logFileCounter`
+/// may differ).
+///
+/// * `CorrosiffError::DimensionsError(DimensionsError::NoConsistentDimensions)` - If
+/// the frames do not all have the same shape.
+///
+/// * `CorrosiffError::IOError` - If the output would overwrite an input,
+/// or if reading or writing fails.
+///
+/// ## See also
+///
+/// - `SiffSeries` to read the files as one without merging them
+pub fn concatenate_siffs<P : AsRef<Path>, Q : AsRef<Path>>(
+    filenames : &[P],
+    save_path : Q,
+    boundary_marker : Option<&str>,
+) -> Result<(), CorrosiffError> {
+    let save_path = save_path.as_ref();
+    if save_path.exists() {
+        let save_path = save_path.canonicalize()?;
+        for filename in filenames {
+            if filename.as_ref().canonicalize()? == save_path {
+                return Err(CorrosiffError::IOError(IOError::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Cannot concatenate into one of the input files"
+                )));
+            }
+        }
+    }
+    SiffSeries::from_files(filenames)?.write_siff(save_path, boundary_marker)
+}
+
 /// `dfof(data, f0)` computes the deltaF/F0 of the input data
 /// using the provided F0 array, operating in-place to prevent
 /// excessive memory usage and using parallelism to speed up
@@ -520,6 +811,264 @@ pub mod tests {
     pub const UNCOMPRESSED_FRAME_NUM : usize = 14;
     pub const COMPRESSED_FRAME_NUM : usize = 40;
 
+    /// NVFD for synthetic files: 64 arrival time bins of 20 ps
+    pub (crate) const SYNTHETIC_NVFD : &str = "SI.hScan2D.Tau_bins = 62\nSI.hScan2D.binResolution = 2\n";
+    /// Laser epoch timestamp of the first synthetic frame
+    pub (crate) const SYNTHETIC_EPOCH : u64 = 1_700_000_000_000_000_000;
+
+    /// A path in the system temp directory that is deleted
+    /// when it goes out of scope, for tests that write files.
+    pub (crate) struct TempPath(PathBuf);
+
+    impl TempPath {
+        pub (crate) fn new(name : &str) -> Self {
+            TempPath(
+                std::env::temp_dir().join(format!("corrosiff_{}_{}", std::process::id(), name))
+            )
+        }
+    }
+
+    impl AsRef<Path> for TempPath {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Deterministic pseudo-random photons for a synthetic frame,
+    /// packed in the raw `.siff` format. Different for every `frame`.
+    pub (crate) fn synthetic_photons(frame : usize, shape : (usize, usize), n_bins : u32)
+    -> Vec<u64> {
+        let mut state = 0x2545F4914F6CDD1Du64 ^ (frame as u64 + 1).wrapping_mul(0x9E3779B97F4A7C15);
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let num_photons = shape.0 * shape.1 / 2 + 13 * frame;
+        (0..num_photons).map(|_| {
+            siffwriter::pack_photon(
+                (next() % shape.0 as u64) as u16,
+                (next() % shape.1 as u64) as u16,
+                (next() % n_bins as u64) as u32,
+            )
+        }).collect()
+    }
+
+    /// A ScanImage-like frame description for synthetic frames,
+    /// with frames 0.1 seconds apart.
+    pub (crate) fn synthetic_description(frame : usize) -> String {
+        format!(
+            "frameNumbers = {}\nframeTimestamps_sec = {}\nepoch = {}\n\
+            mostRecentSystemTimestamp_epoch = {}\nsync Stamps = {}\n",
+            frame + 1,
+            frame as f64 / 10.0,
+            SYNTHETIC_EPOCH + frame as u64 * 100_000_000,
+            SYNTHETIC_EPOCH + (frame as u64 / 10) * 1_000_000_000,
+            frame as u64 * 8_000_000,
+        )
+    }
+
+    /// Writes a synthetic `.siff` file with `num_frames` frames of
+    /// `synthetic_photons` in the requested encoding and returns the photons
+    /// of each frame.
+    pub (crate) fn write_synthetic_siff<P : AsRef<Path>>(
+        path : P,
+        num_frames : usize,
+        shape : (usize, usize),
+        encoding : SiffEncoding,
+    ) -> Vec<Vec<u64>> {
+        let photons = (0..num_frames).map(|frame| synthetic_photons(frame, shape, 64))
+            .collect::<Vec<_>>();
+        let mut writer = SiffWriter::create(path, SYNTHETIC_NVFD, "").unwrap();
+        photons.iter().enumerate().for_each(|(frame, photons)| {
+            writer.write_frame_photons(
+                photons, shape, encoding, &synthetic_description(frame)
+            ).unwrap();
+        });
+        writer.finish().unwrap();
+        photons
+    }
+
+    /// Reads every IFD of a Tiff (classic or `BigTiff`), in
+    /// order, by following the chain from the header.
+    pub (crate) fn read_ifds<P : AsRef<Path>>(path : P) -> Vec<tiff::BigTiffIFD> {
+        let mut file = File::open(path).unwrap();
+        let file_format = tiff::FileFormat::minimal_filetype(&mut file).unwrap();
+        let mut offset = file_format.first_ifd_val();
+        let mut ifds = Vec::new();
+        while offset != 0 {
+            let ifd = file_format.read_ifd(&mut file, offset).unwrap();
+            offset = ifd.next_ifd.unwrap_or(0);
+            ifds.push(ifd);
+        }
+        ifds
+    }
+
+    /// Writes the same frames as `write_synthetic_siff`, but in the classic
+    /// 32-bit Tiff layout ScanImage uses for files under 4 GB. Resolutions are
+    /// stored out-of-line, as the Tiff specification requires for classic files.
+    pub (crate) fn write_classic_siff<P : AsRef<Path>>(
+        path : P,
+        num_frames : usize,
+        shape : (usize, usize),
+        encoding : SiffEncoding,
+    ) -> Vec<Vec<u64>> {
+        let photons = (0..num_frames).map(|frame| synthetic_photons(frame, shape, 64))
+            .collect::<Vec<_>>();
+        let roi_string = "classic ROI";
+        let header_size = 24u32;
+
+        let mut bytes = Vec::<u8>::new();
+        bytes.extend_from_slice(b"II");
+        bytes.extend_from_slice(&42u16.to_le_bytes());
+        bytes.extend_from_slice(
+            &(header_size + (SYNTHETIC_NVFD.len() + roi_string.len()) as u32).to_le_bytes()
+        );
+        [117637889u32, 3, SYNTHETIC_NVFD.len() as u32, roi_string.len() as u32]
+            .iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+        bytes.extend_from_slice(SYNTHETIC_NVFD.as_bytes());
+        bytes.extend_from_slice(roi_string.as_bytes());
+
+        let num_tags = 18usize;
+        let ifd_size = 2 + 12 * num_tags + 4;
+        for (frame, photons) in photons.iter().enumerate() {
+            let description = synthetic_description(frame);
+            let (siff_compress, pre_strip, strip) : (u32, Vec<u8>, Vec<u8>) = match encoding {
+                SiffEncoding::Raw => (0, vec![], bytemuck::cast_slice(photons).to_vec()),
+                SiffEncoding::Compressed => {
+                    let (intensity, arrivals) = data::image::photons::compress_photons(
+                        photons, shape
+                    ).unwrap();
+                    (
+                        1,
+                        bytemuck::cast_slice(&intensity.iter().cloned().collect::<Vec<_>>()).to_vec(),
+                        bytemuck::cast_slice(&arrivals).to_vec()
+                    )
+                },
+            };
+
+            let start = bytes.len();
+            let resolutions = start + ifd_size;
+            let description_start = resolutions + 16;
+            let strip_start = description_start + description.len() + pre_strip.len();
+            let next_ifd = if frame + 1 == num_frames { 0 } else { strip_start + strip.len() };
+            let (ydim, xdim) = (shape.0 as u32, shape.1 as u32);
+
+            let tags : [(u16, u16, u32, u32); 18] = [
+                (256, 4, 1, xdim),
+                (257, 4, 1, ydim),
+                (258, 3, 1, if siff_compress == 0 { 64 } else { 16 }),
+                (259, 3, 1, 1),
+                (262, 3, 1, 1),
+                (270, 2, description.len() as u32, description_start as u32),
+                (273, 4, 1, strip_start as u32),
+                (274, 3, 1, 1),
+                (277, 3, 1, 1),
+                (278, 4, 1, ydim),
+                (279, 4, 1, strip.len() as u32),
+                (282, 5, 1, resolutions as u32),
+                (283, 5, 1, resolutions as u32 + 8),
+                (296, 3, 1, 1),
+                (305, 2, SYNTHETIC_NVFD.len() as u32, header_size),
+                (315, 2, roi_string.len() as u32, header_size + SYNTHETIC_NVFD.len() as u32),
+                (339, 3, 1, 1),
+                (907, 3, 1, siff_compress),
+            ];
+            bytes.extend_from_slice(&(num_tags as u16).to_le_bytes());
+            tags.iter().for_each(|(tag, dtype, count, value)| {
+                bytes.extend_from_slice(&tag.to_le_bytes());
+                bytes.extend_from_slice(&dtype.to_le_bytes());
+                bytes.extend_from_slice(&count.to_le_bytes());
+                bytes.extend_from_slice(&value.to_le_bytes());
+            });
+            bytes.extend_from_slice(&(next_ifd as u32).to_le_bytes());
+            [1u32, 1, 1, 1].iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+            bytes.extend_from_slice(description.as_bytes());
+            bytes.extend_from_slice(&pre_strip);
+            bytes.extend_from_slice(&strip);
+        }
+        std::fs::write(path, bytes).unwrap();
+        photons
+    }
+
+    /// Writes a plain 16 bit classic Tiff stack, like one saved by ImageJ,
+    /// with no ScanImage header. Each frame is split into two strips stored
+    /// in reverse order, so the strip offsets and byte counts are out-of-line.
+    /// The first IFD also has ImageJ's private metadata tag. Returns the
+    /// intensity of each frame.
+    pub (crate) fn write_plain_tiff<P : AsRef<Path>>(
+        path : P,
+        num_frames : usize,
+        shape : (usize, usize),
+    ) -> Array3<u16> {
+        let intensity = Array3::<u16>::from_shape_fn(
+            (num_frames, shape.0, shape.1),
+            |(frame, y, x)| (frame * 1000 + y * shape.1 + x) as u16
+        );
+        let description = format!("ImageJ=1.54f\nimages={}\n\0", num_frames);
+        let split_row = (shape.0 + 1) / 2;
+
+        let mut bytes = Vec::<u8>::new();
+        bytes.extend_from_slice(b"II");
+        bytes.extend_from_slice(&42u16.to_le_bytes());
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+
+        for frame in 0..num_frames {
+            let num_tags = if frame == 0 { 11 } else { 10 };
+            let ifd_size = 2 + 12 * num_tags + 4;
+            let start = bytes.len();
+            let strip_arrays = start + ifd_size;
+            let description_start = strip_arrays + 16;
+            let bottom_start = description_start + description.len();
+            let bottom = bytemuck::cast_slice::<u16, u8>(
+                intensity.slice(s![frame, split_row.., ..]).as_slice().unwrap()
+            ).to_vec();
+            let top_start = bottom_start + bottom.len();
+            let top = bytemuck::cast_slice::<u16, u8>(
+                intensity.slice(s![frame, ..split_row, ..]).as_slice().unwrap()
+            ).to_vec();
+            let next_ifd = if frame + 1 == num_frames { 0 } else { top_start + top.len() };
+
+            let mut tags : Vec<(u16, u16, u32, u32)> = vec![
+                (256, 3, 1, shape.1 as u32),
+                (257, 3, 1, shape.0 as u32),
+                (258, 3, 1, 16),
+                (259, 3, 1, 1),
+                (262, 3, 1, 1),
+                (270, 2, description.len() as u32, description_start as u32),
+                (273, 4, 2, strip_arrays as u32),
+                (277, 3, 1, 1),
+                (278, 3, 1, split_row as u32),
+                (279, 4, 2, strip_arrays as u32 + 8),
+            ];
+            if frame == 0 {
+                tags.push((50839, 7, 4, u32::from_le_bytes(*b"IJIJ")));
+            }
+            bytes.extend_from_slice(&(num_tags as u16).to_le_bytes());
+            tags.iter().for_each(|(tag, dtype, count, value)| {
+                bytes.extend_from_slice(&tag.to_le_bytes());
+                bytes.extend_from_slice(&dtype.to_le_bytes());
+                bytes.extend_from_slice(&count.to_le_bytes());
+                bytes.extend_from_slice(&value.to_le_bytes());
+            });
+            bytes.extend_from_slice(&(next_ifd as u32).to_le_bytes());
+            [top_start, bottom_start, top.len(), bottom.len()].iter()
+                .for_each(|&x| bytes.extend_from_slice(&(x as u32).to_le_bytes()));
+            bytes.extend_from_slice(description.as_bytes());
+            bytes.extend_from_slice(&bottom);
+            bytes.extend_from_slice(&top);
+        }
+        std::fs::write(path, bytes).unwrap();
+        intensity
+    }
+
     #[test]
     fn test_dfof() {
         let data = array![
@@ -568,6 +1117,381 @@ pub mod tests {
         println!("Start: {}, End: {}", start, end);
     }
 
+    #[test]
+    fn test_transcode_siff() {
+        let raw_path = TempPath::new("transcode_raw.siff");
+        let compressed_path = TempPath::new("transcode_compressed.siff");
+        let round_trip_path = TempPath::new("transcode_round_trip.siff");
+        write_synthetic_siff(&raw_path, 6, (12, 20), SiffEncoding::Raw);
+
+        let as_string = |path : &TempPath| path.as_ref().to_str().unwrap().to_string();
+        transcode_siff(
+            &as_string(&raw_path), SiffEncoding::Compressed, Some(&as_string(&compressed_path))
+        ).unwrap();
+        transcode_siff(
+            &as_string(&compressed_path), SiffEncoding::Raw, Some(&as_string(&round_trip_path))
+        ).unwrap();
+
+        let raw = SiffReader::open(&raw_path).unwrap();
+        let compressed = SiffReader::open(&compressed_path).unwrap();
+        let round_trip = SiffReader::open(&round_trip_path).unwrap();
+
+        assert!(
+            std::fs::metadata(&compressed_path).unwrap().len()
+            < std::fs::metadata(&raw_path).unwrap().len()
+        );
+        assert_eq!(compressed.nvfd(), raw.nvfd());
+        assert_eq!(compressed.get_frame_metadata(&[0]).unwrap()[0].siff_compress, Some(1));
+        assert_eq!(round_trip.get_frame_metadata(&[0]).unwrap()[0].siff_compress, Some(0));
+
+        let frames = raw.frames_vec();
+        let tau_d = raw.get_frames_tau_d(&frames, None).unwrap();
+        assert_eq!(compressed.get_frames_tau_d(&frames, None).unwrap(), tau_d);
+        assert_eq!(round_trip.get_frames_tau_d(&frames, None).unwrap(), tau_d);
+
+        let metadata = |reader : &SiffReader| reader.get_frame_metadata(&frames).unwrap()
+            .into_iter().map(|m| m.metadata_string).collect::<Vec<_>>();
+        assert_eq!(metadata(&compressed), metadata(&raw));
+        assert_eq!(metadata(&round_trip), metadata(&raw));
+
+        // Refuses to overwrite its input
+        assert!(
+            transcode_siff(&as_string(&raw_path), SiffEncoding::Raw, Some(&as_string(&raw_path)))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_concatenate_siffs() {
+        let shape = (12, 20);
+        let first_path = TempPath::new("concatenate_first.siff");
+        let second_path = TempPath::new("concatenate_second.siff");
+        let third_path = TempPath::new("concatenate_third.siff");
+        let merged_path = TempPath::new("concatenate_merged.siff");
+        write_synthetic_siff(&first_path, 3, shape, SiffEncoding::Raw);
+        // Later files of an acquisition have their own file counter
+        let source_path = TempPath::new("concatenate_source.siff");
+        write_synthetic_siff(&source_path, 4, shape, SiffEncoding::Compressed);
+        let writer = SiffWriter::create(
+            &second_path, &format!("{}SI.hScan2D.logFileCounter = 2\n", SYNTHETIC_NVFD), ""
+        ).unwrap();
+        SiffReader::open(&source_path).unwrap()
+            .write_siff_frames(writer, None, SiffEncoding::Compressed).unwrap();
+        write_classic_siff(&third_path, 2, shape, SiffEncoding::Raw);
+
+        concatenate_siffs(
+            &[&first_path, &second_path, &third_path], &merged_path, Some("Trial 2")
+        ).unwrap();
+        let first = SiffReader::open(&first_path).unwrap();
+        let second = SiffReader::open(&second_path).unwrap();
+        let third = SiffReader::open(&third_path).unwrap();
+        let merged = SiffReader::open(&merged_path).unwrap();
+        assert_eq!(merged.num_frames(), 9);
+        assert_eq!(merged.nvfd(), first.nvfd());
+
+        let tau_d = merged.get_frames_tau_d(&merged.frames_vec(), None).unwrap();
+        assert_eq!(tau_d.slice(s![..3, .., .., ..]), first.get_frames_tau_d(&[0, 1, 2], None).unwrap());
+        assert_eq!(tau_d.slice(s![3..7, .., .., ..]), second.get_frames_tau_d(&second.frames_vec(), None).unwrap());
+        assert_eq!(tau_d.slice(s![7.., .., .., ..]), third.get_frames_tau_d(&[0, 1], None).unwrap());
+        assert_eq!(merged.get_frame_metadata(&[2, 3, 7]).unwrap().iter()
+            .map(|m| m.siff_compress).collect::<Vec<_>>(), vec![Some(0), Some(1), Some(0)]);
+        assert_eq!(
+            merged.get_experiment_timestamps(&[3, 4]).unwrap(),
+            second.get_experiment_timestamps(&[0, 1]).unwrap()
+        );
+        assert_eq!(
+            merged.get_frame_metadata(&[4, 8]).unwrap().iter()
+                .map(|m| m.metadata_string.clone()).collect::<Vec<_>>(),
+            vec![
+                second.get_frame_metadata(&[1]).unwrap()[0].metadata_string.clone(),
+                third.get_frame_metadata(&[1]).unwrap()[0].metadata_string.clone(),
+            ]
+        );
+        assert_eq!(
+            merged.get_appended_text(&merged.frames_vec()),
+            vec![(3, "Trial 2".to_string(), Some(0.0)), (7, "Trial 2".to_string(), Some(0.0))]
+        );
+        assert!(fsck::check_file(&merged_path).unwrap().is_ok());
+
+        // No marker
+        concatenate_siffs(&[&first_path, &second_path], &merged_path, None).unwrap();
+        assert!(SiffReader::open(&merged_path).unwrap().get_appended_text(&[3]).is_empty());
+
+        // Mismatched settings or shapes
+        let other_path = TempPath::new("concatenate_other.siff");
+        write_synthetic_siff(&other_path, 2, (12, 16), SiffEncoding::Raw);
+        assert!(matches!(
+            concatenate_siffs(&[&first_path, &other_path], &merged_path, None),
+            Err(CorrosiffError::DimensionsError(DimensionsError::NoConsistentDimensions))
+        ));
+        let writer = SiffWriter::create(&other_path, "SI.hScan2D.Tau_bins = 30\n", "").unwrap();
+        first.write_siff_frames(writer, None, SiffEncoding::Raw).unwrap();
+        assert!(matches!(
+            concatenate_siffs(&[&first_path, &other_path], &merged_path, None),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+
+        // Frames that can't be copied are found before anything is written
+        let tiff_path = TempPath::new("concatenate_intensity.tiff");
+        siff_to_tiff(
+            first_path.as_ref().to_str().unwrap(),
+            TiffMode::ScanImage,
+            Some(&tiff_path.as_ref().to_str().unwrap().to_string()),
+        ).unwrap();
+        let merged_bytes = std::fs::read(&merged_path).unwrap();
+        assert!(matches!(
+            concatenate_siffs(&[&first_path, &tiff_path], &merged_path, None),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+        assert_eq!(std::fs::read(&merged_path).unwrap(), merged_bytes);
+
+        // A boundary frame with its own appended text keeps it
+        let marked_path = TempPath::new("concatenate_marked.siff");
+        let mut writer = SiffWriter::create(&marked_path, SYNTHETIC_NVFD, "").unwrap();
+        first.copy_siff_frames(&mut writer, &[0, 1], |frame, description| match frame {
+            0 => FrameMetadata::metadata_str_with_appended_text(&description, "Stimulus"),
+            _ => description,
+        }).unwrap();
+        writer.finish().unwrap();
+        concatenate_siffs(&[&first_path, &marked_path], &merged_path, Some("Trial 2")).unwrap();
+        assert_eq!(
+            SiffReader::open(&merged_path).unwrap().get_appended_text(&[3, 4]),
+            vec![(3, "Stimulus; Trial 2".to_string(), Some(0.0))]
+        );
+
+        // Refuses to overwrite an input
+        assert!(concatenate_siffs(&[&first_path, &second_path], &second_path, None).is_err());
+    }
+
+    #[test]
+    fn test_siff_to_ome_tiff() {
+        use std::io::Seek;
+        use tiff::{IFD, Tag};
+        let siff_path = TempPath::new("ome_export.siff");
+        let tiff_path = TempPath::new("ome_export.ome.tiff");
+        let nvfd = format!(
+            "{}SI.hChannels.channelSave = 1\n\
+            SI.hFastZ.enable = true\n\
+            SI.hFastZ.numDiscardFlybackFrames = 1\n\
+            SI.hRoiManager.imagingFovUm = [-40 -20;40 -20;40 20;-40 20]\n\
+            SI.hStackManager.actualNumSlices = 2\n\
+            SI.hStackManager.stackZStepSize = 3\n",
+            SYNTHETIC_NVFD
+        );
+        let shape = (10, 20);
+        let mut writer = SiffWriter::create(&siff_path, &nvfd, "").unwrap();
+        (0..6).for_each(|frame| {
+            writer.write_frame_photons(
+                &synthetic_photons(frame, shape, 64),
+                shape,
+                SiffEncoding::Compressed,
+                &synthetic_description(frame)
+            ).unwrap();
+        });
+        writer.finish().unwrap();
+
+        siff_to_tiff(
+            siff_path.as_ref().to_str().unwrap(),
+            TiffMode::OME,
+            Some(&tiff_path.as_ref().to_str().unwrap().to_string())
+        ).unwrap();
+
+        let reader = SiffReader::open(&siff_path).unwrap();
+        let intensity = reader.get_frames_intensity(&reader.frames_vec(), None).unwrap();
+
+        // A plain BigTiff
+        let mut tiff = File::open(&tiff_path).unwrap();
+        let mut header = [0u8; 4];
+        std::io::Read::read_exact(&mut tiff, &mut header).unwrap();
+        assert_eq!(header, [73, 73, 43, 0]);
+
+        let ifds = read_ifds(&tiff_path);
+        assert_eq!(ifds.len(), 6);
+
+        let xml = FrameMetadata::metadata_string(&ifds[0], &mut tiff);
+        assert!(xml.starts_with("<?xml"));
+        assert!(xml.contains(
+            "SizeX=\"20\" SizeY=\"10\" SizeC=\"1\" SizeZ=\"3\" SizeT=\"2\" \
+            PhysicalSizeX=\"4\" PhysicalSizeXUnit=\"µm\" \
+            PhysicalSizeY=\"4\" PhysicalSizeYUnit=\"µm\" \
+            PhysicalSizeZ=\"3\" PhysicalSizeZUnit=\"µm\""
+        ));
+        assert!(xml.contains("<Plane TheC=\"0\" TheZ=\"2\" TheT=\"1\" DeltaT=\"0.5\" DeltaTUnit=\"s\"/>"));
+        assert_eq!(
+            FrameMetadata::metadata_string(&ifds[3], &mut tiff),
+            synthetic_description(3)
+        );
+
+        ifds.iter().enumerate().for_each(|(frame, ifd)| {
+            let mut data = vec![0u8; shape.0 * shape.1 * 2];
+            tiff.seek(std::io::SeekFrom::Start(
+                ifd.get_tag(tiff::TiffTagID::StripOffsets).unwrap().value()
+            )).unwrap();
+            std::io::Read::read_exact(&mut tiff, &mut data).unwrap();
+            let data = data.chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]])).collect::<Vec<_>>();
+            assert_eq!(
+                data,
+                intensity.index_axis(Axis(0), frame).iter().cloned().collect::<Vec<_>>()
+            );
+        });
+    }
+
+    #[test]
+    fn test_siff_to_imagej_tiff() {
+        let siff_path = TempPath::new("imagej_export.siff");
+        let tiff_path = TempPath::new("imagej_export.tiff");
+        let nvfd = format!(
+            "{}SI.hChannels.channelSave = 1\n\
+            SI.hFastZ.enable = true\n\
+            SI.hFastZ.numDiscardFlybackFrames = 1\n\
+            SI.hStackManager.actualNumSlices = 2\n\
+            SI.hStackManager.stackZStepSize = 3\n",
+            SYNTHETIC_NVFD
+        );
+        let shape = (10, 20);
+        let mut writer = SiffWriter::create(&siff_path, &nvfd, "").unwrap();
+        (0..6).for_each(|frame| {
+            writer.write_frame_photons(
+                &synthetic_photons(frame, shape, 64),
+                shape,
+                SiffEncoding::Raw,
+                &synthetic_description(frame)
+            ).unwrap();
+        });
+        writer.finish().unwrap();
+
+        siff_to_tiff(
+            siff_path.as_ref().to_str().unwrap(),
+            TiffMode::ImageJ,
+            Some(&tiff_path.as_ref().to_str().unwrap().to_string())
+        ).unwrap();
+
+        // A classic Tiff, as ImageJ reads hyperstacks
+        let mut tiff = File::open(&tiff_path).unwrap();
+        let mut header = [0u8; 4];
+        std::io::Read::read_exact(&mut tiff, &mut header).unwrap();
+        assert_eq!(header, [73, 73, 42, 0]);
+
+        // Frames 2 and 5 are flyback, so they're left out
+        let ifds = read_ifds(&tiff_path);
+        assert_eq!(ifds.len(), 4);
+        let description = FrameMetadata::metadata_string(&ifds[0], &mut tiff);
+        assert!(description.starts_with(
+            "ImageJ=1.54f\nimages=4\nchannels=1\nslices=2\nframes=2\nhyperstack=true\n"
+        ));
+        assert!(description.contains("spacing=3\n"));
+        assert_eq!(
+            FrameMetadata::metadata_string(&ifds[2], &mut tiff),
+            synthetic_description(3)
+        );
+
+        let siff = SiffReader::open(&siff_path).unwrap();
+        let exported = SiffReader::open(&tiff_path).unwrap();
+        assert_eq!(
+            exported.get_frames_intensity(&exported.frames_vec(), None).unwrap(),
+            siff.get_frames_intensity(&[0, 1, 3, 4], None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_siff_to_tiff_payload() {
+        use std::io::Seek;
+        use tiff::{IFD, Tag};
+        let siff_path = TempPath::new("payload_export.siff");
+        let tiff_path = TempPath::new("payload_export.tiff");
+        let shape = (10, 20);
+        write_synthetic_siff(&siff_path, 5, shape, SiffEncoding::Compressed);
+        let as_string = |path : &TempPath| path.as_ref().to_str().unwrap().to_string();
+
+        let reader = SiffReader::open(&siff_path).unwrap();
+        let frames = [1u64, 3, 4];
+        let registration : RegistrationDict = frames.iter()
+            .map(|&frame| (frame, (frame as i32, -2 * frame as i32))).collect();
+
+        // Registered intensity reads back as a ScanImage tiff
+        siff_to_tiff_payload(
+            &as_string(&siff_path),
+            TiffMode::ScanImage,
+            TiffPayload::Intensity,
+            Some(&frames),
+            Some(&registration),
+            Some(&as_string(&tiff_path)),
+        ).unwrap();
+        let tiff = SiffReader::open(&tiff_path).unwrap();
+        assert_eq!(tiff.num_frames(), 3);
+        assert_eq!(tiff.nvfd(), reader.nvfd());
+        assert_eq!(
+            tiff.get_frames_intensity(&tiff.frames_vec(), None).unwrap(),
+            reader.get_frames_intensity(&frames, Some(&registration)).unwrap()
+        );
+        assert_eq!(
+            tiff.get_frame_metadata(&[1]).unwrap()[0].metadata_string,
+            synthetic_description(3)
+        );
+
+        // Missing registration values leave the existing file alone
+        let written = std::fs::read(&tiff_path).unwrap();
+        assert!(matches!(
+            siff_to_tiff_payload(
+                &as_string(&siff_path), TiffMode::ScanImage, TiffPayload::Intensity,
+                None, Some(&registration), Some(&as_string(&tiff_path)),
+            ),
+            Err(CorrosiffError::FramesError(FramesError::RegistrationFramesMissing))
+        ));
+        assert_eq!(std::fs::read(&tiff_path).unwrap(), written);
+
+        // The default output of a .tiff is the file itself
+        assert!(matches!(
+            siff_to_tiff_payload(
+                &as_string(&tiff_path), TiffMode::ScanImage, TiffPayload::Intensity,
+                None, None, None,
+            ),
+            Err(CorrosiffError::IOError(_))
+        ));
+        assert_eq!(std::fs::read(&tiff_path).unwrap(), written);
+
+        // Phasor is two float pages per frame
+        siff_to_tiff_payload(
+            &as_string(&siff_path),
+            TiffMode::OME,
+            TiffPayload::Phasor,
+            Some(&frames),
+            Some(&registration),
+            Some(&as_string(&tiff_path)),
+        ).unwrap();
+        let phasor = reader.get_frames_phasor(&frames, Some(&registration)).unwrap().0;
+
+        let mut tiff = File::open(&tiff_path).unwrap();
+        let ifds = read_ifds(&tiff_path);
+        assert_eq!(ifds.len(), 6);
+        assert_eq!(ifds[0].get_tag(tiff::TiffTagID::SampleFormat).unwrap().value(), 3);
+        assert_eq!(ifds[0].get_tag(tiff::TiffTagID::BitsPerSample).unwrap().value(), 32);
+
+        let xml = FrameMetadata::metadata_string(&ifds[0], &mut tiff);
+        assert!(xml.contains("Type=\"float\" SizeX=\"20\" SizeY=\"10\" SizeC=\"2\""));
+        assert_eq!(
+            FrameMetadata::metadata_string(&ifds[3], &mut tiff),
+            synthetic_description(3)
+        );
+
+        ifds.iter().enumerate().for_each(|(page, ifd)| {
+            let mut data = vec![0u8; shape.0 * shape.1 * 4];
+            tiff.seek(std::io::SeekFrom::Start(
+                ifd.get_tag(tiff::TiffTagID::StripOffsets).unwrap().value()
+            )).unwrap();
+            std::io::Read::read_exact(&mut tiff, &mut data).unwrap();
+            let data = data.chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect::<Vec<_>>();
+            let expected = phasor.index_axis(Axis(0), page / 2).iter()
+                .map(|x| if page % 2 == 0 { x.re as f32 } else { x.im as f32 })
+                .collect::<Vec<_>>();
+            data.iter().zip(expected.iter()).for_each(|(a, b)| {
+                assert!(a == b || (a.is_nan() && b.is_nan()));
+            });
+        });
+    }
+
     #[test]
     fn test_siff_to_tiff() {
         let test_paths = get_test_paths().expect("Failed to read test paths");
@@ -610,9 +1534,23 @@ pub mod tests {
         let mode = TiffMode::from_string_slice("ScanImage");
         assert!(mode.is_ok_and(|val| val == TiffMode::ScanImage));
 
+        let mode = TiffMode::from_string_slice("ImageJ");
+        assert!(mode.is_ok_and(|val| val == TiffMode::ImageJ));
+
         let mode = TiffMode::from_string_slice("Invalid");
         assert!(mode.is_err());
     }
+
+    #[test]
+    fn test_tiff_payload_from_string_slice() {
+        let payload = TiffPayload::from_string_slice("Phasor");
+        assert!(payload.is_ok_and(|val| val == TiffPayload::Phasor));
+        assert_eq!(TiffPayload::Phasor.pages_per_frame(), 2);
+        assert_eq!(TiffPayload::default(), TiffPayload::Intensity);
+
+        let payload = TiffPayload::from_string_slice("Invalid");
+        assert!(payload.is_err());
+    }
 }
 
 #[no_mangle]
@@ -14,6 +14,9 @@ use crate::{
     CorrosiffError,
 };
 
+pub (crate) mod ome;
+pub (crate) mod imagej;
+
 /// Parses the metadata string for a given field.
 /// TODO: Learn procedural macros, and do it with
 /// one of these!
@@ -93,8 +96,8 @@ pub struct FrameMetadata {
     pub samples_per_pixel : u16,
     pub rows_per_strip : u64,
     pub strip_byte_counts : u64,
-    pub x_resolution : u64,
-    pub y_resolution : u64,
+    pub x_resolution : f64,
+    pub y_resolution : f64,
     pub resolution_unit : u16,
     pub nvfd_address : u64,
     pub roi_address : u64,
@@ -107,7 +110,8 @@ impl FrameMetadata {
     /// Creates a new `FrameMetadata` object from an `IFD`.
     /// Because the `IFD` alone does not contain the long metadata
     /// string, this is not a complete metadata object. But its
-    /// usable enough for most purposes.
+    /// usable enough for most purposes. Resolutions that are stored
+    /// out-of-line in the file are `NaN` (see `from_ifd_and_file`).
     /// 
     /// ## Arguments
     /// 
@@ -141,8 +145,8 @@ impl FrameMetadata {
             samples_per_pixel : unwrap_tag_as!(ifd, SamplesPerPixel, u16),
             rows_per_strip : unwrap_tag_as!(ifd, RowsPerStrip, u64),
             strip_byte_counts : unwrap_tag_as!(ifd, StripByteCounts, u64),
-            x_resolution : unwrap_tag_as!(ifd, XResolution, u64),
-            y_resolution : unwrap_tag_as!(ifd, YResolution, u64),
+            x_resolution : Self::inline_resolution(ifd, XResolution),
+            y_resolution : Self::inline_resolution(ifd, YResolution),
             resolution_unit : unwrap_tag_as!(ifd, ResolutionUnit, u16),
             nvfd_address : unwrap_tag_as!(ifd, Software, u64),
             roi_address : unwrap_tag_as!(ifd, Artist, u64),
@@ -188,10 +192,27 @@ impl FrameMetadata {
     pub fn from_ifd_and_file<I : IFD, ReaderT : Read + Seek>(ifd : &I, reader : &mut ReaderT)
         -> Result<Self, CorrosiffError> {
         let mut metadata = FrameMetadata::from_ifd(ifd);
+        let mut resolution = |tag_id| -> Result<f64, CorrosiffError> {
+            Ok(ifd.get_tag(tag_id).map(|tag| tag.parse(reader)).transpose()?
+                .and_then(|value| value.first_f64())
+                .unwrap_or(f64::NAN))
+        };
+        metadata.x_resolution = resolution(XResolution)?;
+        metadata.y_resolution = resolution(YResolution)?;
         metadata.metadata_string = Self::metadata_string(ifd, reader);
         Ok(metadata)
     }
 
+    /// The value of a resolution tag (a `Rational`) if it
+    /// can be decoded without the file -- `NaN` if the tag is
+    /// missing or stored out-of-line (as in classic Tiffs).
+    fn inline_resolution<I : IFD>(ifd : &I, tag_id : crate::tiff::TiffTagID) -> f64 {
+        ifd.get_tag(tag_id)
+            .and_then(|tag| tag.inline_value())
+            .and_then(|value| value.first_f64())
+            .unwrap_or(f64::NAN)
+    }
+
     /// Reads a metadata string from the file without needing to
     /// create a `FrameMetadata` object. Returns
     /// the reader to its original position if successful.
@@ -221,33 +242,26 @@ impl FrameMetadata {
     /// println!("{}", metadata_string);
     /// ```
     pub fn metadata_string<I : IFD, ReaderT : Read + Seek>(ifd : &I, reader : &mut ReaderT)->String {
-        let string_length : u64;
-        if ifd.get_tag(Siff).is_none() {
-            string_length = unwrap_tag_as!(ifd, StripOffsets, u64)
-            - unwrap_tag_as!(ifd, ImageDescription, u64);
-        }
-        else{
-            string_length = match unwrap_tag_as!(ifd, Siff, u16) {
-                0 => {
-                    unwrap_tag_as!(ifd, StripOffsets, u64)
-                    - unwrap_tag_as!(ifd, ImageDescription, u64)
-                },
-                1 => {
-                    unwrap_tag_as!(ifd, StripOffsets, u64)
-                    - unwrap_tag_as!(ifd, ImageDescription, u64)
-                    - unwrap_tag_as!(ifd, ImageWidth, u64)
-                    * unwrap_tag_as!(ifd, ImageLength, u64)
-                    * std::mem::size_of::<u16>() as u64
-                },
-                _ => return "Invalid Siff compression value".to_string(),
-            };
+        let value = |tag_id| ifd.get_tag(tag_id).map(|tag| tag.value().into());
+        // Plain Tiffs from other programs have none of these
+        let (Some(description), Some(strip)) = (value(ImageDescription), value(StripOffsets))
+        else { return String::new() };
+        let string_length = match value(Siff) {
+            None | Some(0) => strip.checked_sub(description),
+            Some(1) => strip.checked_sub(description).and_then(|length| length.checked_sub(
+                unwrap_tag_as!(ifd, ImageWidth, u64)
+                * unwrap_tag_as!(ifd, ImageLength, u64)
+                * std::mem::size_of::<u16>() as u64
+            )),
+            _ => return "Invalid Siff compression value".to_string(),
         };
+        let Some(string_length) = string_length else { return String::new() };
         let curr_pos = reader.stream_position().unwrap();
-        reader.seek(std::io::SeekFrom::Start(unwrap_tag_as!(ifd, ImageDescription, u64))).unwrap();
-        let mut metadata_string = vec![0u8; string_length as usize];
-        reader.read_exact(&mut metadata_string).unwrap();
+        reader.seek(std::io::SeekFrom::Start(description)).unwrap();
+        let mut metadata_string = Vec::new();
+        reader.by_ref().take(string_length).read_to_end(&mut metadata_string).unwrap();
         reader.seek(std::io::SeekFrom::Start(curr_pos)).unwrap();
-        String::from_utf8(metadata_string).unwrap()
+        String::from_utf8_lossy(&metadata_string).into_owned()
     }
 
     /// Public function for extracting the frame number from the metadata string
@@ -292,6 +306,36 @@ impl FrameMetadata {
         let timestamp = metadata_string_field!(string, "\nText timestamp = ", Option<f64>);
         Some((full_string, timestamp))
     }
+
+    /// Returns a copy of a metadata string with `text` appended to it in
+    /// the same format ScanImage uses for appended text, timestamped with
+    /// the frame's experiment time (if it has one), so that it's reported
+    /// by `appended_text_from_metadata_str`. Only one appended text is
+    /// ever read, so if the string already has some, `text` is added to
+    /// the end of it (after a `"; "`) and it keeps its original timestamp.
+    pub fn metadata_str_with_appended_text(string : &str, text : &str) -> String {
+        fn frame_time(string : &str) -> Option<f64> {
+            metadata_string_field!(string, "frameTimestamps_sec = ", Option<f64>)
+        }
+        let needle = "\nAppended text = ";
+        if let Some(start) = string.find(needle).map(|start| start + needle.len()) {
+            if let Some(end) = string[start..].find('\n').map(|end| end + start) {
+                return format!(
+                    "{}; {}{}", string[..end].trim_end(), text, &string[end..]
+                );
+            }
+        }
+        let frame_time = frame_time(string);
+        let mut appended = string.trim_end_matches('\0').to_string();
+        if !appended.ends_with('\n') {
+            appended.push('\n');
+        }
+        appended.push_str(&format!("Appended text = {}\n", text));
+        if let Some(frame_time) = frame_time {
+            appended.push_str(&format!("Text timestamp = {}\n", frame_time));
+        }
+        appended
+    }
 }
 
 /// Returns the timestamps for a set of frames in _experiment time_,
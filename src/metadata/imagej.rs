@@ -0,0 +1,120 @@
+//! Builds the ImageJ description stored in the first
+//! IFD of a `.tiff` so that Fiji opens the pages as a
+//! hyperstack (split by channel, z, and time) instead
+//! of one flat stack.
+//!
+//! ImageJ, like OME, expects the pages in `XYCZT` order,
+//! which is the order ScanImage acquires them in.
+
+use std::fmt::Display;
+
+use crate::{TiffPayload, tiff::FileFormat};
+
+/// The hyperstack dimensions of an exported `.tiff`,
+/// rendered into ImageJ's `key=value` description by its
+/// `Display` implementation.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let description = ImageJMetadata::from_file_format(&file_format, 100)
+///     .with_payload(TiffPayload::Intensity)
+///     .to_string();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageJMetadata {
+    pub channels : usize,
+    pub slices : usize,
+    pub frames : usize,
+    /// The distance between slices in microns
+    pub spacing : Option<f64>,
+}
+
+impl ImageJMetadata {
+    /// Number of pages (IFDs) described
+    pub fn num_images(&self) -> usize {
+        self.channels * self.slices * self.frames
+    }
+}
+
+impl ImageJMetadata {
+    /// The hyperstack dimensions of `num_frames` frames of a file
+    /// with `file_format`, with the flyback frames already left out
+    /// (so each volume has only its imaging planes). Like the OME-XML,
+    /// falls back to a single plane (or channel) if the frames don't
+    /// divide into whole volumes.
+    pub fn from_file_format(file_format : &FileFormat, num_frames : usize) -> Self {
+        let mut channels = file_format.num_channels_saved().unwrap_or(1) as usize;
+        let mut slices = file_format.num_slices().unwrap_or(1) as usize;
+
+        if num_frames % (channels * slices).max(1) != 0 {
+            slices = 1;
+        }
+        if num_frames % channels.max(1) != 0 {
+            channels = 1;
+        }
+        let (channels, slices) = (channels.max(1), slices.max(1));
+
+        ImageJMetadata {
+            channels,
+            slices,
+            frames : num_frames / (channels * slices),
+            spacing : if slices > 1 { file_format.z_step_um() } else { None },
+        }
+    }
+
+    /// Describes the pages written for `payload`: for the phasor,
+    /// each channel is split into consecutive G and S channels.
+    pub fn with_payload(mut self, payload : TiffPayload) -> Self {
+        self.channels *= payload.pages_per_frame();
+        self
+    }
+}
+
+impl Display for ImageJMetadata {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "ImageJ=1.54f")?;
+        writeln!(f, "images={}", self.num_images())?;
+        writeln!(f, "channels={}", self.channels)?;
+        writeln!(f, "slices={}", self.slices)?;
+        writeln!(f, "frames={}", self.frames)?;
+        writeln!(f, "hyperstack=true")?;
+        writeln!(f, "mode=grayscale")?;
+        if let Some(spacing) = self.spacing {
+            writeln!(f, "unit=micron")?;
+            writeln!(f, "spacing={}", spacing)?;
+        }
+        writeln!(f, "loop=false")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imagej_dimensions() {
+        let file_format = FileFormat::new_siff(
+            "SI.hChannels.channelSave = [1;2]\n\
+            SI.hFastZ.enable = true\n\
+            SI.hFastZ.numDiscardFlybackFrames = 1\n\
+            SI.hStackManager.actualNumSlices = 2\n\
+            SI.hStackManager.stackZStepSize = 5\n",
+            ""
+        );
+
+        // 2 volumes of 2 planes, the flyback frames already dropped
+        let imagej = ImageJMetadata::from_file_format(&file_format, 8);
+        assert_eq!(imagej, ImageJMetadata { channels : 2, slices : 2, frames : 2, spacing : Some(5.0) });
+        assert_eq!(
+            imagej.to_string(),
+            "ImageJ=1.54f\nimages=8\nchannels=2\nslices=2\nframes=2\n\
+            hyperstack=true\nmode=grayscale\nunit=micron\nspacing=5\nloop=false\n"
+        );
+
+        let imagej = ImageJMetadata::from_file_format(&file_format, 10)
+            .with_payload(TiffPayload::Phasor);
+        assert_eq!((imagej.channels, imagej.slices, imagej.frames), (4, 1, 5));
+        assert!(!imagej.to_string().contains("spacing"));
+    }
+}
@@ -0,0 +1,247 @@
+//! Builds the OME-XML description stored in the first
+//! IFD of an OME-TIFF, so that Bio-Formats (and Fiji,
+//! napari, etc.) can interpret a stack of frames as a
+//! hyperstack with physical units.
+//!
+//! Frames are always written in the order ScanImage
+//! acquires them: channels vary fastest, then planes
+//! (including flyback frames), then time -- i.e. `XYCZT`.
+
+use std::fmt::{Display, Write};
+
+use crate::{TiffPayload, tiff::FileFormat};
+
+/// The dimensions and units of an OME-TIFF image,
+/// rendered into OME-XML by its `Display` implementation.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let ome = OmeMetadata::from_file_format(&file_format, "file", (256, 128), 100, timestamps);
+/// let xml = ome.to_string();
+/// ```
+#[derive(Debug, Clone)]
+pub struct OmeMetadata {
+    pub name : String,
+    pub size_x : usize,
+    pub size_y : usize,
+    pub size_c : usize,
+    pub size_z : usize,
+    pub size_t : usize,
+    pub physical_size_x : Option<f64>,
+    pub physical_size_y : Option<f64>,
+    pub physical_size_z : Option<f64>,
+    /// The OME pixel type of the pages (e.g. `uint16`, `float`)
+    pub pixel_type : &'static str,
+    /// Seconds since the acquisition began for each plane
+    pub delta_t : Vec<f64>,
+}
+
+impl OmeMetadata {
+    /// Determines the hyperstack dimensions of `num_frames`
+    /// frames using the acquisition parameters in the NVFD. Channels
+    /// come from `SI.hChannels.channelSave`, and the z dimension is
+    /// the number of slices _plus_ the number of flyback frames (because
+    /// the flyback frames are still in the file). If the frames don't
+    /// divide evenly into volumes (e.g. the acquisition was stopped partway
+    /// through), falls back to a single plane (and, if needed, a single channel).
+    /// The pixel type is `uint16`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `file_format` - The `FileFormat` of the file containing the frames
+    ///
+    /// * `name` - The name of the image
+    ///
+    /// * `shape` - The shape of each frame `(y, x)`
+    ///
+    /// * `num_frames` - The number of frames in the image
+    ///
+    /// * `delta_t` - The experiment time of each frame in seconds
+    pub fn from_file_format(
+        file_format : &FileFormat,
+        name : &str,
+        shape : (usize, usize),
+        num_frames : usize,
+        delta_t : Vec<f64>,
+    ) -> Self {
+        let mut size_c = file_format.num_channels_saved().unwrap_or(1) as usize;
+        let mut size_z = (
+            file_format.num_slices().unwrap_or(1)
+            + file_format.num_flyback_frames().unwrap_or(0)
+        ) as usize;
+
+        if num_frames % (size_c * size_z).max(1) != 0 {
+            size_z = 1;
+        }
+        if num_frames % size_c.max(1) != 0 {
+            size_c = 1;
+        }
+        let (size_c, size_z) = (size_c.max(1), size_z.max(1));
+
+        let (physical_size_y, physical_size_x) = file_format.fov_um()
+            .map(|(height, width)| (
+                Some(height / shape.0 as f64),
+                Some(width / shape.1 as f64)
+            ))
+            .unwrap_or((None, None));
+
+        OmeMetadata {
+            name : name.to_string(),
+            size_x : shape.1,
+            size_y : shape.0,
+            size_c,
+            size_z,
+            size_t : num_frames / (size_c * size_z),
+            physical_size_x,
+            physical_size_y,
+            physical_size_z : if size_z > 1 { file_format.z_step_um() } else { None },
+            pixel_type : "uint16",
+            delta_t,
+        }
+    }
+
+    /// Describes the pages written for `payload` instead of
+    /// intensity: float pixels for the lifetime and phasor, and
+    /// for the phasor, each channel split into consecutive G and S
+    /// channels (so the `delta_t` of each plane is repeated).
+    ///
+    /// ## Arguments
+    ///
+    /// * `payload` - The `TiffPayload` of the pages
+    pub fn with_payload(mut self, payload : TiffPayload) -> Self {
+        let pages = payload.pages_per_frame();
+        self.pixel_type = match payload {
+            TiffPayload::Intensity => "uint16",
+            TiffPayload::Lifetime | TiffPayload::Phasor => "float",
+        };
+        self.size_c *= pages;
+        self.delta_t = self.delta_t.iter()
+            .flat_map(|&delta_t| std::iter::repeat(delta_t).take(pages))
+            .collect();
+        self
+    }
+
+    /// Number of planes (IFDs) described
+    pub fn num_planes(&self) -> usize {
+        self.size_c * self.size_z * self.size_t
+    }
+}
+
+/// Escapes the characters that can't appear in
+/// an XML attribute value.
+fn xml_escape(value : &str) -> String {
+    value.chars().fold(String::with_capacity(value.len()), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+impl Display for OmeMetadata {
+    fn fmt(&self, f : &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut physical_sizes = String::new();
+        [
+            ("X", self.physical_size_x),
+            ("Y", self.physical_size_y),
+            ("Z", self.physical_size_z),
+        ].iter().filter_map(|(axis, size)| size.map(|size| (axis, size)))
+        .try_for_each(|(axis, size)| write!(
+            physical_sizes,
+            " PhysicalSize{axis}=\"{size}\" PhysicalSize{axis}Unit=\"µm\"",
+        ))?;
+
+        writeln!(f, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            f,
+            "<OME xmlns=\"http://www.openmicroscopy.org/Schemas/OME/2016-06\" \
+            xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+            xsi:schemaLocation=\"http://www.openmicroscopy.org/Schemas/OME/2016-06 \
+            http://www.openmicroscopy.org/Schemas/OME/2016-06/ome.xsd\" \
+            Creator=\"corrosiff {}\">",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(f, "<Image ID=\"Image:0\" Name=\"{}\">", xml_escape(&self.name))?;
+        writeln!(
+            f,
+            "<Pixels ID=\"Pixels:0\" DimensionOrder=\"XYCZT\" Type=\"{}\" \
+            SizeX=\"{}\" SizeY=\"{}\" SizeC=\"{}\" SizeZ=\"{}\" SizeT=\"{}\"{} \
+            BigEndian=\"false\">",
+            self.pixel_type, self.size_x, self.size_y, self.size_c, self.size_z, self.size_t,
+            physical_sizes,
+        )?;
+        for c in 0..self.size_c {
+            writeln!(f, "<Channel ID=\"Channel:0:{}\" SamplesPerPixel=\"1\"/>", c)?;
+        }
+        writeln!(f, "<TiffData IFD=\"0\" PlaneCount=\"{}\"/>", self.num_planes())?;
+        for plane in 0..self.num_planes() {
+            let (c, z, t) = (
+                plane % self.size_c,
+                (plane / self.size_c) % self.size_z,
+                plane / (self.size_c * self.size_z),
+            );
+            write!(f, "<Plane TheC=\"{}\" TheZ=\"{}\" TheT=\"{}\"", c, z, t)?;
+            if let Some(delta_t) = self.delta_t.get(plane) {
+                write!(f, " DeltaT=\"{}\" DeltaTUnit=\"s\"", delta_t)?;
+            }
+            writeln!(f, "/>")?;
+        }
+        writeln!(f, "</Pixels>")?;
+        writeln!(f, "</Image>")?;
+        write!(f, "</OME>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ome_dimensions() {
+        let file_format = FileFormat::new_siff(
+            "SI.hChannels.channelSave = [1;2]\n\
+            SI.hFastZ.enable = true\n\
+            SI.hFastZ.numDiscardFlybackFrames = 1\n\
+            SI.hRoiManager.imagingFovUm = [-64 -32;64 -32;64 32;-64 32]\n\
+            SI.hStackManager.actualNumSlices = 2\n\
+            SI.hStackManager.stackZStepSize = 5\n",
+            ""
+        );
+
+        let ome = OmeMetadata::from_file_format(
+            &file_format, "a<b", (32, 64), 12, vec![0.0; 12]
+        );
+        assert_eq!((ome.size_c, ome.size_z, ome.size_t), (2, 3, 2));
+        assert_eq!(ome.physical_size_x, Some(2.0));
+        assert_eq!(ome.physical_size_y, Some(2.0));
+        assert_eq!(ome.physical_size_z, Some(5.0));
+
+        let xml = ome.to_string();
+        assert!(xml.contains("Name=\"a&lt;b\""));
+        assert!(xml.contains("DimensionOrder=\"XYCZT\""));
+        assert!(xml.contains("<Plane TheC=\"1\" TheZ=\"2\" TheT=\"1\" DeltaT=\"0\" DeltaTUnit=\"s\"/>"));
+        assert_eq!(xml.matches("<Plane ").count(), 12);
+
+        // An incomplete final volume can't be a hyperstack
+        let ome = OmeMetadata::from_file_format(
+            &file_format, "", (32, 64), 10, vec![]
+        );
+        assert_eq!((ome.size_c, ome.size_z, ome.size_t), (2, 1, 5));
+        assert_eq!(ome.physical_size_z, None);
+
+        // Phasor pages split each channel in two
+        let ome = OmeMetadata::from_file_format(
+            &file_format, "", (32, 64), 12, (0..12).map(|t| t as f64).collect()
+        ).with_payload(TiffPayload::Phasor);
+        assert_eq!((ome.size_c, ome.size_z, ome.size_t), (4, 3, 2));
+        assert_eq!(ome.num_planes(), 24);
+        assert_eq!(&ome.delta_t[..4], &[0.0, 0.0, 1.0, 1.0]);
+        assert!(ome.to_string().contains("Type=\"float\""));
+    }
+}
@@ -8,10 +8,10 @@
 //! forward to thinking about how to make this more natural,
 //! elegant, and (frankly) readable.
 use std::{
-    collections::HashMap, f64::NAN, fmt::Display, fs::File, io::{Seek, Write}, path::{Path, PathBuf}
+    collections::HashMap, f64::NAN, fmt::Display, fs::File, io::{Error as IOError, Read, Seek, SeekFrom, Write}, ops::Range, path::{Path, PathBuf}, sync::Arc
 };
 
-use binrw::io::BufReader;
+use binrw::{BinWrite, io::BufReader};
 use itertools::izip;
 use ndarray::{parallel, prelude::*};
 use rayon::prelude::*;
@@ -21,11 +21,18 @@ use num_complex::Complex;
 // too complex. Or I should hide some of
 // these deeper in the module?
 use crate::{
-    ClockBase, CorrosiffError, TiffMode, data::image::{
-        Dimensions, DimensionsError, load::*
-    }, metadata::{FrameMetadata, getters::*}, tiff::{
-        BigTiffIFD, FileFormat, IFD, dimensions_consistent
-    }, utils::{FramesError, parallelize_op}
+    ClockBase, CorrosiffError, Photon, TiffMode, TiffPayload, data::image::{
+        Dimensions, DimensionsError, load::*, wrap_coordinate,
+        photons::{pack_photon, read_frame_photons},
+    }, export::{
+        NpyElement, NpyPayload, NpyWriter, NpzWriter, SdtWriter, ZarrArray, ZarrCompression,
+        check_sdt, create_zarr_group, json_number, json_string,
+    }, metadata::{FrameMetadata, getters::*, imagej::ImageJMetadata, ome::OmeMetadata}, ptu::ptu_to_siff, siffwriter::{SiffWriter, SiffEncoding}, tiff::{
+        BigTiffIFD, FileFormat, IFD, IfdIndex, Tag, TiffTagID, dimensions_consistent,
+        file_stamp, index_path,
+        TiffKind, image_tags, scanimage_tags, write_bigtiff_header, write_classic_header,
+        write_linked_pages,
+    }, utils::{FrameRead, FramesError, SiffSource, SourceReader, parallelize_op}
 };
 
 pub type RegistrationDict = HashMap<u64, (i32, i32)>;
@@ -40,6 +47,27 @@ fn _check_frames_in_bounds(frames : &[u64], ifds : &Vec<BigTiffIFD>)
         .then(||()).ok_or(DimensionsError::IncorrectFrames)
 }
 
+/// Whether all of the data of the frame described by `ifd`
+/// lies within the first `file_len` bytes of the file. The
+/// metadata and (for compressed frames) intensity data precede
+/// the strip, so only the end of the strip needs to be checked.
+/// 
+/// Images split into several strips (only plain Tiffs, which
+/// aren't written while being read) are assumed complete.
+fn _frame_is_complete(ifd : &BigTiffIFD, file_len : u64) -> bool {
+    match (ifd.get_tag(TiffTagID::StripOffsets), ifd.get_tag(TiffTagID::StripByteCounts)) {
+        (Some(offset), Some(bytes)) if offset.num_values() > 1 || bytes.num_values() > 1 => true,
+        (Some(offset), Some(bytes)) => offset.value().checked_add(bytes.value())
+            .map_or(false, |end| end <= file_len),
+        _ => false,
+    }
+}
+
+/// Frame metadata and timestamps are only stored by ScanImage
+fn _check_scanimage(file_format : &FileFormat) -> Result<(), CorrosiffError> {
+    file_format.is_scanimage().then(||()).ok_or(CorrosiffError::FileFormatError)
+}
+
 /// Checks whether all requested frames share a shape. If not
 /// returns `None`, otherwise returns the shared shape.
 fn _check_shared_shape(frames : &[u64], ifds : &Vec<BigTiffIFD>)
@@ -52,6 +80,17 @@ fn _check_shared_shape(frames : &[u64], ifds : &Vec<BigTiffIFD>)
     .then(||array_dims)
 }
 
+/// Roughly how many bytes of frame data the exporters
+/// hold in memory at once.
+const EXPORT_CHUNK_BYTES : usize = 1 << 28;
+
+/// How many frames of `bytes_per_frame` bytes each the
+/// exporters read at a time (at least one), so that a whole
+/// stack never needs to be in memory.
+fn _frames_per_chunk(bytes_per_frame : usize) -> usize {
+    (EXPORT_CHUNK_BYTES / bytes_per_frame.max(1)).max(1)
+}
+
 /// Returns `Ok` if every element of `frames` is a key of `registration`.
 /// If `registration` is totally empty, converts it to `None` and returns `Ok`.
 /// If it's only partially populated, returns an error.
@@ -75,11 +114,17 @@ fn _check_registration(registration : &mut Option<&RegistrationDict>, frames : &
 /// or a ScanImage-Flim `.tiff` file.
 /// Has methods which return arrays of
 /// image or FLIM
+/// 
+/// Plain Tiff stacks from other programs can be read
+/// too (see `is_scanimage`), though they have no FLIM
+/// data or frame metadata, so only the intensity methods
+/// (e.g. `get_frames_intensity`, `sum_roi_flat`) work on them.
 pub struct SiffReader {
-    _file : File,
-    _filename : PathBuf,
+    _source : SiffSource,
     file_format : FileFormat,
     _ifds : Vec<BigTiffIFD>,
+    /// Location of each IFD in the file
+    _ifd_offsets : Vec<u64>,
     _image_dims : Option<Dimensions>,
 }
 
@@ -103,29 +148,339 @@ impl SiffReader{
     /// * `std::io::Error` - If there is an error opening the file,
     /// it will be returned directly.
     /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is not
+    /// a little-endian Tiff.
+    /// 
     pub fn open<P : AsRef<Path>>(filename : P) -> Result<Self, CorrosiffError> {
+        Self::from_source(SiffSource::Path(filename.as_ref().to_path_buf()))
+    }
+
+    /// Reads a `.siff` or ScanImage `.tiff` file that's
+    /// already in memory (e.g. a decompressed archive or a
+    /// file received over the network). The buffer is shared
+    /// by every method, so it is never copied.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `bytes` - The full contents of the file
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let bytes = std::fs::read("file.siff")?;
+    /// let reader = SiffReader::from_bytes(bytes)?;
+    /// let intensity = reader.get_frames_intensity(&[0, 1, 2], None)?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the data is not
+    /// a little-endian Tiff.
+    pub fn from_bytes<B : Into<Arc<[u8]>>>(bytes : B) -> Result<Self, CorrosiffError> {
+        Self::from_source(SiffSource::Memory(bytes.into()))
+    }
+
+    /// Reads a `.siff` or ScanImage `.tiff` file from any
+    /// cloneable `Read + Seek` source. The parallel methods
+    /// read with a separate clone of `reader` for each chunk of
+    /// frames, so cloning should be cheap and each clone should
+    /// have its own position (e.g. a `Cursor<&[u8]>` or a
+    /// `Cursor<Arc<[u8]>>`). Nothing is read from the filesystem.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `reader` - A reader positioned anywhere in the file's data
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let data : &'static [u8] = include_bytes!("file.siff");
+    /// let reader = SiffReader::from_reader(std::io::Cursor::new(data))?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the data is not
+    /// a little-endian Tiff.
+    /// 
+    /// * `CorrosiffError::IOError` - If the reader cannot be rewound.
+    pub fn from_reader<R>(reader : R) -> Result<Self, CorrosiffError>
+    where R : Read + Seek + Clone + Send + Sync + 'static {
+        Self::from_source(SiffSource::from_reader(reader))
+    }
+
+    /// Reads a PicoQuant `.ptu` file recorded in T3 mode
+    /// (see `ptu::ptu_to_siff`). The frames are rebuilt from the
+    /// line and frame markers and written, a frame at a time, to a
+    /// `.siff` file at `siff_path` with one frame per detector channel,
+    /// which is then opened, so every method behaves just as it does
+    /// for a `.siff` from ScanImage.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `filename` - The path of the `.ptu` file
+    /// 
+    /// * `siff_path` - Where to write the converted `.siff` file
+    /// (overwritten if it exists)
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open_ptu("confocal.ptu", "confocal.siff")?;
+    /// let histogram = reader.get_histogram(&reader.frames_vec())?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is not a `.ptu` file
+    /// 
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If the file
+    /// is not a T3 image
+    /// 
+    /// * `CorrosiffError::IOError` - If either file can't be opened, or
+    /// (`InvalidInput`) if `siff_path` is the `.ptu` file itself.
+    pub fn open_ptu<P : AsRef<Path>, Q : AsRef<Path>>(filename : P, siff_path : Q)
+    -> Result<Self, CorrosiffError> {
+        let ptu = BufReader::new(File::open(&filename).map_err(CorrosiffError::IOError)?);
+        if siff_path.as_ref().exists()
+            && siff_path.as_ref().canonicalize()? == filename.as_ref().canonicalize()? {
+            return Err(CorrosiffError::IOError(IOError::new(
+                std::io::ErrorKind::InvalidInput,
+                "Cannot convert a .ptu file onto itself"
+            )));
+        }
+        ptu_to_siff(ptu, std::io::BufWriter::new(File::create(&siff_path)?))?;
+        Self::open(siff_path)
+    }
+
+    /// Opens a file by mapping it into memory, so that
+    /// frames are decoded from the mapped pages rather than
+    /// through a `read` call for every frame (and the per-chunk
+    /// `File::open` of every parallel method is replaced by
+    /// sharing the map). Otherwise behaves exactly like `open`.
+    /// Requires the `mmap` feature.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `filename` - The path of the file to open
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = unsafe { SiffReader::open_mmap("file.siff")? };
+    /// let intensity = reader.get_frames_intensity(&reader.frames_vec(), None)?;
+    /// ```
+    /// 
+    /// ## Safety
+    /// 
+    /// The file must not be modified or truncated by this or any
+    /// other process while the `SiffReader` exists (e.g. don't map a
+    /// file that ScanImage is still writing).
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::IOError` - If the file can't be opened or mapped.
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is not
+    /// a little-endian Tiff.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn open_mmap<P : AsRef<Path>>(filename : P) -> Result<Self, CorrosiffError> {
+        Self::from_source(SiffSource::map(filename)?)
+    }
+
+    /// Opens a file using the sidecar index stored next to it
+    /// (`file.siff.idx`, see `write_index`), which skips walking the
+    /// IFD chain -- much faster for large files, especially on network
+    /// storage. If there's no index, or the file's size or modification
+    /// time no longer match it, the file is opened as in `open` and a new
+    /// index is written (if possible -- failing to write the index is not
+    /// an error).
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `filename` - The path of the file to open
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// // Slow the first time, fast every time after
+    /// let reader = SiffReader::open_with_index("huge_file.siff")?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::IOError` - If the file can't be opened.
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is not
+    /// a little-endian Tiff.
+    pub fn open_with_index<P : AsRef<Path>>(filename : P) -> Result<Self, CorrosiffError> {
+        let stamp = file_stamp(&std::fs::metadata(&filename)?);
+        let index = stamp.and_then(|stamp| {
+            let mut index_file = BufReader::new(File::open(index_path(&filename)).ok()?);
+            IfdIndex::read_from(&mut index_file).ok().filter(|index| index.is_current(stamp))
+        });
+
+        let source = SiffSource::Path(filename.as_ref().to_path_buf());
+        match index {
+            Some(index) => {
+                let mut buff = BufReader::new(source.open()?);
+                let file_format = FileFormat::parse_filetype(&mut buff)
+                    .map_err(|_| CorrosiffError::FileFormatError)?;
+                let (_ifds, _ifd_offsets) = index.into_ifds();
+                Ok(SiffReader {
+                    _source : source,
+                    file_format,
+                    _image_dims : dimensions_consistent(&_ifds),
+                    _ifds,
+                    _ifd_offsets,
+                })
+            },
+            None => {
+                let reader = Self::from_source(source)?;
+                let _ = reader.write_index();
+                Ok(reader)
+            },
+        }
+    }
 
-        // Open the file and parse its formatting info
-        let file = File::open(&filename)?;
-        let mut buff = BufReader::new(&file);
+    /// Parses the formatting info and IFDs of any source.
+    fn from_source(source : SiffSource) -> Result<Self, CorrosiffError> {
+        let mut buff = BufReader::new(source.open()?);
         let file_format = {
             FileFormat::parse_filetype(&mut buff)
             .map_err(|_| CorrosiffError::FileFormatError)
         }?;
 
+        let mut reader = SiffReader {
+            _source : source,
+            file_format,
+            _ifds : Vec::new(),
+            _ifd_offsets : Vec::new(),
+            _image_dims : None,
+        };
+        reader._read_new_ifds()?;
+        Ok(reader)
+    }
+
+    /// Walks the IFD chain from the last IFD already read (or the
+    /// first IFD of the file), appending every IFD whose frame has
+    /// been completely written. Stops at the end of the chain or at
+    /// the first IFD that can't be read or whose data runs past the
+    /// end of the file (i.e. a frame that's still being written).
+    /// 
+    /// ## Returns
+    /// 
+    /// * `usize` - The number of IFDs appended
+    fn _read_new_ifds(&mut self) -> Result<usize, CorrosiffError> {
         // A small buffer for reading the IFDs which are quite small, using a smaller
         // buffer makes this run much faster
-        let mut wee_buff = BufReader::with_capacity(400, &file);
-        let _ifds = file_format.get_ifd_iter(&mut wee_buff).collect::<Vec<_>>();
-        Ok(
-            SiffReader {
-            _filename : filename.as_ref().to_path_buf(),
-            _image_dims : dimensions_consistent(&_ifds),
-            _ifds,
-            file_format,
-            _file : file,
+        let mut wee_buff = BufReader::with_capacity(400, self._source.open()?);
+        let file_len = wee_buff.seek(SeekFrom::End(0))?;
+        let num_old = self._ifds.len();
+
+        let mut ifd_iter = match self._ifd_offsets.last() {
+            Some(&last) => {
+                // Reread the last IFD, since its pointer to the next
+                // IFD is only filled in once the next frame is written
+                let mut ifd_iter = self.file_format.get_ifd_iter_from(&mut wee_buff, last);
+                if ifd_iter.next().is_none() {
+                    return Ok(0);
+                }
+                ifd_iter
+            },
+            None => self.file_format.get_ifd_iter(&mut wee_buff),
+        };
+
+        loop {
+            let offset = ifd_iter.next_offset();
+            match ifd_iter.next() {
+                Some(ifd) if _frame_is_complete(&ifd, file_len) => {
+                    self._ifds.push(ifd);
+                    self._ifd_offsets.push(offset);
+                },
+                _ => break,
             }
-        )
+        }
+
+        if self._ifds.len() > num_old {
+            self._image_dims = dimensions_consistent(&self._ifds);
+        }
+        Ok(self._ifds.len() - num_old)
+    }
+
+    /// Reads any frames that have been completely written
+    /// since the file was opened (or last refreshed) -- e.g.
+    /// to follow a file that ScanImage is still acquiring. A
+    /// final frame that's only partially written is ignored
+    /// until a later call finds it complete. Frames already
+    /// read are never changed. Memory-mapped readers (see
+    /// `open_mmap`) require that the file is never modified, so
+    /// they never find new frames.
+    /// 
+    /// ## Returns
+    /// 
+    /// * `Vec<u64>` - The frame numbers of the new frames (empty
+    /// if there are none)
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let mut reader = SiffReader::open("still_acquiring.siff")?;
+    /// loop {
+    ///     let new_frames = reader.refresh()?;
+    ///     if !new_frames.is_empty() {
+    ///         let (lifetime, intensity) = reader.sum_roi_flim_flat(
+    ///             &roi.view(), &new_frames, None
+    ///         )?;
+    ///     }
+    ///     std::thread::sleep(std::time::Duration::from_millis(100));
+    /// }
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::IOError` - If the file can't be reopened
+    pub fn refresh(&mut self) -> Result<Vec<u64>, CorrosiffError> {
+        let num_old = self._ifds.len() as u64;
+        let num_new = self._read_new_ifds()? as u64;
+        Ok((num_old..num_old + num_new).collect())
+    }
+
+    /// Writes a sidecar index of the IFDs read so far next
+    /// to the file (at `file.siff.idx`) so that it can be reopened
+    /// quickly with `open_with_index`. The index records the size
+    /// and modification time of the file, and is ignored if either
+    /// changes. If the file may have grown since it was opened,
+    /// call `refresh` first.
+    /// 
+    /// ## Returns
+    /// 
+    /// * `PathBuf` - The path of the index
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::NotImplementedError` - If the reader isn't
+    /// reading a file (e.g. `from_bytes`)
+    /// 
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If
+    /// the frames can't be indexed (their IFDs differ in more than the
+    /// location, shape, and metadata of each frame) or the platform doesn't
+    /// report modification times.
+    /// 
+    /// * `CorrosiffError::IOError` - If the index can't be written.
+    pub fn write_index(&self) -> Result<PathBuf, CorrosiffError> {
+        let path = self._source.path().ok_or(CorrosiffError::NotImplementedError)?;
+        let stamp = file_stamp(&std::fs::metadata(path)?).ok_or(
+            FramesError::FormatError("Unable to read the file's modification time".to_string())
+        )?;
+        let index = IfdIndex::new(&self._ifds, &self._ifd_offsets, stamp)
+            .map_err(FramesError::FormatError)?;
+
+        let index_path = index_path(path);
+        let mut index_file = std::io::BufWriter::new(File::create(&index_path)?);
+        index.write(&mut index_file).map_err(FramesError::from)?;
+        index_file.flush()?;
+        Ok(index_path)
     }
 
     /// Returns number of frames in the file
@@ -142,7 +497,9 @@ impl SiffReader{
         self._image_dims.clone()
     }
 
-    /// Get value of the filename
+    /// Get value of the filename. Readers that
+    /// aren't backed by a file (see `from_bytes` and
+    /// `from_reader`) return an empty string.
     /// 
     /// # Example
     /// 
@@ -151,7 +508,9 @@ impl SiffReader{
     /// println!("{}", reader.filename());
     /// ```
     pub fn filename(&self) -> String {
-        self._filename.to_str().unwrap().to_string()
+        self._source.path()
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_default()
     }
 
     /// Return the non-varying frame data, which
@@ -165,23 +524,41 @@ impl SiffReader{
         self.file_format.roi_string.clone()
     }
 
+    /// The header of the file
+    pub (crate) fn file_format(&self) -> &FileFormat {
+        &self.file_format
+    }
+
     /// Returns whether the file uses the BigTIFF
     /// format or the standard 32-bit TIFF format.
     pub fn is_bigtiff(&self) -> bool {
         self.file_format.is_bigtiff()
     }
 
+    /// Returns whether the file was written by ScanImage
+    /// (or is a `.siff`). Plain Tiff stacks from other programs
+    /// have no NVFD, ROI string, or frame metadata.
+    pub fn is_scanimage(&self) -> bool {
+        self.file_format.is_scanimage()
+    }
+
     /// Returns whether the file being read is a
     /// .siff (and contains lifetime information)
     /// or just a regular tiff file.
     /// 
-    /// For now, this is implemented is a very
+    /// For files, this is implemented is a very
     /// unsophisticated manner -- it simply
     /// checks whether the file ends in `.siff`!
+    /// Readers without a filename check whether
+    /// the first frame has a `Siff` tag.
     /// 
     /// TODO: Better!
     pub fn is_siff(&self) -> bool {
-        self._filename.to_str().unwrap().ends_with(".siff")
+        match self._source.path() {
+            Some(path) => path.to_string_lossy().ends_with(".siff"),
+            None => self._ifds.first()
+                .and_then(|ifd| ifd.get_tag(TiffTagID::Siff)).is_some(),
+        }
     }
 
     /// Size of the FLIM arrival time histogram
@@ -205,15 +582,21 @@ impl SiffReader{
 
     /// Return the metadata objects corresponding to
     /// each of the requested frames.
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is a plain
+    /// Tiff (see `is_scanimage`), which has no frame metadata.
     pub fn get_frame_metadata(&self, frames : &[u64]) 
         -> Result<Vec<FrameMetadata>, CorrosiffError> {
+        _check_scanimage(&self.file_format)?;
 
             _check_frames_in_bounds(frames, &self._ifds).map_err(
             |err| FramesError::DimensionsError(err)
         )?;
 
         let mut metadata = Vec::with_capacity(frames.len());
-        let mut f = File::open(&self._filename).unwrap();
+        let mut f = self._source.open()?;
         for frame in frames {
             metadata.push(FrameMetadata::from_ifd_and_file(
                 &self._ifds[*frame as usize],
@@ -244,9 +627,15 @@ impl SiffReader{
     /// let reader = SiffReader::open("file.siff");
     /// let timestamps = reader.get_experiment_timestamps(&[0, 1, 2]);
     /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is a plain
+    /// Tiff (see `is_scanimage`), which has no frame metadata.
     pub fn get_experiment_timestamps(
         &self, frames : &[u64]
     ) -> Result<Array1<f64>, CorrosiffError> {
+        _check_scanimage(&self.file_format)?;
 
         _check_frames_in_bounds(frames, &self._ifds)?;
         let mut array = Array1::<f64>::zeros(frames.len());
@@ -255,9 +644,9 @@ impl SiffReader{
             array, 
             5000, 
             frames, 
-            self._filename.to_str().unwrap(),
-            | filename : &str |{BufReader::with_capacity(800, File::open(filename).unwrap())},
-            |frames : &[u64], chunk : &mut ArrayBase<_, Ix1>, reader : &mut BufReader<File>| {
+            self._source,
+            | source : &SiffSource | source.open().map(|f| BufReader::with_capacity(800, f)),
+            |frames : &[u64], chunk : &mut ArrayBase<_, Ix1>, reader : &mut BufReader<SourceReader>| {
                 let ifds = frames.iter().map(|&x| &self._ifds[x as usize]).collect::<Vec<_>>();
                 get_experiment_timestamps(&ifds, reader)
                     .iter().zip(chunk.iter_mut())
@@ -293,9 +682,15 @@ impl SiffReader{
     /// let reader = SiffReader::open("file.siff");
     /// let timestamps = reader.get_epoch_timestamps(&[0, 1, 2]);
     /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is a plain
+    /// Tiff (see `is_scanimage`), which has no frame metadata.
     pub fn get_epoch_timestamps_laser(
         &self, frames : &[u64]
     ) -> Result<Array1<u64>, CorrosiffError> {
+        _check_scanimage(&self.file_format)?;
         _check_frames_in_bounds(frames, &self._ifds)?;
 
         let mut array = Array1::<u64>::zeros(frames.len());
@@ -304,9 +699,9 @@ impl SiffReader{
             array, 
             5000, 
             frames, 
-            self._filename.to_str().unwrap(),
-            | filename : &str | {BufReader::with_capacity(800, File::open(filename).unwrap())},
-            | frames : &[u64], chunk : &mut ArrayBase<_, Ix1>, reader : &mut BufReader<File>| {
+            self._source,
+            | source : &SiffSource | source.open().map(|f| BufReader::with_capacity(800, f)),
+            | frames : &[u64], chunk : &mut ArrayBase<_, Ix1>, reader : &mut BufReader<SourceReader>| {
                 let ifds = frames.iter().map(|&x| &self._ifds[x as usize]).collect::<Vec<_>>();
                 get_epoch_timestamps_laser(&ifds, reader)
                     .iter().zip(chunk.iter_mut())
@@ -346,15 +741,19 @@ impl SiffReader{
     /// 
     /// * `CorrosiffError::NoSystemTimestamps` - If the system timestamps
     /// are not present in the file, this error is returned.
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is a plain
+    /// Tiff (see `is_scanimage`), which has no frame metadata.
     pub fn get_epoch_timestamps_system(
         &self, frames : &[u64]
     ) -> Result<Array1<u64>, CorrosiffError> {
+        _check_scanimage(&self.file_format)?;
         _check_frames_in_bounds(frames, &self._ifds)?;
 
         let mut array = Array1::<u64>::zeros(frames.len());
 
         let op = 
-        | frames : &[u64], chunk : &mut ArrayViewMut1<u64>, reader : &mut BufReader<File> |
+        | frames : &[u64], chunk : &mut ArrayViewMut1<u64>, reader : &mut BufReader<SourceReader> |
         -> Result<(), CorrosiffError> {
             let ifds = frames.iter().map(|&x| &self._ifds[x as usize]).collect::<Vec<_>>();
             get_epoch_timestamps_system(&ifds, reader)?
@@ -367,8 +766,8 @@ impl SiffReader{
             array, 
             5000, 
             frames, 
-            self._filename.to_str().unwrap(),
-            | filename : &str | { BufReader::with_capacity(800, File::open(filename).unwrap()) },
+            self._source,
+            | source : &SiffSource | source.open().map(|f| BufReader::with_capacity(800, f)),
             op
         );
 
@@ -412,9 +811,13 @@ impl SiffReader{
     /// 
     /// * `CorrosiffError::DimensionsError(DimensionsError)` - If the frames requested
     /// are out of bounds (the underlying `DimensionsError` is attached to this error)
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is a plain
+    /// Tiff (see `is_scanimage`), which has no frame metadata.
     pub fn get_epoch_timestamps_both(
         &self, frames : &[u64]
     ) -> Result<Array2<u64>, CorrosiffError> {
+        _check_scanimage(&self.file_format)?;
         _check_frames_in_bounds(frames, &self._ifds)?;
 
         let mut array = Array2::<u64>::zeros((2, frames.len()));
@@ -442,9 +845,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = BufReader::with_capacity(800, 
-                File::open(&self._filename).unwrap()
-            );
+            let mut local_f = BufReader::with_capacity(800, self._source.open()?);
 
             let ifds = local_frames.iter().map(|&x| &self._ifds[x as usize]).collect::<Vec<_>>();
             get_epoch_timestamps_both(&ifds, &mut local_f)?
@@ -491,12 +892,16 @@ impl SiffReader{
     /// 
     /// * `CorrosiffError::DimensionsError(DimensionsError)` - If the frames requested
     /// are out of bounds (the underlying `DimensionsError` is attached to this error)
+    /// 
+    /// * `CorrosiffError::FileFormatError` - If the file is a plain
+    /// Tiff (see `is_scanimage`), which has no frame metadata.
     pub fn get_sync_number(&self, frames : &[u64]) -> Result<Array1<u64>, CorrosiffError> {
+        _check_scanimage(&self.file_format)?;
         _check_frames_in_bounds(frames, &self._ifds)?;
 
         let mut array = Array1::<u64>::zeros(frames.len());
 
-        let op = | frames : &[u64], chunk : &mut ArrayBase<_, Ix1>, reader : &mut BufReader<File> | 
+        let op = | frames : &[u64], chunk : &mut ArrayBase<_, Ix1>, reader : &mut BufReader<SourceReader> | 
         -> Result<(), CorrosiffError> {
             let ifds = frames.iter().map(|&x| &self._ifds[x as usize]).collect::<Vec<_>>();
             get_sync_number(&ifds, reader)
@@ -509,8 +914,8 @@ impl SiffReader{
             array,
             5000,
             frames,
-            self._filename.to_str().unwrap(),
-            | filename : &str | { BufReader::with_capacity(800, File::open(filename).unwrap()) },
+            self._source,
+            | source : &SiffSource | source.open().map(|f| BufReader::with_capacity(800, f)),
             op
         );
 
@@ -524,8 +929,11 @@ impl SiffReader{
     /// ## Returns
     /// 
     /// (`frame_number`, `text`, Option<`timestamp`>)
+    /// 
+    /// Always empty for plain Tiffs (see `is_scanimage`).
     pub fn get_appended_text(&self, frames : &[u64]) -> Vec<(u64, String, Option<f64>)> {
-        let mut f = File::open(&self._filename).unwrap();
+        if !self.is_scanimage() { return Vec::new(); }
+        let mut f = self._source.open().unwrap();
         let ifd_by_ref = frames.iter().map(|&x| &self._ifds[x as usize]).collect::<Vec<_>>();
         get_appended_text(&ifd_by_ref, &mut f)
         .iter().map(
@@ -605,7 +1013,7 @@ impl SiffReader{
         // Create the array
         let mut array = Array3::<u16>::zeros((frames.len(), array_dims.ydim as usize, array_dims.xdim as usize));
 
-        let op = | frames : &[u64], chunk : &mut ArrayViewMut3<u16>, reader : &mut File |
+        let op = | frames : &[u64], chunk : &mut ArrayViewMut3<u16>, reader : &mut SourceReader |
         -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -641,7 +1049,7 @@ impl SiffReader{
             array, 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
         
@@ -745,7 +1153,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_intensity : &mut ArrayViewMut3<u16>,
             chunk_lifetime : &mut ArrayViewMut3<f64>,
-            reader : &mut File
+            reader : &mut SourceReader
         | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -795,7 +1203,7 @@ impl SiffReader{
             (intensity, lifetime), 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
         Ok((lifetime, intensity))
@@ -847,7 +1255,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_intensity : &mut ArrayViewMut3<u16>,
             chunk_phasor : &mut ArrayViewMut3<Complex<f64>>,
-            reader : &mut File
+            reader : &mut SourceReader
         | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -901,7 +1309,7 @@ impl SiffReader{
             (intensity, phasor), 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -912,9 +1320,13 @@ impl SiffReader{
         Ok((phasor, intensity))
     }
 
-    /// Returns a 1D array of `u64` values corresponding to the
-    /// photon stream of the frames requested, rather than converting
-    /// the data into an array of intensity (or arrival) values.
+    /// Returns every photon of the frames requested, rather than
+    /// converting the data into an array of intensity (or arrival)
+    /// values. Compressed frames are expanded from their intensity
+    /// image and arrival time list, so both encodings produce the
+    /// same records. Photons are returned in the order of `frames`;
+    /// within a frame, raw frames keep the order they are stored in
+    /// and compressed frames are ordered by pixel (row-major).
     /// 
     /// ## Arguments
     /// 
@@ -922,17 +1334,63 @@ impl SiffReader{
     /// frame numbers to retrieve
     /// 
     /// * `registration` - An optional `HashMap<u64, (i32, i32)>` which
+    /// contains the pixel shifts for each frame. Each photon's pixel is
+    /// shifted (wrapping around the frame) just as the registered arrays are.
     /// 
-    /// ## Panics
+    /// ## Returns
+    /// 
+    /// * `Result<Vec<Photon>, CorrosiffError>` - The `frame`, `y`, `x`,
+    /// and `tau` (arrival time bin) of each photon
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// let photons = reader.get_photon_stream(&[0, 1, 2], None)?;
+    /// let early = photons.iter().filter(|photon| photon.tau < 100).count();
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames)` - If
+    /// any frame requested is out of bounds.
+    /// 
+    /// * `CorrosiffError::FramesError(FramesError::RegistrationFramesMissing)` -
+    /// If registration is used, and the registration values are missing for some frames
     /// 
-    /// *It's not implemented yet!*
-    // pub fn get_photon_stream(
-    //     &self,
-    //     frames : &[u64],
-    //     registration : Option<&RegistrationDict>,
-    // ) -> Result<Array1<u64>, CorrosiffError> {
-    //     unimplemented!()
-    // }
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If a frame
+    /// is not a `.siff` frame (e.g. it's a `.tiff`).
+    pub fn get_photon_stream(
+        &self,
+        frames : &[u64],
+        registration : Option<&RegistrationDict>,
+    ) -> Result<Vec<Photon>, CorrosiffError> {
+        _check_frames_in_bounds(&frames, &self._ifds)?;
+
+        let mut registration = registration;
+        _check_registration(&mut registration, &frames)?;
+
+        let per_chunk = frames.par_chunks(2500).map(
+            |chunk| -> Result<Vec<Photon>, CorrosiffError> {
+            let mut reader = BufReader::new(self._source.open()?);
+            let mut photons = Vec::new();
+            for &frame in chunk {
+                let ifd = &self._ifds[frame as usize];
+                let shape = ifd.dimensions()
+                    .ok_or(DimensionsError::NoConsistentDimensions)?
+                    .to_tuple();
+                let shift = registration.map_or((0, 0), |reg| *reg.get(&frame).unwrap());
+                photons.extend(
+                    read_frame_photons(&mut reader, ifd)?.into_iter()
+                    .map(|photon| Photon::from_packed(frame, photon, shift, shape))
+                );
+            }
+            Ok(photons)
+            }
+        ).collect::<Result<Vec<_>, _>>()?;
+
+        Ok(per_chunk.into_iter().flatten().collect())
+    }
 
     /// Returns a 4d array of `u16` values corresponding to the
     /// number of photons per pixel per arrival time bin for each
@@ -981,7 +1439,7 @@ impl SiffReader{
             )
         );
 
-        let op = | frames: &[u64], chunk : &mut ArrayViewMut4::<u16>, reader : &mut File | -> Result<(), CorrosiffError> {
+        let op = | frames: &[u64], chunk : &mut ArrayViewMut4::<u16>, reader : &mut SourceReader | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
                     frames.iter().zip(chunk.axis_iter_mut(Axis(0)))
@@ -1016,7 +1474,7 @@ impl SiffReader{
             array, 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -1070,7 +1528,7 @@ impl SiffReader{
             )
         );
 
-        let op = |frames : &[u64], chunk : &mut ArrayViewMut2<u64>, reader : &mut File|
+        let op = |frames : &[u64], chunk : &mut ArrayViewMut2<u64>, reader : &mut SourceReader|
         -> Result<(), CorrosiffError> {
             frames.iter().zip(chunk.axis_iter_mut(Axis(0)))
                 .try_for_each(
@@ -1089,7 +1547,7 @@ impl SiffReader{
             array, 
             5000, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -1147,7 +1605,7 @@ impl SiffReader{
         let mut array = Array2::<u64>::zeros((frames.len(), self.file_format.num_flim_tau_bins().unwrap() as usize));
 
         let op = 
-        | frames : &[u64], chunk : &mut ArrayViewMut2<u64>, reader : &mut File | 
+        | frames : &[u64], chunk : &mut ArrayViewMut2<u64>, reader : &mut SourceReader | 
         -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -1186,7 +1644,7 @@ impl SiffReader{
             array, 
             5000, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -1251,7 +1709,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(&self._filename).unwrap();
+            let mut local_f = self._source.open()?;
 
             let roi_cycle = mask.axis_iter(Axis(0)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -1381,7 +1839,7 @@ impl SiffReader{
             }
         }
 
-        let op = | frames : &[u64], chunk : &mut ArrayViewMut2<u16>, reader : &mut File |
+        let op = | frames : &[u64], chunk : &mut ArrayViewMut2<u16>, reader : &mut SourceReader |
         -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -1421,7 +1879,7 @@ impl SiffReader{
             array, 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -1529,7 +1987,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size * roi.dim().0).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(&self._filename).unwrap();
+            let mut local_f = self._source.open()?;
 
             let roi_cycle = roi.axis_iter(Axis(0)).cycle();
             let lookup_cycle = lookup_table.axis_iter(Axis(0)).cycle();
@@ -1640,7 +2098,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_lifetime : &mut ArrayViewMut2<f64>,
             chunk_intensity : &mut ArrayViewMut2<u16>,
-            reader : &mut File
+            reader : &mut SourceReader
         | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -1692,7 +2150,7 @@ impl SiffReader{
             (lifetime_array, intensity_array), 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -1765,7 +2223,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size * roi.dim().0).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(&self._filename).unwrap();
+            let mut local_f = self._source.open()?;
 
             let roi_cycle = roi.axis_iter(Axis(0)).cycle();
             let lookup_cycle = lookup_table.axis_iter(Axis(0)).cycle();
@@ -1941,7 +2399,7 @@ impl SiffReader{
             |   frames : &[u64],
                 chunk_phasor : &mut ArrayViewMut2<Complex<f64>>,
                 chunk_intensity : &mut ArrayViewMut2<u16>,
-                reader : &mut File
+                reader : &mut SourceReader
             | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -1996,7 +2454,7 @@ impl SiffReader{
             (phasor_array, intensity_array),
             2500,
             frames,
-            self._filename,
+            self._source,
             op
         );
 
@@ -2128,7 +2586,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size * roi.dim().0).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(&self._filename).unwrap();
+            let mut local_f = self._source.open()?;
 
             let roi_cycle = roi.axis_iter(Axis(0)).cycle();
             let lookup_cycle = lookup_table.axis_iter(Axis(0)).cycle();
@@ -2274,7 +2732,7 @@ impl SiffReader{
 
         let mut array = Array1::<u64>::zeros(frames.len());
 
-        let op = |frames : &[u64], chunk : &mut ArrayViewMut1<u64>, reader : &mut File| 
+        let op = |frames : &[u64], chunk : &mut ArrayViewMut1<u64>, reader : &mut SourceReader| 
         -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -2312,7 +2770,7 @@ impl SiffReader{
             array,
             2500,
             frames,
-            self._filename,
+            self._source,
             op
         );
 
@@ -2430,7 +2888,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(self._filename.clone()).unwrap();
+            let mut local_f = self._source.open()?;
             
             let roi_cycle = roi.axis_iter(Axis(0)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -2552,7 +3010,7 @@ impl SiffReader{
 
         let mut array = Array2::<u64>::zeros((frames.len(), rois.dim().0));
 
-        let op = | frames : &[u64], chunk : &mut ArrayViewMut2<u64>, reader : &mut File |
+        let op = | frames : &[u64], chunk : &mut ArrayViewMut2<u64>, reader : &mut SourceReader |
         -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -2595,7 +3053,7 @@ impl SiffReader{
             array, 
             2500, 
             frames, 
-            self._filename,
+            self._source,
             op
         );
 
@@ -2718,7 +3176,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(self._filename.clone()).unwrap();
+            let mut local_f = self._source.open()?;
             
             let roi_cycle = rois.axis_iter(Axis(1)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -2854,7 +3312,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_intensity : &mut ArrayViewMut1<u64>,
             chunk_lifetime : &mut ArrayViewMut1<f64>,
-            reader : &mut File
+            reader : &mut SourceReader
         | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -2902,7 +3360,7 @@ impl SiffReader{
             (intensity_array, lifetime_array),
             2500,
             frames,
-            self._filename,
+            self._source,
             op
         );
 
@@ -3025,7 +3483,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(self._filename.clone()).unwrap();
+            let mut local_f = self._source.open()?;
             
             let roi_cycle = roi.axis_iter(Axis(0)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -3147,7 +3605,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_lifetime : &mut ArrayViewMut2<f64>,
             chunk_intensity : &mut ArrayViewMut2<u64>,
-            reader : &mut File
+            reader : &mut SourceReader
             | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -3196,7 +3654,7 @@ impl SiffReader{
             (lifetime_array, intensity_array),
             2500,
             frames,
-            self._filename,
+            self._source,
             op
         );
 
@@ -3329,7 +3787,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(self._filename.clone()).unwrap();
+            let mut local_f = self._source.open()?;
             
             let roi_cycle = rois.axis_iter(Axis(1)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -3472,7 +3930,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_intensity : &mut ArrayViewMut1<u64>,
             chunk_phasor : &mut ArrayViewMut1<Complex<f64>>,
-            reader : &mut File
+            reader : &mut SourceReader
         | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -3524,7 +3982,7 @@ impl SiffReader{
             (intensity_array, phasor_array),
             2500,
             frames,
-            self._filename,
+            self._source,
             op
         );
 
@@ -3653,7 +4111,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(self._filename.clone()).unwrap();
+            let mut local_f = self._source.open()?;
 
             let roi_cycle = roi.axis_iter(Axis(0)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -3796,7 +4254,7 @@ impl SiffReader{
             frames : &[u64],
             chunk_intensity : &mut ArrayViewMut2<u64>,
             chunk_phasor : &mut ArrayViewMut2<Complex<f64>>,
-            reader : &mut File
+            reader : &mut SourceReader
         | -> Result<(), CorrosiffError> {
             match registration {
                 Some(reg) => {
@@ -3848,7 +4306,7 @@ impl SiffReader{
             (intensity_array, phasor_array),
             2500,
             frames,
-            self._filename,
+            self._source,
             op
         );
 
@@ -3979,7 +4437,7 @@ impl SiffReader{
             let end = ((chunk_idx + 1) * chunk_size).min(frames.len());
 
             let local_frames = &frames[start..end];
-            let mut local_f = File::open(self._filename.clone()).unwrap();
+            let mut local_f = self._source.open()?;
 
             let roi_cycle = rois.axis_iter(Axis(1)).cycle();
             // roi_cycle needs to be incremented by the start value
@@ -4034,10 +4492,13 @@ impl SiffReader{
     /// Copies the tiff/siff header from the currently
     /// opened file to the position of the writer.
     /// 
-    /// When finished, this can write either OME-TIFF
-    /// compliant files or the default ScanImage format.
-    /// 
-    /// For now it only does the ScanImage format.
+    /// Can write either OME-TIFF compliant files, ImageJ
+    /// hyperstacks, or the default ScanImage format. The OME-TIFF
+    /// header is a plain `BigTiff` header, and the ImageJ header a
+    /// plain classic Tiff header (ImageJ only reads hyperstacks from
+    /// classic Tiffs) -- the OME-XML or ImageJ description itself is
+    /// written into the first frame by `write_ome_tiff_frames_to_file`
+    /// or `write_tiff_payload_to_file`.
     /// 
     /// ## Arguments
     /// 
@@ -4046,8 +4507,7 @@ impl SiffReader{
     /// Goes directly to the beginning of the file to write.
     /// 
     /// * `mode` - A `TiffMode` enum which specifies the mode
-    /// to write the file in. For now, only `TiffMode::ScanImage`
-    /// is supported.
+    /// to write the file in.
     /// 
     pub fn write_header_to_file<WriterT: Write + Seek>(
         &self,
@@ -4061,16 +4521,27 @@ impl SiffReader{
                 Ok(())
             },
             TiffMode::OME => {
-                Err(
-                    CorrosiffError::NotImplementedError
-                )
+                write_bigtiff_header(file)?;
+                Ok(())
+            },
+            TiffMode::ImageJ => {
+                write_classic_header(file)?;
+                Ok(())
             },
         }
     }
 
     /// Writes the frames requested into the file specified
-    /// by the writer. The saved data is **intensity only**
-    /// and is unregistered.
+    /// by the writer as an OME-TIFF. The saved data is
+    /// **intensity only** and is unregistered. The first frame's
+    /// `ImageDescription` contains the OME-XML describing the whole
+    /// stack: its dimension order (`XYCZT`), channel, plane, and
+    /// timepoint counts from the NVFD, pixel sizes from the imaging
+    /// field of view, and the experiment time of every plane. The
+    /// other frames keep their ScanImage frame metadata strings.
+    /// 
+    /// Expects the writer to be positioned just after a header
+    /// written by `write_header_to_file` with `TiffMode::OME`.
     /// 
     /// ## Arguments
     /// 
@@ -4080,42 +4551,1161 @@ impl SiffReader{
     /// * `frames` - An optional slice of `u64` values corresponding
     /// to the frame numbers to write to the file. If this is `None`,
     /// all frames are written to the file.
-    pub fn write_tiff_frames_to_file<WriterT : Write + Seek>(
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// let mut tiff_file = File::create("file.ome.tiff")?;
+    /// reader.write_header_to_file(&mut tiff_file, &TiffMode::OME)?;
+    /// reader.write_ome_tiff_frames_to_file(&mut tiff_file, None)?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::DimensionsError` - If the frames are out of bounds,
+    /// don't share a shape, or if no frames are requested.
+    /// 
+    /// * `CorrosiffError::IOError` - If reading or writing fails.
+    pub fn write_ome_tiff_frames_to_file<WriterT : Write + Seek>(
+        &self,
+        file : &mut WriterT,
+        frames : Option<&[u64]>,
+    ) -> Result<(), CorrosiffError> {
+        self.write_tiff_payload_to_file(
+            file, &TiffMode::OME, frames, None, TiffPayload::Intensity
+        )
+    }
+
+    /// Writes the frames requested into the file specified by
+    /// the writer, as the image chosen by `payload`, optionally
+    /// registered. Unlike `write_tiff_frames_to_file`, the pages are
+    /// rebuilt from the frame data rather than copied, so they can be
+    /// registered and can hold the lifetime (`f32` pixels) or the phasor
+    /// (two `f32` pages per frame, G then S) instead of intensity.
+    /// 
+    /// Every page keeps the ScanImage metadata string of its frame
+    /// (both pages of a phasor frame get the same one), except that
+    /// in `TiffMode::OME` the first page holds the OME-XML describing
+    /// the stack, and in `TiffMode::ImageJ` it holds the ImageJ hyperstack
+    /// description (channels, slices, and frames, from the NVFD). ImageJ
+    /// files are classic Tiffs, so they can hold at most 4 GB, and leave
+    /// out the flyback frames of volumetric recordings (which ImageJ
+    /// would otherwise show as extra slices).
+    /// 
+    /// Expects the writer to be positioned just after a header
+    /// written by `write_header_to_file` with the same `mode`.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `file` - A mutable reference to a `Write` object
+    /// pointing to the location to write the frames to.
+    /// 
+    /// * `mode` - The `TiffMode` the header was written in
+    /// 
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write to the file. If this is `None`,
+    /// all frames are written to the file.
+    /// 
+    /// * `registration` - An optional `RegistrationDict` to shift
+    /// each frame by before it is written.
+    /// 
+    /// * `payload` - The `TiffPayload` to compute from each frame
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// let mut tiff_file = File::create("file_lifetime.tiff")?;
+    /// reader.write_header_to_file(&mut tiff_file, &TiffMode::ScanImage)?;
+    /// reader.write_tiff_payload_to_file(
+    ///     &mut tiff_file,
+    ///     &TiffMode::ScanImage,
+    ///     None,
+    ///     Some(&registration),
+    ///     TiffPayload::Lifetime,
+    /// )?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::DimensionsError` - If the frames are out of bounds,
+    /// don't share a shape, or if no frames are requested.
+    /// 
+    /// * `CorrosiffError::FramesError(FramesError::RegistrationFramesMissing)` -
+    /// If registration is used, and the registration values are missing for some frames
+    /// 
+    /// * `CorrosiffError::IOError` - If reading or writing fails, or (`InvalidInput`)
+    /// if `mode` is `TiffMode::ImageJ` and the file would be larger than 4 GB.
+    pub fn write_tiff_payload_to_file<WriterT : Write + Seek>(
         &self,
         file : &mut WriterT,
+        mode : &TiffMode,
         frames : Option<&[u64]>,
+        registration : Option<&RegistrationDict>,
+        payload : TiffPayload,
     ) -> Result<(), CorrosiffError> {
-        let mut file_reader = File::open(self._filename.clone())?;
+        let all_frames = self.frames_vec();
+        let frames = frames.unwrap_or(&all_frames);
+        let mut registration = registration;
+        let (frames, shape) = self._check_tiff_payload(frames, &mut registration, mode, payload)?;
+        let frames = &frames[..];
+
+        let stack_description = match mode {
+            TiffMode::ScanImage => None,
+            TiffMode::OME => Some(OmeMetadata::from_file_format(
+                &self.file_format,
+                &self._source.path()
+                    .and_then(|path| path.file_stem())
+                    .unwrap_or_default().to_string_lossy(),
+                shape,
+                frames.len(),
+                self.get_experiment_timestamps(frames)?.to_vec(),
+            ).with_payload(payload).to_string()),
+            TiffMode::ImageJ => Some(
+                ImageJMetadata::from_file_format(&self.file_format, frames.len())
+                    .with_payload(payload).to_string()
+            ),
+        };
 
-        if frames.is_none() {
-            let mut reader_for_ifd = File::open(self._filename.clone())?;
-            self.file_format.get_ifd_iter(&mut reader_for_ifd)
-            .try_for_each(|ifd| {
-                SiffFrame::write_frame_as_tiff(
-                    &mut file_reader, 
-                    file, 
-                    &ifd
-                )
-            })?;
-            return Ok(());
+        let mut tags = match payload {
+            TiffPayload::Intensity => image_tags(shape, 16, 1),
+            TiffPayload::Lifetime | TiffPayload::Phasor => image_tags(shape, 32, 3),
+        };
+        let kind = match mode {
+            TiffMode::ImageJ => TiffKind::Classic,
+            TiffMode::ScanImage | TiffMode::OME => TiffKind::BigTiff,
+        };
+        if let TiffMode::ScanImage = mode {
+            // Points to the header written by `write_header_to_file`
+            tags.extend(scanimage_tags(
+                &FileFormat::new_siff(&self.file_format.nvfd, &self.file_format.roi_string)
+            ));
         }
 
-        frames.unwrap().iter().map(|&frame| &self._ifds[frame as usize])
-        .try_for_each(|ifd| {
-            SiffFrame::write_frame_as_tiff(&mut file_reader,file, ifd)
-        })?;
+        let as_f32_pages = |array : Array3<f64>| array.axis_iter(Axis(0))
+            .map(|page| bytemuck::cast_slice(
+                &page.iter().map(|&x| x as f32).collect::<Vec<f32>>()
+            ).to_vec())
+            .collect::<Vec<Vec<u8>>>();
 
-        Ok(())
-    }
+        let mut reader = BufReader::new(self._source.open()?);
+        // Phasors are the largest arrays read (plus intensity)
+        let frames_per_chunk = _frames_per_chunk(
+            shape.0 * shape.1 * (std::mem::size_of::<Complex<f64>>() + std::mem::size_of::<u16>())
+        );
+        let chunk_pages = |chunk : &[u64]| -> Result<Vec<(String, Vec<u8>)>, CorrosiffError> {
+            // One `Vec` of pages per payload page, each with
+            // one page per frame of the chunk.
+            let pages : Vec<Vec<Vec<u8>>> = match payload {
+                TiffPayload::Intensity => vec![
+                    self.get_frames_intensity(chunk, registration)?
+                    .axis_iter(Axis(0))
+                    .map(|page| bytemuck::cast_slice(
+                        &page.iter().cloned().collect::<Vec<u16>>()
+                    ).to_vec())
+                    .collect()
+                ],
+                TiffPayload::Lifetime => vec![
+                    as_f32_pages(self.get_frames_flim(chunk, registration)?.0)
+                ],
+                TiffPayload::Phasor => {
+                    let phasor = self.get_frames_phasor(chunk, registration)?.0;
+                    vec![
+                        as_f32_pages(phasor.mapv(|x| x.re)),
+                        as_f32_pages(phasor.mapv(|x| x.im)),
+                    ]
+                },
+            };
 
-}
+            let mut pages = pages.into_iter().map(Vec::into_iter).collect::<Vec<_>>();
+            let mut frame_pages = Vec::with_capacity(chunk.len() * pages.len());
+            for &frame in chunk {
+                let metadata_string = FrameMetadata::metadata_string(
+                    &self._ifds[frame as usize], &mut reader
+                );
+                for page in pages.iter_mut() {
+                    frame_pages.push((metadata_string.clone(), page.next().unwrap()));
+                }
+            }
+            Ok(frame_pages)
+        };
 
-impl Display for SiffReader {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "SiffReader: {}\n{} frames",
-            self._filename.to_str().unwrap(),
+        let pages = frames.chunks(frames_per_chunk).map(chunk_pages)
+            .flat_map(|chunk| match chunk {
+                Ok(pages) => pages.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(err) => vec![Err(err)],
+            })
+            .enumerate()
+            .map(|(page_num, page)| page.map(|(metadata_string, strip)|
+                match (&stack_description, page_num) {
+                    (Some(stack_description), 0) => (format!("{}\0", stack_description), strip),
+                    _ => (metadata_string, strip),
+                }
+            ));
+        write_linked_pages(file, kind, &tags, pages)
+    }
+
+    /// Checks that `write_tiff_payload_to_file` can write `payload`
+    /// for `frames` with `registration` in `mode` (clearing an empty
+    /// `registration`, as `_check_registration` does) and returns the
+    /// frames to write (without the flyback frames in `TiffMode::ImageJ`)
+    /// and the shape of each page. Only the strips can be sized up front -- the
+    /// descriptions are checked as they're written.
+    pub (crate) fn _check_tiff_payload(
+        &self,
+        frames : &[u64],
+        registration : &mut Option<&RegistrationDict>,
+        mode : &TiffMode,
+        payload : TiffPayload,
+    ) -> Result<(Vec<u64>, (usize, usize)), CorrosiffError> {
+        // ImageJ can't skip the flyback planes of a hyperstack
+        let frames = match mode {
+            TiffMode::ImageJ => frames.iter().copied()
+                .filter(|&frame| !self.file_format.is_flyback_frame(frame))
+                .collect::<Vec<_>>(),
+            TiffMode::ScanImage | TiffMode::OME => frames.to_vec(),
+        };
+        let frames = &frames[..];
+        if frames.is_empty() {
+            return Err(DimensionsError::IncorrectFrames.into());
+        }
+        _check_frames_in_bounds(frames, &self._ifds)?;
+        let shape = _check_shared_shape(frames, &self._ifds)
+            .ok_or(DimensionsError::NoConsistentDimensions)?
+            .to_tuple();
+        _check_registration(registration, frames)?;
+
+        let (pages_per_frame, bytes_per_pixel) = match payload {
+            TiffPayload::Intensity => (1, 2),
+            TiffPayload::Lifetime => (1, 4),
+            TiffPayload::Phasor => (2, 4),
+        };
+        let strip_bytes = frames.len() * pages_per_frame * shape.0 * shape.1 * bytes_per_pixel;
+        if let TiffMode::ImageJ = mode {
+            if strip_bytes as u64 > u32::MAX as u64 {
+                return Err(CorrosiffError::IOError(IOError::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "An ImageJ .tiff cannot be larger than 4 GB"
+                )));
+            }
+        }
+        Ok((frames.to_vec(), shape))
+    }
+
+    /// Writes the frames requested into the file specified
+    /// by the writer. The saved data is **intensity only**
+    /// and is unregistered.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `file` - A mutable reference to a `Write` object
+    /// pointing to the location to write the frames to.
+    /// 
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write to the file. If this is `None`,
+    /// all frames are written to the file.
+    pub fn write_tiff_frames_to_file<WriterT : Write + Seek>(
+        &self,
+        file : &mut WriterT,
+        frames : Option<&[u64]>,
+    ) -> Result<(), CorrosiffError> {
+        let mut file_reader = self._source.open()?;
+
+        if frames.is_none() {
+            let mut reader_for_ifd = self._source.open()?;
+            self.file_format.get_ifd_iter(&mut reader_for_ifd)
+            .try_for_each(|ifd| {
+                SiffFrame::write_frame_as_tiff(
+                    &mut file_reader, 
+                    file, 
+                    &ifd
+                )
+            })?;
+            return Ok(());
+        }
+
+        frames.unwrap().iter().map(|&frame| &self._ifds[frame as usize])
+        .try_for_each(|ifd| {
+            SiffFrame::write_frame_as_tiff(&mut file_reader,file, ifd)
+        })?;
+
+        Ok(())
+    }
+
+    /// Streams the array chosen by `payload` for the frames requested
+    /// into `writer` as a `.npy` file, a few frames at a time, so the
+    /// whole array never needs to be in memory.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `writer` - Where to write the `.npy` file
+    /// 
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write. If this is `None`, all frames
+    /// are written.
+    /// 
+    /// * `registration` - An optional `RegistrationDict` to shift
+    /// each frame by before it is written.
+    /// 
+    /// * `payload` - The `NpyPayload` to compute from each frame
+    /// 
+    /// ## Returns
+    /// 
+    /// * `Result<W, CorrosiffError>` - The writer, just past the end of the array
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::DimensionsError` - If the frames are out of bounds,
+    /// don't share a shape, or if no frames are requested.
+    /// 
+    /// * `CorrosiffError::FramesError(FramesError::RegistrationFramesMissing)` -
+    /// If registration is used, and the registration values are missing for some frames
+    /// 
+    /// * `CorrosiffError::IOError` - If reading or writing fails.
+    pub fn write_npy<W : Write>(
+        &self,
+        writer : W,
+        frames : Option<&[u64]>,
+        registration : Option<&RegistrationDict>,
+        payload : NpyPayload,
+    ) -> Result<W, CorrosiffError> {
+        let all_frames = self.frames_vec();
+        let frames = frames.unwrap_or(&all_frames);
+        let mut registration = registration;
+        let shape = self._check_npy(frames, &mut registration, payload)?;
+
+        /// Writes `read` of each chunk of `frames` to a `.npy`
+        /// array of `shape`
+        fn stream<W, T, D, F>(writer : W, shape : &[usize], frames : &[u64], mut read : F)
+        -> Result<W, CorrosiffError>
+        where W : Write, T : NpyElement, D : Dimension,
+        F : FnMut(&[u64]) -> Result<Array<T, D>, CorrosiffError> {
+            let bytes_per_frame = shape[1..].iter().product::<usize>() * std::mem::size_of::<T>();
+            let mut npy = NpyWriter::<W, T>::new(writer, shape)?;
+            for chunk in frames.chunks(_frames_per_chunk(bytes_per_frame)) {
+                npy.write(&read(chunk)?.view())?;
+            }
+            Ok(npy.finish()?)
+        }
+
+        match payload {
+            NpyPayload::Intensity => stream(
+                writer, &shape, frames,
+                |chunk| self.get_frames_intensity(chunk, registration)
+            ),
+            NpyPayload::Lifetime => stream(
+                writer, &shape, frames,
+                |chunk| Ok(self.get_frames_flim(chunk, registration)?.0)
+            ),
+            NpyPayload::Phasor => stream(
+                writer, &shape, frames,
+                |chunk| Ok(self.get_frames_phasor(chunk, registration)?.0)
+            ),
+            NpyPayload::TauD => stream(
+                writer, &shape, frames,
+                |chunk| self.get_frames_tau_d(chunk, registration)
+            ),
+        }
+    }
+
+    /// Checks that `write_npy` can write `payload` for `frames`
+    /// with `registration` (clearing an empty `registration`, as
+    /// `_check_registration` does) and returns the shape of the array.
+    fn _check_npy(
+        &self,
+        frames : &[u64],
+        registration : &mut Option<&RegistrationDict>,
+        payload : NpyPayload,
+    ) -> Result<Vec<usize>, CorrosiffError> {
+        if frames.is_empty() {
+            return Err(DimensionsError::IncorrectFrames.into());
+        }
+        _check_frames_in_bounds(frames, &self._ifds)?;
+        let (ydim, xdim) = _check_shared_shape(frames, &self._ifds)
+            .ok_or(DimensionsError::NoConsistentDimensions)?
+            .to_tuple();
+        _check_registration(registration, frames)?;
+
+        match payload {
+            NpyPayload::TauD => {
+                let num_bins = self.file_format.num_flim_tau_bins()
+                    .ok_or(DimensionsError::UnknownHistogramSize)? as usize;
+                Ok(vec![frames.len(), ydim, xdim, num_bins])
+            },
+            _ => Ok(vec![frames.len(), ydim, xdim]),
+        }
+    }
+
+    /// Saves the array chosen by `payload` for the frames requested
+    /// to a new `.npy` file (readable with `numpy.load`). See `write_npy`.
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// reader.export_npy("lifetime.npy", None, Some(&registration), NpyPayload::Lifetime)?;
+    /// ```
+    pub fn export_npy<P : AsRef<Path>>(
+        &self,
+        path : P,
+        frames : Option<&[u64]>,
+        registration : Option<&RegistrationDict>,
+        payload : NpyPayload,
+    ) -> Result<(), CorrosiffError> {
+        // Checked before creating the file so that an existing file
+        // isn't truncated by a call that can't succeed
+        let all_frames = self.frames_vec();
+        let mut checked_registration = registration;
+        self._check_npy(frames.unwrap_or(&all_frames), &mut checked_registration, payload)?;
+
+        let file = std::io::BufWriter::new(File::create(path)?);
+        self.write_npy(file, frames, registration, payload)?;
+        Ok(())
+    }
+
+    /// Saves an array for each of `payloads` for the frames requested
+    /// to a new `.npz` file, each named by `NpyPayload::name` (so that
+    /// e.g. `numpy.load(path)["lifetime"]` is the lifetime). Each array
+    /// is streamed a few frames at a time, as in `write_npy`.
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// reader.export_npz(
+    ///     "flim.npz",
+    ///     None,
+    ///     None,
+    ///     &[NpyPayload::Intensity, NpyPayload::Lifetime],
+    /// )?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// As `write_npy`
+    pub fn export_npz<P : AsRef<Path>>(
+        &self,
+        path : P,
+        frames : Option<&[u64]>,
+        registration : Option<&RegistrationDict>,
+        payloads : &[NpyPayload],
+    ) -> Result<(), CorrosiffError> {
+        let all_frames = self.frames_vec();
+        let mut checked_registration = registration;
+        for &payload in payloads {
+            self._check_npy(frames.unwrap_or(&all_frames), &mut checked_registration, payload)?;
+        }
+
+        let mut npz = NpzWriter::new(std::io::BufWriter::new(File::create(path)?));
+        for payload in payloads {
+            let entry = npz.start_array(payload.name())?;
+            self.write_npy(entry, frames, registration, *payload)?.finish()?;
+        }
+        npz.finish()?;
+        Ok(())
+    }
+
+    /// Saves the intensity (`u16`) and empirical lifetime (`f64`)
+    /// of the frames requested to a Zarr (v2) directory store at `path`,
+    /// as the arrays `intensity` and `lifetime` of a group whose
+    /// attributes are read from the file's NVFD. The arrays are chunked
+    /// along frames, and only a few chunks are in memory at once, so
+    /// acquisitions larger than memory can be exported. The chunks
+    /// are filled and written in parallel.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `path` - The directory of the Zarr store. Created if needed.
+    /// 
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write. If this is `None`, all frames
+    /// are written.
+    /// 
+    /// * `registration` - An optional `RegistrationDict` to shift
+    /// each frame by before it is written.
+    /// 
+    /// * `chunk_frames` - The number of frames in each chunk
+    /// 
+    /// * `compression` - How each chunk is stored
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// reader.export_zarr("file.zarr", None, None, 100, ZarrCompression::Zlib(1))?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// As `write_npy`
+    pub fn export_zarr<P : AsRef<Path>>(
+        &self,
+        path : P,
+        frames : Option<&[u64]>,
+        registration : Option<&RegistrationDict>,
+        chunk_frames : usize,
+        compression : ZarrCompression,
+    ) -> Result<(), CorrosiffError> {
+        let all_frames = self.frames_vec();
+        let frames = frames.unwrap_or(&all_frames);
+        if frames.is_empty() {
+            return Err(DimensionsError::IncorrectFrames.into());
+        }
+        _check_frames_in_bounds(frames, &self._ifds)?;
+        let (ydim, xdim) = _check_shared_shape(frames, &self._ifds)
+            .ok_or(DimensionsError::NoConsistentDimensions)?
+            .to_tuple();
+        let mut registration = registration;
+        _check_registration(&mut registration, frames)?;
+        let chunk_frames = chunk_frames.max(1);
+
+        let as_json = |value : Option<String>| value.unwrap_or("null".to_string());
+        create_zarr_group(&path, &[
+            ("source".to_string(), json_string(&self.filename())),
+            ("num_frames".to_string(), frames.len().to_string()),
+            ("num_channels_saved".to_string(),
+                as_json(self.file_format.num_channels_saved().map(|x| x.to_string()))),
+            ("num_slices".to_string(),
+                as_json(self.file_format.num_slices().map(|x| x.to_string()))),
+            ("num_flyback_frames".to_string(),
+                as_json(self.file_format.num_flyback_frames().map(|x| x.to_string()))),
+            ("z_step_um".to_string(),
+                as_json(self.file_format.z_step_um().map(json_number))),
+            ("fov_um".to_string(),
+                as_json(self.file_format.fov_um().map(
+                    |(y, x)| format!("[{}, {}]", json_number(y), json_number(x))
+                ))),
+            ("num_flim_tau_bins".to_string(),
+                as_json(self.file_format.num_flim_tau_bins().map(|x| x.to_string()))),
+            ("flim_tau_bin_size_picoseconds".to_string(),
+                as_json(self.file_format.flim_tau_bin_size_picoseconds().map(|x| x.to_string()))),
+            ("nvfd".to_string(), json_string(&self.nvfd())),
+        ])?;
+
+        let shape = [frames.len(), ydim, xdim];
+        let intensity_zarr = ZarrArray::<u16>::create(
+            path.as_ref().join("intensity"), &shape, chunk_frames, compression, &[]
+        )?;
+        let lifetime_zarr = ZarrArray::<f64>::create(
+            path.as_ref().join("lifetime"), &shape, chunk_frames, compression,
+            &[("units".to_string(), json_string("arrival time bins"))]
+        )?;
+
+        let op = 
+        |
+            frames : &[u64],
+            chunk_intensity : &mut ArrayViewMut3<u16>,
+            chunk_lifetime : &mut ArrayViewMut3<f64>,
+            reader : &mut SourceReader
+        | -> Result<(), CorrosiffError> {
+            izip!(
+                frames,
+                chunk_lifetime.axis_iter_mut(Axis(0)),
+                chunk_intensity.axis_iter_mut(Axis(0))
+            ).try_for_each(
+                |(&this_frame, mut this_chunk_l, mut this_chunk_i)|
+                -> Result<(), CorrosiffError> {
+                match registration {
+                    Some(reg) => load_flim_empirical_and_intensity_arrays_registered(
+                        reader,
+                        &self._ifds[this_frame as usize],
+                        &mut this_chunk_l,
+                        &mut this_chunk_i,
+                        *reg.get(&this_frame).unwrap(),
+                    ),
+                    None => load_flim_empirical_and_intensity_arrays(
+                        reader,
+                        &self._ifds[this_frame as usize],
+                        &mut this_chunk_l,
+                        &mut this_chunk_i,
+                    ),
+                }
+                }
+            )
+        };
+
+        // As many whole chunks as fit in the memory budget (at least one),
+        // which are then filled and written in parallel
+        let bytes_per_frame = ydim * xdim
+            * (std::mem::size_of::<u16>() + std::mem::size_of::<f64>());
+        let chunks_per_batch = (_frames_per_chunk(bytes_per_frame) / chunk_frames).max(1);
+
+        for (batch_idx, batch_frames) in frames.chunks(chunks_per_batch * chunk_frames).enumerate() {
+            let (mut intensity, mut lifetime) = (
+                Array3::<u16>::zeros((batch_frames.len(), ydim, xdim)),
+                Array3::<f64>::zeros((batch_frames.len(), ydim, xdim)),
+            );
+
+            parallelize_op!(
+                (intensity, lifetime),
+                chunk_frames,
+                batch_frames,
+                self._source,
+                op
+            );
+
+            intensity.axis_chunks_iter(Axis(0), chunk_frames).into_par_iter()
+            .zip(lifetime.axis_chunks_iter(Axis(0), chunk_frames).into_par_iter())
+            .enumerate()
+            .try_for_each(|(idx, (chunk_intensity, chunk_lifetime))| -> Result<(), CorrosiffError> {
+                let chunk_idx = batch_idx * chunks_per_batch + idx;
+                intensity_zarr.write_chunk(chunk_idx, &chunk_intensity)?;
+                lifetime_zarr.write_chunk(chunk_idx, &chunk_lifetime)?;
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Saves the arrival time histogram of every pixel of the frames
+    /// requested (as in `get_frames_tau_d`) to a Becker & Hickl `.sdt`
+    /// file, which SPCImage and FLIMfit can open. Each frame is stored
+    /// as its own image, or, if `sum_frames` is `true`, the frames are
+    /// summed into a single image (with `u32` counts, so that they
+    /// can't overflow). The bin width is `flim_tau_bin_size_picoseconds`.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `path` - The path of the `.sdt` file
+    /// 
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write. If this is `None`, all frames
+    /// are written.
+    /// 
+    /// * `registration` - An optional `RegistrationDict` to shift
+    /// each frame by before it is written.
+    /// 
+    /// * `sum_frames` - Whether to write one image of all of the frames
+    /// summed together, rather than one per frame
+    /// 
+    /// ## Example
+    /// 
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// let green = (0..reader.num_frames() as u64).step_by(2).collect::<Vec<_>>();
+    /// reader.export_sdt("green.sdt", Some(&green), None, true)?;
+    /// ```
+    /// 
+    /// ## Errors
+    /// 
+    /// * `CorrosiffError::DimensionsError(DimensionsError::UnknownHistogramSize)` -
+    /// If the number or width of the arrival time bins is not in the file's header.
+    /// 
+    /// * `CorrosiffError::IOError` - If writing fails, or the file would be
+    /// larger than the 2 GB an `.sdt` file can hold (checked before the
+    /// file is created).
+    /// 
+    /// * Otherwise as `write_npy`
+    pub fn export_sdt<P : AsRef<Path>>(
+        &self,
+        path : P,
+        frames : Option<&[u64]>,
+        registration : Option<&RegistrationDict>,
+        sum_frames : bool,
+    ) -> Result<(), CorrosiffError> {
+        let all_frames = self.frames_vec();
+        let frames = frames.unwrap_or(&all_frames);
+        if frames.is_empty() {
+            return Err(DimensionsError::IncorrectFrames.into());
+        }
+        _check_frames_in_bounds(frames, &self._ifds)?;
+        let (ydim, xdim) = _check_shared_shape(frames, &self._ifds)
+            .ok_or(DimensionsError::NoConsistentDimensions)?
+            .to_tuple();
+        let mut registration = registration;
+        _check_registration(&mut registration, frames)?;
+
+        let num_bins = self.file_format.num_flim_tau_bins()
+            .ok_or(DimensionsError::UnknownHistogramSize)? as usize;
+        let bin_width_ps = self.file_format.flim_tau_bin_size_picoseconds()
+            .ok_or(DimensionsError::UnknownHistogramSize)? as f64;
+        let epoch = self.get_epoch_timestamps_system(&frames[..1]).ok().map(|epochs| epochs[0]);
+        let shape = (ydim, xdim, num_bins);
+        if sum_frames {
+            check_sdt::<u32>(shape, 1, bin_width_ps, epoch)?;
+        } else {
+            check_sdt::<u16>(shape, frames.len(), bin_width_ps, epoch)?;
+        }
+        let file = std::io::BufWriter::new(File::create(path)?);
+        let chunk_frames = _frames_per_chunk(ydim * xdim * num_bins * std::mem::size_of::<u16>());
+
+        if sum_frames {
+            let mut summed = Array3::<u32>::zeros(shape);
+            for chunk in frames.chunks(chunk_frames) {
+                self.get_frames_tau_d(chunk, registration)?.axis_iter(Axis(0))
+                    .for_each(|frame| summed.zip_mut_with(&frame, |s, &f| *s += f as u32));
+            }
+            let mut sdt = SdtWriter::<_, u32>::new(file, shape, 1, bin_width_ps, epoch)?;
+            sdt.write_block(&summed.view())?;
+            sdt.finish()?;
+        } else {
+            let mut sdt = SdtWriter::<_, u16>::new(file, shape, frames.len(), bin_width_ps, epoch)?;
+            for chunk in frames.chunks(chunk_frames) {
+                self.get_frames_tau_d(chunk, registration)?.axis_iter(Axis(0))
+                    .try_for_each(|frame| sdt.write_block(&frame))?;
+            }
+            sdt.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Appends the frames requested to a `SiffWriter`, storing
+    /// each in the `encoding` requested. The metadata string of
+    /// every frame is copied unchanged, and the photons of each
+    /// pixel are preserved, so the new frames have exactly the same
+    /// `tau_d` (and intensity, FLIM, etc.) data as the originals.
+    ///
+    /// ## Arguments
+    ///
+    /// * `writer` - The `SiffWriter` to append the frames to. Its header
+    /// should generally be this file's `nvfd` and `roi_string`.
+    ///
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write. If this is `None`, all frames
+    /// are written.
+    ///
+    /// * `encoding` - The `SiffEncoding` of the new frames
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<W, CorrosiffError>` - The underlying writer of the `SiffWriter`,
+    /// flushed.
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("raw.siff")?;
+    /// let writer = SiffWriter::create("compressed.siff", &reader.nvfd(), &reader.roi_string())?;
+    /// reader.write_siff_frames(writer, None, SiffEncoding::Compressed)?;
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames)` - If
+    /// any frame requested is out of bounds.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If a frame
+    /// is not a `.siff` frame or cannot be stored in the requested encoding.
+    ///
+    /// * `CorrosiffError::IOError` - If reading or writing fails.
+    pub fn write_siff_frames<W : Write + Seek>(
+        &self,
+        mut writer : SiffWriter<W>,
+        frames : Option<&[u64]>,
+        encoding : SiffEncoding,
+    ) -> Result<W, CorrosiffError> {
+        let all_frames = self.frames_vec();
+        let frames = frames.unwrap_or(&all_frames);
+        _check_frames_in_bounds(frames, &self._ifds)?;
+
+        let mut reader = BufReader::new(self._source.open()?);
+        frames.iter().try_for_each(|&frame| -> Result<(), CorrosiffError> {
+            let ifd = &self._ifds[frame as usize];
+            let photons = read_frame_photons(&mut reader, ifd)?;
+            writer.write_frame_photons(
+                &photons,
+                ifd.dimensions().ok_or(DimensionsError::NoConsistentDimensions)?.to_tuple(),
+                encoding,
+                &FrameMetadata::metadata_string(ifd, &mut reader),
+            )
+        })?;
+        writer.finish()
+    }
+
+    /// Appends an excerpt of this file to a `SiffWriter`: the frames
+    /// requested, optionally cropped to a window, still as `.siff` frames
+    /// with every photon's arrival time. Each frame keeps its encoding
+    /// (raw or compressed) and its metadata string. Photons outside of the
+    /// crop window are dropped, and the rest are shifted so that the
+    /// window's corner is the new frame's origin.
+    ///
+    /// ## Arguments
+    ///
+    /// * `writer` - The `SiffWriter` to append the frames to. Its header
+    /// should generally be this file's `nvfd` and `roi_string`.
+    ///
+    /// * `frames` - The frames to copy, in the order they should be written
+    ///
+    /// * `crop` - An optional window `(rows, columns)` of each frame to keep.
+    /// If `None`, the whole frame is kept.
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<W, CorrosiffError>` - The underlying writer of the `SiffWriter`,
+    /// flushed.
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("huge.siff")?;
+    /// let writer = SiffWriter::create("excerpt.siff", &reader.nvfd(), &reader.roi_string())?;
+    /// let frames = (1000..2000).collect::<Vec<u64>>();
+    /// reader.write_siff_excerpt(writer, &frames, Some((64..192, 64..192)))?;
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames)` - If
+    /// any frame requested is out of bounds.
+    ///
+    /// * `CorrosiffError::DimensionsError(DimensionsError::MismatchedDimensions)` - If
+    /// the crop window is empty or doesn't fit inside a frame.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If a frame
+    /// is not a `.siff` frame.
+    ///
+    /// * `CorrosiffError::IOError` - If reading or writing fails.
+    ///
+    /// ## See also
+    ///
+    /// - `extract_siff` to write the excerpt to a new file
+    pub fn write_siff_excerpt<W : Write + Seek>(
+        &self,
+        mut writer : SiffWriter<W>,
+        frames : &[u64],
+        crop : Option<(Range<usize>, Range<usize>)>,
+    ) -> Result<W, CorrosiffError> {
+        self._check_excerpt(frames, &crop)?;
+        self.rewrite_siff_frames(&mut writer, frames, |_, (ydim, xdim), photons| {
+            let (rows, cols) = crop.clone().unwrap_or((0..ydim, 0..xdim));
+            let photons = photons.into_iter().filter_map(|photon| {
+                let (y, x) = ((photon >> 48) as usize, ((photon >> 32) & 0xFFFF) as usize);
+                (rows.contains(&y) && cols.contains(&x)).then(|| pack_photon(
+                    (y - rows.start) as u16,
+                    (x - cols.start) as u16,
+                    photon as u32,
+                ))
+            }).collect();
+            Ok(((rows.len(), cols.len()), photons))
+        }, |_, description| description)?;
+        writer.finish()
+    }
+
+    /// Writes an excerpt of this file to a new `.siff` file at `path`
+    /// with the same NVFD and ROI string as this one. The NVFD is copied
+    /// unchanged, so fields describing the whole frame (e.g.
+    /// `SI.hRoiManager.pixelsPerLine`) still describe the original acquisition.
+    /// See `write_siff_excerpt`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - Where to create the new file
+    ///
+    /// * `frames` - The frames to copy, in the order they should be written
+    ///
+    /// * `crop` - An optional window `(rows, columns)` of each frame to keep.
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("huge.siff")?;
+    /// let frames = (1000..2000).collect::<Vec<u64>>();
+    /// reader.extract_siff("excerpt.siff", &frames, Some((0..128, 0..128)))?;
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::IOError` - If `path` is this file, or
+    /// reading or writing fails.
+    ///
+    /// Otherwise as `write_siff_excerpt`. Nothing is written
+    /// unless the frames and crop are valid.
+    pub fn extract_siff<P : AsRef<Path>>(
+        &self,
+        path : P,
+        frames : &[u64],
+        crop : Option<(Range<usize>, Range<usize>)>,
+    ) -> Result<(), CorrosiffError> {
+        self._check_excerpt(frames, &crop)?;
+        if self._is_source(&path)? {
+            return Err(CorrosiffError::IOError(IOError::new(
+                std::io::ErrorKind::InvalidInput,
+                "Cannot extract a file onto itself"
+            )));
+        }
+        let writer = SiffWriter::create(path, &self.nvfd(), &self.roi_string())?;
+        self.write_siff_excerpt(writer, frames, crop)?;
+        Ok(())
+    }
+
+    /// Appends the frames requested to a `SiffWriter` with their
+    /// registration applied to the photons themselves: every photon
+    /// is moved by its frame's shift, wrapping around the edges of
+    /// the frame as in `roll`. Unregistered reads of the new frames
+    /// match registered reads of the originals, so the new file can
+    /// be used without carrying the `RegistrationDict` around. Each
+    /// frame keeps its encoding and metadata string.
+    ///
+    /// ## Arguments
+    ///
+    /// * `writer` - The `SiffWriter` to append the frames to. Its header
+    /// should generally be this file's `nvfd` and `roi_string`.
+    ///
+    /// * `frames` - An optional slice of `u64` values corresponding
+    /// to the frame numbers to write. If this is `None`, all frames
+    /// are written.
+    ///
+    /// * `registration` - The `(y, x)` shift of every frame written
+    ///
+    /// ## Returns
+    ///
+    /// * `Result<W, CorrosiffError>` - The underlying writer of the `SiffWriter`,
+    /// flushed.
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// let writer = SiffWriter::create("registered.siff", &reader.nvfd(), &reader.roi_string())?;
+    /// reader.write_siff_registered(writer, None, &registration)?;
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames)` - If
+    /// any frame requested is out of bounds.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::RegistrationFramesMissing)` - If
+    /// `registration` has no shift for a frame requested.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If a frame
+    /// is not a `.siff` frame.
+    ///
+    /// * `CorrosiffError::IOError` - If reading or writing fails.
+    ///
+    /// ## See also
+    ///
+    /// - `bake_registration` to write every frame to a new file
+    pub fn write_siff_registered<W : Write + Seek>(
+        &self,
+        mut writer : SiffWriter<W>,
+        frames : Option<&[u64]>,
+        registration : &RegistrationDict,
+    ) -> Result<W, CorrosiffError> {
+        let all_frames = self.frames_vec();
+        let frames = frames.unwrap_or(&all_frames);
+        self._check_registered(frames, registration)?;
+
+        self.rewrite_siff_frames(&mut writer, frames, |frame, (ydim, xdim), photons| {
+            let (y_shift, x_shift) = registration[&frame];
+            let photons = photons.into_iter().map(|photon| pack_photon(
+                wrap_coordinate((photon >> 48) as usize, y_shift, ydim) as u16,
+                wrap_coordinate(((photon >> 32) & 0xFFFF) as usize, x_shift, xdim) as u16,
+                photon as u32,
+            )).collect();
+            Ok(((ydim, xdim), photons))
+        }, |_, description| description)?;
+        writer.finish()
+    }
+
+    /// Writes every frame of this file, with its registration
+    /// applied, to a new `.siff` file at `path` with the same NVFD
+    /// and ROI string as this one. See `write_siff_registered`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - Where to create the new file
+    ///
+    /// * `registration` - The `(y, x)` shift of every frame
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let reader = SiffReader::open("file.siff")?;
+    /// reader.bake_registration("registered.siff", &registration)?;
+    ///
+    /// let registered = SiffReader::open("registered.siff")?;
+    /// assert_eq!(
+    ///     registered.get_frames_intensity(&frames, None)?,
+    ///     reader.get_frames_intensity(&frames, Some(&registration))?,
+    /// );
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::IOError` - If `path` is this file, or
+    /// reading or writing fails.
+    ///
+    /// Otherwise as `write_siff_registered`. Nothing is written
+    /// unless every frame is a `.siff` frame with a shift.
+    pub fn bake_registration<P : AsRef<Path>>(
+        &self,
+        path : P,
+        registration : &RegistrationDict,
+    ) -> Result<(), CorrosiffError> {
+        self._check_registered(&self.frames_vec(), registration)?;
+        if self._is_source(&path)? {
+            return Err(CorrosiffError::IOError(IOError::new(
+                std::io::ErrorKind::InvalidInput,
+                "Cannot bake registration into the file being read"
+            )));
+        }
+        let writer = SiffWriter::create(path, &self.nvfd(), &self.roi_string())?;
+        self.write_siff_registered(writer, None, registration)?;
+        Ok(())
+    }
+
+    /// Checks that every frame requested is a `.siff` frame
+    /// that `crop` fits inside, before anything is written.
+    fn _check_excerpt(&self, frames : &[u64], crop : &Option<(Range<usize>, Range<usize>)>)
+    -> Result<(), CorrosiffError> {
+        _check_frames_in_bounds(frames, &self._ifds)?;
+        frames.iter().try_for_each(|&frame| {
+            let ifd = &self._ifds[frame as usize];
+            if ifd.get_tag(TiffTagID::Siff).is_none() {
+                return Err(FramesError::FormatError(
+                    format!("Frame {} is not a .siff frame", frame)
+                ).into());
+            }
+            let (ydim, xdim) = ifd.dimensions().ok_or(
+                FramesError::FormatError("Image dimensions not found".to_string())
+            )?.to_tuple();
+            let (rows, cols) = crop.clone().unwrap_or((0..ydim, 0..xdim));
+            if rows.is_empty() || cols.is_empty() || rows.end > ydim || cols.end > xdim {
+                return Err(DimensionsError::MismatchedDimensions {
+                    required : Dimensions::new(xdim as u64, ydim as u64),
+                    requested : Dimensions::new(cols.end as u64, rows.end as u64),
+                }.into());
+            }
+            Ok(())
+        })
+    }
+
+    /// Checks that every frame requested is a `.siff` frame
+    /// with a shift in `registration`, before anything is written.
+    fn _check_registered(&self, frames : &[u64], registration : &RegistrationDict)
+    -> Result<(), CorrosiffError> {
+        self._check_excerpt(frames, &None)?;
+        if !frames.iter().all(|frame| registration.contains_key(frame)) {
+            return Err(FramesError::RegistrationFramesMissing.into());
+        }
+        Ok(())
+    }
+
+    /// Whether `path` is the file this reader reads from
+    fn _is_source<P : AsRef<Path>>(&self, path : P) -> Result<bool, IOError> {
+        Ok(match self._source.path() {
+            Some(source) if path.as_ref().exists() => {
+                path.as_ref().canonicalize()? == source.canonicalize()?
+            },
+            _ => false,
+        })
+    }
+
+    /// Appends each frame requested to `writer` byte for byte, without
+    /// decoding its photons, after passing its metadata string through
+    /// `describe` (which takes the frame number and its metadata string
+    /// and returns the new metadata string).
+    pub (crate) fn copy_siff_frames<W, D>(
+        &self,
+        writer : &mut SiffWriter<W>,
+        frames : &[u64],
+        mut describe : D,
+    ) -> Result<(), CorrosiffError>
+    where W : Write + Seek, D : FnMut(u64, String) -> String {
+        self._check_copy(frames)?;
+
+        let mut reader = BufReader::new(self._source.open()?);
+        frames.iter().try_for_each(|&frame| -> Result<(), CorrosiffError> {
+            let ifd = &self._ifds[frame as usize];
+            let (pre_strip_offset, pre_strip_bytes, strip_bytes) = self._siff_frame_span(frame)?;
+
+            let description = describe(frame, FrameMetadata::metadata_string(ifd, &mut reader));
+            reader.seek(SeekFrom::Start(pre_strip_offset))?;
+            let data = reader.read_frame_bytes((pre_strip_bytes + strip_bytes) as usize)?;
+            let (pre_strip, strip) = data.split_at(pre_strip_bytes as usize);
+            writer.copy_page(ifd.tags(), description.as_bytes(), pre_strip, strip)
+        })
+    }
+
+    /// Checks that every frame requested can be copied by
+    /// `copy_siff_frames`, before anything is written.
+    pub (crate) fn _check_copy(&self, frames : &[u64]) -> Result<(), CorrosiffError> {
+        _check_frames_in_bounds(frames, &self._ifds)?;
+        frames.iter().try_for_each(|&frame| self._siff_frame_span(frame).map(|_| ()))
+    }
+
+    /// Where the data of a `.siff` frame stored in a single strip
+    /// is: the offset of its intensity image (or strip, for raw
+    /// frames), the size of the intensity image, and the size of
+    /// the strip.
+    fn _siff_frame_span(&self, frame : u64) -> Result<(u64, u64, u64), CorrosiffError> {
+        let ifd = &self._ifds[frame as usize];
+        let format_error = |problem : &str| FramesError::FormatError(
+            format!("Frame {} {}", frame, problem)
+        );
+        let pre_strip_bytes = match ifd.get_tag(TiffTagID::Siff).map(|tag| tag.value()) {
+            Some(0) => 0,
+            Some(1) => {
+                let (ydim, xdim) = ifd.dimensions()
+                    .ok_or(format_error("has no dimensions"))?.to_tuple();
+                (ydim * xdim * std::mem::size_of::<u16>()) as u64
+            },
+            _ => return Err(format_error("is not a .siff frame").into()),
+        };
+        let (strip_offset, strip_bytes) = match (
+            ifd.get_tag(TiffTagID::StripOffsets), ifd.get_tag(TiffTagID::StripByteCounts)
+        ) {
+            (Some(offset), Some(bytes)) if offset.num_values() == 1 && bytes.num_values() == 1 => {
+                (offset.value(), bytes.value())
+            },
+            _ => return Err(format_error("is not stored in a single strip").into()),
+        };
+        let pre_strip_offset = strip_offset.checked_sub(pre_strip_bytes)
+            .ok_or(format_error("has its strip before its intensity image"))?;
+        Ok((pre_strip_offset, pre_strip_bytes, strip_bytes))
+    }
+
+    /// Appends each frame requested to `writer` in its original encoding,
+    /// after passing its photons through `transform` and its metadata string
+    /// through `describe`. `transform` takes the frame number, its shape and its
+    /// photons and returns the new shape and photons; `describe` takes the frame
+    /// number and its metadata string and returns the new metadata string.
+    pub (crate) fn rewrite_siff_frames<W, F, D>(
+        &self,
+        writer : &mut SiffWriter<W>,
+        frames : &[u64],
+        mut transform : F,
+        mut describe : D,
+    ) -> Result<(), CorrosiffError>
+    where W : Write + Seek,
+    F : FnMut(u64, (usize, usize), Vec<u64>) -> Result<((usize, usize), Vec<u64>), CorrosiffError>,
+    D : FnMut(u64, String) -> String {
+        _check_frames_in_bounds(frames, &self._ifds)?;
+
+        let mut reader = BufReader::new(self._source.open()?);
+        frames.iter().try_for_each(|&frame| -> Result<(), CorrosiffError> {
+            let ifd = &self._ifds[frame as usize];
+            let encoding = match ifd.get_tag(TiffTagID::Siff).map(|tag| tag.value()) {
+                Some(1) => SiffEncoding::Compressed,
+                _ => SiffEncoding::Raw,
+            };
+            let shape = ifd.dimensions().ok_or(
+                FramesError::FormatError("Image dimensions not found".to_string())
+            )?.to_tuple();
+            let (shape, photons) = transform(
+                frame,
+                shape,
+                read_frame_photons(&mut reader, ifd)?,
+            )?;
+            writer.write_frame_photons(
+                &photons,
+                shape,
+                encoding,
+                &describe(frame, FrameMetadata::metadata_string(ifd, &mut reader)),
+            )
+        })
+    }
+
+}
+
+impl Display for SiffReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SiffReader: {}\n{} frames",
+            self.filename(),
             self._ifds.len(),
         )
     }
@@ -4124,8 +5714,711 @@ impl Display for SiffReader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::{fs::File, io::Cursor};
     use crate::tests::{get_test_paths, UNCOMPRESSED_FRAME_NUM, COMPRESSED_FRAME_NUM};
 
+    #[test]
+    fn in_memory_matches_file() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let shape = (12, 16);
+        let mut roi = Array3::<bool>::from_elem((2, shape.0, shape.1), false);
+        roi.slice_mut(s![.., 2..7, 3..12]).fill(true);
+        let mut reg = RegistrationDict::new();
+        (0..6).for_each(|frame| { reg.insert(frame, (1, frame as i32 - 3)); });
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let path = TempPath::new(&format!("in_memory_{:?}.siff", encoding));
+            write_synthetic_siff(&path, 6, shape, encoding);
+            let bytes = std::fs::read(&path).unwrap();
+
+            let file = SiffReader::open(&path).unwrap();
+            let memory = SiffReader::from_bytes(bytes.clone()).unwrap();
+            let cursor = SiffReader::from_reader(Cursor::new(Arc::<[u8]>::from(bytes))).unwrap();
+
+            for reader in [&memory, &cursor] {
+                assert_eq!(reader.filename(), "");
+                assert!(reader.is_siff());
+                assert_eq!(reader.num_frames(), file.num_frames());
+                assert_eq!(reader.nvfd(), file.nvfd());
+
+                let frames = reader.frames_vec();
+                assert_eq!(
+                    reader.get_frames_intensity(&frames, Some(&reg)).unwrap(),
+                    file.get_frames_intensity(&frames, Some(&reg)).unwrap()
+                );
+                let (lifetime, intensity) = reader.get_frames_flim(&frames, None).unwrap();
+                let (file_lifetime, file_intensity) = file.get_frames_flim(&frames, None).unwrap();
+                assert_eq!(intensity, file_intensity);
+                // Pixels without photons are NaN
+                assert_eq!(lifetime.mapv(f64::to_bits), file_lifetime.mapv(f64::to_bits));
+                assert_eq!(reader.get_histogram(&frames).unwrap(), file.get_histogram(&frames).unwrap());
+                assert_eq!(
+                    reader.sum_roi_volume(&roi.view(), &frames, Some(&reg)).unwrap(),
+                    file.sum_roi_volume(&roi.view(), &frames, Some(&reg)).unwrap()
+                );
+                assert_eq!(
+                    reader.get_epoch_timestamps_both(&frames).unwrap(),
+                    file.get_epoch_timestamps_both(&frames).unwrap()
+                );
+                assert_eq!(
+                    reader.get_frame_metadata(&[3]).unwrap()[0].metadata_string,
+                    file.get_frame_metadata(&[3]).unwrap()[0].metadata_string
+                );
+            }
+        }
+
+        assert!(matches!(
+            SiffReader::from_bytes(vec![0u8; 64]),
+            Err(CorrosiffError::FileFormatError)
+        ));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_matches_file() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let shape = (12, 16);
+        let mut roi = Array2::<bool>::from_elem(shape, false);
+        roi.slice_mut(s![4..9, 0..10]).fill(true);
+        let mut reg = RegistrationDict::new();
+        (0..6).for_each(|frame| { reg.insert(frame, (frame as i32, -2)); });
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let path = TempPath::new(&format!("mmap_{:?}.siff", encoding));
+            write_synthetic_siff(&path, 6, shape, encoding);
+
+            let file = SiffReader::open(&path).unwrap();
+            let mapped = unsafe { SiffReader::open_mmap(&path).unwrap() };
+            assert_eq!(mapped.filename(), file.filename());
+            assert!(mapped.is_siff());
+
+            let frames = mapped.frames_vec();
+            assert_eq!(
+                mapped.get_frames_intensity(&frames, Some(&reg)).unwrap(),
+                file.get_frames_intensity(&frames, Some(&reg)).unwrap()
+            );
+            assert_eq!(
+                mapped.get_frames_tau_d(&frames, None).unwrap(),
+                file.get_frames_tau_d(&frames, None).unwrap()
+            );
+            assert_eq!(
+                mapped.get_histogram_mask(&frames, &roi.view(), Some(&reg)).unwrap(),
+                file.get_histogram_mask(&frames, &roi.view(), Some(&reg)).unwrap()
+            );
+            assert_eq!(
+                mapped.get_roi_flat(&roi.view(), &frames, None).unwrap(),
+                file.get_roi_flat(&roi.view(), &frames, None).unwrap()
+            );
+            assert_eq!(
+                mapped.get_experiment_timestamps(&frames).unwrap(),
+                file.get_experiment_timestamps(&frames).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn refresh_follows_growing_file() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let shape = (12, 16);
+        let mut roi = Array2::<bool>::from_elem(shape, false);
+        roi.slice_mut(s![1..10, 5..14]).fill(true);
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let full_path = TempPath::new(&format!("refresh_full_{:?}.siff", encoding));
+            write_synthetic_siff(&full_path, 6, shape, encoding);
+            let full = SiffReader::open(&full_path).unwrap();
+            let bytes = std::fs::read(&full_path).unwrap();
+            let strip_end = |frame : usize| -> usize {
+                let ifd = &full._ifds[frame];
+                (ifd.get_tag(TiffTagID::StripOffsets).unwrap().value()
+                + ifd.get_tag(TiffTagID::StripByteCounts).unwrap().value()) as usize
+            };
+
+            // Halfway through writing the third frame
+            let growing_path = TempPath::new(&format!("refresh_growing_{:?}.siff", encoding));
+            let half_written = strip_end(2) - 4;
+            std::fs::write(&growing_path, &bytes[..half_written]).unwrap();
+            let mut growing = SiffReader::open(&growing_path).unwrap();
+            assert_eq!(growing.num_frames(), 2);
+            assert!(growing.refresh().unwrap().is_empty());
+
+            let mut file = std::fs::OpenOptions::new().append(true).open(&growing_path).unwrap();
+            file.write_all(&bytes[half_written..strip_end(4)]).unwrap();
+            file.flush().unwrap();
+            assert_eq!(growing.refresh().unwrap(), vec![2, 3, 4]);
+
+            file.write_all(&bytes[strip_end(4)..]).unwrap();
+            file.flush().unwrap();
+            assert_eq!(growing.refresh().unwrap(), vec![5]);
+            assert!(growing.refresh().unwrap().is_empty());
+            assert_eq!(growing.num_frames(), full.num_frames());
+            assert_eq!(growing._ifd_offsets, full._ifd_offsets);
+
+            let frames = [2, 3, 4, 5];
+            let (lifetime, intensity) = growing.sum_roi_flim_flat(&roi.view(), &frames, None).unwrap();
+            let (full_lifetime, full_intensity) = full.sum_roi_flim_flat(&roi.view(), &frames, None).unwrap();
+            assert_eq!(intensity, full_intensity);
+            assert_eq!(lifetime.mapv(f64::to_bits), full_lifetime.mapv(f64::to_bits));
+        }
+    }
+
+    #[test]
+    fn excerpt_crops_and_keeps_encoding() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let shape = (12, 16);
+        let frames = [4, 1, 2];
+        let (rows, cols) = (2..9, 3..14);
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let path = TempPath::new(&format!("excerpt_source_{:?}.siff", encoding));
+            let excerpt_path = TempPath::new(&format!("excerpt_{:?}.siff", encoding));
+            write_synthetic_siff(&path, 6, shape, encoding);
+            let reader = SiffReader::open(&path).unwrap();
+
+            reader.extract_siff(&excerpt_path, &frames, Some((rows.clone(), cols.clone()))).unwrap();
+            let excerpt = SiffReader::open(&excerpt_path).unwrap();
+            assert_eq!(excerpt.num_frames(), 3);
+            assert_eq!(excerpt.image_dims().unwrap().to_tuple(), (7, 11));
+            assert_eq!(excerpt.nvfd(), reader.nvfd());
+
+            assert_eq!(
+                excerpt.get_frames_tau_d(&[0, 1, 2], None).unwrap(),
+                reader.get_frames_tau_d(&frames, None).unwrap()
+                    .slice(s![.., rows.clone(), cols.clone(), ..])
+            );
+            let metadata = excerpt.get_frame_metadata(&[0, 1, 2]).unwrap();
+            let original = reader.get_frame_metadata(&frames).unwrap();
+            metadata.iter().zip(original.iter()).for_each(|(new, old)| {
+                assert_eq!(new.metadata_string, old.metadata_string);
+                assert_eq!(new.siff_compress, old.siff_compress);
+            });
+
+            // Uncropped is a faithful copy
+            reader.extract_siff(&excerpt_path, &frames, None).unwrap();
+            assert_eq!(
+                SiffReader::open(&excerpt_path).unwrap().get_frames_tau_d(&[0, 1, 2], None).unwrap(),
+                reader.get_frames_tau_d(&frames, None).unwrap()
+            );
+
+            // Invalid requests and the source itself leave every file untouched
+            let excerpt_bytes = std::fs::read(&excerpt_path).unwrap();
+            let source_bytes = std::fs::read(&path).unwrap();
+            assert!(matches!(
+                reader.extract_siff(&excerpt_path, &frames, Some((0..13, 0..4))),
+                Err(CorrosiffError::DimensionsError(DimensionsError::MismatchedDimensions {..}))
+            ));
+            assert!(matches!(
+                reader.extract_siff(&excerpt_path, &[6], None),
+                Err(CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames))
+            ));
+            assert_eq!(std::fs::read(&excerpt_path).unwrap(), excerpt_bytes);
+            assert!(matches!(
+                reader.extract_siff(&path, &frames, None),
+                Err(CorrosiffError::IOError(_))
+            ));
+            assert_eq!(std::fs::read(&path).unwrap(), source_bytes);
+        }
+    }
+
+    #[test]
+    fn baked_registration_matches_registered_reads() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        // Shifts in both directions, on shapes that aren't powers of two
+        let registration = (0..5).map(|frame| (frame, (3 - 2 * frame as i32, 7 * frame as i32 - 9)))
+            .collect::<RegistrationDict>();
+
+        for (shape, encoding) in [
+            ((16, 32), SiffEncoding::Raw), ((16, 32), SiffEncoding::Compressed),
+            ((6, 10), SiffEncoding::Raw), ((6, 10), SiffEncoding::Compressed),
+        ] {
+            let mut roi = Array2::<bool>::from_elem(shape, false);
+            roi.slice_mut(s![1..5, 2..9]).fill(true);
+            let path = TempPath::new(&format!("bake_source_{:?}_{:?}.siff", shape, encoding));
+            let baked_path = TempPath::new(&format!("bake_{:?}_{:?}.siff", shape, encoding));
+            write_synthetic_siff(&path, 5, shape, encoding);
+            let reader = SiffReader::open(&path).unwrap();
+            reader.bake_registration(&baked_path, &registration).unwrap();
+            let baked = SiffReader::open(&baked_path).unwrap();
+
+            let frames = reader.frames_vec();
+            assert_eq!(
+                baked.get_frames_intensity(&frames, None).unwrap(),
+                reader.get_frames_intensity(&frames, Some(&registration)).unwrap()
+            );
+            assert_eq!(
+                baked.get_frames_tau_d(&frames, None).unwrap(),
+                reader.get_frames_tau_d(&frames, Some(&registration)).unwrap()
+            );
+            assert_eq!(
+                baked.sum_roi_flat(&roi.view(), &frames, None).unwrap(),
+                reader.sum_roi_flat(&roi.view(), &frames, Some(&registration)).unwrap()
+            );
+            assert_eq!(
+                baked.get_frames_phasor(&frames, None).unwrap().0
+                    .mapv(|z| (z.re.to_bits(), z.im.to_bits())),
+                reader.get_frames_phasor(&frames, Some(&registration)).unwrap().0
+                    .mapv(|z| (z.re.to_bits(), z.im.to_bits()))
+            );
+            assert_eq!(
+                baked.get_frame_metadata(&frames).unwrap()[2].siff_compress,
+                reader.get_frame_metadata(&frames).unwrap()[2].siff_compress
+            );
+
+            // A failed bake leaves the existing output alone
+            let baked_bytes = std::fs::read(&baked_path).unwrap();
+            let mut partial = registration.clone();
+            partial.remove(&3);
+            assert!(matches!(
+                reader.bake_registration(&baked_path, &partial),
+                Err(CorrosiffError::FramesError(FramesError::RegistrationFramesMissing))
+            ));
+            assert_eq!(std::fs::read(&baked_path).unwrap(), baked_bytes);
+
+            let source_bytes = std::fs::read(&path).unwrap();
+            assert!(matches!(
+                reader.bake_registration(&path, &registration),
+                Err(CorrosiffError::IOError(_))
+            ));
+            assert_eq!(std::fs::read(&path).unwrap(), source_bytes);
+        }
+
+        // Plain Tiff frames have no photons to move
+        let tiff_path = TempPath::new("bake_source.tiff");
+        let baked_path = TempPath::new("bake_tiff.siff");
+        crate::tests::write_plain_tiff(&tiff_path, 5, (6, 10));
+        assert!(matches!(
+            SiffReader::open(&tiff_path).unwrap().bake_registration(&baked_path, &registration),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+        assert!(!baked_path.as_ref().exists());
+    }
+
+    #[test]
+    fn registered_reads_wrap_shifts() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        // Negative shifts and shifts larger than the frame, on a
+        // shape that isn't a power of two
+        let shape = (6, 10);
+        let shifts = [(-1, -3), (7, 23), (-13, -10), (0, -21)];
+        let registration = shifts.iter().enumerate()
+            .map(|(frame, &shift)| (frame as u64, shift))
+            .collect::<RegistrationDict>();
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let path = TempPath::new(&format!("wrap_shifts_{:?}.siff", encoding));
+            write_synthetic_siff(&path, shifts.len(), shape, encoding);
+            let reader = SiffReader::open(&path).unwrap();
+            let frames = reader.frames_vec();
+
+            let unregistered = reader.get_frames_intensity(&frames, None).unwrap();
+            let mut expected = Array3::<u16>::zeros(unregistered.dim());
+            unregistered.indexed_iter().for_each(|((frame, y, x), &count)| {
+                let (dy, dx) = shifts[frame];
+                expected[[
+                    frame,
+                    (y as i32 + dy).rem_euclid(shape.0 as i32) as usize,
+                    (x as i32 + dx).rem_euclid(shape.1 as i32) as usize,
+                ]] = count;
+            });
+            assert_eq!(reader.get_frames_intensity(&frames, Some(&registration)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn npy_export_matches_arrays() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let shape = (8, 16);
+        let path = TempPath::new("npy_source.siff");
+        write_synthetic_siff(&path, 5, shape, SiffEncoding::Compressed);
+        let reader = SiffReader::open(&path).unwrap();
+        let frames = [0u64, 2, 4];
+        let registration = frames.iter().map(|&frame| (frame, (frame as i32, -1)))
+            .collect::<RegistrationDict>();
+
+        // Splits a `.npy` file into its header dictionary and data
+        let split = |bytes : &[u8]| {
+            let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+            (
+                std::str::from_utf8(&bytes[10..10 + header_len]).unwrap().trim_end().to_string(),
+                bytes[10 + header_len..].to_vec()
+            )
+        };
+
+        let tau_d = reader.get_frames_tau_d(&frames, Some(&registration)).unwrap();
+        let (header, data) = split(
+            &reader.write_npy(Vec::new(), Some(&frames), Some(&registration), NpyPayload::TauD).unwrap()
+        );
+        assert_eq!(header, "{'descr': '<u2', 'fortran_order': False, 'shape': (3, 8, 16, 64), }");
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, u16>(&data),
+            tau_d.iter().cloned().collect::<Vec<_>>()
+        );
+
+        let phasor = reader.get_frames_phasor(&reader.frames_vec(), None).unwrap().0;
+        let (header, data) = split(
+            &reader.write_npy(Vec::new(), None, None, NpyPayload::Phasor).unwrap()
+        );
+        assert!(header.contains("'descr': '<c16'") && header.contains("'shape': (5, 8, 16)"));
+        let data = bytemuck::pod_collect_to_vec::<u8, f64>(&data);
+        phasor.iter().zip(data.chunks_exact(2)).for_each(|(expected, written)| {
+            assert!(
+                (expected.re == written[0] || expected.re.is_nan() && written[0].is_nan())
+                && (expected.im == written[1] || expected.im.is_nan() && written[1].is_nan())
+            );
+        });
+
+        let npz_path = TempPath::new("npy_export.npz");
+        reader.export_npz(
+            &npz_path, Some(&frames), None, &[NpyPayload::Intensity, NpyPayload::Lifetime]
+        ).unwrap();
+        let bytes = std::fs::read(&npz_path).unwrap();
+        assert_eq!(&bytes[30..43], b"intensity.npy");
+        let (header, data) = split(
+            &bytes[30 + 13 + 20..30 + 13 + 20 + 128 + 3 * 8 * 16 * 2]
+        );
+        assert!(header.contains("'descr': '<u2'"));
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, u16>(&data),
+            reader.get_frames_intensity(&frames, None).unwrap().iter().cloned().collect::<Vec<_>>()
+        );
+
+        // Failing calls leave existing files alone and create no new ones
+        assert!(matches!(
+            reader.export_npy(&npz_path, None, Some(&registration), NpyPayload::Intensity),
+            Err(CorrosiffError::FramesError(FramesError::RegistrationFramesMissing))
+        ));
+        assert!(matches!(
+            reader.export_npz(&npz_path, Some(&[0, 9]), None, &[NpyPayload::Intensity]),
+            Err(CorrosiffError::DimensionsError(_))
+        ));
+        assert_eq!(std::fs::read(&npz_path).unwrap(), bytes);
+
+        let missing_path = TempPath::new("npy_export_missing.npy");
+        assert!(reader.export_npy(&missing_path, Some(&[]), None, NpyPayload::Lifetime).is_err());
+        assert!(!missing_path.as_ref().exists());
+    }
+
+    #[test]
+    fn photon_stream_matches_arrays() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let cases = [
+            (SiffEncoding::Raw, (8, 16)),
+            (SiffEncoding::Compressed, (8, 16)),
+            (SiffEncoding::Raw, (6, 10)),
+            (SiffEncoding::Compressed, (6, 10)),
+        ];
+        for (encoding, shape) in cases {
+            let path = TempPath::new("photon_stream.siff");
+            let written = write_synthetic_siff(&path, 4, shape, encoding);
+            let reader = SiffReader::open(&path).unwrap();
+            let frames = [3u64, 1, 2];
+            // Negative, and some larger than the frame
+            let registration = frames.iter()
+                .map(|&frame| (frame, (-5 * frame as i32, 2 * frame as i32 - 13)))
+                .collect::<RegistrationDict>();
+
+            let photons = reader.get_photon_stream(&frames, None).unwrap();
+            assert_eq!(
+                photons.len(),
+                frames.iter().map(|&frame| written[frame as usize].len()).sum::<usize>()
+            );
+            if encoding == SiffEncoding::Raw {
+                assert_eq!(
+                    photons.iter().take(written[3].len()).cloned().collect::<Vec<_>>(),
+                    written[3].iter().map(|&p| Photon::from_packed(3, p, (0, 0), shape))
+                        .collect::<Vec<_>>()
+                );
+            }
+
+            let photons = reader.get_photon_stream(&frames, Some(&registration)).unwrap();
+            let mut intensity = Array3::<u16>::zeros((frames.len(), shape.0, shape.1));
+            photons.iter().for_each(|photon| {
+                let idx = frames.iter().position(|&frame| frame == photon.frame).unwrap();
+                intensity[[idx, photon.y as usize, photon.x as usize]] += 1;
+            });
+            assert_eq!(
+                intensity,
+                reader.get_frames_intensity(&frames, Some(&registration)).unwrap()
+            );
+            assert_eq!(
+                photons.iter().map(|photon| photon.tau as f64).sum::<f64>(),
+                frames.iter().map(|&frame| written[frame as usize].iter()
+                    .map(|&p| (p & 0xFFFF_FFFF) as f64).sum::<f64>()
+                ).sum::<f64>()
+            );
+
+            assert!(matches!(
+                reader.get_photon_stream(&[0, 1], Some(&registration)),
+                Err(CorrosiffError::FramesError(FramesError::RegistrationFramesMissing))
+            ));
+        }
+    }
+
+    #[test]
+    fn sdt_export_matches_tau_d() {
+        use crate::tests::{TempPath, write_synthetic_siff};
+        let path = TempPath::new("sdt_source.siff");
+        write_synthetic_siff(&path, 3, (8, 16), SiffEncoding::Raw);
+        let reader = SiffReader::open(&path).unwrap();
+        let frames = [2u64, 0];
+        let tau_d = reader.get_frames_tau_d(&frames, None).unwrap();
+
+        // Reads the first data block of an `.sdt` file
+        let first_block = |bytes : &[u8]| {
+            let block = i32::from_le_bytes(bytes[14..18].try_into().unwrap()) as usize;
+            let data = i32::from_le_bytes(bytes[block + 2..block + 6].try_into().unwrap()) as usize;
+            let length = u32::from_le_bytes(bytes[block + 18..block + 22].try_into().unwrap()) as usize;
+            (i16::from_le_bytes([bytes[18], bytes[19]]), bytes[data..data + length].to_vec())
+        };
+
+        let sdt_path = TempPath::new("export.sdt");
+        reader.export_sdt(&sdt_path, Some(&frames), None, false).unwrap();
+        let (num_blocks, data) = first_block(&std::fs::read(&sdt_path).unwrap());
+        assert_eq!(num_blocks, 2);
+        // 64 bins are already a power of two
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, u16>(&data),
+            tau_d.index_axis(Axis(0), 0).iter().cloned().collect::<Vec<_>>()
+        );
+
+        reader.export_sdt(&sdt_path, Some(&frames), None, true).unwrap();
+        let (num_blocks, data) = first_block(&std::fs::read(&sdt_path).unwrap());
+        assert_eq!(num_blocks, 1);
+        assert_eq!(
+            bytemuck::pod_collect_to_vec::<u8, u32>(&data),
+            tau_d.sum_axis(Axis(0)).iter().map(|&x| x as u32).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn zarr_export_matches_arrays() {
+        use std::io::Read;
+        use flate2::read::ZlibDecoder;
+        use crate::tests::{
+            TempPath, SYNTHETIC_NVFD, synthetic_description, synthetic_photons, write_synthetic_siff,
+        };
+        let path = TempPath::new("zarr_source.siff");
+        write_synthetic_siff(&path, 7, (8, 16), SiffEncoding::Compressed);
+        let reader = SiffReader::open(&path).unwrap();
+        let frames = [6u64, 0, 1, 2, 5];
+        let registration = frames.iter().map(|&frame| (frame, (1, frame as i32)))
+            .collect::<RegistrationDict>();
+
+        let zarr_path = TempPath::new("zarr_export.zarr");
+        reader.export_zarr(
+            &zarr_path, Some(&frames), Some(&registration), 2, ZarrCompression::Zlib(1)
+        ).unwrap();
+
+        let attributes = std::fs::read_to_string(zarr_path.as_ref().join(".zattrs")).unwrap();
+        assert!(attributes.contains("\"num_frames\": 5"));
+        assert!(attributes.contains("\"num_flim_tau_bins\": 64"));
+        let zarray = std::fs::read_to_string(zarr_path.as_ref().join("lifetime/.zarray")).unwrap();
+        assert!(zarray.contains("\"shape\": [5, 8, 16]") && zarray.contains("\"chunks\": [2, 8, 16]"));
+
+        let read_chunk = |name : &str| {
+            let mut bytes = Vec::new();
+            (0..3).for_each(|idx| {
+                ZlibDecoder::new(
+                    File::open(zarr_path.as_ref().join(name).join(format!("{}.0.0", idx))).unwrap()
+                ).read_to_end(&mut bytes).unwrap();
+            });
+            bytes
+        };
+
+        let (lifetime, intensity) = reader.get_frames_flim(&frames, Some(&registration)).unwrap();
+        let written = bytemuck::pod_collect_to_vec::<u8, u16>(&read_chunk("intensity"));
+        assert_eq!(written.len(), 6 * 8 * 16);
+        assert_eq!(&written[..5 * 8 * 16], intensity.as_slice().unwrap());
+        assert!(written[5 * 8 * 16..].iter().all(|&x| x == 0));
+        let written = bytemuck::pod_collect_to_vec::<u8, f64>(&read_chunk("lifetime"));
+        lifetime.iter().zip(written.iter()).for_each(|(expected, written)| {
+            assert!(expected == written || expected.is_nan() && written.is_nan());
+        });
+
+        assert!(matches!(
+            reader.export_zarr(&zarr_path, Some(&[7]), None, 2, ZarrCompression::Raw),
+            Err(CorrosiffError::DimensionsError(DimensionsError::IncorrectFrames))
+        ));
+
+        // ScanImage writes `NaN` for unset values, which JSON can't hold
+        let nan_path = TempPath::new("zarr_nan_source.siff");
+        let mut writer = SiffWriter::create(
+            &nan_path,
+            &format!("{}SI.hStackManager.stackZStepSize = NaN\n", SYNTHETIC_NVFD),
+            ""
+        ).unwrap();
+        writer.write_frame_photons(
+            &synthetic_photons(0, (8, 16), 64), (8, 16), SiffEncoding::Raw, &synthetic_description(0)
+        ).unwrap();
+        writer.finish().unwrap();
+        let nan_zarr_path = TempPath::new("zarr_nan_export.zarr");
+        SiffReader::open(&nan_path).unwrap()
+            .export_zarr(&nan_zarr_path, None, None, 1, ZarrCompression::Raw).unwrap();
+        let attributes = std::fs::read_to_string(nan_zarr_path.as_ref().join(".zattrs")).unwrap();
+        assert!(attributes.contains("\"z_step_um\": null"));
+    }
+
+    #[test]
+    fn open_with_index_skips_ifd_chain() {
+        use crate::tests::{TempPath, write_classic_siff, write_synthetic_siff};
+        let shape = (8, 10);
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let path = TempPath::new(&format!("indexed_{:?}.siff", encoding));
+            let _index = TempPath::new(&format!("indexed_{:?}.siff.idx", encoding));
+            write_synthetic_siff(&path, 5, shape, encoding);
+
+            // No index yet -- walks the chain and writes one
+            let walked = SiffReader::open_with_index(&path).unwrap();
+            assert!(index_path(&path).exists());
+            let indexed = SiffReader::open_with_index(&path).unwrap();
+            assert_eq!(indexed._ifd_offsets, walked._ifd_offsets);
+            indexed._ifds.iter().zip(&walked._ifds).for_each(|(a, b)| {
+                assert_eq!(a.tags(), b.tags());
+                assert_eq!(a.next_ifd, b.next_ifd);
+            });
+            let frames = indexed.frames_vec();
+            assert_eq!(
+                indexed.get_frames_tau_d(&frames, None).unwrap(),
+                walked.get_frames_tau_d(&frames, None).unwrap()
+            );
+            assert_eq!(
+                indexed.get_frame_metadata(&[4]).unwrap()[0].metadata_string,
+                walked.get_frame_metadata(&[4]).unwrap()[0].metadata_string
+            );
+
+            // A current index is trusted without reading the IFDs
+            let stamp = file_stamp(&std::fs::metadata(&path).unwrap()).unwrap();
+            IfdIndex::new(&walked._ifds[..3], &walked._ifd_offsets[..3], stamp).unwrap()
+                .write(&mut File::create(index_path(&path)).unwrap()).unwrap();
+            assert_eq!(SiffReader::open_with_index(&path).unwrap().num_frames(), 3);
+
+            // ... but a stale one is replaced
+            write_classic_siff(&path, 7, shape, encoding);
+            let reopened = SiffReader::open_with_index(&path).unwrap();
+            assert_eq!(reopened.num_frames(), 7);
+            assert!(!reopened.is_bigtiff());
+            assert_eq!(SiffReader::open_with_index(&path).unwrap()._ifd_offsets, reopened._ifd_offsets);
+        }
+
+        let path = TempPath::new("unindexable.siff");
+        write_synthetic_siff(&path, 1, shape, SiffEncoding::Raw);
+        let memory = SiffReader::from_bytes(std::fs::read(&path).unwrap()).unwrap();
+        assert!(matches!(memory.write_index(), Err(CorrosiffError::NotImplementedError)));
+    }
+
+    #[test]
+    fn classic_tiff_matches_bigtiff() {
+        use crate::tests::{TempPath, write_classic_siff, write_synthetic_siff};
+        let shape = (12, 16);
+        let mut roi = Array2::<bool>::from_elem(shape, false);
+        roi.slice_mut(s![2..7, 3..12]).fill(true);
+        let mut reg = RegistrationDict::new();
+        (0..5).for_each(|frame| { reg.insert(frame, (frame as i32 - 2, 1)); });
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let classic_path = TempPath::new(&format!("classic_{:?}.siff", encoding));
+            let big_path = TempPath::new(&format!("bigtiff_{:?}.siff", encoding));
+            write_classic_siff(&classic_path, 5, shape, encoding);
+            write_synthetic_siff(&big_path, 5, shape, encoding);
+
+            let classic = SiffReader::open(&classic_path).unwrap();
+            let big = SiffReader::open(&big_path).unwrap();
+            assert!(!classic.is_bigtiff());
+            assert_eq!(classic.num_frames(), 5);
+            assert_eq!(classic.image_dims().unwrap().to_tuple(), shape);
+            assert_eq!(classic.roi_string(), "classic ROI");
+
+            let frames = classic.frames_vec();
+            assert_eq!(
+                classic.get_frames_intensity(&frames, Some(&reg)).unwrap(),
+                big.get_frames_intensity(&frames, Some(&reg)).unwrap()
+            );
+            assert_eq!(
+                classic.get_frames_tau_d(&frames, None).unwrap(),
+                big.get_frames_tau_d(&frames, None).unwrap()
+            );
+            assert_eq!(classic.get_histogram(&frames).unwrap(), big.get_histogram(&frames).unwrap());
+            assert_eq!(
+                classic.get_roi_flat(&roi.view(), &frames, None).unwrap(),
+                big.get_roi_flat(&roi.view(), &frames, None).unwrap()
+            );
+            assert_eq!(
+                classic.sum_roi_flat(&roi.view(), &frames, Some(&reg)).unwrap(),
+                big.sum_roi_flat(&roi.view(), &frames, Some(&reg)).unwrap()
+            );
+            assert_eq!(
+                classic.get_experiment_timestamps(&frames).unwrap(),
+                big.get_experiment_timestamps(&frames).unwrap()
+            );
+
+            let metadata = classic.get_frame_metadata(&[2]).unwrap().remove(0);
+            assert_eq!(metadata.metadata_string, crate::tests::synthetic_description(2));
+            // Out-of-line rationals are moved inline
+            assert_eq!(metadata.x_resolution, 1.0);
+
+            // ScanImage-style tiffs of classic files are BigTiffs
+            let tiff_path = TempPath::new(&format!("classic_{:?}.tiff", encoding));
+            let mut tiff_file = File::create(&tiff_path).unwrap();
+            classic.write_header_to_file(&mut tiff_file, &TiffMode::ScanImage).unwrap();
+            classic.write_tiff_frames_to_file(&mut tiff_file, None).unwrap();
+            drop(tiff_file);
+            let tiff = SiffReader::open(&tiff_path).unwrap();
+            assert!(tiff.is_bigtiff());
+            assert_eq!(tiff.nvfd(), classic.nvfd());
+            assert_eq!(
+                tiff.get_frames_intensity(&frames, None).unwrap(),
+                classic.get_frames_intensity(&frames, None).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn plain_tiff_stack() {
+        use crate::tests::{TempPath, write_plain_tiff};
+        let shape = (7, 10);
+        let path = TempPath::new("plain_stack.tif");
+        let intensity = write_plain_tiff(&path, 4, shape);
+
+        let reader = SiffReader::open(&path).unwrap();
+        assert!(!reader.is_scanimage());
+        assert!(!reader.is_bigtiff());
+        assert_eq!(reader.nvfd(), "");
+        assert_eq!(reader.num_frames(), 4);
+        assert_eq!(reader.image_dims().unwrap().to_tuple(), shape);
+
+        let frames = reader.frames_vec();
+        assert_eq!(reader.get_frames_intensity(&frames, None).unwrap(), intensity);
+
+        let mut roi = Array2::<bool>::from_elem(shape, false);
+        roi.slice_mut(s![1..6, 2..8]).fill(true);
+        let mut reg = RegistrationDict::new();
+        (0..4).for_each(|frame| { reg.insert(frame, (frame as i32 - 1, 2)); });
+        let registered = reader.get_frames_intensity(&frames, Some(&reg)).unwrap();
+        let masked_sum = |frames : &Array3<u16>| frames.outer_iter()
+            .map(|frame| frame.iter().zip(roi.iter())
+                .filter(|(_, &in_roi)| in_roi).map(|(&px, _)| px as u64).sum::<u64>())
+            .collect::<Array1<u64>>();
+        assert_eq!(reader.sum_roi_flat(&roi.view(), &frames, None).unwrap(), masked_sum(&intensity));
+        assert_eq!(
+            reader.sum_roi_flat(&roi.view(), &frames, Some(&reg)).unwrap(),
+            masked_sum(&registered)
+        );
+        assert_eq!(
+            reader.get_roi_flat(&roi.view(), &frames, Some(&reg)).unwrap()
+                .mapv(|x| x as u64).sum_axis(Axis(1)),
+            masked_sum(&registered)
+        );
+
+        // No metadata to read, but no panics either
+        assert!(matches!(
+            reader.get_experiment_timestamps(&frames), Err(CorrosiffError::FileFormatError)
+        ));
+        assert!(reader.get_frame_metadata(&[0]).is_err());
+        assert!(reader.get_appended_text(&frames).is_empty());
+        assert!(reader.get_frames_flim(&frames, None).is_err());
+        assert!(reader.get_histogram(&frames).is_err());
+    }
+
     #[test]
     fn test_open_siff() {
         let test_paths = get_test_paths().expect("Failed to read test paths");
@@ -4164,7 +6457,6 @@ mod tests {
     );
     }
 
-    use rand::Rng;
     /// Read several frames and test.
     #[test]
     fn read_frames(){
@@ -4200,12 +6492,11 @@ mod tests {
 
         assert_eq!(frames.index_axis(Axis(0),22).sum(), 794);
 
-        let mut rng = rand::thread_rng();
         for _ in 0..400 {
             // spot check -- they should all be the same but this makes sure no random
             // elements are wrong.
 
-            assert_eq!(frames.index_axis(Axis(0), rng.gen_range(0..40000)).sum(), 63333);
+            assert_eq!(frames.index_axis(Axis(0), rand::random_range(0..40000)).sum(), 63333);
         }
     }
 
@@ -4454,7 +6745,8 @@ mod tests {
         assert_eq!(whole_sum, image_itself_as_u64.sum_axis(Axis(1)).sum_axis(Axis(1)));
         
         // Set the ROI to false in the middle
-        roi.slice_mut(s![roi.shape()[0]/4..3*roi.shape()[0]/4, ..]).fill(false);
+        let ydim = roi.shape()[0];
+        roi.slice_mut(s![ydim/4..3*ydim/4, ..]).fill(false);
         let lesser_sum = reader.sum_roi_flat(&roi.view(), &frames, None).unwrap();
         assert!(lesser_sum.iter().all(|&x| x < whole_sum.iter().sum::<u64>()));
 
@@ -0,0 +1,856 @@
+//! The `SiffSeries` object, which reads a long acquisition
+//! that ScanImage split across several files (`name_00001.siff`,
+//! `name_00002.siff`, ...) as though it were one file.
+//!
+//! Frames are numbered globally: frame `0` is the first frame
+//! of the first file, and the frames of each subsequent file
+//! follow on from the last frame of the one before it. Every
+//! frame-reading method of `SiffReader` has a counterpart here
+//! that accepts global frame numbers (and registration keyed by
+//! global frame numbers) and stitches together the results from
+//! each file.
+//!
+//! The `_volume` methods cycle through the planes of their ROIs
+//! by the position of each frame in the `frames` argument, just as
+//! `SiffReader`'s do, even when a volume spans two files.
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use ndarray::{prelude::*, RemoveAxis, concatenate};
+use num_complex::Complex;
+
+use crate::{
+    CorrosiffError,
+    SiffReader,
+    SiffWriter,
+    RegistrationDict,
+    data::image::{Dimensions, DimensionsError},
+    metadata::FrameMetadata,
+    utils::FramesError,
+};
+
+/// Results from each file that can be joined
+/// into one result along the frame axis.
+trait Stack : Sized {
+    fn stack(parts : Vec<Self>) -> Result<Self, CorrosiffError>;
+}
+
+impl<A : Clone, D : RemoveAxis> Stack for Array<A, D> {
+    fn stack(mut parts : Vec<Self>) -> Result<Self, CorrosiffError> {
+        if parts.len() == 1 {
+            return Ok(parts.pop().unwrap());
+        }
+        let views = parts.iter().map(|part| part.view()).collect::<Vec<_>>();
+        concatenate(Axis(0), &views).map_err(
+            |_| DimensionsError::NoConsistentDimensions.into()
+        )
+    }
+}
+
+impl<S : Stack, T : Stack> Stack for (S, T) {
+    fn stack(parts : Vec<Self>) -> Result<Self, CorrosiffError> {
+        let (first, second) : (Vec<S>, Vec<T>) = parts.into_iter().unzip();
+        Ok((S::stack(first)?, T::stack(second)?))
+    }
+}
+
+/// Splits the name of a ScanImage file into the part shared by
+/// every file of its acquisition and its file number, e.g.
+/// `/data/name_00002.siff` -> (`name`, `2`).
+fn _split_file_number(path : &Path) -> Option<(String, u64)> {
+    let stem = path.file_stem()?.to_str()?;
+    let (base, number) = stem.rsplit_once('_')?;
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((base.to_string(), number.parse().ok()?))
+}
+
+/// A set of ScanImage files from the same acquisition,
+/// read as though they were a single file.
+pub struct SiffSeries {
+    _readers : Vec<SiffReader>,
+    /// Global frame number of the first frame of each file
+    _first_frames : Vec<u64>,
+}
+
+impl SiffSeries {
+    /// Opens every file of the acquisition that `filename`
+    /// belongs to -- all of the files in the same directory
+    /// named like `filename` but with a different file number
+    /// (`name_00001.siff`, `name_00002.siff`, ...) -- in order
+    /// of their file numbers. If `filename` has no file number,
+    /// the series is just `filename`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `filename` - Any one of the files of the acquisition
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let series = SiffSeries::open("/data/fly1/flashes_00003.siff")?;
+    /// // Frames from the end of the first file and the start of the second
+    /// let intensity = series.get_frames_intensity(&[998, 999, 1000, 1001], None)?;
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::IOError` - If the directory can't be listed
+    /// or a file can't be opened.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If the
+    /// files were not all acquired with the same settings (see
+    /// `FileFormat::acquisition_mismatch`).
+    pub fn open<P : AsRef<Path>>(filename : P) -> Result<Self, CorrosiffError> {
+        let filename = filename.as_ref();
+        let (base, _) = match _split_file_number(filename) {
+            Some(split) => split,
+            None => return Self::from_files(&[filename]),
+        };
+        let directory = match filename.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut files = std::fs::read_dir(&directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension() == filename.extension())
+            .filter_map(|path| {
+                let (this_base, number) = _split_file_number(&path)?;
+                (this_base == base).then_some((number, path))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        Self::from_files(&files.into_iter().map(|(_, path)| path).collect::<Vec<_>>())
+    }
+
+    /// Opens the files provided, in the order provided,
+    /// as one series.
+    ///
+    /// ## Arguments
+    ///
+    /// * `filenames` - The files of the series in acquisition order
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::IOError` - If a file can't be opened.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If there
+    /// are no files, a file is provided more than once, or they were not
+    /// all acquired with the same settings.
+    pub fn from_files<P : AsRef<Path>>(filenames : &[P]) -> Result<Self, CorrosiffError> {
+        let mut seen = HashSet::new();
+        for filename in filenames {
+            if !seen.insert(filename.as_ref().canonicalize()?) {
+                return Err(FramesError::FormatError(format!(
+                    "{} is in the series more than once", filename.as_ref().display()
+                )).into());
+            }
+        }
+        let readers = filenames.iter().map(SiffReader::open).collect::<Result<Vec<_>, _>>()?;
+        Self::from_readers(readers)
+    }
+
+    /// Combines already-opened readers into a series, in
+    /// the order provided.
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If there
+    /// are no readers or they were not all acquired with the same settings.
+    pub fn from_readers(readers : Vec<SiffReader>) -> Result<Self, CorrosiffError> {
+        let first = readers.first().ok_or(
+            FramesError::FormatError("A series needs at least one file".to_string())
+        )?;
+        for reader in readers.iter() {
+            if let Some(setting) = first.file_format().acquisition_mismatch(reader.file_format()) {
+                return Err(FramesError::FormatError(format!(
+                    "{} was acquired with a different {} from {}",
+                    reader.filename(), setting, first.filename()
+                )).into());
+            }
+        }
+
+        let mut first_frames = Vec::with_capacity(readers.len());
+        let mut num_frames = 0;
+        for reader in readers.iter() {
+            first_frames.push(num_frames);
+            num_frames += reader.num_frames() as u64;
+        }
+        Ok(SiffSeries { _readers : readers, _first_frames : first_frames })
+    }
+
+    /// The readers of each file of the series, in order.
+    pub fn readers(&self) -> &[SiffReader] {
+        &self._readers
+    }
+
+    /// The names of the files of the series, in order.
+    pub fn filenames(&self) -> Vec<String> {
+        self._readers.iter().map(SiffReader::filename).collect()
+    }
+
+    /// Total number of frames in all files
+    /// (including flyback etc).
+    pub fn num_frames(&self) -> usize {
+        self._readers.iter().map(SiffReader::num_frames).sum()
+    }
+
+    pub fn frames_vec(&self) -> Vec<u64> {
+        (0..self.num_frames() as u64).collect()
+    }
+
+    /// The shape of every frame in the series, if they all
+    /// share one.
+    pub fn image_dims(&self) -> Option<Dimensions> {
+        let dims = self._readers[0].image_dims()?;
+        self._readers.iter().all(|reader| reader.image_dims().as_ref() == Some(&dims))
+            .then_some(dims)
+    }
+
+    /// The non-varying frame data shared by every file
+    pub fn nvfd(&self) -> String {
+        self._readers[0].nvfd()
+    }
+
+    /// The mROI information of the first file
+    pub fn roi_string(&self) -> String {
+        self._readers[0].roi_string()
+    }
+
+    /// Size of the FLIM arrival time histogram in bins.
+    /// See `SiffReader::num_flim_bins`.
+    pub fn num_flim_bins(&self) -> Result<u32, CorrosiffError> {
+        self._readers[0].num_flim_bins()
+    }
+
+    /// Converts a global frame number into the index of the
+    /// file containing it and the frame number within that file.
+    ///
+    /// ## Errors
+    ///
+    /// * `DimensionsError::IncorrectFrames` - If the frame is out of bounds
+    pub fn locate_frame(&self, frame : u64) -> Result<(usize, u64), DimensionsError> {
+        if frame >= self.num_frames() as u64 {
+            return Err(DimensionsError::IncorrectFrames);
+        }
+        let file = self._first_frames.partition_point(|&first| first <= frame) - 1;
+        Ok((file, frame - self._first_frames[file]))
+    }
+
+    /// Splits the requested frames into runs of consecutive
+    /// requests from the same file, returning the file of each
+    /// run and its frames as numbered within that file.
+    fn _split_frames(&self, frames : &[u64]) -> Result<Vec<(usize, Vec<u64>)>, CorrosiffError> {
+        let mut runs : Vec<(usize, Vec<u64>)> = Vec::new();
+        for &frame in frames {
+            let (file, local) = self.locate_frame(frame)?;
+            match runs.last_mut() {
+                Some((last_file, locals)) if *last_file == file => locals.push(local),
+                _ => runs.push((file, vec![local])),
+            }
+        }
+        if runs.is_empty() {
+            runs.push((0, Vec::new()));
+        }
+        Ok(runs)
+    }
+
+    /// Calls `op` on each file's run of the requested frames, with
+    /// the frames and registration translated to that file's frame
+    /// numbers, and returns the results in the order requested.
+    fn _across_files<T, F>(
+        &self,
+        frames : &[u64],
+        registration : Option<&RegistrationDict>,
+        op : F,
+    ) -> Result<Vec<T>, CorrosiffError>
+    where F : Fn(&SiffReader, &[u64], Option<&RegistrationDict>) -> Result<T, CorrosiffError> {
+        // Same rules as `SiffReader` -- an empty registration is no registration
+        let registration = registration.filter(|reg| !reg.is_empty());
+        if let Some(reg) = registration {
+            if !frames.iter().all(|frame| reg.contains_key(frame)) {
+                return Err(FramesError::RegistrationFramesMissing.into());
+            }
+        }
+
+        self._split_frames(frames)?.into_iter().map(|(file, locals)| {
+            let first_frame = self._first_frames[file];
+            let local_registration = registration.map(|reg| {
+                locals.iter().map(|&local| (local, reg[&(local + first_frame)]))
+                    .collect::<RegistrationDict>()
+            });
+            op(&self._readers[file], &locals, local_registration.as_ref())
+        }).collect()
+    }
+
+    /// `_across_files` followed by stacking the results along
+    /// the frame axis.
+    fn _stack_across_files<T : Stack, F>(
+        &self,
+        frames : &[u64],
+        registration : Option<&RegistrationDict>,
+        op : F,
+    ) -> Result<T, CorrosiffError>
+    where F : Fn(&SiffReader, &[u64], Option<&RegistrationDict>) -> Result<T, CorrosiffError> {
+        T::stack(self._across_files(frames, registration, op)?)
+    }
+
+    /// Writes every frame of the series, in order, to a single new
+    /// `.siff` file at `path` with the NVFD and ROI string of the first
+    /// file. Each frame is copied byte for byte (only the offsets in its
+    /// IFD change), so the new file reads exactly like the series.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - Where to create the new file
+    ///
+    /// * `boundary_marker` - If provided, this text is appended to the metadata
+    /// string of the first frame of every file after the first, so that
+    /// `SiffReader::get_appended_text` on the new file reports where each
+    /// file began. If that frame already has appended text, the marker is
+    /// added to the end of it (see `FrameMetadata::metadata_str_with_appended_text`).
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let series = SiffSeries::from_files(&["trial1.siff", "trial2.siff"])?;
+    /// series.write_siff("trials.siff", Some("New trial"))?;
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::DimensionsError(DimensionsError::NoConsistentDimensions)` - If
+    /// the frames do not all have the same shape.
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If a frame
+    /// is not a `.siff` frame stored in a single strip. Checked before the
+    /// file is created.
+    ///
+    /// * `CorrosiffError::IOError` - If reading or writing fails.
+    pub fn write_siff<P : AsRef<Path>>(&self, path : P, boundary_marker : Option<&str>)
+    -> Result<(), CorrosiffError> {
+        if self.image_dims().is_none() {
+            return Err(DimensionsError::NoConsistentDimensions.into());
+        }
+        // Nothing is written unless every frame can be copied
+        self._readers.iter().try_for_each(|reader| reader._check_copy(&reader.frames_vec()))?;
+
+        let mut writer = SiffWriter::create(path, &self.nvfd(), &self.roi_string())?;
+        for (file, reader) in self._readers.iter().enumerate() {
+            reader.copy_siff_frames(
+                &mut writer,
+                &reader.frames_vec(),
+                |frame, description| match boundary_marker {
+                    Some(marker) if file > 0 && frame == 0 => {
+                        FrameMetadata::metadata_str_with_appended_text(&description, marker)
+                    },
+                    _ => description,
+                },
+            )?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /******************************
+     *
+     * Metadata methods
+     *
+     * ***************************
+     */
+
+    /// See `SiffReader::get_frame_metadata`.
+    pub fn get_frame_metadata(&self, frames : &[u64])
+    -> Result<Vec<FrameMetadata>, CorrosiffError> {
+        Ok(self._across_files(frames, None, |reader, local, _| reader.get_frame_metadata(local))?
+            .into_iter().flatten().collect())
+    }
+
+    /// Experiment time of each frame requested (seconds since the
+    /// onset of the acquisition). See `SiffReader::get_experiment_timestamps`.
+    pub fn get_experiment_timestamps(&self, frames : &[u64]) -> Result<Array1<f64>, CorrosiffError> {
+        self._stack_across_files(frames, None, |reader, local, _| reader.get_experiment_timestamps(local))
+    }
+
+    /// See `SiffReader::get_epoch_timestamps_laser`.
+    pub fn get_epoch_timestamps_laser(&self, frames : &[u64]) -> Result<Array1<u64>, CorrosiffError> {
+        self._stack_across_files(frames, None, |reader, local, _| reader.get_epoch_timestamps_laser(local))
+    }
+
+    /// See `SiffReader::get_epoch_timestamps_system`.
+    pub fn get_epoch_timestamps_system(&self, frames : &[u64]) -> Result<Array1<u64>, CorrosiffError> {
+        self._stack_across_files(frames, None, |reader, local, _| reader.get_epoch_timestamps_system(local))
+    }
+
+    /// See `SiffReader::get_epoch_timestamps_both`. Shape is `(2, frames.len())`.
+    pub fn get_epoch_timestamps_both(&self, frames : &[u64]) -> Result<Array2<u64>, CorrosiffError> {
+        // Frames are along the second axis for this one
+        let transposed : Array2<u64> = self._stack_across_files(frames, None,
+            |reader, local, _| Ok(reader.get_epoch_timestamps_both(local)?.reversed_axes())
+        )?;
+        Ok(transposed.reversed_axes())
+    }
+
+    /// See `SiffReader::get_sync_number`.
+    pub fn get_sync_number(&self, frames : &[u64]) -> Result<Array1<u64>, CorrosiffError> {
+        self._stack_across_files(frames, None, |reader, local, _| reader.get_sync_number(local))
+    }
+
+    /// All frames requested containing appended text, as
+    /// (global `frame_number`, `text`, Option<`timestamp`>).
+    /// See `SiffReader::get_appended_text`.
+    pub fn get_appended_text(&self, frames : &[u64])
+    -> Result<Vec<(u64, String, Option<f64>)>, CorrosiffError> {
+        let runs = self._split_frames(frames)?;
+        Ok(runs.into_iter().flat_map(|(file, locals)| {
+            let first_frame = self._first_frames[file];
+            self._readers[file].get_appended_text(&locals).into_iter()
+                .map(move |(frame, text, timestamp)| (frame + first_frame, text, timestamp))
+        }).collect())
+    }
+
+    /******************************
+     *
+     * Frame data methods
+     *
+     * ***************************
+     */
+
+    /// See `SiffReader::get_frames_intensity`.
+    pub fn get_frames_intensity(&self, frames : &[u64], registration : Option<&RegistrationDict>)
+    -> Result<Array3<u16>, CorrosiffError> {
+        self._stack_across_files(frames, registration, SiffReader::get_frames_intensity)
+    }
+
+    /// See `SiffReader::get_frames_flim`.
+    pub fn get_frames_flim(&self, frames : &[u64], registration : Option<&RegistrationDict>)
+    -> Result<(Array3<f64>, Array3<u16>), CorrosiffError> {
+        self._stack_across_files(frames, registration, SiffReader::get_frames_flim)
+    }
+
+    /// See `SiffReader::get_frames_phasor`.
+    pub fn get_frames_phasor(&self, frames : &[u64], registration : Option<&RegistrationDict>)
+    -> Result<(Array3<Complex<f64>>, Array3<u16>), CorrosiffError> {
+        self._stack_across_files(frames, registration, SiffReader::get_frames_phasor)
+    }
+
+    /// See `SiffReader::get_frames_tau_d`.
+    pub fn get_frames_tau_d(&self, frames : &[u64], registration : Option<&RegistrationDict>)
+    -> Result<Array4<u16>, CorrosiffError> {
+        self._stack_across_files(frames, registration, SiffReader::get_frames_tau_d)
+    }
+
+    /// See `SiffReader::get_histogram`.
+    pub fn get_histogram(&self, frames : &[u64]) -> Result<Array2<u64>, CorrosiffError> {
+        self._stack_across_files(frames, None, |reader, local, _| reader.get_histogram(local))
+    }
+
+    /// See `SiffReader::get_histogram_mask`.
+    pub fn get_histogram_mask(
+        &self, frames : &[u64], mask : &ArrayView2<bool>, registration : Option<&RegistrationDict>
+    ) -> Result<Array2<u64>, CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.get_histogram_mask(local, mask, reg)
+        )
+    }
+
+    /// See `SiffReader::get_histogram_mask_volume`.
+    pub fn get_histogram_mask_volume(
+        &self, frames : &[u64], mask : &ArrayView3<bool>, registration : Option<&RegistrationDict>
+    ) -> Result<Array2<u64>, CorrosiffError> {
+        let mut array = Array2::<u64>::zeros((frames.len(), self.num_flim_bins()? as usize));
+        for (plane, plane_mask) in mask.axis_iter(Axis(0)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, mask.dim().0);
+            array.slice_mut(s![plane..;mask.dim().0, ..]).assign(
+                &self.get_histogram_mask(&plane_frames, &plane_mask, registration)?
+            );
+        }
+        Ok(array)
+    }
+
+    /******************************
+     *
+     * ROI methods
+     *
+     * ***************************
+     */
+
+    /// See `SiffReader::get_roi_flat`.
+    pub fn get_roi_flat(
+        &self, roi : &ArrayView2<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<Array2<u16>, CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.get_roi_flat(roi, local, reg)
+        )
+    }
+
+    /// See `SiffReader::get_roi_volume`. Each row is one volume,
+    /// so any frames after the last complete volume are ignored.
+    pub fn get_roi_volume(
+        &self, roi : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<Array2<u16>, CorrosiffError> {
+        Ok(_join_planes(roi, frames, |plane_roi, plane_frames| {
+            self.get_roi_flat(plane_roi, plane_frames, registration)
+        })?.remove(0))
+    }
+
+    /// See `SiffReader::get_roi_flim_flat`.
+    pub fn get_roi_flim_flat(
+        &self, roi : &ArrayView2<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<f64>, Array2<u16>), CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.get_roi_flim_flat(roi, local, reg)
+        )
+    }
+
+    /// See `SiffReader::get_roi_flim_volume`. Each row is one volume,
+    /// so any frames after the last complete volume are ignored.
+    pub fn get_roi_flim_volume(
+        &self, roi : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<f64>, Array2<u16>), CorrosiffError> {
+        let lifetime = _join_planes(roi, frames, |plane_roi, plane_frames| {
+            Ok(self.get_roi_flim_flat(plane_roi, plane_frames, registration)?.0)
+        })?.remove(0);
+        let intensity = self.get_roi_volume(roi, frames, registration)?;
+        Ok((lifetime, intensity))
+    }
+
+    /// See `SiffReader::get_roi_phasor_flat`.
+    pub fn get_roi_phasor_flat(
+        &self, roi : &ArrayView2<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<Complex<f64>>, Array2<u16>), CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.get_roi_phasor_flat(roi, local, reg)
+        )
+    }
+
+    /// See `SiffReader::get_roi_phasor_volume`. Each row is one volume,
+    /// so any frames after the last complete volume are ignored.
+    pub fn get_roi_phasor_volume(
+        &self, roi : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<Complex<f64>>, Array2<u16>), CorrosiffError> {
+        let phasor = _join_planes(roi, frames, |plane_roi, plane_frames| {
+            Ok(self.get_roi_phasor_flat(plane_roi, plane_frames, registration)?.0)
+        })?.remove(0);
+        let intensity = self.get_roi_volume(roi, frames, registration)?;
+        Ok((phasor, intensity))
+    }
+
+    /// See `SiffReader::sum_roi_flat`.
+    pub fn sum_roi_flat(
+        &self, roi : &ArrayView2<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<Array1<u64>, CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.sum_roi_flat(roi, local, reg)
+        )
+    }
+
+    /// See `SiffReader::sum_roi_volume`.
+    pub fn sum_roi_volume(
+        &self, roi : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<Array1<u64>, CorrosiffError> {
+        let mut array = Array1::<u64>::zeros(frames.len());
+        for (plane, plane_roi) in roi.axis_iter(Axis(0)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, roi.dim().0);
+            array.slice_mut(s![plane..;roi.dim().0]).assign(
+                &self.sum_roi_flat(&plane_roi, &plane_frames, registration)?
+            );
+        }
+        Ok(array)
+    }
+
+    /// See `SiffReader::sum_rois_flat`.
+    pub fn sum_rois_flat(
+        &self, rois : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<Array2<u64>, CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.sum_rois_flat(rois, local, reg)
+        )
+    }
+
+    /// See `SiffReader::sum_rois_volume`.
+    pub fn sum_rois_volume(
+        &self, rois : &ArrayView4<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<Array2<u64>, CorrosiffError> {
+        let num_planes = rois.dim().1;
+        let mut array = Array2::<u64>::zeros((frames.len(), rois.dim().0));
+        for (plane, plane_rois) in rois.axis_iter(Axis(1)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, num_planes);
+            array.slice_mut(s![plane..;num_planes, ..]).assign(
+                &self.sum_rois_flat(&plane_rois, &plane_frames, registration)?
+            );
+        }
+        Ok(array)
+    }
+
+    /// See `SiffReader::sum_roi_flim_flat`.
+    pub fn sum_roi_flim_flat(
+        &self, roi : &ArrayView2<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array1<f64>, Array1<u64>), CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.sum_roi_flim_flat(roi, local, reg)
+        )
+    }
+
+    /// See `SiffReader::sum_roi_flim_volume`.
+    pub fn sum_roi_flim_volume(
+        &self, roi : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array1<f64>, Array1<u64>), CorrosiffError> {
+        let mut lifetime = Array1::<f64>::zeros(frames.len());
+        let mut intensity = Array1::<u64>::zeros(frames.len());
+        for (plane, plane_roi) in roi.axis_iter(Axis(0)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, roi.dim().0);
+            let (plane_lifetime, plane_intensity) = self.sum_roi_flim_flat(
+                &plane_roi, &plane_frames, registration
+            )?;
+            lifetime.slice_mut(s![plane..;roi.dim().0]).assign(&plane_lifetime);
+            intensity.slice_mut(s![plane..;roi.dim().0]).assign(&plane_intensity);
+        }
+        Ok((lifetime, intensity))
+    }
+
+    /// See `SiffReader::sum_rois_flim_flat`.
+    pub fn sum_rois_flim_flat(
+        &self, rois : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<f64>, Array2<u64>), CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.sum_rois_flim_flat(rois, local, reg)
+        )
+    }
+
+    /// See `SiffReader::sum_rois_flim_volume`.
+    pub fn sum_rois_flim_volume(
+        &self, rois : &ArrayView4<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<f64>, Array2<u64>), CorrosiffError> {
+        let num_planes = rois.dim().1;
+        let mut lifetime = Array2::<f64>::zeros((frames.len(), rois.dim().0));
+        let mut intensity = Array2::<u64>::zeros((frames.len(), rois.dim().0));
+        for (plane, plane_rois) in rois.axis_iter(Axis(1)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, num_planes);
+            let (plane_lifetime, plane_intensity) = self.sum_rois_flim_flat(
+                &plane_rois, &plane_frames, registration
+            )?;
+            lifetime.slice_mut(s![plane..;num_planes, ..]).assign(&plane_lifetime);
+            intensity.slice_mut(s![plane..;num_planes, ..]).assign(&plane_intensity);
+        }
+        Ok((lifetime, intensity))
+    }
+
+    /// See `SiffReader::sum_roi_phasor_flat`.
+    pub fn sum_roi_phasor_flat(
+        &self, roi : &ArrayView2<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array1<Complex<f64>>, Array1<u64>), CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.sum_roi_phasor_flat(roi, local, reg)
+        )
+    }
+
+    /// See `SiffReader::sum_roi_phasor_volume`.
+    pub fn sum_roi_phasor_volume(
+        &self, roi : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array1<Complex<f64>>, Array1<u64>), CorrosiffError> {
+        let mut phasor = Array1::<Complex<f64>>::zeros(frames.len());
+        let mut intensity = Array1::<u64>::zeros(frames.len());
+        for (plane, plane_roi) in roi.axis_iter(Axis(0)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, roi.dim().0);
+            let (plane_phasor, plane_intensity) = self.sum_roi_phasor_flat(
+                &plane_roi, &plane_frames, registration
+            )?;
+            phasor.slice_mut(s![plane..;roi.dim().0]).assign(&plane_phasor);
+            intensity.slice_mut(s![plane..;roi.dim().0]).assign(&plane_intensity);
+        }
+        Ok((phasor, intensity))
+    }
+
+    /// See `SiffReader::sum_rois_phasor_flat`.
+    pub fn sum_rois_phasor_flat(
+        &self, rois : &ArrayView3<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<Complex<f64>>, Array2<u64>), CorrosiffError> {
+        self._stack_across_files(frames, registration,
+            |reader, local, reg| reader.sum_rois_phasor_flat(rois, local, reg)
+        )
+    }
+
+    /// See `SiffReader::sum_rois_phasor_volume`.
+    pub fn sum_rois_phasor_volume(
+        &self, rois : &ArrayView4<bool>, frames : &[u64], registration : Option<&RegistrationDict>
+    ) -> Result<(Array2<Complex<f64>>, Array2<u64>), CorrosiffError> {
+        let num_planes = rois.dim().1;
+        let mut phasor = Array2::<Complex<f64>>::zeros((frames.len(), rois.dim().0));
+        let mut intensity = Array2::<u64>::zeros((frames.len(), rois.dim().0));
+        for (plane, plane_rois) in rois.axis_iter(Axis(1)).enumerate() {
+            let plane_frames = _plane_frames(frames, plane, num_planes);
+            let (plane_phasor, plane_intensity) = self.sum_rois_phasor_flat(
+                &plane_rois, &plane_frames, registration
+            )?;
+            phasor.slice_mut(s![plane..;num_planes, ..]).assign(&plane_phasor);
+            intensity.slice_mut(s![plane..;num_planes, ..]).assign(&plane_intensity);
+        }
+        Ok((phasor, intensity))
+    }
+}
+
+/// The frames requested at positions `plane`, `plane + num_planes`, ...
+/// i.e. the frames that the `_volume` methods pair with that plane.
+fn _plane_frames(frames : &[u64], plane : usize, num_planes : usize) -> Vec<u64> {
+    frames.iter().skip(plane).step_by(num_planes.max(1)).cloned().collect()
+}
+
+/// Builds the `(volumes, pixels)` array of a `get_roi_*_volume` method
+/// from its `get_roi_*_flat` counterpart: the pixels of each volume are
+/// the ROI pixels of each plane in turn. Returned in a `Vec` of length
+/// one so that the element type needs only `Clone`.
+fn _join_planes<A, F>(roi : &ArrayView3<bool>, frames : &[u64], flat : F)
+-> Result<Vec<Array2<A>>, CorrosiffError>
+where A : Clone, F : Fn(&ArrayView2<bool>, &[u64]) -> Result<Array2<A>, CorrosiffError> {
+    let num_planes = roi.dim().0;
+    let num_volumes = frames.len() / num_planes.max(1);
+    let planes = roi.axis_iter(Axis(0)).enumerate().map(|(plane, plane_roi)| {
+        let plane_frames = _plane_frames(&frames[..num_volumes * num_planes], plane, num_planes);
+        flat(&plane_roi, &plane_frames)
+    }).collect::<Result<Vec<_>, _>>()?;
+    let views = planes.iter().map(|plane| plane.view()).collect::<Vec<_>>();
+    Ok(vec![concatenate(Axis(1), &views).map_err(
+        |_| CorrosiffError::DimensionsError(DimensionsError::NoConsistentDimensions)
+    )?])
+}
+
+impl Display for SiffSeries {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "SiffSeries: {} files\n{} frames",
+            self._readers.len(),
+            self.num_frames(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SiffEncoding, SiffWriter, tests::{TempPath, write_synthetic_siff}};
+
+    #[test]
+    fn series_matches_single_file() {
+        let shape = (12, 16);
+        let mut roi = Array2::<bool>::from_elem(shape, false);
+        roi.slice_mut(s![1..10, 5..14]).fill(true);
+        let mut rois = Array3::<bool>::from_elem((2, shape.0, shape.1), false);
+        rois.slice_mut(s![0, 2..6, ..]).fill(true);
+        rois.slice_mut(s![1, .., 3..9]).fill(true);
+        let mut volume_roi = Array3::<bool>::from_elem((3, shape.0, shape.1), false);
+        volume_roi.slice_mut(s![0, 1..4, 2..8]).fill(true);
+        volume_roi.slice_mut(s![1, 5..9, ..]).fill(true);
+        volume_roi.slice_mut(s![2, .., 10..12]).fill(true);
+
+        for encoding in [SiffEncoding::Raw, SiffEncoding::Compressed] {
+            let whole_path = TempPath::new(&format!("series_whole_{:?}.siff", encoding));
+            write_synthetic_siff(&whole_path, 9, shape, encoding);
+            let whole = SiffReader::open(&whole_path).unwrap();
+
+            // Split into files of 4, 3, and 2 frames
+            let parts = [vec![0, 1, 2, 3], vec![4, 5, 6], vec![7, 8]];
+            let paths = parts.iter().enumerate().map(|(n, frames)| {
+                let path = TempPath::new(&format!("series_{:?}_{:05}.siff", encoding, n + 1));
+                let writer = SiffWriter::create(&path, &whole.nvfd(), &whole.roi_string()).unwrap();
+                whole.write_siff_frames(writer, Some(frames), encoding).unwrap();
+                path
+            }).collect::<Vec<_>>();
+
+            // Any file finds the rest
+            let series = SiffSeries::open(&paths[1]).unwrap();
+            assert_eq!(series.readers().len(), 3);
+            assert_eq!(series.num_frames(), 9);
+            assert_eq!(series.locate_frame(5).unwrap(), (1, 1));
+            assert!(series.locate_frame(9).is_err());
+            assert_eq!(series.image_dims(), whole.image_dims());
+
+            let frames = [8, 2, 3, 4, 7, 0, 5, 6];
+            let registration = frames.iter().map(|&frame| (frame, (frame as i32 - 4, 2 * frame as i32)))
+                .collect::<RegistrationDict>();
+
+            assert_eq!(
+                series.get_frames_intensity(&frames, Some(&registration)).unwrap(),
+                whole.get_frames_intensity(&frames, Some(&registration)).unwrap()
+            );
+            assert_eq!(
+                series.get_frames_tau_d(&frames, None).unwrap(),
+                whole.get_frames_tau_d(&frames, None).unwrap()
+            );
+            assert_eq!(series.get_histogram(&frames).unwrap(), whole.get_histogram(&frames).unwrap());
+            assert_eq!(
+                series.get_experiment_timestamps(&frames).unwrap(),
+                whole.get_experiment_timestamps(&frames).unwrap()
+            );
+            assert_eq!(
+                series.get_epoch_timestamps_both(&frames).unwrap(),
+                whole.get_epoch_timestamps_both(&frames).unwrap()
+            );
+            assert_eq!(series.get_appended_text(&frames).unwrap(), whole.get_appended_text(&frames));
+
+            let (lifetime, intensity) = series.sum_rois_flim_flat(&rois.view(), &frames, Some(&registration)).unwrap();
+            let (whole_lifetime, whole_intensity) = whole.sum_rois_flim_flat(&rois.view(), &frames, Some(&registration)).unwrap();
+            assert_eq!(intensity, whole_intensity);
+            assert_eq!(lifetime.mapv(f64::to_bits), whole_lifetime.mapv(f64::to_bits));
+
+            assert_eq!(
+                series.get_roi_flat(&roi.view(), &frames, None).unwrap(),
+                whole.get_roi_flat(&roi.view(), &frames, None).unwrap()
+            );
+
+            // Volumes that straddle files
+            assert_eq!(
+                series.sum_roi_volume(&volume_roi.view(), &frames, Some(&registration)).unwrap(),
+                whole.sum_roi_volume(&volume_roi.view(), &frames, Some(&registration)).unwrap()
+            );
+            assert_eq!(
+                series.get_roi_volume(&volume_roi.view(), &frames, None).unwrap(),
+                whole.get_roi_volume(&volume_roi.view(), &frames, None).unwrap()
+            );
+            let (phasor, intensity) = series.sum_roi_phasor_volume(&volume_roi.view(), &frames, None).unwrap();
+            let (whole_phasor, whole_intensity) = whole.sum_roi_phasor_volume(&volume_roi.view(), &frames, None).unwrap();
+            assert_eq!(intensity, whole_intensity);
+            assert_eq!(phasor.mapv(|z| z.re.to_bits()), whole_phasor.mapv(|z| z.re.to_bits()));
+
+            // Registration must cover every frame requested
+            let mut partial = registration.clone();
+            partial.remove(&7);
+            assert!(series.get_frames_intensity(&frames, Some(&partial)).is_err());
+        }
+
+        // Files from different acquisitions can't be combined
+        let first_path = TempPath::new("series_mismatch.siff");
+        write_synthetic_siff(&first_path, 2, shape, SiffEncoding::Raw);
+        let first = SiffReader::open(&first_path).unwrap();
+        let other_path = TempPath::new("series_other.siff");
+        let writer = SiffWriter::create(
+            &other_path, &first.nvfd().replace("Tau_bins = 62", "Tau_bins = 31"), &first.roi_string()
+        ).unwrap();
+        first.write_siff_frames(writer, None, SiffEncoding::Raw).unwrap();
+        assert!(matches!(
+            SiffSeries::from_files(&[&first_path, &other_path]),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+        // ... but files that only differ in how they were saved can
+        let renamed_path = TempPath::new("series_renamed.siff");
+        let writer = SiffWriter::create(
+            &renamed_path,
+            &format!("{}SI.hScan2D.logFileCounter = 2\nSI.hScan2D.logFileStem = 'other'\n", first.nvfd()),
+            &first.roi_string()
+        ).unwrap();
+        first.write_siff_frames(writer, None, SiffEncoding::Raw).unwrap();
+        assert_eq!(SiffSeries::from_files(&[&first_path, &renamed_path]).unwrap().num_frames(), 4);
+
+        // The same file can't be in a series twice
+        assert!(matches!(
+            SiffSeries::from_files(&[&first_path, &renamed_path, &first_path]),
+            Err(CorrosiffError::FramesError(FramesError::FormatError(_)))
+        ));
+    }
+}
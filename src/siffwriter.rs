@@ -0,0 +1,503 @@
+//! The `SiffWriter` object, the counterpart to
+//! `SiffReader`, which produces new `.siff` files
+//! frame by frame.
+//!
+//! Files are always written in the BigTiff format
+//! with the same layout ScanImage uses, so anything
+//! written here can be read back by `SiffReader` (or
+//! by any other `.siff` reader).
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+use ndarray::prelude::*;
+
+use crate::{
+    CorrosiffError,
+    data::image::{Dimensions, DimensionsError},
+    tiff::{
+        BigTag, FileFormat, Tag, TiffTagID, TiffTagType,
+        image_tags, link_ifd, scanimage_tags, write_bigtiff_page,
+    },
+    utils::FramesError,
+};
+
+use crate::data::image::photons::compress_photons;
+pub use crate::data::image::photons::pack_photon;
+
+/// The two ways photons can be stored in a `.siff` frame,
+/// as reflected by the value of its `Siff` tag.
+///
+/// ## Variants
+///
+/// * `Raw` - One `u64` per photon containing its pixel and
+/// arrival time (`Siff` tag 0).
+///
+/// * `Compressed` - A `u16` intensity image followed by the
+/// `u16` arrival time of every photon, sorted by pixel (`Siff` tag 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiffEncoding {
+    Raw,
+    Compressed,
+}
+
+impl SiffEncoding {
+    /// Parses a string slice (`"raw"` or `"compressed"`) to
+    /// produce a `SiffEncoding`. Useful for argument parsing
+    /// from the command line.
+    pub fn from_string_slice(str : &str) -> Result<SiffEncoding, CorrosiffError> {
+        match str.to_lowercase().as_str() {
+            "raw" => Ok(SiffEncoding::Raw),
+            "compressed" => Ok(SiffEncoding::Compressed),
+            _ => Err(CorrosiffError::IOError(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid SiffEncoding"
+            ))),
+        }
+    }
+}
+
+/// A struct for writing `.siff` files one frame
+/// at a time. The header (NVFD and ROI string) is
+/// written when the `SiffWriter` is created, and every
+/// frame written is appended to the IFD chain, so the
+/// file on disk is a valid `.siff` after each frame.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// use corrosiff::{SiffWriter, siffwriter::pack_photon};
+///
+/// let mut writer = SiffWriter::create(
+///     "simulated.siff",
+///     "SI.hScan2D.Tau_bins = 627\n",
+///     ""
+/// )?;
+///
+/// let photons = vec![pack_photon(0, 0, 100), pack_photon(5, 3, 250)];
+/// writer.write_frame_raw(&photons, (128, 128), "frameNumbers = 1\n")?;
+/// writer.finish()?;
+/// ```
+pub struct SiffWriter<W : Write + Seek> {
+    _writer : W,
+    file_format : FileFormat,
+    _last_ifd_pointer : Option<u64>,
+    _num_frames : u64,
+}
+
+impl SiffWriter<BufWriter<File>> {
+    /// Creates a new `.siff` file at `path` (overwriting
+    /// any existing file) and writes its header.
+    ///
+    /// ## Arguments
+    ///
+    /// * `path` - Where to create the file
+    ///
+    /// * `nvfd` - The non-varying-frame-data string describing
+    /// the acquisition (e.g. from `SiffReader::nvfd`). Should contain
+    /// the `Tau_bins` field for the file to be read as FLIM data.
+    ///
+    /// * `roi_string` - The MROI data string (may be empty)
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::IOError` - If the file cannot be created
+    /// or the header cannot be written.
+    pub fn create<P : AsRef<Path>>(path : P, nvfd : &str, roi_string : &str)
+    -> Result<Self, CorrosiffError> {
+        let file = File::create(path).map_err(CorrosiffError::IOError)?;
+        SiffWriter::new(BufWriter::new(file), nvfd, roi_string)
+    }
+}
+
+impl<W : Write + Seek> SiffWriter<W> {
+
+    /// Wraps a writer pointing to the _start_ of an empty
+    /// file or buffer and writes the `.siff` header into it.
+    ///
+    /// ## Arguments
+    ///
+    /// * `writer` - Any object implementing `Write` and `Seek`,
+    /// e.g. a `BufWriter<File>` or a `Cursor<Vec<u8>>`.
+    ///
+    /// * `nvfd` - The non-varying-frame-data string
+    ///
+    /// * `roi_string` - The MROI data string (may be empty)
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let mut writer = SiffWriter::new(Cursor::new(Vec::new()), nvfd, "")?;
+    /// ```
+    pub fn new(mut writer : W, nvfd : &str, roi_string : &str) -> Result<Self, CorrosiffError> {
+        let file_format = FileFormat::new_siff(nvfd, roi_string);
+        file_format.write(&mut writer)?;
+        Ok(SiffWriter {
+            _writer : writer,
+            file_format,
+            _last_ifd_pointer : None,
+            _num_frames : 0,
+        })
+    }
+
+    /// Number of frames written so far
+    pub fn num_frames(&self) -> u64 {
+        self._num_frames
+    }
+
+    /// Writes a raw-format frame: one `u64` per photon, each
+    /// packed as in `pack_photon`. The photons are written in
+    /// the order provided.
+    ///
+    /// ## Arguments
+    ///
+    /// * `photons` - The photons of the frame, packed into `u64`s
+    ///
+    /// * `shape` - The shape of the frame `(y, x)`
+    ///
+    /// * `description` - The frame-specific metadata string (frame number,
+    /// timestamps, appended text, etc.)
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If any
+    /// photon lies outside of the frame.
+    ///
+    /// * `CorrosiffError::IOError` - If writing fails.
+    ///
+    /// ## See also
+    ///
+    /// * `write_frame_compressed` - for writing frames in the compressed
+    /// `.siff` format.
+    pub fn write_frame_raw(
+        &mut self,
+        photons : &[u64],
+        shape : (usize, usize),
+        description : &str,
+    ) -> Result<(), CorrosiffError> {
+        let (ydim, xdim) = shape;
+        if let Some(photon) = photons.iter().find(
+            |&&photon| (photon >> 48) as usize >= ydim
+            || ((photon >> 32) & 0xFFFF) as usize >= xdim
+        ) {
+            return Err(FramesError::FormatError(format!(
+                "Photon at ({}, {}) lies outside of a frame with shape ({}, {})",
+                photon >> 48, (photon >> 32) & 0xFFFF, ydim, xdim
+            )).into());
+        }
+
+        self.write_frame(
+            shape,
+            0,
+            description.as_bytes(),
+            &[],
+            bytemuck::cast_slice(photons),
+        )
+    }
+
+    /// Writes a compressed-format frame: an intensity image
+    /// followed by the arrival time of every photon, sorted
+    /// by pixel (in row-major order) so that the first
+    /// `intensity[[0,0]]` arrival times belong to the first pixel,
+    /// the next `intensity[[0,1]]` to the second, etc.
+    ///
+    /// ## Arguments
+    ///
+    /// * `intensity` - The photon counts of each pixel
+    ///
+    /// * `arrival_times` - The arrival time bin of each photon
+    ///
+    /// * `description` - The frame-specific metadata string
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If the
+    /// number of arrival times does not match the total intensity.
+    ///
+    /// * `CorrosiffError::IOError` - If writing fails.
+    pub fn write_frame_compressed(
+        &mut self,
+        intensity : &ArrayView2<u16>,
+        arrival_times : &[u16],
+        description : &str,
+    ) -> Result<(), CorrosiffError> {
+        let total_photons = intensity.iter().map(|&x| x as usize).sum::<usize>();
+        if total_photons != arrival_times.len() {
+            return Err(FramesError::FormatError(format!(
+                "Intensity image contains {} photons but {} arrival times were provided",
+                total_photons, arrival_times.len()
+            )).into());
+        }
+
+        let intensity_data = intensity.iter().cloned().collect::<Vec<u16>>();
+        self.write_frame(
+            intensity.dim(),
+            1,
+            description.as_bytes(),
+            bytemuck::cast_slice(&intensity_data),
+            bytemuck::cast_slice(arrival_times),
+        )
+    }
+
+    /// Writes a frame from a list of packed photons in either
+    /// encoding. Compressed frames sort the photons by pixel,
+    /// so the photon order of a raw frame is not preserved,
+    /// but the frame's data (e.g. its `tau_d` array) is.
+    ///
+    /// ## Arguments
+    ///
+    /// * `photons` - The photons of the frame, packed as in `pack_photon`
+    ///
+    /// * `shape` - The shape of the frame `(y, x)`
+    ///
+    /// * `encoding` - Which `SiffEncoding` to store the frame in
+    ///
+    /// * `description` - The frame-specific metadata string
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If any
+    /// photon lies outside of the frame or cannot be stored in the
+    /// compressed format (see `compress_photons`).
+    ///
+    /// * `CorrosiffError::IOError` - If writing fails.
+    pub fn write_frame_photons(
+        &mut self,
+        photons : &[u64],
+        shape : (usize, usize),
+        encoding : SiffEncoding,
+        description : &str,
+    ) -> Result<(), CorrosiffError> {
+        match encoding {
+            SiffEncoding::Raw => self.write_frame_raw(photons, shape, description),
+            SiffEncoding::Compressed => {
+                let (intensity, arrival_times) = compress_photons(photons, shape)?;
+                self.write_frame_compressed(&intensity.view(), &arrival_times, description)
+            },
+        }
+    }
+
+    /// Flushes all data written and returns the underlying
+    /// writer.
+    pub fn finish(mut self) -> Result<W, CorrosiffError> {
+        self._writer.flush()?;
+        Ok(self._writer)
+    }
+
+    /// Appends a frame to the end of the file, then points
+    /// the previous IFD at it.
+    fn write_frame(
+        &mut self,
+        shape : (usize, usize),
+        siff_compress : u16,
+        description : &[u8],
+        pre_strip : &[u8],
+        strip : &[u8],
+    ) -> Result<(), CorrosiffError> {
+        if shape.0 > u16::MAX as usize || shape.1 > u16::MAX as usize {
+            return Err(DimensionsError::MismatchedDimensions {
+                required : Dimensions::new(u16::MAX as u64, u16::MAX as u64),
+                requested : Dimensions::from_tuple(shape),
+            }.into());
+        }
+
+        let tags = self.frame_tags(shape, siff_compress);
+        self.append_page(&tags, description, pre_strip, strip)
+    }
+
+    /// Appends a page copied from another `.siff` file without
+    /// decoding its frame: `tags` are the tags of its IFD, followed
+    /// by its metadata string, the data preceding its strip (the
+    /// intensity image of a compressed frame), and the strip itself.
+    /// The tags pointing into the header of the original file (the
+    /// NVFD and ROI string) are pointed at this file's header instead,
+    /// and the description and strip tags are recomputed.
+    ///
+    /// ## Errors
+    ///
+    /// * `CorrosiffError::FramesError(FramesError::FormatError)` - If
+    /// any other tag's values are stored outside of the IFD, where they
+    /// would not be copied.
+    ///
+    /// * `CorrosiffError::IOError` - If writing fails.
+    pub (crate) fn copy_page(
+        &mut self,
+        tags : &[BigTag],
+        description : &[u8],
+        pre_strip : &[u8],
+        strip : &[u8],
+    ) -> Result<(), CorrosiffError> {
+        let header_tags = scanimage_tags(&self.file_format);
+        let tags = tags.iter().map(|tag| {
+            match tag.tag() {
+                TiffTagID::ImageDescription | TiffTagID::StripOffsets
+                | TiffTagID::StripByteCounts => Ok(tag.clone()),
+                id => match header_tags.iter().find(|header_tag| header_tag.tag() == id) {
+                    Some(header_tag) if !tag.is_inline() => Ok(header_tag.clone()),
+                    _ if tag.is_inline() => Ok(tag.clone()),
+                    _ => Err(FramesError::FormatError(format!(
+                        "Tag {} is stored outside of its IFD and can't be copied", id
+                    ))),
+                },
+            }
+        }).collect::<Result<Vec<_>, _>>()?;
+        self.append_page(&tags, description, pre_strip, strip)
+    }
+
+    /// Writes a page at the end of the file, then points
+    /// the previous IFD at it.
+    fn append_page(
+        &mut self,
+        tags : &[BigTag],
+        description : &[u8],
+        pre_strip : &[u8],
+        strip : &[u8],
+    ) -> Result<(), CorrosiffError> {
+        let start_of_ifd = self._writer.seek(std::io::SeekFrom::End(0))?;
+        let next_ifd_pointer = write_bigtiff_page(
+            &mut self._writer,
+            tags,
+            description,
+            pre_strip,
+            strip,
+        )?;
+
+        if let Some(last_pointer) = self._last_ifd_pointer {
+            link_ifd(&mut self._writer, last_pointer, start_of_ifd)?;
+        } else {
+            debug_assert_eq!(start_of_ifd, self.file_format.first_ifd_val());
+        }
+        self._last_ifd_pointer = Some(next_ifd_pointer);
+        self._num_frames += 1;
+        Ok(())
+    }
+
+    /// The tags ScanImage writes for every `.siff` frame
+    /// (less the ones computed by `write_bigtiff_page`)
+    fn frame_tags(&self, shape : (usize, usize), siff_compress : u16) -> Vec<BigTag> {
+        let bits_per_sample = match siff_compress { 0 => 64, _ => 16 };
+        let mut tags = image_tags(shape, bits_per_sample, 1);
+        tags.extend(scanimage_tags(&self.file_format));
+        tags.push(BigTag::new(TiffTagID::Siff, TiffTagType::Short, 1, siff_compress as u64));
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::{
+        SiffReader,
+        tests::{synthetic_photons, synthetic_description, SYNTHETIC_NVFD, TempPath},
+    };
+
+    /// Builds a `tau_d` array from lists of photons directly
+    fn expected_tau_d(photons : &[Vec<u64>], shape : (usize, usize), n_bins : usize)
+    -> Array4<u16> {
+        let mut array = Array4::<u16>::zeros((photons.len(), shape.0, shape.1, n_bins));
+        photons.iter().enumerate().for_each(|(frame, photons)| {
+            photons.iter().for_each(|&photon| {
+                array[[
+                    frame,
+                    (photon >> 48) as usize,
+                    ((photon >> 32) & 0xFFFF) as usize,
+                    (photon & 0xFFFF_FFFF) as usize,
+                ]] += 1;
+            })
+        });
+        array
+    }
+
+    #[test]
+    fn raw_round_trip() {
+        let path = TempPath::new("siffwriter_raw.siff");
+        let shape = (16, 24);
+        let photons = (0..5).map(|frame| synthetic_photons(frame, shape, 64))
+            .collect::<Vec<_>>();
+
+        let mut writer = SiffWriter::create(&path, SYNTHETIC_NVFD, "some ROI").unwrap();
+        photons.iter().enumerate().for_each(|(frame, photons)| {
+            writer.write_frame_raw(photons, shape, &synthetic_description(frame)).unwrap();
+        });
+        assert_eq!(writer.num_frames(), 5);
+        writer.finish().unwrap();
+
+        let reader = SiffReader::open(&path).unwrap();
+        assert_eq!(reader.num_frames(), 5);
+        assert_eq!(reader.nvfd(), SYNTHETIC_NVFD);
+        assert_eq!(reader.roi_string(), "some ROI");
+        assert_eq!(reader.image_dims().unwrap().to_tuple(), shape);
+        assert_eq!(reader.num_flim_bins().unwrap(), 64);
+
+        let tau_d = reader.get_frames_tau_d(&reader.frames_vec(), None).unwrap();
+        assert_eq!(tau_d, expected_tau_d(&photons, shape, 64));
+
+        let intensity = reader.get_frames_intensity(&reader.frames_vec(), None).unwrap();
+        assert_eq!(intensity, tau_d.sum_axis(Axis(3)));
+
+        let timestamps = reader.get_experiment_timestamps(&reader.frames_vec()).unwrap();
+        assert_eq!(timestamps, array![0.0, 0.1, 0.2, 0.3, 0.4]);
+
+        let metadata = reader.get_frame_metadata(&[3]).unwrap();
+        assert_eq!(metadata[0].siff_compress, Some(0));
+        assert_eq!(metadata[0].metadata_string, synthetic_description(3));
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        let path = TempPath::new("siffwriter_compressed.siff");
+        let shape = (8, 12);
+        let photons = (0..4).map(|frame| synthetic_photons(frame, shape, 64))
+            .collect::<Vec<_>>();
+
+        let mut writer = SiffWriter::create(&path, SYNTHETIC_NVFD, "").unwrap();
+        photons.iter().enumerate().for_each(|(frame, photons)| {
+            let mut intensity = Array2::<u16>::zeros(shape);
+            let mut by_pixel = vec![Vec::<u16>::new(); shape.0 * shape.1];
+            photons.iter().for_each(|&photon| {
+                let (y, x) = ((photon >> 48) as usize, ((photon >> 32) & 0xFFFF) as usize);
+                intensity[[y, x]] += 1;
+                by_pixel[y * shape.1 + x].push((photon & 0xFFFF) as u16);
+            });
+            let arrivals = by_pixel.concat();
+            writer.write_frame_compressed(
+                &intensity.view(), &arrivals, &synthetic_description(frame)
+            ).unwrap();
+        });
+        writer.finish().unwrap();
+
+        let reader = SiffReader::open(&path).unwrap();
+        assert_eq!(reader.num_frames(), 4);
+        let tau_d = reader.get_frames_tau_d(&reader.frames_vec(), None).unwrap();
+        assert_eq!(tau_d, expected_tau_d(&photons, shape, 64));
+        assert_eq!(
+            reader.get_frames_intensity(&reader.frames_vec(), None).unwrap(),
+            tau_d.sum_axis(Axis(3))
+        );
+        assert_eq!(
+            reader.get_epoch_timestamps_laser(&[2]).unwrap()[0],
+            crate::tests::SYNTHETIC_EPOCH + 2 * 100_000_000
+        );
+        let metadata = reader.get_frame_metadata(&[1]).unwrap();
+        assert_eq!(metadata[0].siff_compress, Some(1));
+        assert_eq!(metadata[0].metadata_string, synthetic_description(1));
+    }
+
+    #[test]
+    fn rejects_invalid_frames() {
+        let mut writer = SiffWriter::new(Cursor::new(Vec::new()), SYNTHETIC_NVFD, "").unwrap();
+        assert!(writer.write_frame_raw(&[pack_photon(4, 0, 0)], (4, 4), "").is_err());
+        assert!(writer.write_frame_raw(&[pack_photon(0, 4, 0)], (4, 4), "").is_err());
+        assert!(
+            writer.write_frame_compressed(
+                &Array2::<u16>::ones((2, 2)).view(), &[0, 1, 2], ""
+            ).is_err()
+        );
+        assert_eq!(writer.num_frames(), 0);
+        writer.write_frame_raw(&[pack_photon(3, 3, 0)], (4, 4), "").unwrap();
+        assert_eq!(writer.num_frames(), 1);
+    }
+}
@@ -4,8 +4,15 @@
 
 mod file_format;
 mod ifd;
+mod index;
 mod tags;
+mod writer;
 
-pub use tags::{Tag, TiffTagID};
+pub use tags::{Tag, TagValue, TiffTagID, TiffTagType, BigTag};
 pub use ifd::{IFD, BigTiffIFD, IFDPtrIterator};
-pub use file_format::{FileFormat, dimensions_consistent};
\ No newline at end of file
+pub use file_format::{FileFormat, dimensions_consistent};
+pub use index::{IfdIndex, index_path, file_stamp};
+pub use writer::{
+    TiffKind, write_bigtiff_page, write_bigtiff_header, write_classic_header, write_linked_pages,
+    link_ifd, image_tags, scanimage_tags
+};
\ No newline at end of file
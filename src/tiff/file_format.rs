@@ -20,7 +20,9 @@ use binrw::{
 use crate::{
     tiff::ifd::{
         BigTiffIFD,
+        BigTiffIFDIterator,
         IFDIterator,
+        TiffIFD,
         IFD,
     },
     data::image::Dimensions,
@@ -29,6 +31,24 @@ use crate::{
 use super::ifd::SeekRead;
 
 
+/// The NVFD fields that describe how frames were acquired
+/// (as opposed to e.g. where or when they were saved, like
+/// `SI.hScan2D.logFileCounter`), which must agree for frames of
+/// two files to be treated as one acquisition.
+const ACQUISITION_FIELDS : &[&str] = &[
+    "SI.hChannels.channelSave",
+    "SI.hFastZ.enable",
+    "SI.hFastZ.numDiscardFlybackFrames",
+    "SI.hRoiManager.imagingFovUm",
+    "SI.hRoiManager.linesPerFrame",
+    "SI.hRoiManager.pixelsPerLine",
+    "SI.hRoiManager.scanZoomFactor",
+    "SI.hStackManager.actualNumSlices",
+    "SI.hStackManager.enable",
+    "SI.hStackManager.numSlices",
+    "SI.hStackManager.stackZStepSize",
+];
+
 /// A struct that holds the file format
 /// information, which determines information
 /// such as how to read individual frames,
@@ -80,6 +100,18 @@ enum TiffHeader {
     }
 }
 
+/// The header of a Tiff that wasn't written by ScanImage:
+/// just the byte order and the `TiffHeader`.
+#[derive(BinRead)]
+#[br(little, assert(endian == [73, 73]))]
+struct PlainTiffHeader {
+    endian : [u8; 2],
+    tiffheader : TiffHeader,
+}
+
+/// The ScanImage magic number of `SiffHeader::tiff_magic`
+const SCANIMAGE_MAGIC : u32 = 117637889;
+
 /// Contains the header information that dictates
 /// the .tiff parameters to read the file, including:
 /// * endian
@@ -98,7 +130,7 @@ enum TiffHeader {
 #[br(
     little,
     assert(endian == [73, 73]),
-    assert(tiff_magic == 117637889),
+    assert(tiff_magic == SCANIMAGE_MAGIC),
     assert((si_version == 4) || (si_version == 3)),
 )]
 #[bw(little)]
@@ -152,8 +184,92 @@ pub fn dimensions_consistent<IFDType : IFD>(ifds : &Vec<IFDType>)->Option<Dimens
     }
 }
 
+impl SiffHeader {
+    /// Reads the header of a ScanImage file or, failing that,
+    /// of any little-endian Tiff or BigTiff, which is treated as a
+    /// ScanImage header with no NVFD or ROI string (and a `tiff_magic`
+    /// of 0, to tell them apart).
+    fn read_any<T : Read + Seek>(buffer : &mut T) -> Result<Self, String> {
+        let start = buffer.stream_position()
+            .map_err(|err| format!("Error reading header: {}", err))?;
+        let scanimage_err = match SiffHeader::read(buffer) {
+            Ok(header) => return Ok(header),
+            Err(err) => err,
+        };
+        buffer.seek(std::io::SeekFrom::Start(start))
+            .map_err(|err| format!("Error reading header: {}", err))?;
+        let plain = PlainTiffHeader::read(buffer)
+            .map_err(|err| format!(
+                "Error reading header: {} (nor is it a little-endian Tiff: {})",
+                scanimage_err, err
+            ))?;
+        Ok(SiffHeader {
+            endian : plain.endian,
+            tiffheader : plain.tiffheader,
+            tiff_magic : 0,
+            si_version : 0,
+            nvfd_length : 0,
+            roi_string_length : 0,
+        })
+    }
+}
+
 impl FileFormat{
 
+    /// Creates the file format for a new BigTiff `.siff`
+    /// file containing the provided NVFD and ROI strings.
+    /// Used when writing files rather than reading them --
+    /// the first IFD is placed immediately after the ROI
+    /// string.
+    ///
+    /// ## Arguments
+    ///
+    /// * `nvfd` - The non-varying-frame-data to store in the header
+    ///
+    /// * `roi_string` - The MROI data to store in the header
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// let file_format = FileFormat::new_siff(reader.nvfd().as_str(), "");
+    /// file_format.write(&mut writer)?;
+    /// ```
+    pub fn new_siff(nvfd : &str, roi_string : &str) -> Self {
+        // endian (2) + tiff magic (2) + pointer size (2) + pad (2)
+        // + first_ifd (8) + ScanImage magic, version, and two lengths (4 x 4)
+        let header_size = 32u64;
+        FileFormat {
+            siff_header : SiffHeader {
+                endian : [73, 73],
+                tiffheader : TiffHeader::BigTiff {
+                    _bytes_per_pointer : 8,
+                    first_ifd : header_size + nvfd.len() as u64 + roi_string.len() as u64,
+                },
+                tiff_magic : SCANIMAGE_MAGIC,
+                si_version : 4,
+                nvfd_length : nvfd.len() as u32,
+                roi_string_length : roi_string.len() as u32,
+            },
+            nvfd : nvfd.to_string(),
+            roi_string : roi_string.to_string(),
+            _tiff_type : TiffType::BigTiff,
+        }
+    }
+
+    /// Returns the location of the NVFD in the file
+    /// (from start).
+    pub fn nvfd_offset(&self) -> u64 {
+        self.first_ifd_val()
+            - self.siff_header.nvfd_length as u64
+            - self.siff_header.roi_string_length as u64
+    }
+
+    /// Returns the location of the ROI string in the file
+    /// (from start).
+    pub fn roi_string_offset(&self) -> u64 {
+        self.first_ifd_val() - self.siff_header.roi_string_length as u64
+    }
+
     /// Returns whether the file being read
     /// uses the BigTiff format vs. a standard
     /// 32 bit Tiff specification.
@@ -164,9 +280,18 @@ impl FileFormat{
         }
     }
 
+    /// Returns whether the file was written by ScanImage
+    /// (or is a `.siff`), rather than being a plain Tiff
+    /// stack with no NVFD, ROI string, or frame metadata.
+    pub fn is_scanimage(&self) -> bool {
+        self.siff_header.tiff_magic == SCANIMAGE_MAGIC
+    }
+
     /// Checks a file against all the
     /// criteria to determine the enum
-    /// type of file contained.
+    /// type of file contained. Files that aren't
+    /// from ScanImage are read as plain Tiffs
+    /// (see `is_scanimage`).
     /// 
     /// ## Arguments
     /// 
@@ -177,10 +302,7 @@ impl FileFormat{
     /// system calls.
     pub fn parse_filetype<'a, 'b, T>(buffer : &'a mut T) -> Result<Self, String>
         where T : Read + Seek {
-        let siff_header = SiffHeader::read(buffer)
-            .map_err(
-                |err| format!("Error reading header: {}", err)
-            )?;
+        let siff_header = SiffHeader::read_any(buffer)?;
 
         let mut nvfd = vec![0u8; siff_header.nvfd_length as usize];
         buffer.read_exact(&mut nvfd)
@@ -208,10 +330,7 @@ impl FileFormat{
     /// Just reads and stores enough to determine how to find IFDs.
     pub fn minimal_filetype<'a, 'b, T>(buffer : &'a mut T) -> Result<Self, String>
         where T : Read + Seek {
-            let siff_header = SiffHeader::read(buffer)
-            .map_err(
-                |err| format!("Error reading header: {}", err)
-            )?;
+            let siff_header = SiffHeader::read_any(buffer)?;
             Ok(
                 FileFormat {
                     _tiff_type : match &siff_header.tiffheader {
@@ -238,24 +357,49 @@ impl FileFormat{
         }
     }
 
-    /// Returns an `IFD` object from the pointer to its start. Mostly
-    /// for debugging.
+    /// Returns an `IFD` object from the pointer to its start. Classic
+    /// Tiff IFDs are converted to `BigTiffIFD`s. Mostly for debugging.
     #[allow(dead_code)]
-    pub fn read_ifd<ReaderT : SeekRead>(buffer : &mut ReaderT, offset : u64)
+    pub fn read_ifd<ReaderT : SeekRead>(&self, buffer : &mut ReaderT, offset : u64)
         -> Result<BigTiffIFD, std::io::Error> {
         buffer.seek(std::io::SeekFrom::Start(offset))?;
-        BigTiffIFD::read(buffer).map_err(
-            |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err)
-        )
+        match self._tiff_type {
+            TiffType::Tiff => {
+                TiffIFD::read(buffer).map_err(
+                    |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+                )?.into_bigtiff(buffer)
+            },
+            TiffType::BigTiff => {
+                BigTiffIFD::read(buffer).map_err(
+                    |err| std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+                )
+            },
+        }
     }
 
-    /// Returns an iterator over all IFDs in the file.
+    /// Returns an iterator over all IFDs in the file. IFDs of
+    /// classic Tiff files are converted to `BigTiffIFD`s.
     pub fn get_ifd_iter<'reader, ReaderT>(&self,buff: &'reader mut ReaderT) 
-    -> IFDIterator<'reader, ReaderT, BigTiffIFD>
+    -> BigTiffIFDIterator<'reader, ReaderT>
     where ReaderT : SeekRead + Sized, {
-        IFDIterator {
-            reader : buff,
-            to_next : self.first_ifd_val(),
+        self.get_ifd_iter_from(buff, self.first_ifd_val())
+    }
+
+    /// Returns an iterator over the IFDs in the file starting
+    /// from the IFD at `offset` (e.g. to resume walking the chain
+    /// of a file that's still being written).
+    pub fn get_ifd_iter_from<'reader, ReaderT>(&self, buff : &'reader mut ReaderT, offset : u64)
+    -> BigTiffIFDIterator<'reader, ReaderT>
+    where ReaderT : SeekRead + Sized, {
+        match self._tiff_type {
+            TiffType::Tiff => BigTiffIFDIterator::Tiff(IFDIterator {
+                reader : buff,
+                to_next : offset as u32,
+            }),
+            TiffType::BigTiff => BigTiffIFDIterator::BigTiff(IFDIterator {
+                reader : buff,
+                to_next : offset,
+            }),
         }
     }
 
@@ -278,9 +422,18 @@ impl FileFormat{
         tau_bin.parse::<u32>().map(|x| x + 2).ok()
     }
 
-    /// Returns the size of a single bin in picoseconds
+    /// Returns the size of a single bin in picoseconds. Files
+    /// converted from `.ptu` store the exact width (in seconds)
+    /// in `PTU.MeasDesc_Resolution`, which is used if present;
+    /// otherwise it's ScanImage's `5 * 2^binResolution` ps.
     #[allow(dead_code)]
     pub fn flim_tau_bin_size_picoseconds(&self) -> Option<u32> {
+        let exact = self.nvfd_field("PTU.MeasDesc_Resolution")
+            .and_then(|resolution| resolution.parse::<f64>().ok())
+            .filter(|&resolution| resolution > 0.0);
+        if let Some(resolution) = exact {
+            return Some((resolution * 1e12).round() as u32);
+        }
         let needle = "binResolution = ";
         let tau_bin_ptr = self.nvfd.find(needle)?;
         let tau_bin_ptr = tau_bin_ptr + needle.len();
@@ -290,8 +443,128 @@ impl FileFormat{
         Some(5 * u32::pow(2,bin_res))
     }
 
+    /// Returns the value of a field of the non-varying-frame-data
+    /// as a trimmed string slice (e.g. `"[1;2]"` for
+    /// `SI.hChannels.channelSave`), if the field is present.
+    ///
+    /// ## Arguments
+    ///
+    /// * `field` - The full name of the field, e.g. `SI.hStackManager.numSlices`
+    pub fn nvfd_field(&self, field : &str) -> Option<&str> {
+        let needle = format!("{} = ", field);
+        let start = self.nvfd.match_indices(needle.as_str())
+            .map(|(idx, _)| idx)
+            .find(|&idx| idx == 0 || self.nvfd.as_bytes()[idx - 1] == b'\n')?
+            + needle.len();
+        let len = self.nvfd[start..].find('\n').unwrap_or(self.nvfd.len() - start);
+        Some(self.nvfd[start..start + len].trim())
+    }
+
+    /// Compares the acquisition settings of two files: the FLIM
+    /// histogram (number and width of arrival time bins) and the
+    /// imaging fields of the NVFD (channels, planes, field of view, etc.).
+    /// Fields that differ between files of the same acquisition,
+    /// like the file counter, are ignored. Returns the name of the
+    /// first setting that differs, if any.
+    ///
+    /// ## Example
+    ///
+    /// ```rust, ignore
+    /// if let Some(setting) = first.acquisition_mismatch(&second) {
+    ///     println!("Files were acquired with different {}", setting);
+    /// }
+    /// ```
+    pub fn acquisition_mismatch(&self, other : &FileFormat) -> Option<String> {
+        if self.num_flim_tau_bins() != other.num_flim_tau_bins() {
+            return Some("number of arrival time bins".to_string());
+        }
+        if self.flim_tau_bin_size_picoseconds() != other.flim_tau_bin_size_picoseconds() {
+            return Some("arrival time bin size".to_string());
+        }
+        ACQUISITION_FIELDS.iter()
+            .find(|&&field| self.nvfd_field(field) != other.nvfd_field(field))
+            .map(|field| field.to_string())
+    }
+
+    /// Number of channels saved in each frame group,
+    /// parsed from `SI.hChannels.channelSave` (which is
+    /// either a single channel or a list like `[1;2]`).
+    pub fn num_channels_saved(&self) -> Option<u32> {
+        let channels = self.nvfd_field("SI.hChannels.channelSave")?
+            .split(|c : char| "[];, ".contains(c))
+            .filter(|c| !c.is_empty())
+            .count() as u32;
+        (channels > 0).then_some(channels)
+    }
+
+    /// Number of imaging planes per volume (not counting
+    /// flyback frames). `1` if the stack manager is disabled.
+    pub fn num_slices(&self) -> Option<u32> {
+        if matches!(self.nvfd_field("SI.hStackManager.enable"), Some("false") | Some("0")) {
+            return Some(1);
+        }
+        self.nvfd_field("SI.hStackManager.actualNumSlices")
+            .or_else(|| self.nvfd_field("SI.hStackManager.numSlices"))?
+            .parse::<u32>().ok()
+    }
+
+    /// Number of flyback frames discarded at the end of
+    /// each volume. `0` if fast z is disabled.
+    pub fn num_flyback_frames(&self) -> Option<u32> {
+        if matches!(self.nvfd_field("SI.hFastZ.enable"), Some("false") | Some("0")) {
+            return Some(0);
+        }
+        self.nvfd_field("SI.hFastZ.numDiscardFlybackFrames")?.parse::<u32>().ok()
+    }
+
+    /// Whether `frame` (counting from the start of the file) is
+    /// one of the flyback frames discarded at the end of each
+    /// volume, assuming the file starts at the start of a volume.
+    pub fn is_flyback_frame(&self, frame : u64) -> bool {
+        let flyback = self.num_flyback_frames().unwrap_or(0) as u64;
+        if flyback == 0 {
+            return false;
+        }
+        let channels = self.num_channels_saved().unwrap_or(1).max(1) as u64;
+        let slices = self.num_slices().unwrap_or(1) as u64;
+        (frame / channels) % (slices + flyback) >= slices
+    }
+
+    /// Distance between imaging planes in microns
+    pub fn z_step_um(&self) -> Option<f64> {
+        self.nvfd_field("SI.hStackManager.stackZStepSize")?.parse::<f64>().ok()
+    }
+
+    /// The size of the imaging field of view in microns as
+    /// `(height, width)`, computed from the corners stored in
+    /// `SI.hRoiManager.imagingFovUm`.
+    pub fn fov_um(&self) -> Option<(f64, f64)> {
+        let corners = self.nvfd_field("SI.hRoiManager.imagingFovUm")?
+            .trim_matches(|c| c == '[' || c == ']')
+            .split(';')
+            .map(|corner| {
+                corner.split([' ', ','])
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<f64>().ok())
+                .collect::<Option<Vec<f64>>>()
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        if corners.is_empty() || corners.iter().any(|corner| corner.len() != 2) {
+            return None;
+        }
+        let extent = |axis : usize| {
+            let values = corners.iter().map(|corner| corner[axis]);
+            values.clone().fold(f64::NEG_INFINITY, f64::max)
+            - values.fold(f64::INFINITY, f64::min)
+        };
+        Some((extent(1), extent(0)))
+    }
+
     /// Writes the file-format data that is common to all tiff/siff files
-    /// into the position the writer is currently at.
+    /// into the position the writer is currently at. Always writes a
+    /// `BigTiff` header (even if the file read was a classic Tiff), because
+    /// the frames are always written with `BigTiff` IFDs.
     /// 
     /// ## Arguments
     /// 
@@ -312,13 +585,17 @@ impl FileFormat{
     /// ```
     /// 
     pub fn write<T: Write + Seek>(&self, writer : &mut T) -> binrw::io::Result<()> {
-        self.siff_header.write(writer)
+        let mut header = FileFormat::new_siff(&self.nvfd, &self.roi_string);
+        // Plain Tiffs stay plain (the zeroed fields are just padding)
+        header.siff_header.tiff_magic = self.siff_header.tiff_magic;
+        header.siff_header.si_version = self.siff_header.si_version;
+        header.siff_header.write(writer)
         .map_err(|e| binrw::io::Error::new(binrw::io::ErrorKind::Other, e))?;
-        writer.write_all(&mut self.nvfd.as_bytes())?;
-        writer.write_all(&mut self.roi_string.as_bytes())?;
+        writer.write_all(self.nvfd.as_bytes())?;
+        writer.write_all(self.roi_string.as_bytes())?;
         debug_assert_eq!(
             writer.stream_position()?,
-            self.first_ifd_val()
+            header.first_ifd_val()
         );
         Ok(())
     }
@@ -366,6 +643,88 @@ mod tests {
     use binrw::io::BufReader;
     use crate::tests::get_test_paths;
 
+    #[test]
+    fn parse_nvfd_fields() {
+        let file_format = FileFormat::new_siff(
+            "SI.hChannels.channelSave = [1;2]\n\
+            SI.hFastZ.enable = true\n\
+            SI.hFastZ.numDiscardFlybackFrames = 1\n\
+            SI.hRoiManager.imagingFovUm = [-200 -100;200 -100;200 100;-200 100]\n\
+            SI.hStackManager.actualNumSlices = 4\n\
+            SI.hStackManager.numSlices = 3\n\
+            SI.hStackManager.stackZStepSize = 2.5",
+            ""
+        );
+        assert_eq!(file_format.num_channels_saved(), Some(2));
+        assert_eq!(file_format.num_slices(), Some(4));
+        assert_eq!(file_format.num_flyback_frames(), Some(1));
+        assert_eq!(file_format.z_step_um(), Some(2.5));
+        assert_eq!(file_format.fov_um(), Some((200.0, 400.0)));
+        assert_eq!(file_format.nvfd_field("SI.hStackManager.numSlices"), Some("3"));
+        assert_eq!(file_format.nvfd_field("numSlices"), None);
+
+        let file_format = FileFormat::new_siff("SI.hChannels.channelSave = 2\n", "");
+        assert_eq!(file_format.num_channels_saved(), Some(1));
+        assert_eq!(file_format.num_slices(), None);
+        assert_eq!(file_format.fov_um(), None);
+    }
+
+    #[test]
+    fn compare_acquisitions() {
+        let nvfd = "SI.hScan2D.Tau_bins = 62\n\
+            SI.hScan2D.binResolution = 2\n\
+            SI.hScan2D.logFileCounter = 1\n\
+            SI.hScan2D.logFileStem = 'flashes'\n\
+            SI.hStackManager.numSlices = 3\n";
+        let file_format = FileFormat::new_siff(nvfd, "");
+        let same = FileFormat::new_siff(
+            &nvfd.replace("Counter = 1", "Counter = 2").replace("'flashes'", "'flashes_2'"), ""
+        );
+        assert_eq!(file_format.acquisition_mismatch(&same), None);
+
+        let fewer_slices = FileFormat::new_siff(&nvfd.replace("numSlices = 3", "numSlices = 2"), "");
+        assert_eq!(
+            file_format.acquisition_mismatch(&fewer_slices).as_deref(),
+            Some("SI.hStackManager.numSlices")
+        );
+        let fewer_bins = FileFormat::new_siff(&nvfd.replace("Tau_bins = 62", "Tau_bins = 31"), "");
+        assert!(file_format.acquisition_mismatch(&fewer_bins).is_some());
+        let wider_bins = FileFormat::new_siff(&nvfd.replace("binResolution = 2", "binResolution = 3"), "");
+        assert!(file_format.acquisition_mismatch(&wider_bins).is_some());
+    }
+
+    #[test]
+    fn plain_tiff_headers() {
+        use binrw::io::Cursor;
+        let mut bigtiff = b"II".to_vec();
+        [43u16, 8, 0].iter().for_each(|x| bigtiff.extend_from_slice(&x.to_le_bytes()));
+        bigtiff.extend_from_slice(&16u64.to_le_bytes());
+        let file_format = FileFormat::parse_filetype(&mut Cursor::new(&bigtiff)).unwrap();
+        assert!(!file_format.is_scanimage());
+        assert!(file_format.is_bigtiff());
+        assert_eq!(file_format.first_ifd_val(), 16);
+        assert_eq!(file_format.nvfd, "");
+
+        // Rewritten plain Tiffs are still plain
+        let mut rewritten = Cursor::new(Vec::new());
+        file_format.write(&mut rewritten).unwrap();
+        let file_format = FileFormat::parse_filetype(&mut Cursor::new(rewritten.into_inner())).unwrap();
+        assert!(!file_format.is_scanimage());
+        assert_eq!(file_format.first_ifd_val(), 32);
+
+        let siff = FileFormat::new_siff("nvfd", "roi");
+        let mut written = Cursor::new(Vec::new());
+        siff.write(&mut written).unwrap();
+        let written = written.into_inner();
+        assert!(FileFormat::parse_filetype(&mut Cursor::new(&written)).unwrap().is_scanimage());
+
+        // Big-endian
+        let mut motorola = b"MM".to_vec();
+        motorola.extend_from_slice(&42u16.to_be_bytes());
+        motorola.extend_from_slice(&8u32.to_be_bytes());
+        assert!(FileFormat::parse_filetype(&mut Cursor::new(&motorola)).is_err());
+    }
+
     #[test]
     fn test_parse_filetype() {
         let test_paths = get_test_paths().expect("Failed to read test paths");
@@ -387,7 +746,7 @@ mod tests {
         let mut buffer = BufReader::new(&f);
         let file_format = FileFormat::parse_filetype(&mut buffer).unwrap();
         println!("First IFD is {}" , file_format.first_ifd_val());
-        let ifd = FileFormat::read_ifd(&mut buffer, file_format.first_ifd_val()).unwrap();
+        let ifd = file_format.read_ifd(&mut buffer, file_format.first_ifd_val()).unwrap();
         println!("{:?}", ifd);
     }
 
@@ -11,6 +11,7 @@
 //! NYPD KKK IFD they're all the same
 
 use std::fmt::Debug;
+use std::io::{Error as IOError, ErrorKind as IOErrorKind};
 use std::iter::Iterator;
 use binrw::{
     io::{Read, Seek, SeekFrom},
@@ -122,6 +123,39 @@ pub trait IFD : BinRead + ReadEndian + Default {
     /// println!("{}", tag)
     fn get_tag_from_str<'a>(&self, str_slice : &'a str)->Option<&Self::TagType>;
 
+    /// Returns the (offset, byte count) of every strip of the image
+    /// this IFD describes. `.siff` frames are always one strip, but
+    /// other Tiffs may split the image into many, in which case the
+    /// offsets and counts are read from the file. Returns the reader
+    /// to its original position.
+    ///
+    /// ## Arguments
+    ///
+    /// * `reader` - The file containing this IFD
+    ///
+    /// ## Errors
+    ///
+    /// * `IOError` - If either tag is missing, isn't an unsigned
+    /// integer type, can't be read, or the two have different lengths
+    fn strips<R : Read + Seek>(&self, reader : &mut R) -> std::io::Result<Vec<(u64, u64)>> {
+        let read_values = |tag_id : TiffTagID, reader : &mut R| {
+            self.get_tag(tag_id)
+                .ok_or(IOError::new(IOErrorKind::InvalidData, format!("{} not found", tag_id)))?
+                .parse(reader)?
+                .as_u64_vec()
+                .ok_or(IOError::new(IOErrorKind::InvalidData, format!("{} is not an integer", tag_id)))
+        };
+        let offsets = read_values(TiffTagID::StripOffsets, reader)?;
+        let byte_counts = read_values(TiffTagID::StripByteCounts, reader)?;
+        if offsets.len() != byte_counts.len() {
+            return Err(IOError::new(
+                IOErrorKind::InvalidData,
+                "StripOffsets and StripByteCounts have different lengths"
+            ));
+        }
+        Ok(offsets.into_iter().zip(byte_counts).collect())
+    }
+
     /// Creates an iterator starting at the current IFD, and reads successive
     /// IFDs in the file
     fn to_iter<'a, T : Read + Seek>(&self, _reader : &'a mut T)->IFDIterator<'a, T, Self>{
@@ -187,6 +221,40 @@ impl IFD for TiffIFD {
     }
 }
 
+impl TiffIFD {
+    /// Converts a classic Tiff IFD into a `BigTiffIFD` so that the
+    /// rest of the crate only needs to handle one type of IFD. Values
+    /// between 5 and 8 bytes long (e.g. a single `Rational`) are stored
+    /// out-of-line in classic Tiffs but inline in `BigTiff`, so these
+    /// are read from the file and moved into the tag. Returns the reader
+    /// to its original position.
+    ///
+    /// ## Arguments
+    ///
+    /// * `reader` - The file containing this IFD
+    pub fn into_bigtiff<R : Read + Seek>(self, reader : &mut R) -> binrw::io::Result<BigTiffIFD> {
+        let pos = reader.stream_position()?;
+        let tags = self.tags.into_iter().map(|tag| {
+            let num_bytes = tag.num_values as u64 * tag.tag_dtype.size_of();
+            let mut big_tag = BigTag::from(tag);
+            if num_bytes > 4 && num_bytes <= 8 {
+                reader.seek(SeekFrom::Start(big_tag.value))?;
+                let mut value = [0u8; 8];
+                reader.read_exact(&mut value[..num_bytes as usize])?;
+                big_tag.value = u64::from_le_bytes(value);
+            }
+            Ok(big_tag)
+        }).collect::<binrw::io::Result<Vec<_>>>()?;
+        reader.seek(SeekFrom::Start(pos))?;
+
+        Ok(BigTiffIFD {
+            num_tags : tags.len() as u64,
+            tags,
+            next_ifd : self.next_ifd.map(|next| next as u64),
+        })
+    }
+}
+
 #[derive(BinRead, Default)]
 #[br(little)]
 pub struct BigTiffIFD {
@@ -198,6 +266,18 @@ pub struct BigTiffIFD {
     pub next_ifd : Option<u64>,
 }
 
+impl BigTiffIFD {
+    /// Creates an IFD from its tags rather than
+    /// reading it from a file (e.g. from an index).
+    pub fn from_tags(tags : Vec<BigTag>, next_ifd : Option<u64>) -> Self {
+        BigTiffIFD {
+            num_tags : tags.len() as u64,
+            tags,
+            next_ifd,
+        }
+    }
+}
+
 impl IFD for BigTiffIFD {
     type ByteSize = u64;
     type TagType = BigTag;
@@ -321,6 +401,47 @@ impl<'a, S, IFDT> Iterator for IFDIterator<'a, S, IFDT>
     }
 }   
 
+/// Iterates over the IFDs of either a classic Tiff or a `BigTiff`
+/// file, always returning `BigTiffIFD`s (classic IFDs are converted
+/// with `TiffIFD::into_bigtiff`). Ends at the first IFD that can't be read.
+pub enum BigTiffIFDIterator<'reader, S> where S : SeekRead {
+    Tiff(IFDIterator<'reader, S, TiffIFD>),
+    BigTiff(IFDIterator<'reader, S, BigTiffIFD>),
+}
+
+impl<'reader, S> BigTiffIFDIterator<'reader, S> where S : SeekRead {
+    /// The reader used to walk the IFDs
+    pub fn reader(&mut self) -> &mut S {
+        match self {
+            BigTiffIFDIterator::Tiff(iter) => iter.reader,
+            BigTiffIFDIterator::BigTiff(iter) => iter.reader,
+        }
+    }
+
+    /// The location of the IFD that will be returned
+    /// by the next call to `next` (0 if there are none left)
+    pub fn next_offset(&self) -> u64 {
+        match self {
+            BigTiffIFDIterator::Tiff(iter) => iter.to_next as u64,
+            BigTiffIFDIterator::BigTiff(iter) => iter.to_next,
+        }
+    }
+}
+
+impl<'reader, S> Iterator for BigTiffIFDIterator<'reader, S> where S : SeekRead {
+    type Item = BigTiffIFD;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            BigTiffIFDIterator::Tiff(iter) => {
+                let ifd = iter.next()?;
+                ifd.into_bigtiff(iter.reader).ok()
+            },
+            BigTiffIFDIterator::BigTiff(iter) => iter.next(),
+        }
+    }
+}
+
 /// Does not read the whole IFD, only the number of tags and the next IFD
 pub struct IFDPtrIterator<'a, S> where S : SeekRead {
     reader : &'a mut S,
@@ -0,0 +1,269 @@
+//! # Index
+//!
+//! A compact sidecar file storing where every IFD of a
+//! file is and the handful of tag values that change from
+//! frame to frame, so that a file can be reopened without
+//! walking its IFD chain (which means a seek and a small read
+//! per frame -- painful for large files on network storage).
+//!
+//! Every other tag is assumed to be the same in every IFD
+//! (as it is for ScanImage files) and is stored once. Files
+//! whose IFDs differ in other ways can't be indexed.
+//!
+//! The index records the size and modification time of the
+//! file it describes and should be ignored if either changes.
+
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use binrw::{
+    BinRead, BinResult, BinWrite,
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::tiff::{
+    ifd::{BigTiffIFD, IFD},
+    tags::{BigTag, TiffTagID},
+};
+
+/// The index of the file at `path` is stored next to
+/// it, at `path` with `.idx` appended (e.g. `file.siff.idx`).
+pub fn index_path<P : AsRef<Path>>(path : P) -> PathBuf {
+    let mut index = path.as_ref().as_os_str().to_owned();
+    index.push(".idx");
+    PathBuf::from(index)
+}
+
+/// The size and modification time (nanoseconds since the
+/// Unix epoch) of a file, or `None` if the platform doesn't
+/// report modification times.
+pub fn file_stamp(metadata : &std::fs::Metadata) -> Option<(u64, u64)> {
+    let modified = metadata.modified().ok()?
+        .duration_since(UNIX_EPOCH).ok()?
+        .as_nanos();
+    Some((metadata.len(), modified as u64))
+}
+
+/// Bytes per `BigTag` in the index
+const TAG_SIZE : u64 = 20;
+/// Bytes per `IndexEntry` in the index
+const ENTRY_SIZE : u64 = 72;
+
+/// The values of a single IFD that aren't shared
+/// with the other IFDs of the file.
+#[derive(BinRead, BinWrite, Debug, Clone, PartialEq)]
+#[brw(little)]
+struct IndexEntry {
+    ifd_offset : u64,
+    next_ifd : u64,
+    width : u64,
+    height : u64,
+    siff : u64,
+    description_offset : u64,
+    description_length : u64,
+    strip_offset : u64,
+    strip_byte_count : u64,
+}
+
+/// An index of the IFDs of a file. See the module
+/// documentation.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let index = IfdIndex::new(&ifds, &offsets, file_stamp(&file.metadata()?).unwrap())?;
+/// index.write(&mut File::create(index_path("file.siff"))?)?;
+///
+/// let index = IfdIndex::read_from(&mut BufReader::new(File::open(index_path("file.siff"))?))?;
+/// if index.is_current(file_stamp(&file.metadata()?).unwrap()) {
+///     let (ifds, offsets) = index.into_ifds();
+/// }
+/// ```
+#[derive(BinRead, BinWrite, Debug)]
+#[brw(little, magic = b"SIFFIDX1")]
+#[br(import(index_size : u64))]
+pub struct IfdIndex {
+    file_size : u64,
+    file_modified : u64,
+    #[br(assert(num_tags <= index_size / TAG_SIZE, "Index has more tags than fit in the file"))]
+    num_tags : u64,
+    #[br(count = num_tags)]
+    template : Vec<BigTag>,
+    #[br(assert(
+        num_frames <= index_size / ENTRY_SIZE,
+        "Index has more frames than fit in the file"
+    ))]
+    num_frames : u64,
+    #[br(count = num_frames)]
+    entries : Vec<IndexEntry>,
+}
+
+impl IfdIndex {
+    /// Reads an index written by `write`. The counts stored
+    /// in the index are checked against the length of `reader`
+    /// before anything is allocated for them.
+    ///
+    /// ## Errors
+    ///
+    /// * `binrw::Error` - If the index can't be read, or claims
+    /// to hold more tags or frames than `reader` contains.
+    pub fn read_from<R : Read + Seek>(reader : &mut R) -> BinResult<Self> {
+        let start = reader.stream_position()?;
+        let index_size = reader.seek(SeekFrom::End(0))? - start;
+        reader.seek(SeekFrom::Start(start))?;
+        IfdIndex::read_args(reader, (index_size,))
+    }
+
+    /// Builds an index of `ifds`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `ifds` - Every IFD of the file, in order
+    ///
+    /// * `offsets` - The location of each IFD in the file
+    ///
+    /// * `stamp` - The size and modification time of the file
+    /// (from `file_stamp`)
+    ///
+    /// ## Errors
+    ///
+    /// * `String` - If the IFDs differ in any tag other than those
+    /// stored for every frame (so the index would not reproduce them).
+    pub fn new(ifds : &[BigTiffIFD], offsets : &[u64], stamp : (u64, u64))
+    -> Result<Self, String> {
+        let template = ifds.first().map(|ifd| ifd.tags().clone()).unwrap_or_default();
+        let mut index = IfdIndex {
+            file_size : stamp.0,
+            file_modified : stamp.1,
+            num_tags : template.len() as u64,
+            template,
+            num_frames : ifds.len() as u64,
+            entries : Vec::with_capacity(ifds.len()),
+        };
+
+        for (frame, (ifd, &ifd_offset)) in ifds.iter().zip(offsets).enumerate() {
+            let value = |id : TiffTagID| ifd.get_tag(id).map_or(0, |tag| tag.value);
+            let entry = IndexEntry {
+                ifd_offset,
+                next_ifd : ifd.next_ifd.unwrap_or(0),
+                width : value(TiffTagID::ImageWidth),
+                height : value(TiffTagID::ImageLength),
+                siff : value(TiffTagID::Siff),
+                description_offset : value(TiffTagID::ImageDescription),
+                description_length : ifd.get_tag(TiffTagID::ImageDescription)
+                    .map_or(0, |tag| tag.num_values),
+                strip_offset : value(TiffTagID::StripOffsets),
+                strip_byte_count : value(TiffTagID::StripByteCounts),
+            };
+            if index.tags(&entry) != *ifd.tags() {
+                return Err(format!(
+                    "IFD {} has different tags from the first IFD and can't be indexed",
+                    frame
+                ));
+            }
+            index.entries.push(entry);
+        }
+        Ok(index)
+    }
+
+    /// Whether the index describes a file with
+    /// this size and modification time.
+    pub fn is_current(&self, stamp : (u64, u64)) -> bool {
+        (self.file_size, self.file_modified) == stamp
+    }
+
+    /// Rebuilds the IFDs of the file and their locations.
+    pub fn into_ifds(self) -> (Vec<BigTiffIFD>, Vec<u64>) {
+        self.entries.iter().map(|entry| (
+            BigTiffIFD::from_tags(self.tags(entry), Some(entry.next_ifd)),
+            entry.ifd_offset
+        )).unzip()
+    }
+
+    /// The tags of the IFD described by `entry`
+    fn tags(&self, entry : &IndexEntry) -> Vec<BigTag> {
+        self.template.iter().map(|tag| {
+            let mut tag = tag.clone();
+            match tag.tag {
+                TiffTagID::ImageWidth => tag.value = entry.width,
+                TiffTagID::ImageLength => tag.value = entry.height,
+                TiffTagID::Siff => tag.value = entry.siff,
+                TiffTagID::ImageDescription => {
+                    tag.value = entry.description_offset;
+                    tag.num_values = entry.description_length;
+                },
+                TiffTagID::StripOffsets => tag.value = entry.strip_offset,
+                TiffTagID::StripByteCounts => tag.value = entry.strip_byte_count,
+                _ => {},
+            }
+            tag
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binrw::io::Cursor;
+    use crate::tiff::TiffTagType;
+
+    fn ifd(description : u64, strip : u64, next : u64) -> BigTiffIFD {
+        BigTiffIFD::from_tags(vec![
+            BigTag::new(TiffTagID::ImageWidth, TiffTagType::Short, 1, 8),
+            BigTag::new(TiffTagID::ImageLength, TiffTagType::Short, 1, 4),
+            BigTag::new(TiffTagID::ImageDescription, TiffTagType::Ascii, description, 500),
+            BigTag::new(TiffTagID::StripOffsets, TiffTagType::Long8, 1, strip),
+            BigTag::new(TiffTagID::StripByteCounts, TiffTagType::Long8, 1, 64),
+        ], Some(next))
+    }
+
+    #[test]
+    fn index_round_trip() {
+        let ifds = vec![ifd(20, 1000, 2000), ifd(31, 3000, 0)];
+        let index = IfdIndex::new(&ifds, &[16, 2000], (4000, 7)).unwrap();
+
+        let mut buffer = Cursor::new(Vec::new());
+        index.write(&mut buffer).unwrap();
+        buffer.set_position(0);
+        let index = IfdIndex::read_from(&mut buffer).unwrap();
+        assert!(index.is_current((4000, 7)));
+        assert!(!index.is_current((4001, 7)));
+
+        let (read_ifds, offsets) = index.into_ifds();
+        assert_eq!(offsets, vec![16, 2000]);
+        read_ifds.iter().zip(&ifds).for_each(|(read, original)| {
+            assert_eq!(read.tags(), original.tags());
+            assert_eq!(read.next_ifd, original.next_ifd);
+        });
+
+        // A tag that isn't stored per-frame changed
+        let mut odd = ifd(20, 1000, 0).tags().clone();
+        odd[4].num_values = 2;
+        let ifds = vec![ifd(20, 1000, 2000), BigTiffIFD::from_tags(odd, None)];
+        assert!(IfdIndex::new(&ifds, &[16, 2000], (4000, 7)).is_err());
+
+        assert_eq!(index_path("/data/file.siff"), PathBuf::from("/data/file.siff.idx"));
+    }
+
+    #[test]
+    fn corrupt_counts() {
+        let index = IfdIndex::new(&[ifd(20, 1000, 0)], &[16], (4000, 7)).unwrap();
+        let mut buffer = Cursor::new(Vec::new());
+        index.write(&mut buffer).unwrap();
+        let bytes = buffer.into_inner();
+
+        // `num_tags` follows the magic, size, and modification time
+        let mut corrupt = bytes.clone();
+        corrupt[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(IfdIndex::read_from(&mut Cursor::new(corrupt)).is_err());
+
+        let num_frames_at = 32 + 5 * TAG_SIZE as usize;
+        let mut corrupt = bytes.clone();
+        corrupt[num_frames_at..num_frames_at + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert!(IfdIndex::read_from(&mut Cursor::new(corrupt)).is_err());
+
+        assert!(IfdIndex::read_from(&mut Cursor::new(bytes)).is_ok());
+    }
+}
@@ -5,9 +5,13 @@
 //! as vectors in the tag itself or as pointers to the
 //! data in the file.
 
-use std::convert::TryFrom;
 use std::fmt::Display;
-use binrw::BinRead;
+use std::io::{
+    Error as IOError,
+    ErrorKind as IOErrorKind,
+    Read, Seek, SeekFrom,
+};
+use binrw::{BinRead, BinWrite};
 
 /// The ValueType field can either
 /// be of size u32 or u64, depending
@@ -42,11 +46,64 @@ pub trait Tag {
     fn tag_dtype(&self) -> TiffTagType;
     fn num_values(&self) -> Self::ValueType;
     fn value(&self) -> Self::ValueType;
+
+    /// The number of bytes taken up by all of the values
+    /// of the tag, wherever they're stored.
+    fn data_size(&self) -> u64 {
+        self.num_values().into().saturating_mul(self.tag_dtype().size_of())
+    }
+
+    /// Whether the values of the tag are stored in the tag
+    /// itself (`true`) or `value` is a pointer to them (`false`).
+    fn is_inline(&self) -> bool {
+        self.data_size() <= std::mem::size_of::<Self::ValueType>() as u64
+    }
+
+    /// Decodes the values of the tag if they're stored in the
+    /// tag itself, without needing the file. Returns `None` if
+    /// the values are stored elsewhere in the file.
+    fn inline_value(&self) -> Option<TagValue> {
+        self.is_inline().then(|| TagValue::from_le_bytes(
+            self.tag_dtype(),
+            &self.value().into().to_le_bytes()[..self.data_size() as usize],
+        ))
+    }
+
     /// Parses the tag data from its raw reads into
-    /// real data. For many data types, that's simply
-    /// returning the value, but for some it may be
-    /// a transformation of the data.
-    fn parse(&self);
+    /// real data. Values that fit in the tag are decoded
+    /// directly, otherwise they're read from the offset
+    /// the tag points to. Returns the reader to its original
+    /// position.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `reader` - The file containing the tag
+    /// 
+    /// ## Errors
+    /// 
+    /// * `IOError` - If the values can't be read from the file,
+    /// e.g. the tag points past its end.
+    fn parse<R : Read + Seek>(&self, reader : &mut R) -> std::io::Result<TagValue> {
+        if let Some(value) = self.inline_value() {
+            return Ok(value);
+        }
+        let size = self.data_size();
+        let pos = reader.stream_position()?;
+        reader.seek(SeekFrom::Start(self.value().into()))?;
+        // Read through `take` so a corrupt count can't allocate the world
+        let mut bytes = Vec::new();
+        let read = reader.by_ref().take(size).read_to_end(&mut bytes);
+        reader.seek(SeekFrom::Start(pos))?;
+        read?;
+        if (bytes.len() as u64) < size {
+            return Err(IOError::new(
+                IOErrorKind::UnexpectedEof,
+                format!("Values of tag {} extend past the end of the file", self.tag())
+            ));
+        }
+        Ok(TagValue::from_le_bytes(self.tag_dtype(), &bytes))
+    }
+
     fn to_string(&self) -> String {
         format!("{}", self.tag())
     }
@@ -56,10 +113,10 @@ pub trait Tag {
 #[derive(BinRead, Debug)]
 #[br(little)]
 pub struct TiffTag {
-    #[br(map  = |x: u16| TiffTagID::try_from(x).unwrap())]
+    #[br(map = |x : u16| TiffTagID::from(x))]
     //#[bw(map = |x : &TiffTagID| x.into())]
     pub tag : TiffTagID,
-    #[br(map = |x: u16| TiffTagType::try_from(x).unwrap())]
+    #[br(map = |x : u16| TiffTagType::from(x))]
     //#[bw(map = |x : &TiffTagType| x.into())]
     pub tag_dtype : TiffTagType,
     pub num_values : u32,
@@ -85,9 +142,6 @@ impl Tag for TiffTag {
         self.value.clone()
     }
 
-    fn parse(&self) {
-    }
-
     fn sizeof() -> usize {
         std::mem::size_of::<u16>() 
         + std::mem::size_of::<u16>()
@@ -96,20 +150,50 @@ impl Tag for TiffTag {
     }
 }
 
-#[derive(BinRead, Debug)]
+#[derive(BinRead, BinWrite, Debug, Clone, PartialEq)]
 #[br(little)]
+#[bw(little)]
 pub struct BigTag {
-    #[br(map  = |x: u16| TiffTagID::try_from(x).unwrap())]
-    //#[bw(map = |x : &TiffTagID| x.into())]
+    #[br(map = |x : u16| TiffTagID::from(x))]
+    #[bw(map = |x : &TiffTagID| u16::from(*x))]
     pub tag : TiffTagID,
-    #[br(map = |x: u16| TiffTagType::try_from(x).unwrap())]
-    //#[bw(map = |x : &TiffTagType| x.into())]
+    #[br(map = |x : u16| TiffTagType::from(x))]
+    #[bw(map = |x : &TiffTagType| u16::from(*x))]
     pub tag_dtype : TiffTagType,
     //bytes_per_value : u64,
     pub num_values : u64,
     pub value : u64,
 }
 
+impl BigTag {
+    /// Creates a new `BigTag` -- used when writing
+    /// IFDs rather than reading them.
+    /// 
+    /// ## Arguments
+    /// 
+    /// * `tag` - The `TiffTagID` of the tag
+    /// * `tag_dtype` - The type of the data the tag refers to
+    /// * `num_values` - The number of values of type `tag_dtype`
+    /// * `value` - Either the value itself or a pointer to the values
+    pub fn new(tag : TiffTagID, tag_dtype : TiffTagType, num_values : u64, value : u64) -> Self {
+        BigTag { tag, tag_dtype, num_values, value }
+    }
+}
+
+/// Widens a classic Tiff tag into a `BigTag`. The value is
+/// copied as-is, so any value that was stored out-of-line in the
+/// classic file (i.e. more than 4 bytes of data) is still a pointer.
+impl From<TiffTag> for BigTag {
+    fn from(tag : TiffTag) -> Self {
+        BigTag {
+            tag : tag.tag,
+            tag_dtype : tag.tag_dtype,
+            num_values : tag.num_values as u64,
+            value : tag.value as u64,
+        }
+    }
+}
+
 impl Tag for BigTag {
     type ValueType = u64;
 
@@ -129,10 +213,6 @@ impl Tag for BigTag {
         self.value
     }
 
-    fn parse(&self) {
-        // Do nothing for now
-    }
-
     fn sizeof() -> usize {
         std::mem::size_of::<u16>() 
         + std::mem::size_of::<u16>()
@@ -169,6 +249,9 @@ impl Tag for BigTag {
 /// * `ExtraSamples` - The number of extra samples.
 /// * `SampleFormat` - The format of the samples.
 /// * `Siff` - SiffCompressed form if 1, straight photon counts if 0.
+/// * `Unknown(id)` - Any other tag, e.g. the private tags written by
+/// ImageJ or OME tools. Kept by its numeric id so it can be copied
+/// back out unchanged.
 /// 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TiffTagID {
@@ -194,6 +277,7 @@ pub enum TiffTagID {
     ExtraSamples,
     SampleFormat,
     Siff, // SiffCompress if 1
+    Unknown(u16),
 }
 
 impl From<TiffTagID> for u16 {
@@ -221,6 +305,7 @@ impl From<TiffTagID> for u16 {
             TiffTagID::ExtraSamples => 338,
             TiffTagID::SampleFormat => 339,
             TiffTagID::Siff => 907,
+            TiffTagID::Unknown(id) => id,
         }
     }
 }
@@ -254,34 +339,32 @@ impl From<TiffTagID> for u16 {
 //     }
 // }
 
-impl TryFrom<u16> for TiffTagID {
-    type Error = ();
-
-    fn try_from(v: u16) -> Result<Self, Self::Error> {
+impl From<u16> for TiffTagID {
+    fn from(v: u16) -> Self {
         match v {
-            256 => Ok(TiffTagID::ImageWidth),
-            257 => Ok(TiffTagID::ImageLength),
-            258 => Ok(TiffTagID::BitsPerSample),
-            259 => Ok(TiffTagID::Compression),
-            262 => Ok(TiffTagID::PhotometricInterpretation),
-            270 => Ok(TiffTagID::ImageDescription),
-            273 => Ok(TiffTagID::StripOffsets),
-            274 => Ok(TiffTagID::Orientation),
-            277 => Ok(TiffTagID::SamplesPerPixel),
-            278 => Ok(TiffTagID::RowsPerStrip),
-            279 => Ok(TiffTagID::StripByteCounts),
-            282 => Ok(TiffTagID::XResolution),
-            283 => Ok(TiffTagID::YResolution),
-            284 => Ok(TiffTagID::PlanarConfiguration),
-            296 => Ok(TiffTagID::ResolutionUnit),
-            305 => Ok(TiffTagID::Software),
-            306 => Ok(TiffTagID::DateTime),
-            315 => Ok(TiffTagID::Artist),
-            317 => Ok(TiffTagID::Predictor),
-            338 => Ok(TiffTagID::ExtraSamples),
-            339 => Ok(TiffTagID::SampleFormat),
-            907 => Ok(TiffTagID::Siff),
-            _ => Err(()),
+            256 => TiffTagID::ImageWidth,
+            257 => TiffTagID::ImageLength,
+            258 => TiffTagID::BitsPerSample,
+            259 => TiffTagID::Compression,
+            262 => TiffTagID::PhotometricInterpretation,
+            270 => TiffTagID::ImageDescription,
+            273 => TiffTagID::StripOffsets,
+            274 => TiffTagID::Orientation,
+            277 => TiffTagID::SamplesPerPixel,
+            278 => TiffTagID::RowsPerStrip,
+            279 => TiffTagID::StripByteCounts,
+            282 => TiffTagID::XResolution,
+            283 => TiffTagID::YResolution,
+            284 => TiffTagID::PlanarConfiguration,
+            296 => TiffTagID::ResolutionUnit,
+            305 => TiffTagID::Software,
+            306 => TiffTagID::DateTime,
+            315 => TiffTagID::Artist,
+            317 => TiffTagID::Predictor,
+            338 => TiffTagID::ExtraSamples,
+            339 => TiffTagID::SampleFormat,
+            907 => TiffTagID::Siff,
+            _ => TiffTagID::Unknown(v),
         }
     }
 }
@@ -292,6 +375,15 @@ impl Display for TiffTagID {
     }
 }
 
+/// The type of the values of a tag.
+///
+/// ## Variants
+///
+/// One per Tiff type, plus:
+///
+/// * `Unknown(type)` - Any other type. The size of its values is
+/// unknown, so they can't be decoded, but the tag is kept (by its
+/// numeric type) rather than failing the whole IFD.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TiffTagType {
     Byte,
@@ -306,9 +398,11 @@ pub enum TiffTagType {
     SRational,
     Float,
     Double,
+    IFD,
     Long8, // BigTiff only
     SLong8, // BigTiff only
     IFD8, // BigTiff only
+    Unknown(u16),
 }
 
 // impl Into<u16> for TiffTagType {
@@ -333,6 +427,24 @@ pub enum TiffTagType {
 //     }
 // }
 
+impl TiffTagType {
+    /// The size of a single value of this type in bytes,
+    /// 0 for `Unknown` types (so their values are never read
+    /// or moved, only copied as they are).
+    pub fn size_of(&self) -> u64 {
+        match self {
+            TiffTagType::Byte | TiffTagType::Ascii | TiffTagType::SByte
+            | TiffTagType::Undefined => 1,
+            TiffTagType::Short | TiffTagType::SShort => 2,
+            TiffTagType::Long | TiffTagType::SLong | TiffTagType::Float
+            | TiffTagType::IFD => 4,
+            TiffTagType::Rational | TiffTagType::SRational | TiffTagType::Double
+            | TiffTagType::Long8 | TiffTagType::SLong8 | TiffTagType::IFD8 => 8,
+            TiffTagType::Unknown(_) => 0,
+        }
+    }
+}
+
 impl From<TiffTagType> for u16 {
     fn from(tag: TiffTagType) -> Self {
         match tag {
@@ -348,35 +460,149 @@ impl From<TiffTagType> for u16 {
             TiffTagType::SRational => 10,
             TiffTagType::Float => 11,
             TiffTagType::Double => 12,
+            TiffTagType::IFD => 13,
             TiffTagType::Long8 => 16,
             TiffTagType::SLong8 => 17,
             TiffTagType::IFD8 => 18,
+            TiffTagType::Unknown(v) => v,
         }
     }
 }
 
 
-impl TryFrom<u16> for TiffTagType {
-    type Error = ();
-
-    fn try_from(v: u16) -> Result<Self, Self::Error> {
+impl From<u16> for TiffTagType {
+    fn from(v: u16) -> Self {
         match v {
-            1 => Ok(TiffTagType::Byte),
-            2 => Ok(TiffTagType::Ascii),
-            3 => Ok(TiffTagType::Short),
-            4 => Ok(TiffTagType::Long),
-            5 => Ok(TiffTagType::Rational),
-            6 => Ok(TiffTagType::SByte),
-            7 => Ok(TiffTagType::Undefined),
-            8 => Ok(TiffTagType::SShort),
-            9 => Ok(TiffTagType::SLong),
-            10 => Ok(TiffTagType::SRational),
-            11 => Ok(TiffTagType::Float),
-            12 => Ok(TiffTagType::Double),
-            16 => Ok(TiffTagType::Long8),
-            17 => Ok(TiffTagType::SLong8),
-            18 => Ok(TiffTagType::IFD8),
-            _ => Err(()),
+            1 => TiffTagType::Byte,
+            2 => TiffTagType::Ascii,
+            3 => TiffTagType::Short,
+            4 => TiffTagType::Long,
+            5 => TiffTagType::Rational,
+            6 => TiffTagType::SByte,
+            7 => TiffTagType::Undefined,
+            8 => TiffTagType::SShort,
+            9 => TiffTagType::SLong,
+            10 => TiffTagType::SRational,
+            11 => TiffTagType::Float,
+            12 => TiffTagType::Double,
+            13 => TiffTagType::IFD,
+            16 => TiffTagType::Long8,
+            17 => TiffTagType::SLong8,
+            18 => TiffTagType::IFD8,
+            _ => TiffTagType::Unknown(v),
+        }
+    }
+}
+
+/// The decoded values of a tag, as returned by `Tag::parse`.
+/// Every variant but `Ascii` holds one entry per value of the tag.
+/// 
+/// ## Variants
+/// 
+/// One per `TiffTagType`. `Rational` and `SRational` are stored as
+/// (numerator, denominator) pairs, and `Ascii` as a single string
+/// with any trailing `NUL`s removed (several `NUL`-separated strings
+/// are kept in one `String`). The values of `Unknown` types can't be
+/// decoded and are returned as an empty `Undefined`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagValue {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    IFD(Vec<u32>),
+    Long8(Vec<u64>),
+    SLong8(Vec<i64>),
+    IFD8(Vec<u64>),
+}
+
+/// Splits `bytes` into little-endian values of `N` bytes each
+fn le_values<const N : usize, T>(bytes : &[u8], from_bytes : fn([u8; N]) -> T) -> Vec<T> {
+    bytes.chunks_exact(N)
+        .map(|chunk| from_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+impl TagValue {
+    /// Decodes the little-endian bytes of the values of a tag of
+    /// type `dtype`. Any bytes past the last whole value are ignored.
+    pub fn from_le_bytes(dtype : TiffTagType, bytes : &[u8]) -> Self {
+        match dtype {
+            TiffTagType::Byte => TagValue::Byte(bytes.to_vec()),
+            TiffTagType::Ascii => TagValue::Ascii(
+                String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()
+            ),
+            TiffTagType::Short => TagValue::Short(le_values(bytes, u16::from_le_bytes)),
+            TiffTagType::Long => TagValue::Long(le_values(bytes, u32::from_le_bytes)),
+            TiffTagType::Rational => TagValue::Rational(
+                le_values(bytes, u32::from_le_bytes).chunks_exact(2)
+                    .map(|pair| (pair[0], pair[1])).collect()
+            ),
+            TiffTagType::SByte => TagValue::SByte(le_values(bytes, i8::from_le_bytes)),
+            TiffTagType::Undefined | TiffTagType::Unknown(_) => TagValue::Undefined(bytes.to_vec()),
+            TiffTagType::SShort => TagValue::SShort(le_values(bytes, i16::from_le_bytes)),
+            TiffTagType::SLong => TagValue::SLong(le_values(bytes, i32::from_le_bytes)),
+            TiffTagType::SRational => TagValue::SRational(
+                le_values(bytes, i32::from_le_bytes).chunks_exact(2)
+                    .map(|pair| (pair[0], pair[1])).collect()
+            ),
+            TiffTagType::Float => TagValue::Float(le_values(bytes, f32::from_le_bytes)),
+            TiffTagType::Double => TagValue::Double(le_values(bytes, f64::from_le_bytes)),
+            TiffTagType::IFD => TagValue::IFD(le_values(bytes, u32::from_le_bytes)),
+            TiffTagType::Long8 => TagValue::Long8(le_values(bytes, u64::from_le_bytes)),
+            TiffTagType::SLong8 => TagValue::SLong8(le_values(bytes, i64::from_le_bytes)),
+            TiffTagType::IFD8 => TagValue::IFD8(le_values(bytes, u64::from_le_bytes)),
+        }
+    }
+
+    /// The values as unsigned integers (e.g. offsets or counts).
+    /// Returns `None` for signed, fractional, or text values.
+    pub fn as_u64_vec(&self) -> Option<Vec<u64>> {
+        match self {
+            TagValue::Byte(values) => Some(values.iter().map(|&x| x as u64).collect()),
+            TagValue::Short(values) => Some(values.iter().map(|&x| x as u64).collect()),
+            TagValue::Long(values) | TagValue::IFD(values) => Some(values.iter().map(|&x| x as u64).collect()),
+            TagValue::Long8(values) | TagValue::IFD8(values) => Some(values.clone()),
+            _ => None,
+        }
+    }
+
+    /// The values as floats, with rationals divided out.
+    /// Returns `None` for text and `Undefined` values.
+    pub fn as_f64_vec(&self) -> Option<Vec<f64>> {
+        Some(match self {
+            TagValue::Rational(values) => values.iter()
+                .map(|&(num, den)| num as f64 / den as f64).collect(),
+            TagValue::SRational(values) => values.iter()
+                .map(|&(num, den)| num as f64 / den as f64).collect(),
+            TagValue::SByte(values) => values.iter().map(|&x| x as f64).collect(),
+            TagValue::SShort(values) => values.iter().map(|&x| x as f64).collect(),
+            TagValue::SLong(values) => values.iter().map(|&x| x as f64).collect(),
+            TagValue::SLong8(values) => values.iter().map(|&x| x as f64).collect(),
+            TagValue::Float(values) => values.iter().map(|&x| x as f64).collect(),
+            TagValue::Double(values) => values.clone(),
+            _ => self.as_u64_vec()?.into_iter().map(|x| x as f64).collect(),
+        })
+    }
+
+    /// The first value as a float (e.g. a resolution)
+    pub fn first_f64(&self) -> Option<f64> {
+        self.as_f64_vec()?.first().copied()
+    }
+
+    /// The string of an `Ascii` tag, `None` for any other type
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TagValue::Ascii(string) => Some(string),
+            _ => None,
         }
     }
 }
@@ -416,4 +642,109 @@ mod tests {
         assert_eq!(tag.num_values(), 1);
         assert_eq!(tag.value(), (1<<32) + 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn write_bigtiff_tags(){
+        let data = [
+            0x00, 0x01, // Tag ID
+            0x01, 0x00, // Tag Type
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Num Values
+            0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,// Value
+        ];
+        let tag = BigTag::new(TiffTagID::ImageWidth, TiffTagType::Byte, 1, (1<<32) + 1);
+        let mut cursor = Cursor::new(Vec::new());
+        tag.write(&mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), data);
+    }
+
+    #[test]
+    fn unknown_tags(){
+        // ImageJ's private metadata tag, 50839 = 0xC697
+        let data = [
+            0x97, 0xC6, // Tag ID
+            0x07, 0x00, // Tag Type
+            0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // Num Values
+            0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,// Value
+        ];
+        let tag = BigTag::read(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(tag.tag(), TiffTagID::Unknown(50839));
+        assert_eq!(tag.tag_dtype(), TiffTagType::Undefined);
+        assert_eq!(tag.value(), 0x1000);
+
+        let mut cursor = Cursor::new(Vec::new());
+        tag.write(&mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), data);
+
+        let tag = TiffTag::read(&mut Cursor::new(&data[..12])).unwrap();
+        assert_eq!(tag.tag(), TiffTagID::Unknown(50839));
+        assert_eq!(u16::from(TiffTagID::from(907)), 907);
+        assert_eq!(TiffTagID::from(907), TiffTagID::Siff);
+    }
+
+    #[test]
+    fn unknown_tag_type(){
+        let mut data = [
+            0x4A, 0x01, // Tag ID (SubIFDs)
+            0x0D, 0x00, // Tag Type (13 is IFD)
+            0x01, 0x00, 0x00, 0x00, // Num Values
+            0x00, 0x20, 0x00, 0x00, // Value
+        ];
+        let tag = TiffTag::read(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(tag.tag_dtype(), TiffTagType::IFD);
+        assert_eq!(tag.inline_value(), Some(TagValue::IFD(vec![0x2000])));
+
+        // Not a Tiff type, but the tag is still read
+        data[2] = 0x99;
+        let tag = TiffTag::read(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(tag.tag_dtype(), TiffTagType::Unknown(0x99));
+        assert_eq!(tag.value(), 0x2000);
+        assert_eq!(tag.inline_value(), Some(TagValue::Undefined(vec![])));
+        assert_eq!(u16::from(tag.tag_dtype()), 0x99);
+
+        let tag = BigTag::from(tag);
+        let mut cursor = Cursor::new(Vec::new());
+        tag.write(&mut cursor).unwrap();
+        assert_eq!(BigTag::read(&mut Cursor::new(cursor.into_inner())).unwrap(), tag);
+    }
+
+    #[test]
+    fn parse_tag_values(){
+        // Values past the tags: a string, then two rationals
+        let mut file = vec![0u8; 16];
+        file.extend_from_slice(b"Fiji 2.14\0\0");
+        [72u32, 1, 5, 2].iter().for_each(|x| file.extend_from_slice(&x.to_le_bytes()));
+        let mut cursor = Cursor::new(file);
+        cursor.set_position(3);
+
+        // Out-of-line in a classic Tiff
+        let ascii = TiffTag {
+            tag : TiffTagID::Software, tag_dtype : TiffTagType::Ascii, num_values : 11, value : 16
+        };
+        assert!(!ascii.is_inline());
+        assert_eq!(ascii.parse(&mut cursor).unwrap().as_str(), Some("Fiji 2.14"));
+        assert_eq!(cursor.position(), 3);
+
+        let rationals = TiffTag {
+            tag : TiffTagID::XResolution, tag_dtype : TiffTagType::Rational, num_values : 2, value : 27
+        };
+        let value = rationals.parse(&mut cursor).unwrap();
+        assert_eq!(value, TagValue::Rational(vec![(72, 1), (5, 2)]));
+        assert_eq!(value.as_f64_vec(), Some(vec![72.0, 2.5]));
+
+        // Inline in a BigTiff
+        let resolution = BigTag::new(
+            TiffTagID::XResolution, TiffTagType::Rational, 1, 3 | (4 << 32)
+        );
+        assert_eq!(resolution.inline_value().unwrap().first_f64(), Some(0.75));
+        let shorts = BigTag::new(
+            TiffTagID::BitsPerSample, TiffTagType::Short, 3, 8 | (8 << 16) | (16 << 32)
+        );
+        assert_eq!(shorts.inline_value().unwrap().as_u64_vec(), Some(vec![8, 8, 16]));
+        assert_eq!(shorts.parse(&mut cursor).unwrap(), TagValue::Short(vec![8, 8, 16]));
+
+        // Past the end of the file
+        let truncated = BigTag::new(TiffTagID::Artist, TiffTagType::Ascii, 100, 20);
+        assert!(truncated.parse(&mut cursor).is_err());
+        assert_eq!(cursor.position(), 3);
+    }
+}
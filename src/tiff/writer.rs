@@ -0,0 +1,563 @@
+//! # Writer
+//!
+//! Low-level helpers for writing `BigTiff` (or, for
+//! small files, classic Tiff) IFDs and the data that
+//! follows them. Like the rest
+//! of the `tiff` module, this knows nothing about
+//! imaging -- the caller decides what the tags mean
+//! and what bytes belong in the data strip.
+//!
+//! Every "page" is laid out the way ScanImage lays
+//! out its frames:
+//!
+//! `[IFD][ImageDescription][pre-strip data][strip]`
+//!
+//! so that the metadata string of a frame can be found
+//! by subtracting `ImageDescription` from `StripOffsets`
+//! (and the compressed `.siff` intensity image sits
+//! immediately before the arrival times).
+
+use binrw::{
+    BinWrite,
+    io::{Seek, SeekFrom, Write},
+};
+
+use crate::tiff::{
+    FileFormat,
+    tags::{BigTag, Tag, TiffTag, TiffTagID, TiffTagType},
+};
+
+/// Which kind of Tiff file to write pages into.
+///
+/// ## Variants
+///
+/// * `BigTiff` - 64 bit offsets, so files of any size
+///
+/// * `Classic` - 32 bit offsets, so files up to 4 GB. Some
+/// readers (e.g. ImageJ's own hyperstack reader) only read
+/// these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TiffKind {
+    #[default]
+    BigTiff,
+    Classic,
+}
+
+/// Returns the size in bytes of a `BigTiff` IFD with
+/// `num_tags` tags, including the tag count and the
+/// pointer to the next IFD.
+pub fn bigtiff_ifd_size(num_tags : usize) -> u64 {
+    (
+        std::mem::size_of::<u64>()
+        + num_tags * BigTag::sizeof()
+        + std::mem::size_of::<u64>()
+    ) as u64
+}
+
+/// Writes a plain (non-ScanImage) `BigTiff` header at the
+/// start of the `writer`, pointing to a first IFD immediately
+/// after it. Leaves the writer at the end of the header.
+pub fn write_bigtiff_header<W : Write + Seek>(writer : &mut W) -> binrw::io::Result<()> {
+    // endian (2) + tiff magic (2) + pointer size (2) + pad (2) + first_ifd (8)
+    let header_size = 16u64;
+    writer.seek(SeekFrom::Start(0))?;
+    writer.write_all(b"II")?;
+    writer.write_all(&43u16.to_le_bytes())?;
+    writer.write_all(&8u16.to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())?;
+    writer.write_all(&header_size.to_le_bytes())?;
+    Ok(())
+}
+
+/// Returns the size in bytes of a classic Tiff IFD with
+/// `num_tags` tags, including the tag count and the
+/// pointer to the next IFD.
+pub fn classic_ifd_size(num_tags : usize) -> u64 {
+    (
+        std::mem::size_of::<u16>()
+        + num_tags * TiffTag::sizeof()
+        + std::mem::size_of::<u32>()
+    ) as u64
+}
+
+/// Writes a plain classic Tiff header at the start of the
+/// `writer`, pointing to a first IFD immediately after it.
+/// Leaves the writer at the end of the header.
+pub fn write_classic_header<W : Write + Seek>(writer : &mut W) -> binrw::io::Result<()> {
+    // endian (2) + tiff magic (2) + first_ifd (4)
+    let header_size = 8u32;
+    writer.seek(SeekFrom::Start(0))?;
+    writer.write_all(b"II")?;
+    writer.write_all(&42u16.to_le_bytes())?;
+    writer.write_all(&header_size.to_le_bytes())?;
+    Ok(())
+}
+
+/// The tags needed to describe a single-channel, uncompressed
+/// image stored in one strip (less the ones computed by
+/// `write_bigtiff_page`).
+///
+/// ## Arguments
+///
+/// * `shape` - The shape of the image `(y, x)`
+///
+/// * `bits_per_sample` - The size of each pixel in bits
+///
+/// * `sample_format` - `1` for unsigned integers, `2` for signed
+/// integers, and `3` for floating point
+pub fn image_tags(shape : (usize, usize), bits_per_sample : u16, sample_format : u16)
+-> Vec<BigTag> {
+    let (ydim, xdim) = (shape.0 as u64, shape.1 as u64);
+    vec![
+        BigTag::new(TiffTagID::ImageWidth, TiffTagType::Long, 1, xdim),
+        BigTag::new(TiffTagID::ImageLength, TiffTagType::Long, 1, ydim),
+        BigTag::new(TiffTagID::BitsPerSample, TiffTagType::Short, 1, bits_per_sample as u64),
+        BigTag::new(TiffTagID::Compression, TiffTagType::Short, 1, 1),
+        BigTag::new(TiffTagID::PhotometricInterpretation, TiffTagType::Short, 1, 1),
+        BigTag::new(TiffTagID::SamplesPerPixel, TiffTagType::Short, 1, 1),
+        BigTag::new(TiffTagID::RowsPerStrip, TiffTagType::Long, 1, ydim),
+        BigTag::new(TiffTagID::PlanarConfiguration, TiffTagType::Short, 1, 1),
+        BigTag::new(TiffTagID::SampleFormat, TiffTagType::Short, 1, sample_format as u64),
+    ]
+}
+
+/// The tags ScanImage adds to every frame beyond those of
+/// `image_tags`: unit orientation and resolution, and the
+/// locations of the NVFD and ROI strings in the header.
+///
+/// ## Arguments
+///
+/// * `header` - The `FileFormat` whose header starts the file
+/// the pages are written to
+pub fn scanimage_tags(header : &FileFormat) -> Vec<BigTag> {
+    // Rationals fit inline in a BigTiff tag: numerator, then denominator.
+    let unit_rational = 1u64 | (1u64 << 32);
+    vec![
+        BigTag::new(TiffTagID::Orientation, TiffTagType::Short, 1, 1),
+        BigTag::new(TiffTagID::XResolution, TiffTagType::Rational, 1, unit_rational),
+        BigTag::new(TiffTagID::YResolution, TiffTagType::Rational, 1, unit_rational),
+        BigTag::new(TiffTagID::ResolutionUnit, TiffTagType::Short, 1, 1),
+        BigTag::new(
+            TiffTagID::Software,
+            TiffTagType::Ascii,
+            header.nvfd.len() as u64,
+            header.nvfd_offset()
+        ),
+        BigTag::new(
+            TiffTagID::Artist,
+            TiffTagType::Ascii,
+            header.roi_string.len() as u64,
+            header.roi_string_offset()
+        ),
+    ]
+}
+
+/// Writes one page of a `BigTiff` file at the current
+/// position of the `writer`: the IFD, its description,
+/// any data that should precede the strip, and the strip
+/// itself. The `ImageDescription`, `StripOffsets`, and
+/// `StripByteCounts` tags are computed here (and replace
+/// any provided in `tags`). The pointer to the next IFD
+/// is written as `0`, so the file is always terminated --
+/// use `link_ifd` to point it at a subsequent page.
+///
+/// ## Arguments
+///
+/// * `writer` - Where to write the page. The page begins at
+/// the current position of the writer.
+///
+/// * `tags` - The tags to write in the IFD. Sorted by tag
+/// number before writing, as the TIFF specification requires.
+///
+/// * `description` - The bytes of the `ImageDescription`
+///
+/// * `pre_strip` - Bytes written between the description and
+/// the strip (e.g. the intensity image of a compressed `.siff` frame).
+///
+/// * `strip` - The data strip itself.
+///
+/// ## Returns
+///
+/// * `binrw::io::Result<u64>` - The position in the file of this
+/// page's next-IFD pointer.
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// let first_pointer = write_bigtiff_page(&mut writer, &tags, b"", &[], &strip)?;
+/// let second_page = writer.stream_position()?;
+/// write_bigtiff_page(&mut writer, &tags, b"", &[], &strip)?;
+/// link_ifd(&mut writer, first_pointer, second_page)?;
+/// ```
+pub fn write_bigtiff_page<W : Write + Seek>(
+    writer : &mut W,
+    tags : &[BigTag],
+    description : &[u8],
+    pre_strip : &[u8],
+    strip : &[u8],
+) -> binrw::io::Result<u64> {
+
+    let mut tags = tags.iter().filter(|tag| !matches!(
+            tag.tag(),
+            TiffTagID::ImageDescription | TiffTagID::StripOffsets | TiffTagID::StripByteCounts
+        ))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let start_of_ifd = writer.stream_position()?;
+    let end_of_ifd = start_of_ifd + bigtiff_ifd_size(tags.len() + 3);
+    let strip_offset = end_of_ifd + description.len() as u64 + pre_strip.len() as u64;
+
+    tags.push(BigTag::new(
+        TiffTagID::ImageDescription, TiffTagType::Ascii, description.len() as u64, end_of_ifd
+    ));
+    tags.push(BigTag::new(
+        TiffTagID::StripOffsets, TiffTagType::Long8, 1, strip_offset
+    ));
+    tags.push(BigTag::new(
+        TiffTagID::StripByteCounts, TiffTagType::Long8, 1, strip.len() as u64
+    ));
+    tags.sort_by_key(|tag| u16::from(tag.tag()));
+
+    writer.write_all(&(tags.len() as u64).to_le_bytes())?;
+    for tag in tags.iter() {
+        tag.write(writer).map_err(
+            |err| binrw::io::Error::new(binrw::io::ErrorKind::Other, err)
+        )?;
+    }
+    let next_ifd_pointer = writer.stream_position()?;
+    writer.write_all(&0u64.to_le_bytes())?;
+    debug_assert_eq!(writer.stream_position()?, end_of_ifd);
+
+    writer.write_all(description)?;
+    writer.write_all(pre_strip)?;
+    writer.write_all(strip)?;
+
+    Ok(next_ifd_pointer)
+}
+
+/// Writes one page of a classic Tiff file at the current
+/// position of the `writer`, laid out and with its tags
+/// computed as in `write_bigtiff_page`.
+///
+/// ## Returns
+///
+/// * `binrw::io::Result<u64>` - The position in the file of this
+/// page's next-IFD pointer.
+///
+/// ## Errors
+///
+/// * `binrw::io::Error` (`InvalidInput`) - If the page would end
+/// past the 4 GB a classic Tiff can address, or a tag's value
+/// doesn't fit in a classic IFD (e.g. a `Rational`, which
+/// `BigTag`s store inline).
+pub fn write_classic_page<W : Write + Seek>(
+    writer : &mut W,
+    tags : &[BigTag],
+    description : &[u8],
+    pre_strip : &[u8],
+    strip : &[u8],
+) -> binrw::io::Result<u64> {
+
+    let mut tags = tags.iter().filter(|tag| !matches!(
+            tag.tag(),
+            TiffTagID::ImageDescription | TiffTagID::StripOffsets | TiffTagID::StripByteCounts
+        ))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let start_of_ifd = writer.stream_position()?;
+    let end_of_ifd = start_of_ifd + classic_ifd_size(tags.len() + 3);
+    let strip_offset = end_of_ifd + description.len() as u64 + pre_strip.len() as u64;
+    if strip_offset + strip.len() as u64 > u32::MAX as u64 {
+        return Err(binrw::io::Error::new(
+            binrw::io::ErrorKind::InvalidInput,
+            "A classic Tiff cannot be larger than 4 GB"
+        ));
+    }
+
+    tags.push(BigTag::new(
+        TiffTagID::ImageDescription, TiffTagType::Ascii, description.len() as u64, end_of_ifd
+    ));
+    tags.push(BigTag::new(
+        TiffTagID::StripOffsets, TiffTagType::Long, 1, strip_offset
+    ));
+    tags.push(BigTag::new(
+        TiffTagID::StripByteCounts, TiffTagType::Long, 1, strip.len() as u64
+    ));
+    tags.sort_by_key(|tag| u16::from(tag.tag()));
+
+    writer.write_all(&(tags.len() as u16).to_le_bytes())?;
+    for tag in tags.iter() {
+        let num_bytes = tag.num_values * tag.tag_dtype.size_of();
+        let inline_value = num_bytes > 4 && num_bytes <= 8
+            && tag.tag != TiffTagID::ImageDescription;
+        let (num_values, value) = match (u32::try_from(tag.num_values), u32::try_from(tag.value)) {
+            (Ok(num_values), Ok(value)) if !inline_value => (num_values, value),
+            _ => return Err(binrw::io::Error::new(
+                binrw::io::ErrorKind::InvalidInput,
+                format!("Tag {} does not fit in a classic Tiff IFD", tag.tag)
+            )),
+        };
+        writer.write_all(&u16::from(tag.tag).to_le_bytes())?;
+        writer.write_all(&u16::from(tag.tag_dtype).to_le_bytes())?;
+        writer.write_all(&num_values.to_le_bytes())?;
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    let next_ifd_pointer = writer.stream_position()?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    debug_assert_eq!(writer.stream_position()?, end_of_ifd);
+
+    writer.write_all(description)?;
+    writer.write_all(pre_strip)?;
+    writer.write_all(strip)?;
+
+    Ok(next_ifd_pointer)
+}
+
+/// Points the next-IFD pointer at `pointer_location` to the
+/// IFD at `ifd_location`, then returns the writer to its
+/// original position.
+///
+/// ## Arguments
+///
+/// * `writer` - The writer containing both IFDs
+///
+/// * `pointer_location` - Where the pointer to overwrite is in the file
+/// (as returned by `write_bigtiff_page`)
+///
+/// * `ifd_location` - The location of the IFD to point to
+pub fn link_ifd<W : Write + Seek>(
+    writer : &mut W,
+    pointer_location : u64,
+    ifd_location : u64,
+) -> binrw::io::Result<()> {
+    let pos = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(pointer_location))?;
+    writer.write_all(&ifd_location.to_le_bytes())?;
+    writer.seek(SeekFrom::Start(pos))?;
+    Ok(())
+}
+
+/// Points the 32 bit next-IFD pointer of a classic Tiff at
+/// `pointer_location` to the IFD at `ifd_location`, then returns
+/// the writer to its original position.
+///
+/// ## Errors
+///
+/// * `binrw::io::Error` (`InvalidInput`) - If `ifd_location` is past
+/// the 4 GB a classic Tiff can address.
+pub fn link_classic_ifd<W : Write + Seek>(
+    writer : &mut W,
+    pointer_location : u64,
+    ifd_location : u64,
+) -> binrw::io::Result<()> {
+    let ifd_location = u32::try_from(ifd_location).map_err(|_| binrw::io::Error::new(
+        binrw::io::ErrorKind::InvalidInput,
+        "A classic Tiff cannot be larger than 4 GB"
+    ))?;
+    let pos = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(pointer_location))?;
+    writer.write_all(&ifd_location.to_le_bytes())?;
+    writer.seek(SeekFrom::Start(pos))?;
+    Ok(())
+}
+
+/// Writes each `(description, strip)` of `pages` as a page with
+/// `tags` (as in `write_bigtiff_page` or `write_classic_page`, by
+/// `kind`), starting at the current position of the `writer` and
+/// pointing each page's IFD to the next. The last page is left
+/// terminated. Stops at the first `Err` in `pages`, so the pages can
+/// be computed as they are written.
+///
+/// ## Arguments
+///
+/// * `writer` - Where to write the pages
+///
+/// * `kind` - Whether to write `BigTiff` or classic Tiff IFDs
+/// (matching the header of the file)
+///
+/// * `tags` - The tags to write in every IFD
+///
+/// * `pages` - The description and strip of each page, in order
+///
+/// ## Example
+///
+/// ```rust, ignore
+/// write_bigtiff_header(&mut writer)?;
+/// write_linked_pages(
+///     &mut writer,
+///     TiffKind::BigTiff,
+///     &image_tags((256, 256), 16, 1),
+///     strips.into_iter().map(|strip| Ok::<_, std::io::Error>((b"", strip))),
+/// )?;
+/// ```
+pub fn write_linked_pages<W, I, D, S, E>(
+    writer : &mut W,
+    kind : TiffKind,
+    tags : &[BigTag],
+    pages : I,
+) -> Result<(), E>
+where
+    W : Write + Seek,
+    I : IntoIterator<Item = Result<(D, S), E>>,
+    D : AsRef<[u8]>,
+    S : AsRef<[u8]>,
+    E : From<binrw::io::Error>,
+{
+    let mut last_pointer = None;
+    for page in pages {
+        let (description, strip) = page?;
+        let start_of_ifd = writer.stream_position()?;
+        if let Some(pointer) = last_pointer {
+            match kind {
+                TiffKind::BigTiff => link_ifd(writer, pointer, start_of_ifd)?,
+                TiffKind::Classic => link_classic_ifd(writer, pointer, start_of_ifd)?,
+            }
+        }
+        last_pointer = Some(match kind {
+            TiffKind::BigTiff => write_bigtiff_page(
+                writer, tags, description.as_ref(), &[], strip.as_ref()
+            )?,
+            TiffKind::Classic => write_classic_page(
+                writer, tags, description.as_ref(), &[], strip.as_ref()
+            )?,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binrw::{BinRead, io::Cursor};
+    use crate::tiff::{BigTiffIFD, IFD};
+
+    #[test]
+    fn write_and_link_pages() {
+        let mut cursor = Cursor::new(Vec::new());
+        let tags = vec![
+            BigTag::new(TiffTagID::ImageLength, TiffTagType::Long, 1, 2),
+            BigTag::new(TiffTagID::ImageWidth, TiffTagType::Long, 1, 3),
+        ];
+        let strip = [7u8; 12];
+        let first_pointer = write_bigtiff_page(
+            &mut cursor, &tags, b"first", &[1, 2], &strip
+        ).unwrap();
+        let second_page = cursor.stream_position().unwrap();
+        write_bigtiff_page(&mut cursor, &tags, b"second", &[], &strip).unwrap();
+        link_ifd(&mut cursor, first_pointer, second_page).unwrap();
+
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        let first = BigTiffIFD::read(&mut cursor).unwrap();
+        assert_eq!(first.num_tags(), 5);
+        assert_eq!(first.next_ifd(), Some(second_page));
+        // Sorted by tag number
+        assert_eq!(first.tags()[0].tag(), TiffTagID::ImageWidth);
+        assert_eq!(
+            first.get_tag(TiffTagID::ImageDescription).unwrap().value(),
+            bigtiff_ifd_size(5)
+        );
+        assert_eq!(
+            first.get_tag(TiffTagID::StripOffsets).unwrap().value(),
+            bigtiff_ifd_size(5) + 5 + 2
+        );
+
+        cursor.seek(SeekFrom::Start(second_page)).unwrap();
+        let second = BigTiffIFD::read(&mut cursor).unwrap();
+        assert_eq!(second.next_ifd(), Some(0));
+        assert_eq!(
+            second.get_tag(TiffTagID::StripByteCounts).unwrap().value(),
+            12
+        );
+    }
+
+    #[test]
+    fn write_plain_bigtiff() {
+        let mut cursor = Cursor::new(Vec::new());
+        write_bigtiff_header(&mut cursor).unwrap();
+        write_bigtiff_page(&mut cursor, &image_tags((2, 2), 16, 1), b"", &[], &[0; 8])
+            .unwrap();
+
+        let bytes = cursor.into_inner();
+        assert_eq!(&bytes[..4], &[73, 73, 43, 0]);
+        assert_eq!(u64::from_le_bytes(bytes[8..16].try_into().unwrap()), 16);
+
+        let ifd = BigTiffIFD::read(&mut Cursor::new(&bytes[16..])).unwrap();
+        assert_eq!(ifd.num_tags(), 12);
+        assert_eq!(ifd.get_tag(TiffTagID::BitsPerSample).unwrap().value(), 16);
+    }
+
+    #[test]
+    fn write_linked_strips() {
+        let mut cursor = Cursor::new(Vec::new());
+        let tags = image_tags((1, 2), 8, 1);
+        write_linked_pages(
+            &mut cursor,
+            TiffKind::BigTiff,
+            &tags,
+            [(b"a", [1u8, 2]), (b"b", [3, 4]), (b"c", [5, 6])].into_iter().map(Ok::<_, binrw::io::Error>),
+        ).unwrap();
+
+        let mut pointer = 0;
+        let mut strips = Vec::new();
+        loop {
+            cursor.seek(SeekFrom::Start(pointer)).unwrap();
+            let ifd = BigTiffIFD::read(&mut cursor).unwrap();
+            let offset = ifd.get_tag(TiffTagID::StripOffsets).unwrap().value() as usize;
+            strips.push(cursor.get_ref()[offset..offset + 2].to_vec());
+            pointer = ifd.next_ifd().unwrap();
+            if pointer == 0 { break; }
+        }
+        assert_eq!(strips, vec![vec![1, 2], vec![3, 4], vec![5, 6]]);
+
+        // Stops at the first error
+        let mut cursor = Cursor::new(Vec::new());
+        let pages = vec![
+            Ok((b"a", [1u8, 2])),
+            Err(binrw::io::Error::new(binrw::io::ErrorKind::Other, "failed")),
+            Ok((b"c", [5, 6])),
+        ];
+        assert!(write_linked_pages(&mut cursor, TiffKind::BigTiff, &tags, pages).is_err());
+        cursor.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(BigTiffIFD::read(&mut cursor).unwrap().next_ifd(), Some(0));
+    }
+
+    #[test]
+    fn write_classic_tiff() {
+        use crate::tiff::ifd::TiffIFD;
+        let mut cursor = Cursor::new(Vec::new());
+        write_classic_header(&mut cursor).unwrap();
+        let tags = image_tags((1, 2), 8, 1);
+        write_linked_pages(
+            &mut cursor,
+            TiffKind::Classic,
+            &tags,
+            [(&b"first"[..], [1u8, 2]), (&b"second"[..], [3, 4])].into_iter().map(Ok::<_, binrw::io::Error>),
+        ).unwrap();
+
+        let bytes = cursor.get_ref().clone();
+        assert_eq!(&bytes[..8], &[73, 73, 42, 0, 8, 0, 0, 0]);
+        cursor.seek(SeekFrom::Start(8)).unwrap();
+        let first = TiffIFD::read(&mut cursor).unwrap();
+        assert_eq!(first.num_tags(), 12);
+        assert_eq!(first.get_tag(TiffTagID::ImageDescription).unwrap().value() as u64, 8 + classic_ifd_size(12));
+        let strip = first.get_tag(TiffTagID::StripOffsets).unwrap().value() as usize;
+        assert_eq!(&bytes[strip..strip + 2], &[1, 2]);
+
+        let second = first.next_ifd().unwrap() as u64;
+        assert_eq!(second, 8 + classic_ifd_size(12) + 5 + 2);
+        cursor.seek(SeekFrom::Start(second)).unwrap();
+        assert_eq!(TiffIFD::read(&mut cursor).unwrap().next_ifd(), Some(0));
+
+        // Offsets past 4 GB can't be stored
+        let mut cursor = Cursor::new(Vec::new());
+        cursor.set_position(u32::MAX as u64 - 16);
+        assert!(write_classic_page(&mut cursor, &tags, b"", &[], &[0; 64]).is_err());
+        assert!(cursor.get_ref().is_empty());
+
+        // Nor can the inline `Rational`s of a `BigTag`
+        let rational = BigTag::new(TiffTagID::XResolution, TiffTagType::Rational, 1, 1 | 1 << 32);
+        assert!(write_classic_page(&mut Cursor::new(Vec::new()), &[rational], b"", &[], &[]).is_err());
+    }
+}
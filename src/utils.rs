@@ -1,6 +1,8 @@
 mod parallelize_op;
+mod source;
 
 pub (super) use parallelize_op::parallelize_op as parallelize_op;
+pub (crate) use source::{SiffSource, SourceReader, FrameRead};
 //pub (super) use parallelize_op::registration_dependent_op;
 
 use crate::data::image::DimensionsError;
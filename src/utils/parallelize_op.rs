@@ -8,19 +8,19 @@
 ///
 ///     <br>
 /// 
-/// - `parallelize_op!(array, chunk_size, frames, filename, op)`
+/// - `parallelize_op!(array, chunk_size, frames, source, op)`
 ///     
 ///     For loading an array along its slow axis in parallel.
 ///     Divides the array into chunks and parallelizes the operation
 ///     `op` on each chunk. The operation `op` should take a slice of
 ///     frames, a mutable reference to a chunk of the array along its 0th axis,
-///     and able to accept a `std::fs::File`, with the signature 
-///     `op(frames : &[u64], chunk : &mut ArrayBase, reader : &mut Reader)`. Opens
-///     local copies of the file for reading.
+///     and able to accept a `SourceReader`, with the signature 
+///     `op(frames : &[u64], chunk : &mut ArrayBase, reader : &mut SourceReader)`. Opens
+///     local readers of the `SiffSource` for each chunk.
 /// 
 ///     <br>
 /// 
-/// - `parallelize_op!((array1, array2, ...), chunk_size, frames, filename, op)`
+/// - `parallelize_op!((array1, array2, ...), chunk_size, frames, source, op)`
 /// 
 ///    For loading multiple arrays in parallel for functions that require
 ///    more than one array to be loaded. The arrays should be passed as a tuple
@@ -42,11 +42,11 @@
 /// 
 ///    <br>
 /// 
-/// - `parallelize_op!(array, chunk_size, frames, filename, reader_call, op)`
+/// - `parallelize_op!(array, chunk_size, frames, source, reader_call, op)`
 ///     
 ///     Allows alternative implementations for creating the reader instead of
-///     producing `&mut File` objects (e.g. `&mut BufReader<&File>`) using a 
-///     closure expecting the `filename` arg.
+///     producing `&mut SourceReader` objects (e.g. `&mut BufReader<SourceReader>`) using a 
+///     closure expecting a `&SiffSource` and returning a `std::io::Result`.
 macro_rules! parallelize_op {
 
     (   $chunk_size : literal,
@@ -76,7 +76,7 @@ macro_rules! parallelize_op {
     (   $array : ident,
         $chunk_size : literal,
         $frames : ident,
-        $filename : expr,
+        $source : expr,
         $op : expr
     ) => {
         let n_threads = $frames.len()/$chunk_size + 1;
@@ -101,7 +101,7 @@ macro_rules! parallelize_op {
             let end = ((chunk_idx + 1) * $chunk_size).min($frames.len());
 
             let local_frames = &$frames[start..end];
-            let mut local_f = File::open(&$filename).unwrap();
+            let mut local_f = $source.open()?;
 
             $op(local_frames, &mut chunk, &mut local_f)
             }
@@ -110,9 +110,9 @@ macro_rules! parallelize_op {
 
     // Multiple arrays as a leading tuple
     (   ( $($array : ident),+ ),
-        $chunk_size : literal,
+        $chunk_size : expr,
         $frames : ident,
-        $filename : expr,
+        $source : expr,
         $op : expr
     ) => {
         let n_threads = $frames.len()/$chunk_size + 1;
@@ -146,7 +146,7 @@ macro_rules! parallelize_op {
             let end = ((chunk_idx + 1) * $chunk_size).min($frames.len());
 
             let local_frames = &$frames[start..end];
-            let mut local_f = File::open(&$filename).unwrap();
+            let mut local_f = $source.open()?;
 
             // Unpack the chunk tuple into separate mutable references
             let ($(ref mut $array),+) = chunk_tuple;
@@ -163,7 +163,7 @@ macro_rules! parallelize_op {
     (   $array : ident,
         $chunk_size : literal,
         $frames : ident,
-        $filename : expr,
+        $source : expr,
         Axis($axis:literal),
         $op : expr
     ) => {
@@ -189,7 +189,7 @@ macro_rules! parallelize_op {
             let end = ((chunk_idx + 1) * $chunk_size).min($frames.len());
 
             let local_frames = &$frames[start..end];
-            let mut local_f = File::open(&$filename).unwrap();
+            let mut local_f = $source.open()?;
 
             $op(local_frames, &mut chunk, &mut local_f)
             }
@@ -199,7 +199,7 @@ macro_rules! parallelize_op {
     (   $array : ident,
         $chunk_size : literal,
         $frames : ident,
-        $filename : expr,
+        $source : expr,
         $reader_call : expr,
         Axis($axis:literal),
         $op : expr
@@ -226,7 +226,7 @@ macro_rules! parallelize_op {
             let end = ((chunk_idx + 1) * $chunk_size).min($frames.len());
 
             let local_frames = &$frames[start..end];
-            let mut local_f = $reader_call($filename);
+            let mut local_f = $reader_call(&$source)?;
 
             $op(local_frames, &mut chunk, &mut local_f)
             }
@@ -236,7 +236,7 @@ macro_rules! parallelize_op {
     (   $array : ident,
         $chunk_size : literal,
         $frames : ident,
-        $filename : expr,
+        $source : expr,
         $reader_call : expr,
         $op : expr
     ) => {
@@ -262,7 +262,7 @@ macro_rules! parallelize_op {
             let end = ((chunk_idx + 1) * $chunk_size).min($frames.len());
 
             let local_frames = &$frames[start..end];
-            let mut local_f = $reader_call($filename);
+            let mut local_f = $reader_call(&$source)?;
 
             $op(local_frames, &mut chunk, &mut local_f)
             }
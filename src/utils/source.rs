@@ -0,0 +1,348 @@
+//! Where a `SiffReader` gets its bytes from. The parallel
+//! methods each open their own reader for every chunk of frames
+//! they process, so a source has to be able to hand out many
+//! independent readers -- either by reopening a path, by sharing
+//! an in-memory buffer (or, with the `mmap` feature, a memory-mapped
+//! file), or by cloning a user-provided reader.
+
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bytemuck::{Pod, PodCastError, pod_collect_to_vec, try_cast_slice};
+
+/// Any reader that can be handed to another thread.
+pub trait ReadSeek : Read + Seek + Send {}
+
+impl<T : Read + Seek + Send> ReadSeek for T {}
+
+/// Produces a fresh reader over the same bytes each time it's called.
+type ReaderFactory = Arc<dyn Fn() -> Box<dyn ReadSeek> + Send + Sync>;
+
+/// The underlying data of a `SiffReader`.
+#[derive(Clone)]
+pub (crate) enum SiffSource {
+    /// A file on disk, reopened for every reader.
+    Path(PathBuf),
+    /// A buffer in memory, shared by every reader.
+    Memory(Arc<[u8]>),
+    /// A cloneable reader, cloned for every reader.
+    Reader(ReaderFactory),
+    /// A memory-mapped file, shared by every reader.
+    #[cfg(feature = "mmap")]
+    Mapped(PathBuf, MappedBytes),
+}
+
+/// A memory map that can be shared across threads
+/// and read through a `Cursor`.
+#[cfg(feature = "mmap")]
+#[derive(Clone)]
+pub (crate) struct MappedBytes(Arc<memmap2::Mmap>);
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MappedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl SiffSource {
+    /// Wraps a cloneable reader. Each reader produced
+    /// by `open` is a clone of `reader` rewound to the
+    /// start of the stream.
+    pub fn from_reader<R>(reader : R) -> Self
+    where R : Read + Seek + Clone + Send + Sync + 'static {
+        SiffSource::Reader(Arc::new(move || Box::new(reader.clone())))
+    }
+
+    /// Maps the file at `path` into memory.
+    ///
+    /// ## Safety
+    ///
+    /// The file must not be modified (e.g. truncated or
+    /// rewritten) while the map is alive -- see `memmap2::Mmap::map`.
+    ///
+    /// ## Errors
+    ///
+    /// * `std::io::Error` - If the file can't be opened or mapped.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn map<P : AsRef<Path>>(path : P) -> std::io::Result<Self> {
+        let file = File::open(&path)?;
+        let map = memmap2::Mmap::map(&file)?;
+        Ok(SiffSource::Mapped(path.as_ref().to_path_buf(), MappedBytes(Arc::new(map))))
+    }
+
+    /// Opens a new, independent reader over the source
+    /// positioned at the start of the data.
+    ///
+    /// ## Errors
+    ///
+    /// * `std::io::Error` - If the file can't be opened or
+    /// the cloned reader can't be rewound.
+    pub fn open(&self) -> std::io::Result<SourceReader> {
+        Ok(match self {
+            SiffSource::Path(path) => SourceReader::File(File::open(path)?),
+            SiffSource::Memory(bytes) => SourceReader::Memory(Cursor::new(bytes.clone())),
+            SiffSource::Reader(factory) => {
+                let mut reader = factory();
+                reader.seek(SeekFrom::Start(0))?;
+                SourceReader::Reader(reader)
+            },
+            #[cfg(feature = "mmap")]
+            SiffSource::Mapped(_, bytes) => SourceReader::Mapped(Cursor::new(bytes.clone())),
+        })
+    }
+
+    /// The path of the file, if the source is on disk.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            SiffSource::Path(path) => Some(path),
+            #[cfg(feature = "mmap")]
+            SiffSource::Mapped(path, _) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Data already in memory that readers can share
+/// rather than copy out of.
+#[derive(Clone)]
+pub enum SharedBytes {
+    Memory(Arc<[u8]>),
+    #[cfg(feature = "mmap")]
+    Mapped(MappedBytes),
+}
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            SharedBytes::Memory(bytes) => bytes,
+            #[cfg(feature = "mmap")]
+            SharedBytes::Mapped(bytes) => bytes.as_ref(),
+        }
+    }
+}
+
+/// Bytes returned by `FrameRead::read_frame_bytes`: either
+/// a window onto data already in memory, or a copy read out
+/// of the source.
+pub enum FrameBytes {
+    Shared(SharedBytes, Range<usize>),
+    Owned(Vec<u8>),
+}
+
+impl Deref for FrameBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FrameBytes::Shared(bytes, range) => &bytes.as_ref()[range.clone()],
+            FrameBytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+impl FrameBytes {
+    /// Views the bytes as a slice of `T`, like `bytemuck::try_cast_slice`.
+    /// Bytes in memory aren't necessarily aligned for `T` (e.g. a
+    /// frame in a memory-mapped file starts wherever the previous one
+    /// ended), in which case they're copied into a new buffer instead.
+    ///
+    /// ## Errors
+    ///
+    /// * `PodCastError` - If the length isn't a multiple of the size of `T`
+    pub fn cast<T : Pod>(&self) -> Result<Cow<'_, [T]>, PodCastError> {
+        match try_cast_slice::<u8, T>(self) {
+            Ok(values) => Ok(Cow::Borrowed(values)),
+            Err(PodCastError::TargetAlignmentGreaterAndInputNotAligned) => {
+                Ok(Cow::Owned(pod_collect_to_vec::<u8, T>(self)))
+            },
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A reader that frame data is decoded from. Readers whose data
+/// is already in memory (a shared buffer or a memory map) lend out
+/// windows onto it, so frames are decoded straight from those bytes
+/// with no copy. Every other reader copies the bytes out with `read_exact`.
+pub trait FrameRead : Read + Seek {
+    /// The entire data of the reader, if it's already in memory.
+    fn shared_bytes(&self) -> Option<SharedBytes> {
+        None
+    }
+
+    /// Reads the next `len` bytes, advancing the reader past them.
+    ///
+    /// ## Errors
+    ///
+    /// * `std::io::Error` - If there are fewer than `len` bytes left,
+    /// or the reader fails.
+    fn read_frame_bytes(&mut self, len : usize) -> std::io::Result<FrameBytes> {
+        match self.shared_bytes() {
+            Some(bytes) => {
+                let start = self.stream_position()? as usize;
+                let end = start.checked_add(len)
+                    .filter(|&end| end <= bytes.as_ref().len())
+                    .ok_or(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "Frame extends past the end of the data"
+                    ))?;
+                self.seek(SeekFrom::Start(end as u64))?;
+                Ok(FrameBytes::Shared(bytes, start..end))
+            },
+            None => {
+                // Read through `take` so a corrupt length can't allocate the world
+                let mut data = Vec::new();
+                Read::take(&mut *self, len as u64).read_to_end(&mut data)?;
+                if data.len() < len {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "Frame extends past the end of the data"
+                    ));
+                }
+                Ok(FrameBytes::Owned(data))
+            },
+        }
+    }
+}
+
+impl FrameRead for File {}
+
+impl<T : AsRef<[u8]>> FrameRead for Cursor<T> {}
+
+/// `BufReader`s keep track of their position in the inner
+/// reader, so windows onto its data start at the right place.
+impl<R : FrameRead> FrameRead for BufReader<R> {
+    fn shared_bytes(&self) -> Option<SharedBytes> {
+        self.get_ref().shared_bytes()
+    }
+}
+
+impl<R : FrameRead> FrameRead for binrw::io::BufReader<R> {
+    fn shared_bytes(&self) -> Option<SharedBytes> {
+        self.get_ref().shared_bytes()
+    }
+}
+
+impl FrameRead for SourceReader {
+    fn shared_bytes(&self) -> Option<SharedBytes> {
+        match self {
+            SourceReader::Memory(cursor) => Some(SharedBytes::Memory(cursor.get_ref().clone())),
+            #[cfg(feature = "mmap")]
+            SourceReader::Mapped(cursor) => Some(SharedBytes::Mapped(cursor.get_ref().clone())),
+            _ => None,
+        }
+    }
+}
+
+/// A reader produced by `SiffSource::open`.
+pub (crate) enum SourceReader {
+    File(File),
+    Memory(Cursor<Arc<[u8]>>),
+    Reader(Box<dyn ReadSeek>),
+    #[cfg(feature = "mmap")]
+    Mapped(Cursor<MappedBytes>),
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf : &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SourceReader::File(file) => file.read(buf),
+            SourceReader::Memory(cursor) => cursor.read(buf),
+            SourceReader::Reader(reader) => reader.read(buf),
+            #[cfg(feature = "mmap")]
+            SourceReader::Mapped(cursor) => cursor.read(buf),
+        }
+    }
+
+    fn read_exact(&mut self, buf : &mut [u8]) -> std::io::Result<()> {
+        match self {
+            SourceReader::File(file) => file.read_exact(buf),
+            SourceReader::Memory(cursor) => cursor.read_exact(buf),
+            SourceReader::Reader(reader) => reader.read_exact(buf),
+            #[cfg(feature = "mmap")]
+            SourceReader::Mapped(cursor) => cursor.read_exact(buf),
+        }
+    }
+}
+
+impl Seek for SourceReader {
+    fn seek(&mut self, pos : SeekFrom) -> std::io::Result<u64> {
+        match self {
+            SourceReader::File(file) => file.seek(pos),
+            SourceReader::Memory(cursor) => cursor.seek(pos),
+            SourceReader::Reader(reader) => reader.seek(pos),
+            #[cfg(feature = "mmap")]
+            SourceReader::Mapped(cursor) => cursor.seek(pos),
+        }
+    }
+
+    fn stream_position(&mut self) -> std::io::Result<u64> {
+        match self {
+            SourceReader::File(file) => file.stream_position(),
+            SourceReader::Memory(cursor) => cursor.stream_position(),
+            SourceReader::Reader(reader) => reader.stream_position(),
+            #[cfg(feature = "mmap")]
+            SourceReader::Mapped(cursor) => cursor.stream_position(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_are_independent() {
+        let source = SiffSource::Memory(Arc::from(vec![0u8, 1, 2, 3]));
+        let mut first = source.open().unwrap();
+        let mut second = source.open().unwrap();
+
+        first.seek(SeekFrom::Start(2)).unwrap();
+        let mut byte = [0u8; 1];
+        second.read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [0]);
+        first.read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [2]);
+
+        let mut cursor = Cursor::new(vec![4u8, 5, 6]);
+        cursor.seek(SeekFrom::End(0)).unwrap();
+        let mut reader = SiffSource::from_reader(cursor).open().unwrap();
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte, [4]);
+    }
+
+    #[test]
+    fn frame_bytes_are_shared() {
+        let data : Vec<u8> = (0..16).collect();
+        let source = SiffSource::Memory(Arc::from(data.clone()));
+        let mut reader = BufReader::new(source.open().unwrap());
+
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        let bytes = reader.read_frame_bytes(6).unwrap();
+        assert!(matches!(bytes, FrameBytes::Shared(_, ref range) if *range == (3..9)));
+        assert_eq!(&*bytes, &data[3..9]);
+        assert_eq!(reader.stream_position().unwrap(), 9);
+
+        // Starts at an odd offset, so it can't be borrowed as `u16`s
+        let values = bytes.cast::<u16>().unwrap();
+        assert!(matches!(values, Cow::Owned(_)));
+        assert_eq!(&*values, &[0x0403, 0x0605, 0x0807]);
+        assert!(reader.read_frame_bytes(8).is_err());
+
+        // Anything else is read out
+        let mut cursor = Cursor::new(data.clone());
+        cursor.seek(SeekFrom::Start(4)).unwrap();
+        let bytes = cursor.read_frame_bytes(4).unwrap();
+        assert!(matches!(bytes, FrameBytes::Owned(_)));
+        assert_eq!(&*bytes, &data[4..8]);
+        // Only what's there is read, however much is asked for
+        assert!(cursor.read_frame_bytes(1 << 40).is_err());
+    }
+}